@@ -0,0 +1,48 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use lox_rust::lox;
+
+// Programs under test-data/bench, run from scratch on each iteration with the output discarded
+fn bench_programs(c: &mut Criterion) {
+    let mut files: Vec<_> = std::fs::read_dir("test-data/bench")
+        .expect("test-data/bench should be readable")
+        .map(|entry| entry.expect("entry should be readable").path())
+        .filter(|path| path.extension().is_some_and(|e| e == "lox"))
+        .collect();
+    files.sort();
+
+    let mut group = c.benchmark_group("interpreter");
+    for file in files {
+        let name = file.file_stem().unwrap().to_string_lossy().to_string();
+        let source = std::fs::read_to_string(&file).expect("program should be readable");
+
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut interpreter = lox::Interpreter::with_output(Box::new(std::io::sink()));
+                interpreter.execute(source.clone()).unwrap();
+            })
+        });
+    }
+    group.finish();
+}
+
+// Transactional execution of a short line over a heap of 100k values, the cost of capturing the
+// checkpoint of each line of an interactive session with large data loaded
+fn bench_transactional(c: &mut Criterion) {
+    let mut interpreter = lox::Interpreter::with_output(Box::new(std::io::sink()));
+    let items = (0..100_000)
+        .map(|i| lox::new_value_box(lox::Value::Number(i as f64)))
+        .collect();
+    interpreter.set("items", lox::Value::List(items)).unwrap();
+
+    c.bench_function("interpreter/transactional", |b| {
+        b.iter(|| {
+            interpreter
+                .execute_transactional("1 + 1;".to_string())
+                .unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, bench_programs, bench_transactional);
+criterion_main!(benches);
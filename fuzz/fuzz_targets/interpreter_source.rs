@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lox_rust::lox;
+
+// Runs arbitrary text as a script, the interpreter must report errors instead of panicking
+fuzz_target!(|source: &str| {
+    // no host I/O natives, scripts must not touch the filesystem or wait on stdin
+    let mut interpreter = lox::Interpreter::without_stdlib();
+    interpreter.set_output(Box::new(std::io::sink()));
+
+    let _ = interpreter.execute(source.to_string());
+});
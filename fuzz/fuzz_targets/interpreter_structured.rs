@@ -0,0 +1,206 @@
+#![no_main]
+
+use std::fmt::{Display, Formatter, Result};
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use lox_rust::lox;
+
+// Programs generated from a constrained syntax tree, so most inputs get past the parser and
+// exercise the interpreter. Loops are left out to keep every program terminating
+
+#[derive(Arbitrary, Debug)]
+struct Program(Vec<Statement>);
+
+#[derive(Arbitrary, Debug)]
+enum Statement {
+    Print(Expression),
+    Expression(Expression),
+    Var(Name, Option<Expression>),
+    Const(Name, Expression),
+    Block(Vec<Statement>),
+    If(Expression, Box<Statement>, Option<Box<Statement>>),
+    Switch(Expression, Vec<(Expression, Vec<Statement>)>, Option<Vec<Statement>>),
+    Function(Name, Vec<Name>, Vec<Statement>),
+}
+
+#[derive(Arbitrary, Debug)]
+enum Expression {
+    Number(u16),
+    String(Name),
+    True,
+    False,
+    Nil,
+    Variable(Name),
+    Assign(Name, Box<Expression>),
+    List(Vec<Expression>),
+    Index(Box<Expression>, Box<Expression>),
+    Call(Box<Expression>, Vec<Expression>),
+    Unary(UnaryOperator, Box<Expression>),
+    Binary(BinaryOperator, Box<Expression>, Box<Expression>),
+}
+
+// A small set of names, so declarations and uses of the same variable are likely
+#[derive(Arbitrary, Debug)]
+enum Name {
+    A,
+    B,
+    F,
+    Len,
+}
+
+#[derive(Arbitrary, Debug)]
+enum UnaryOperator {
+    Bang,
+    Minus,
+}
+
+#[derive(Arbitrary, Debug)]
+enum BinaryOperator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Display for Program {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        for stmt in self.0.iter() {
+            writeln!(f, "{stmt}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for Statement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Statement::Print(expr) => write!(f, "print {expr};"),
+            Statement::Expression(expr) => write!(f, "{expr};"),
+            Statement::Var(name, None) => write!(f, "var {name};"),
+            Statement::Var(name, Some(expr)) => write!(f, "var {name} = {expr};"),
+            Statement::Const(name, expr) => write!(f, "const {name} = {expr};"),
+            Statement::Block(stmts) => {
+                write!(f, "{{ ")?;
+                for stmt in stmts {
+                    write!(f, "{stmt} ")?;
+                }
+                write!(f, "}}")
+            }
+            Statement::If(condition, then_branch, None) => {
+                write!(f, "if ({condition}) {then_branch}")
+            }
+            Statement::If(condition, then_branch, Some(else_branch)) => {
+                write!(f, "if ({condition}) {then_branch} else {else_branch}")
+            }
+            Statement::Switch(subject, cases, default) => {
+                write!(f, "switch ({subject}) {{ ")?;
+                for (value, body) in cases {
+                    write!(f, "case {value}: ")?;
+                    for stmt in body {
+                        write!(f, "{stmt} ")?;
+                    }
+                }
+                if let Some(default) = default {
+                    write!(f, "default: ")?;
+                    for stmt in default {
+                        write!(f, "{stmt} ")?;
+                    }
+                }
+                write!(f, "}}")
+            }
+            Statement::Function(name, parameters, body) => {
+                let parameters: Vec<String> = parameters.iter().map(|p| p.to_string()).collect();
+                write!(f, "fun {name}({}) {{ ", parameters.join(", "))?;
+                for stmt in body {
+                    write!(f, "{stmt} ")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Expression::Number(n) => write!(f, "{n}"),
+            Expression::String(name) => write!(f, "\"{name}\""),
+            Expression::True => write!(f, "true"),
+            Expression::False => write!(f, "false"),
+            Expression::Nil => write!(f, "nil"),
+            Expression::Variable(name) => write!(f, "{name}"),
+            Expression::Assign(name, value) => write!(f, "({name} = {value})"),
+            Expression::List(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Expression::Index(list, index) => write!(f, "({list})[{index}]"),
+            Expression::Call(callee, arguments) => {
+                let arguments: Vec<String> = arguments.iter().map(|a| a.to_string()).collect();
+                write!(f, "({callee})({})", arguments.join(", "))
+            }
+            Expression::Unary(operator, expr) => write!(f, "({operator}{expr})"),
+            Expression::Binary(operator, left, right) => write!(f, "({left} {operator} {right})"),
+        }
+    }
+}
+
+impl Display for Name {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Name::A => write!(f, "a"),
+            Name::B => write!(f, "b"),
+            Name::F => write!(f, "f"),
+            Name::Len => write!(f, "len"),
+        }
+    }
+}
+
+impl Display for UnaryOperator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            UnaryOperator::Bang => write!(f, "!"),
+            UnaryOperator::Minus => write!(f, "-"),
+        }
+    }
+}
+
+impl Display for BinaryOperator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let operator = match self {
+            BinaryOperator::Or => "or",
+            BinaryOperator::And => "and",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::Less => "<",
+            BinaryOperator::LessEqual => "<=",
+            BinaryOperator::Greater => ">",
+            BinaryOperator::GreaterEqual => ">=",
+            BinaryOperator::Add => "+",
+            BinaryOperator::Sub => "-",
+            BinaryOperator::Mul => "*",
+            BinaryOperator::Div => "/",
+        };
+        write!(f, "{operator}")
+    }
+}
+
+// TODO: once a bytecode backend exists, run the program on it as well and compare the output
+//       and result with the tree-walking interpreter
+fuzz_target!(|program: Program| {
+    let source = program.to_string();
+
+    let mut interpreter = lox::Interpreter::without_stdlib();
+    interpreter.set_output(Box::new(std::io::sink()));
+
+    let _ = interpreter.execute(source);
+});
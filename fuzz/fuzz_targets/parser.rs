@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lox_rust::lox;
+
+// Parses arbitrary bytes, malformed programs must be parse errors and the programs that parse
+// must format back to the same statements
+fuzz_target!(|bytes: &[u8]| {
+    lox::fuzz_parse(bytes);
+});
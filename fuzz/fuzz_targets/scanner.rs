@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lox_rust::lox;
+
+// Scans arbitrary bytes, the scanner must report errors instead of panicking
+fuzz_target!(|bytes: &[u8]| {
+    lox::fuzz_scan(bytes);
+});
@@ -1,35 +1,704 @@
-use std::fs::File;
-use std::io::{BufReader, Read};
+use std::cell::RefCell;
+use std::io::{IsTerminal, Read};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use lox_rust::lox;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::FileHistory;
+use rustyline::validate::Validator;
+use rustyline::{Config, Editor, Helper};
 
-/// Simple program to greet a person
+/// Lox interpreter and the tools to inspect each stage of its pipeline
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// File to run
-    #[arg(short, long)]
+    /// Run the given code instead of starting an interactive session, e.g. -e 'print 1 + 2;'
+    #[arg(short = 'e', long = "eval", value_name = "CODE")]
+    eval: Option<String>,
+
+    /// Language version of scripts without a version header: 1 or extended
+    #[arg(long, global = true, default_value = "extended")]
+    lox_version: String,
+
+    /// Also treat 0, empty strings and empty lists as false in conditions, as older releases did
+    #[arg(long, global = true)]
+    permissive_truthiness: bool,
+
+    /// How errors and warnings are written to stderr: human, or json for one record per line
+    /// for editors and other tools (needs the serde feature)
+    #[arg(long, global = true, value_enum, default_value_t = Diagnostics::Human)]
+    diagnostics: Diagnostics,
+
+    /// Starts an interactive session if not given
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Run a script
+    Run {
+        /// Script to run, - to read it from stdin
+        file: String,
+
+        /// Also report style warnings: unused and shadowed variables
+        #[arg(long)]
+        warnings: bool,
+
+        /// Run the script the given number of times with its output discarded and report the
+        /// timings
+        #[arg(long, value_name = "RUNS")]
+        bench: Option<usize>,
+    },
+
+    /// Start an interactive session
+    Repl,
+
+    /// Print the tokens of a script
+    Tokenize {
+        /// Script to scan, - to read it from stdin
+        file: String,
+
+        #[command(flatten)]
+        emit: EmitArgs,
+    },
+
+    /// Print the syntax tree of a script
+    Parse {
+        /// Script to parse, - to read it from stdin
+        file: String,
+
+        #[command(flatten)]
+        emit: EmitArgs,
+    },
+
+    /// Format a script in place, or print it formatted if read from stdin
+    Fmt {
+        /// Script to format, - to read it from stdin
+        file: String,
+
+        /// Fail if the script is not formatted instead of rewriting it
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// Run the test_* functions of the *_test.lox files
+    Test {
+        /// Test files or directories to search for them, the working directory if none
+        paths: Vec<PathBuf>,
+    },
+}
+
+// Output options of the commands printing a stage of the pipeline
+#[derive(clap::Args, Debug)]
+struct EmitArgs {
+    /// Output format: text, tree (syntax trees only) or json (needs the serde feature)
+    #[arg(long, value_enum, default_value_t = Emit::Text)]
+    emit: Emit,
+
+    /// Annotate the output with source lines
+    #[arg(long)]
+    spans: bool,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum Emit {
+    // tokens one per line, syntax trees as s-expressions
+    Text,
+    Tree,
+    Json,
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq)]
+enum Diagnostics {
+    // with the source line they point at, colored on terminals
+    Human,
+    Json,
+}
+
+// Failure of the command, each kind exits with its code from sysexits.h
+#[derive(Debug)]
+enum CliError {
+    // the command line is wrong, e.g. an unknown language version
+    Usage(String),
+    // the script does not scan or parse
+    Compile(Box<ScriptError>),
+    // the input can not be read
+    NoInput(String),
+    // the script fails while running
+    Runtime(Box<ScriptError>),
+    // an output can not be written
+    Io(String),
+    // the command ran as expected and reports a negative result, e.g. failed tests
+    Failed(String),
+}
+
+impl CliError {
+    fn exit_code(&self) -> u8 {
+        match self {
+            CliError::Usage(_) => 64,
+            CliError::Compile(_) => 65,
+            CliError::NoInput(_) => 66,
+            CliError::Runtime(_) => 70,
+            CliError::Io(_) => 74,
+            CliError::Failed(_) => 1,
+        }
+    }
+
+    fn message(&self) -> &str {
+        match self {
+            CliError::Compile(error) | CliError::Runtime(error) => &error.diagnostic.message,
+            CliError::Usage(message)
+            | CliError::NoInput(message)
+            | CliError::Io(message)
+            | CliError::Failed(message) => message,
+        }
+    }
+
+    // Diagnostic code of the errors not found in a script, see lox::Diagnostic
+    fn code(&self) -> &'static str {
+        match self {
+            CliError::Compile(error) | CliError::Runtime(error) => error.diagnostic.code,
+            CliError::Usage(_) => "E0001",
+            CliError::NoInput(_) => "E0002",
+            CliError::Io(_) => "E0003",
+            CliError::Failed(_) => "E0004",
+        }
+    }
+
+    // Text printed for the error in the given format
+    fn report(&self, format: Diagnostics) -> String {
+        match (self, format) {
+            (CliError::Compile(error) | CliError::Runtime(error), _) => error.report(format),
+            (other, Diagnostics::Human) => format!("error: {}", other.message()),
+            (other, Diagnostics::Json) => diagnostic_json(
+                &lox::Diagnostic::error(other.code(), other.message(), None),
+                None,
+            ),
+        }
+    }
+
+    fn compile(diagnostic: lox::Diagnostic, source: &str, file: &str) -> Self {
+        CliError::Compile(Box::new(ScriptError::new(diagnostic, source, file)))
+    }
+
+    fn runtime(error: lox::LoxError, source: &str, file: &str) -> Self {
+        let diagnostic = lox::Diagnostic::from(error);
+        CliError::Runtime(Box::new(ScriptError::new(diagnostic, source, file)))
+    }
+}
+
+// Error found in a script, kept with the source it points into
+#[derive(Debug)]
+struct ScriptError {
+    diagnostic: lox::Diagnostic,
+    source: String,
     file: String,
 }
 
-fn main() -> Result<(), String> {
-    // read a file and create a scanner
+impl ScriptError {
+    fn new(diagnostic: lox::Diagnostic, source: &str, file: &str) -> Self {
+        Self {
+            diagnostic,
+            source: source.to_string(),
+            file: file.to_string(),
+        }
+    }
+
+    fn report(&self, format: Diagnostics) -> String {
+        report_diagnostic(&self.diagnostic, &self.source, &self.file, format)
+    }
+}
+
+// Diagnostic of a script in the given format. Rendered for humans with the line it points at,
+// colored if stderr is a terminal and NO_COLOR is not set
+fn report_diagnostic(
+    diagnostic: &lox::Diagnostic,
+    source: &str,
+    file: &str,
+    format: Diagnostics,
+) -> String {
+    let name = match file {
+        "-" => "<stdin>",
+        file => file,
+    };
+
+    match format {
+        Diagnostics::Human => {
+            let color = std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+            diagnostic.render(source, name, color)
+        }
+        Diagnostics::Json => diagnostic_json(diagnostic, Some(name)),
+    }
+}
+
+#[cfg(feature = "serde")]
+fn diagnostic_json(diagnostic: &lox::Diagnostic, file: Option<&str>) -> String {
+    // the record only holds strings, it can not fail to serialize
+    diagnostic
+        .to_json(file)
+        .unwrap_or_else(|e| format!("error: {e}"))
+}
+
+// Only reached to report that JSON is not available, see run_command
+#[cfg(not(feature = "serde"))]
+fn diagnostic_json(diagnostic: &lox::Diagnostic, _file: Option<&str>) -> String {
+    format!("{}: {}", diagnostic.severity, diagnostic.message)
+}
+
+fn main() -> ExitCode {
+    let args = match Args::try_parse() {
+        Ok(args) => args,
+        Err(e) => {
+            // help and version requests are printed to stdout and are not errors
+            let _ = e.print();
+            return match e.use_stderr() {
+                true => ExitCode::from(CliError::Usage(e.to_string()).exit_code()),
+                false => ExitCode::SUCCESS,
+            };
+        }
+    };
+
+    let diagnostics = args.diagnostics;
+    match run_command(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{}", e.report(diagnostics));
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+fn run_command(args: Args) -> Result<(), CliError> {
+    if args.diagnostics == Diagnostics::Json && !cfg!(feature = "serde") {
+        return Err(CliError::Usage(
+            "lox was built without the serde feature".to_string(),
+        ));
+    }
+
+    let version = lox::LanguageVersion::from_name(&args.lox_version).ok_or(CliError::Usage(
+        format!("Unknown language version '{}'", args.lox_version),
+    ))?;
+
+    let truthiness = if args.permissive_truthiness {
+        lox::Truthiness::Permissive
+    } else {
+        lox::Truthiness::Strict
+    };
+
+    let options = lox::InterpreterOptions {
+        version,
+        truthiness,
+        ..Default::default()
+    };
+
+    // checked here rather than by clap, which would also reject the global options given
+    // before a subcommand
+    if args.eval.is_some() && args.command.is_some() {
+        return Err(CliError::Usage(
+            "-e can not be used with a subcommand".to_string(),
+        ));
+    }
+
+    if let Some(code) = args.eval {
+        let analyzer_options = lox::AnalyzerOptions {
+            warnings: false,
+            truthiness,
+        };
+        return run_source(
+            options,
+            "<eval>",
+            code,
+            vec![PathBuf::from(".")],
+            analyzer_options,
+            args.diagnostics,
+        );
+    }
+
+    match args.command.unwrap_or(Command::Repl) {
+        Command::Run {
+            file,
+            bench: Some(runs),
+            ..
+        } => bench_file(options, &file, runs),
+        Command::Run { file, warnings, .. } => {
+            let analyzer_options = lox::AnalyzerOptions {
+                warnings,
+                truthiness,
+            };
+            run_file(options, &file, analyzer_options, args.diagnostics)
+        }
+        Command::Repl => run_repl(options).map_err(CliError::Io),
+        Command::Tokenize { file, emit } => print_tokens(&file, &emit),
+        Command::Parse { file, emit } => print_ast(&file, &emit, options.version),
+        Command::Fmt { file, check } => format_file(&file, check, options.version),
+        Command::Test { paths } => run_tests(paths, options),
+    }
+}
+
+// Source of the script, read from stdin for -
+fn read_source(file: &str) -> Result<String, CliError> {
+    if file == "-" {
+        let mut source = String::new();
+        std::io::stdin()
+            .read_to_string(&mut source)
+            .map_err(|e| CliError::NoInput(format!("Error reading stdin: {e}")))?;
+        return Ok(source);
+    }
+
+    std::fs::read_to_string(file)
+        .map_err(|e| CliError::NoInput(format!("Error reading '{file}': {e}")))
+}
+
+fn print_tokens(file: &str, emit: &EmitArgs) -> Result<(), CliError> {
+    let source = read_source(file)?;
+    let tokens = lox::tokenize(&source).map_err(|e| CliError::compile(e, &source, file))?;
+
+    match emit.emit {
+        Emit::Text => {
+            for token in tokens.iter().filter(|t| t.token != lox::Token::Eof) {
+                match emit.spans {
+                    true => println!("{}: {}", token.span, token.token),
+                    false => println!("{}", token.token),
+                }
+            }
+            Ok(())
+        }
+        Emit::Tree => Err(CliError::Usage(
+            "Tokens can only be emitted as text or json".to_string(),
+        )),
+        Emit::Json => emit_tokens_json(&tokens),
+    }
+}
+
+fn print_ast(file: &str, emit: &EmitArgs, version: lox::LanguageVersion) -> Result<(), CliError> {
+    let source = read_source(file)?;
+    let program = lox::parse_with_version(&source, version)
+        .map_err(|e| CliError::compile(e, &source, file))?;
+
+    let style = match emit.emit {
+        Emit::Text => lox::AstStyle::SExpression,
+        Emit::Tree => lox::AstStyle::Tree,
+        Emit::Json => return emit_ast_json(&program.statements),
+    };
+
+    let mut printer = lox::AstPrinter::with_options(lox::AstPrinterOptions {
+        style,
+        spans: emit.spans,
+    });
+    print!("{}", printer.print(&program.statements));
+
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+fn emit_tokens_json(tokens: &[lox::SpannedToken]) -> Result<(), CliError> {
+    println!("{}", lox::tokens_to_json(tokens).map_err(CliError::Io)?);
+    Ok(())
+}
+
+#[cfg(not(feature = "serde"))]
+fn emit_tokens_json(_tokens: &[lox::SpannedToken]) -> Result<(), CliError> {
+    Err(CliError::Usage(
+        "lox was built without the serde feature".to_string(),
+    ))
+}
+
+#[cfg(feature = "serde")]
+fn emit_ast_json(statements: &[lox::Stmt]) -> Result<(), CliError> {
+    println!("{}", lox::ast_to_json(statements).map_err(CliError::Io)?);
+    Ok(())
+}
+
+#[cfg(not(feature = "serde"))]
+fn emit_ast_json(_statements: &[lox::Stmt]) -> Result<(), CliError> {
+    Err(CliError::Usage(
+        "lox was built without the serde feature".to_string(),
+    ))
+}
 
-    let args = Args::parse();
+fn format_file(file: &str, check: bool, version: lox::LanguageVersion) -> Result<(), CliError> {
+    let source = read_source(file)?;
+    lox::parse_with_version(&source, version).map_err(|e| CliError::compile(e, &source, file))?;
+
+    // the source parses, it can only fail for the comments the formatter does not support
+    let formatted = lox::Formatter::format_source(&source).map_err(CliError::Failed)?;
+
+    if check {
+        return match formatted == source {
+            true => Ok(()),
+            false => Err(CliError::Failed(format!("{file} is not formatted"))),
+        };
+    }
+
+    if file == "-" {
+        print!("{formatted}");
+        return Ok(());
+    }
+
+    if formatted == source {
+        return Ok(());
+    }
+
+    std::fs::write(file, formatted)
+        .map_err(|e| CliError::Io(format!("Error writing '{file}': {e}")))
+}
+
+fn run_file(
+    options: lox::InterpreterOptions,
+    file: &str,
+    analyzer_options: lox::AnalyzerOptions,
+    diagnostics: Diagnostics,
+) -> Result<(), CliError> {
+    let source = read_source(file)?;
+    run_source(
+        options,
+        file,
+        source,
+        script_search_paths(file),
+        analyzer_options,
+        diagnostics,
+    )
+}
 
-    let f = File::open(args.file).map_err(|e| e.to_string())?;
+// Runs a script, looking up its imports in the given directories. The file names the script in
+// its diagnostics
+fn run_source(
+    options: lox::InterpreterOptions,
+    file: &str,
+    source: String,
+    search_paths: Vec<PathBuf>,
+    analyzer_options: lox::AnalyzerOptions,
+    diagnostics: Diagnostics,
+) -> Result<(), CliError> {
+    let mut interpreter = lox::Interpreter::with_options(options);
+    interpreter.set_module_search_paths(search_paths);
 
-    let mut reader = BufReader::new(f);
+    let program = interpreter
+        .parse(source.clone())
+        .map_err(|e| CliError::compile(e, &source, file))?;
 
-    let mut source = String::new();
-    reader
-        .read_to_string(&mut source)
-        .map_err(|e| format!("Failed to read file as String: {}", e))?;
+    // report suspicious code before running it
+    for lint in lox::Analyzer::analyze_with_options(&program.statements, analyzer_options) {
+        let diagnostic = lox::Diagnostic::from(&lint);
+        eprintln!(
+            "{}",
+            report_diagnostic(&diagnostic, &source, file, diagnostics)
+        );
+    }
 
-    let mut interepreter = lox::Interpreter::new();
-    interepreter.execute(source)?;
+    interpreter
+        .execute_program(&program)
+        .map_err(|e| CliError::runtime(e, &source, file))?;
 
     Ok(())
 }
+
+fn run_tests(paths: Vec<PathBuf>, options: lox::InterpreterOptions) -> Result<(), CliError> {
+    let paths = match paths.is_empty() {
+        true => vec![PathBuf::from(".")],
+        false => paths,
+    };
+
+    let report = lox::run_tests(&paths, options).map_err(CliError::NoInput)?;
+
+    for result in &report.results {
+        println!("{result}");
+
+        // the output of failed tests helps finding out why they failed
+        if !result.passed() {
+            for line in result.output.lines() {
+                println!("    {line}");
+            }
+        }
+    }
+
+    println!("{}", report.summary());
+
+    match report.success() {
+        true => Ok(()),
+        false => Err(CliError::Failed("Some tests failed".to_string())),
+    }
+}
+
+// Modules are looked up next to the script first, then in the working directory. Scripts read
+// from stdin only look in the working directory
+fn script_search_paths(file: &str) -> Vec<PathBuf> {
+    if file == "-" {
+        return vec![PathBuf::from(".")];
+    }
+
+    let script_directory = PathBuf::from(file)
+        .parent()
+        .map(|p| p.to_path_buf())
+        .unwrap_or_default();
+
+    vec![script_directory, PathBuf::from(".")]
+}
+
+fn bench_file(options: lox::InterpreterOptions, file: &str, runs: usize) -> Result<(), CliError> {
+    if runs == 0 {
+        return Err(CliError::Usage(
+            "--bench needs at least one run".to_string(),
+        ));
+    }
+
+    let source = read_source(file)?;
+
+    // each run starts from a fresh interpreter, so the timings include the stdlib setup
+    let mut timings = Vec::with_capacity(runs);
+    for _ in 0..runs {
+        let mut interpreter = lox::Interpreter::with_options(options.clone());
+        interpreter.set_output(Box::new(std::io::sink()));
+        interpreter.set_module_search_paths(script_search_paths(file));
+
+        let start = Instant::now();
+        let program = interpreter
+            .parse(source.clone())
+            .map_err(|e| CliError::compile(e, &source, file))?;
+        interpreter
+            .execute_program(&program)
+            .map_err(|e| CliError::runtime(e, &source, file))?;
+        timings.push(start.elapsed());
+    }
+
+    let total: Duration = timings.iter().sum();
+    let min = timings.iter().min().copied().unwrap_or_default();
+    let max = timings.iter().max().copied().unwrap_or_default();
+    println!(
+        "interpreter: {runs} runs, min {min:?}, mean {:?}, max {max:?}",
+        total / runs as u32
+    );
+
+    Ok(())
+}
+
+// Completes the REPL input with keywords and the names defined in the session
+struct ReplHelper {
+    repl: Rc<RefCell<lox::Repl>>,
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let repl = self.repl.borrow();
+        Ok(repl.get_session().get_interpreter().complete(line, pos))
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ReplHelper {}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+// File keeping the REPL history between sessions: $LOX_HISTORY, or .lox_history in the home
+// directory. Without either the history only lasts for the session
+fn history_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os("LOX_HISTORY") {
+        return Some(PathBuf::from(path));
+    }
+
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".lox_history"))
+}
+
+fn run_repl(options: lox::InterpreterOptions) -> Result<(), String> {
+    let repl = Rc::new(RefCell::new(lox::Repl::new(options)));
+
+    let config = Config::builder()
+        .max_history_size(1000)
+        .map_err(|e| e.to_string())?
+        .history_ignore_dups(true)
+        .map_err(|e| e.to_string())?
+        .build();
+
+    let mut editor: Editor<ReplHelper, FileHistory> =
+        Editor::with_config(config).map_err(|e| e.to_string())?;
+    editor.set_helper(Some(ReplHelper { repl: repl.clone() }));
+
+    // a missing history file is the first session
+    let history = history_path();
+    if let Some(path) = &history {
+        let _ = editor.load_history(path);
+    }
+
+    let result = repl_loop(&mut editor, &repl);
+
+    if let Some(path) = &history {
+        if let Err(e) = editor.save_history(path) {
+            eprintln!(
+                "warning: could not save the history to {}: {e}",
+                path.display()
+            );
+        }
+    }
+
+    result
+}
+
+// Reads and runs inputs until Ctrl-D, either Lox code or commands like :help. Lines are read with
+// a continuation prompt while the code is incomplete, e.g. inside a block, Ctrl-C drops the
+// pending input
+fn repl_loop(
+    editor: &mut Editor<ReplHelper, FileHistory>,
+    repl: &Rc<RefCell<lox::Repl>>,
+) -> Result<(), String> {
+    let mut input = String::new();
+
+    loop {
+        let prompt = if input.is_empty() { "> " } else { ". " };
+
+        let line = match editor.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) if !input.is_empty() => {
+                input.clear();
+                continue;
+            }
+            Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => return Ok(()),
+            Err(e) => return Err(e.to_string()),
+        };
+
+        if input.is_empty() && line.trim().is_empty() {
+            continue;
+        }
+
+        if !input.is_empty() {
+            input.push('\n');
+        }
+        input.push_str(&line);
+
+        if !repl.borrow().is_complete(&input) {
+            continue;
+        }
+
+        // the whole input is a single entry, recalled at once
+        let _ = editor.add_history_entry(input.as_str());
+
+        // errors are reported and the session continues as if the input was never entered
+        match repl.borrow_mut().eval(&input) {
+            Ok(output) if !output.is_empty() => println!("{output}"),
+            Ok(_) => {}
+            Err(e) => eprintln!("error: {e}"),
+        }
+
+        input.clear();
+    }
+}
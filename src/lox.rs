@@ -1,19 +1,56 @@
+mod analyzer;
+mod api;
+mod ast_printer;
+mod completion;
+mod convert;
+mod diagnostic;
 mod environment;
 mod expr;
+mod formatter;
 mod function;
+mod fuzz;
+#[cfg(test)]
+mod golden;
 mod interpreter;
+mod limits;
+mod loader;
 mod parser;
+mod prelude;
+mod repl;
 mod scanner;
+mod semantics;
+mod session;
+pub mod stdlib;
 mod stmt;
+mod symbol;
+mod test_runner;
 mod token;
 mod value;
+mod version;
 
+pub use analyzer::*;
+pub use api::*;
+pub use ast_printer::*;
+pub use completion::*;
+pub use convert::*;
+pub use diagnostic::*;
 pub use environment::*;
 pub use expr::*;
+pub use formatter::*;
 pub use function::*;
+pub use fuzz::*;
 pub use interpreter::*;
+pub use limits::*;
+pub use loader::*;
 pub use parser::*;
+pub use prelude::*;
+pub use repl::*;
 pub use scanner::*;
+pub use semantics::*;
+pub use session::*;
 pub use stmt::*;
+pub use symbol::*;
+pub use test_runner::*;
 pub use token::*;
 pub use value::*;
+pub use version::*;
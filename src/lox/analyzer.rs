@@ -0,0 +1,948 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+use super::{
+    lox_add, lox_compare, lox_equals, lox_multiply, Comparison, Expr, ExprVisitor, Span, Stmt,
+    StmtVisitor, Symbol, Truthiness, Value,
+};
+
+// Suspicious code found by the analyzer, reported as warnings since the program is still valid.
+// Each lint ends with the span of the loop, condition, declaration, call or case it is about
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lint {
+    // loop whose condition is always true and whose body does not throw, which is the only way
+    // out of a loop as the language has no break
+    InfiniteLoop(Span),
+    // if or while condition that is always false, its body never runs
+    ConstantFalseCondition(Span),
+    // comparison of a variable with itself, e.g. `a == a`
+    SelfComparison(String, Span),
+    // call with a number of arguments other than the parameters of the function declared with
+    // that name: name, parameters, arguments
+    ArityMismatch(String, usize, usize, Span),
+    // assignment to a name declared as a constant in an enclosing scope, an error at runtime
+    ConstantAssignment(String, Span),
+    // local variable that is declared but never read, only reported with warnings enabled
+    UnusedVariable(String, Span),
+    // local variable declared with the name of a variable of an enclosing scope, only reported
+    // with warnings enabled
+    ShadowedVariable(String, Span),
+    // switch case whose value equals the value of an earlier case, so it never runs
+    DuplicateCase(String, Span),
+}
+
+impl Lint {
+    pub fn get_span(&self) -> Span {
+        match self {
+            Lint::InfiniteLoop(span)
+            | Lint::ConstantFalseCondition(span)
+            | Lint::SelfComparison(_, span)
+            | Lint::ArityMismatch(_, _, _, span)
+            | Lint::ConstantAssignment(_, span)
+            | Lint::UnusedVariable(_, span)
+            | Lint::ShadowedVariable(_, span)
+            | Lint::DuplicateCase(_, span) => *span,
+        }
+    }
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Lint::InfiniteLoop(_) => {
+                write!(f, "Loop condition is always true, the loop never ends")
+            }
+            Lint::ConstantFalseCondition(_) => {
+                write!(f, "Condition is always false, its body never runs")
+            }
+            Lint::SelfComparison(name, span) => {
+                write!(f, "Comparison of '{name}' with itself at {span}")
+            }
+            Lint::ArityMismatch(name, parameters, arguments, _) => write!(
+                f,
+                "Function '{name}' takes {parameters} arguments but is called with {arguments}"
+            ),
+            Lint::ConstantAssignment(name, _) => write!(f, "Assignment to constant '{name}'"),
+            Lint::UnusedVariable(name, _) => write!(f, "Variable '{name}' is never read"),
+            Lint::ShadowedVariable(name, _) => {
+                write!(
+                    f,
+                    "Variable '{name}' shadows a variable of an enclosing scope"
+                )
+            }
+            Lint::DuplicateCase(value, _) => {
+                write!(f, "Case {value} is already matched by an earlier case")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AnalyzerOptions {
+    // also report style warnings, i.e. unused and shadowed variables
+    pub warnings: bool,
+    // truthiness of the interpreter running the program, used to fold the conditions
+    pub truthiness: Truthiness,
+}
+
+// Variable declared in a scope of the analyzed program
+struct Declaration {
+    name: Symbol,
+    // read at least once, or exempt from the unused check
+    read: bool,
+    constant: bool,
+    span: Span,
+}
+
+// Walks a program collecting lints
+pub struct Analyzer {
+    options: AnalyzerOptions,
+    lints: Vec<Lint>,
+    // number of parameters of the functions declared in the program, None for names bound more
+    // than once or to something else than a function, whose value is only known at runtime
+    arities: HashMap<Symbol, Option<usize>>,
+    // callee name, number of arguments and span of the calls to identifiers
+    calls: Vec<(Symbol, usize, Span)>,
+    // variables declared in each enclosing scope in declaration order, the first one is the
+    // global scope
+    scopes: Vec<Vec<Declaration>>,
+    // local variables that were never read in their scope, with the span of their declaration
+    unused: Vec<(Symbol, Span)>,
+    // names read where no variable with that name is declared. Variables are looked up at
+    // runtime, so these may read the locals of the caller of a function
+    free: HashSet<Symbol>,
+}
+
+impl Analyzer {
+    pub fn analyze(statements: &[Stmt]) -> Vec<Lint> {
+        Self::analyze_with_options(statements, AnalyzerOptions::default())
+    }
+
+    pub fn analyze_with_options(statements: &[Stmt], options: AnalyzerOptions) -> Vec<Lint> {
+        let mut analyzer = Analyzer {
+            options,
+            lints: Vec::new(),
+            arities: HashMap::new(),
+            calls: Vec::new(),
+            scopes: vec![Vec::new()],
+            unused: Vec::new(),
+            free: HashSet::new(),
+        };
+
+        for stmt in statements {
+            stmt.accept(&mut analyzer);
+        }
+
+        // calls are checked once every declaration is known, functions can be called before
+        // being declared
+        for (name, arguments, span) in std::mem::take(&mut analyzer.calls) {
+            if let Some(Some(parameters)) = analyzer.arities.get(&name) {
+                if *parameters != arguments {
+                    analyzer.lints.push(Lint::ArityMismatch(
+                        name.to_string(),
+                        *parameters,
+                        arguments,
+                        span,
+                    ));
+                }
+            }
+        }
+
+        // globals are not reported, they can be read later by the REPL or by importers
+        if analyzer.options.warnings {
+            for (name, span) in std::mem::take(&mut analyzer.unused) {
+                if !analyzer.free.contains(&name) {
+                    analyzer
+                        .lints
+                        .push(Lint::UnusedVariable(name.to_string(), span));
+                }
+            }
+        }
+
+        analyzer.lints
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    fn pop_scope(&mut self) {
+        if let Some(scope) = self.scopes.pop() {
+            self.unused.extend(
+                scope
+                    .into_iter()
+                    .filter(|declaration| !declaration.read)
+                    .map(|declaration| (declaration.name, declaration.span)),
+            );
+        }
+    }
+
+    // Declares a variable in the current scope, read tells if it is exempt from the unused check.
+    // The span is only reported for the declarations that are not exempt
+    fn declare(&mut self, name: Symbol, read: bool, constant: bool, span: Span) {
+        let Some((current, enclosing)) = self.scopes.split_last_mut() else {
+            return;
+        };
+
+        if self.options.warnings
+            && !read
+            && !enclosing.is_empty()
+            && enclosing
+                .iter()
+                .flatten()
+                .any(|declaration| declaration.name == name)
+        {
+            self.lints
+                .push(Lint::ShadowedVariable(name.to_string(), span));
+        }
+
+        current.push(Declaration {
+            name,
+            read,
+            constant,
+            span,
+        });
+    }
+
+    // Innermost declaration of the name
+    fn resolve(&mut self, name: &Symbol) -> Option<&mut Declaration> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .flat_map(|scope| scope.iter_mut().rev())
+            .find(|declaration| declaration.name == *name)
+    }
+
+    fn read(&mut self, name: Symbol) {
+        match self.resolve(&name) {
+            Some(declaration) => declaration.read = true,
+            None => {
+                self.free.insert(name);
+            }
+        }
+    }
+
+    fn bind(&mut self, name: Symbol, arity: Option<usize>) {
+        self.arities
+            .entry(name)
+            .and_modify(|a| *a = None)
+            .or_insert(arity);
+    }
+
+    fn check_self_comparison(&mut self, left: &Expr, right: &Expr, span: &Span) {
+        if let (Expr::Identifier(l), Expr::Identifier(r)) = (left, right) {
+            if l == r {
+                self.lints.push(Lint::SelfComparison(l.to_string(), *span));
+            }
+        }
+    }
+
+    fn visit_comparison(&mut self, left: &Expr, right: &Expr, span: &Span) {
+        self.check_self_comparison(left, right, span);
+        left.accept(self);
+        right.accept(self);
+    }
+}
+
+// Value of an expression that can be computed without running the program
+#[derive(Debug, Clone, PartialEq)]
+enum Constant {
+    Number(f64),
+    String(String),
+    Boolean(bool),
+    Nil,
+}
+
+impl Constant {
+    fn to_value(&self) -> Value {
+        match self {
+            Constant::Number(n) => Value::Number(*n),
+            Constant::String(s) => Value::String(s.clone()),
+            Constant::Boolean(b) => Value::Boolean(*b),
+            Constant::Nil => Value::Nil,
+        }
+    }
+
+    // None for the values that have no literal form, e.g. lists
+    fn from_value(value: Value) -> Option<Constant> {
+        match value {
+            Value::Number(n) => Some(Constant::Number(n)),
+            Value::String(s) => Some(Constant::String(s)),
+            Value::Boolean(b) => Some(Constant::Boolean(b)),
+            Value::Nil => Some(Constant::Nil),
+            _ => None,
+        }
+    }
+
+    // truthiness, equality, comparisons and arithmetic follow the same rules as at runtime
+    fn is_truthy(&self, truthiness: Truthiness) -> bool {
+        truthiness.is_truthy(&self.to_value())
+    }
+
+    fn equals(&self, other: &Constant) -> bool {
+        lox_equals(&self.to_value(), &other.to_value())
+    }
+
+    fn compare(&self, comparison: Comparison, other: &Constant) -> Option<Constant> {
+        lox_compare(comparison, &self.to_value(), &other.to_value()).map(Constant::Boolean)
+    }
+
+    // None if the operation is an error at runtime
+    fn add(&self, other: &Constant) -> Option<Constant> {
+        Constant::from_value(lox_add(&self.to_value(), &other.to_value()).ok()??)
+    }
+
+    fn multiply(&self, other: &Constant) -> Option<Constant> {
+        Constant::from_value(lox_multiply(&self.to_value(), &other.to_value()).ok()??)
+    }
+}
+
+// Folds expressions made only of literals and operators, None if the value depends on the
+// program state or the operation is an error at runtime
+fn fold(expr: &Expr, truthiness: Truthiness) -> Option<Constant> {
+    let numbers =
+        |left: &Expr, right: &Expr| match (fold(left, truthiness)?, fold(right, truthiness)?) {
+            (Constant::Number(l), Constant::Number(r)) => Some((l, r)),
+            _ => None,
+        };
+
+    match expr {
+        Expr::LiteralNumber(n) => Some(Constant::Number(*n)),
+        Expr::LiteralString(s) => Some(Constant::String(s.clone())),
+        Expr::True => Some(Constant::Boolean(true)),
+        Expr::False => Some(Constant::Boolean(false)),
+        Expr::Nil => Some(Constant::Nil),
+        Expr::UnaryBang(e) => Some(Constant::Boolean(
+            !fold(e, truthiness)?.is_truthy(truthiness),
+        )),
+        Expr::UnaryMinus(e) => match fold(e, truthiness)? {
+            Constant::Number(n) => Some(Constant::Number(-n)),
+            _ => None,
+        },
+        Expr::BinaryOr(left, right, _) => {
+            let left = fold(left, truthiness)?;
+            if left.is_truthy(truthiness) {
+                Some(left)
+            } else {
+                fold(right, truthiness)
+            }
+        }
+        Expr::BinaryAnd(left, right, _) => {
+            let left = fold(left, truthiness)?;
+            if !left.is_truthy(truthiness) {
+                Some(left)
+            } else {
+                fold(right, truthiness)
+            }
+        }
+        Expr::BinaryEqual(left, right, _) => Some(Constant::Boolean(
+            fold(left, truthiness)?.equals(&fold(right, truthiness)?),
+        )),
+        Expr::BinaryNotEqual(left, right, _) => Some(Constant::Boolean(
+            !fold(left, truthiness)?.equals(&fold(right, truthiness)?),
+        )),
+        Expr::BinaryLess(left, right, _) => {
+            fold(left, truthiness)?.compare(Comparison::Less, &fold(right, truthiness)?)
+        }
+        Expr::BinaryLessEqual(left, right, _) => {
+            fold(left, truthiness)?.compare(Comparison::LessEqual, &fold(right, truthiness)?)
+        }
+        Expr::BinaryGreater(left, right, _) => {
+            fold(left, truthiness)?.compare(Comparison::Greater, &fold(right, truthiness)?)
+        }
+        Expr::BinaryGreaterEqual(left, right, _) => {
+            fold(left, truthiness)?.compare(Comparison::GreaterEqual, &fold(right, truthiness)?)
+        }
+        Expr::BinaryAdd(left, right, _) => fold(left, truthiness)?.add(&fold(right, truthiness)?),
+        Expr::BinarySub(left, right, _) => {
+            numbers(left, right).map(|(l, r)| Constant::Number(l - r))
+        }
+        Expr::BinaryMul(left, right, _) => {
+            fold(left, truthiness)?.multiply(&fold(right, truthiness)?)
+        }
+        Expr::BinaryDiv(left, right, _) => {
+            // division by zero is an error at runtime
+            let (l, r) = numbers(left, right)?;
+            (r != 0.0).then(|| Constant::Number(l / r))
+        }
+        _ => None,
+    }
+}
+
+// Whether a throw of the statement can leave it, values thrown in a try body are caught there.
+// Calls are not followed, a function called in the statement may still throw
+fn throws(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Throw(_, _) => true,
+        Stmt::Block(stmts) => stmts.iter().any(throws),
+        Stmt::If(_, then_branch, else_branch, _) => {
+            throws(then_branch) || else_branch.as_deref().is_some_and(throws)
+        }
+        Stmt::While(_, body, _) | Stmt::DoWhile(body, _, _) | Stmt::ForIn(_, _, body) => {
+            throws(body)
+        }
+        Stmt::Switch(_, cases, default) => {
+            cases.iter().any(|(_, body, _)| body.iter().any(throws))
+                || default.iter().flatten().any(throws)
+        }
+        Stmt::Try(_, _, handler) => handler.iter().any(throws),
+        _ => false,
+    }
+}
+
+impl StmtVisitor<()> for Analyzer {
+    fn visit_print(&mut self, expr: &Box<Expr>) {
+        expr.accept(self);
+    }
+
+    fn visit_expr(&mut self, expr: &Box<Expr>) {
+        expr.accept(self);
+    }
+
+    fn visit_var_declaration(
+        &mut self,
+        name: &Symbol,
+        initializer: &Option<Box<Expr>>,
+        span: &Span,
+    ) {
+        self.bind(name.clone(), None);
+
+        // the initializer is evaluated before the variable is declared
+        if let Some(expr) = initializer {
+            expr.accept(self);
+        }
+
+        self.declare(name.clone(), false, false, *span);
+    }
+
+    fn visit_multi_var_declaration(
+        &mut self,
+        declarations: &Vec<(Symbol, Option<Box<Expr>>, Span)>,
+    ) {
+        for (name, initializer, span) in declarations {
+            self.visit_var_declaration(name, initializer, span);
+        }
+    }
+
+    fn visit_const_declaration(&mut self, name: &Symbol, initializer: &Box<Expr>, span: &Span) {
+        self.bind(name.clone(), None);
+        initializer.accept(self);
+        self.declare(name.clone(), false, true, *span);
+    }
+
+    fn visit_block(&mut self, stmts: &Vec<Stmt>) {
+        self.push_scope();
+        for stmt in stmts {
+            stmt.accept(self);
+        }
+        self.pop_scope();
+    }
+
+    fn visit_if(
+        &mut self,
+        condition: &Box<Expr>,
+        then_branch: &Box<Stmt>,
+        else_branch: &Option<Box<Stmt>>,
+        span: &Span,
+    ) {
+        if let Some(constant) = fold(condition, self.options.truthiness) {
+            if !constant.is_truthy(self.options.truthiness) {
+                self.lints.push(Lint::ConstantFalseCondition(*span));
+            }
+        }
+
+        condition.accept(self);
+        then_branch.accept(self);
+        if let Some(else_branch) = else_branch {
+            else_branch.accept(self);
+        }
+    }
+
+    fn visit_while(&mut self, condition: &Box<Expr>, body: &Box<Stmt>, span: &Span) {
+        let truthiness = self.options.truthiness;
+        match fold(condition, truthiness).map(|constant| constant.is_truthy(truthiness)) {
+            Some(true) if !throws(body) => self.lints.push(Lint::InfiniteLoop(*span)),
+            Some(false) => self.lints.push(Lint::ConstantFalseCondition(*span)),
+            _ => {}
+        }
+
+        condition.accept(self);
+        body.accept(self);
+    }
+
+    fn visit_for_in(&mut self, name: &Symbol, collection: &Box<Expr>, body: &Box<Stmt>) {
+        collection.accept(self);
+
+        // the loop variable is only bound in the scope of the body, and like parameters it does
+        // not need to be read
+        self.push_scope();
+        self.bind(name.clone(), None);
+        self.declare(name.clone(), true, false, Span::default());
+        body.accept(self);
+        self.pop_scope();
+    }
+
+    fn visit_do_while(&mut self, body: &Box<Stmt>, condition: &Box<Expr>, span: &Span) {
+        // a constant false condition is the usual way to run a body once, only loops that never
+        // end are reported
+        if let Some(constant) = fold(condition, self.options.truthiness) {
+            if constant.is_truthy(self.options.truthiness) && !throws(body) {
+                self.lints.push(Lint::InfiniteLoop(*span));
+            }
+        }
+
+        body.accept(self);
+        condition.accept(self);
+    }
+
+    fn visit_switch(
+        &mut self,
+        subject: &Box<Expr>,
+        cases: &Vec<(Box<Expr>, Vec<Stmt>, Span)>,
+        default: &Option<Vec<Stmt>>,
+    ) {
+        subject.accept(self);
+
+        let mut matched: Vec<Constant> = Vec::new();
+        for (value, body, span) in cases {
+            if let Some(constant) = fold(value, self.options.truthiness) {
+                if matched.iter().any(|other| other.equals(&constant)) {
+                    self.lints
+                        .push(Lint::DuplicateCase(constant.to_value().to_string(), *span));
+                }
+                matched.push(constant);
+            }
+
+            // each case runs in its own scope, like a block
+            value.accept(self);
+            self.visit_block(body);
+        }
+
+        if let Some(default) = default {
+            self.visit_block(default);
+        }
+    }
+
+    fn visit_function_declaration(
+        &mut self,
+        name: &Symbol,
+        arguments: &Vec<Symbol>,
+        body: &Box<Stmt>,
+    ) {
+        self.bind(name.clone(), Some(arguments.len()));
+        self.declare(name.clone(), true, false, Span::default());
+
+        // parameters live in their own scope around the body
+        self.push_scope();
+        for argument in arguments {
+            self.bind(argument.clone(), None);
+            self.declare(argument.clone(), true, false, Span::default());
+        }
+
+        body.accept(self);
+        self.pop_scope();
+    }
+
+    fn visit_throw(&mut self, expr: &Box<Expr>, _span: &Span) {
+        expr.accept(self);
+    }
+
+    fn visit_try(&mut self, body: &Vec<Stmt>, name: &Symbol, handler: &Vec<Stmt>) {
+        self.visit_block(body);
+
+        // the caught value is only bound in the scope of the handler, and like parameters it
+        // does not need to be read
+        self.push_scope();
+        self.bind(name.clone(), None);
+        self.declare(name.clone(), true, false, Span::default());
+        self.visit_block(handler);
+        self.pop_scope();
+    }
+
+    fn visit_assert(&mut self, condition: &Box<Expr>, message: &Option<Box<Expr>>, _span: &Span) {
+        condition.accept(self);
+        if let Some(message) = message {
+            message.accept(self);
+        }
+    }
+
+    fn visit_import(&mut self, _path: &String, alias: &Option<Symbol>) {
+        if let Some(alias) = alias {
+            self.bind(alias.clone(), None);
+            self.declare(alias.clone(), true, false, Span::default());
+        }
+    }
+}
+
+impl ExprVisitor<()> for Analyzer {
+    fn visit_assign(&mut self, left: &Symbol, right: &Box<Expr>, span: &Span) {
+        if self
+            .resolve(left)
+            .is_some_and(|declaration| declaration.constant)
+        {
+            self.lints
+                .push(Lint::ConstantAssignment(left.to_string(), *span));
+        }
+
+        self.bind(left.clone(), None);
+        right.accept(self);
+    }
+
+    fn visit_index_assign(&mut self, list: &Box<Expr>, index: &Box<Expr>, value: &Box<Expr>) {
+        list.accept(self);
+        index.accept(self);
+        value.accept(self);
+    }
+
+    fn visit_set(&mut self, object: &Box<Expr>, _name: &Symbol, value: &Box<Expr>) {
+        object.accept(self);
+        value.accept(self);
+    }
+
+    fn visit_binary_or(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) {
+        left.accept(self);
+        right.accept(self);
+    }
+
+    fn visit_binary_and(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) {
+        left.accept(self);
+        right.accept(self);
+    }
+
+    fn visit_binary_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) {
+        self.visit_comparison(left, right, span);
+    }
+
+    fn visit_binary_not_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) {
+        self.visit_comparison(left, right, span);
+    }
+
+    fn visit_binary_less(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) {
+        self.visit_comparison(left, right, span);
+    }
+
+    fn visit_binary_less_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) {
+        self.visit_comparison(left, right, span);
+    }
+
+    fn visit_binary_greater(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) {
+        self.visit_comparison(left, right, span);
+    }
+
+    fn visit_binary_greater_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) {
+        self.visit_comparison(left, right, span);
+    }
+
+    fn visit_binary_add(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) {
+        left.accept(self);
+        right.accept(self);
+    }
+
+    fn visit_binary_sub(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) {
+        left.accept(self);
+        right.accept(self);
+    }
+
+    fn visit_binary_mul(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) {
+        left.accept(self);
+        right.accept(self);
+    }
+
+    fn visit_binary_div(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) {
+        left.accept(self);
+        right.accept(self);
+    }
+
+    fn visit_range(&mut self, start: &Box<Expr>, end: &Box<Expr>, _inclusive: &bool, _span: &Span) {
+        start.accept(self);
+        end.accept(self);
+    }
+
+    fn visit_unary_bang(&mut self, expr: &Box<Expr>) {
+        expr.accept(self);
+    }
+
+    fn visit_unary_minus(&mut self, expr: &Box<Expr>) {
+        expr.accept(self);
+    }
+
+    fn visit_literal_string(&mut self, _value: &String) {}
+
+    fn visit_literal_number(&mut self, _value: &f64) {}
+
+    fn visit_false(&mut self) {}
+
+    fn visit_true(&mut self) {}
+
+    fn visit_nil(&mut self) {}
+
+    fn visit_identifier(&mut self, value: &Symbol) {
+        self.read(value.clone());
+    }
+
+    fn visit_call(&mut self, callee: &Box<Expr>, arguments: &Vec<Expr>, span: &Span) {
+        if let Expr::Identifier(name) = callee.as_ref() {
+            self.calls.push((name.clone(), arguments.len(), *span));
+        }
+
+        callee.accept(self);
+        for argument in arguments {
+            argument.accept(self);
+        }
+    }
+
+    fn visit_index(&mut self, list: &Box<Expr>, index: &Box<Expr>) {
+        list.accept(self);
+        index.accept(self);
+    }
+
+    fn visit_get(&mut self, object: &Box<Expr>, _name: &Symbol) {
+        object.accept(self);
+    }
+
+    fn visit_list(&mut self, elements: &Vec<Expr>) {
+        for element in elements {
+            element.accept(self);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rstest::rstest;
+
+    use crate::lox::{self, Span, Truthiness};
+
+    use super::{Analyzer, AnalyzerOptions, Lint};
+
+    #[rstest]
+    #[case::infinite_loop("while (true) print 1;", vec![Lint::InfiniteLoop(Span { line: 1 })])]
+    #[case::folded_infinite_loop("while (1 < 2 and !nil) print 1;", vec![Lint::InfiniteLoop(Span { line: 1 })])]
+    #[case::dead_loop("while (false) print 1;", vec![Lint::ConstantFalseCondition(Span { line: 1 })])]
+    #[case::dead_if("if (1 + 1 == 3) print 1;", vec![Lint::ConstantFalseCondition(Span { line: 1 })])]
+    #[case::dead_if_nil("if (nil) print 1; else print 2;", vec![Lint::ConstantFalseCondition(Span { line: 1 })])]
+    #[case::if_zero("if (0) print 1;", vec![])]
+    #[case::do_while_infinite("do print 1; while (true);", vec![Lint::InfiniteLoop(Span { line: 1 })])]
+    #[case::do_while_once("do print 1; while (false);", vec![])]
+    #[case::loop_throws("while (true) { if (a) throw a; }", vec![])]
+    #[case::do_while_throws("do { switch (a) { case 1: throw a; } } while (true);", vec![])]
+    #[case::loop_throws_in_handler("while (true) try { f(); } catch (e) { throw e; }", vec![])]
+    #[case::loop_catches_throw(
+        "while (true) try { throw 1; } catch (e) {}",
+        vec![Lint::InfiniteLoop(Span { line: 1 })]
+    )]
+    #[case::loop_declares_throwing_function(
+        "while (true) { fun f() { throw 1; } }",
+        vec![Lint::InfiniteLoop(Span { line: 1 })]
+    )]
+    #[case::duplicate_case(
+        "switch (a) { case 1: print 1; case 2: case 3 - 2: print 2; }",
+        vec![Lint::DuplicateCase("1".to_string(), Span { line: 1 })]
+    )]
+    #[case::distinct_cases("switch (a) { case 1: case \"1\": case b: case b: }", vec![])]
+    #[case::switch_arity(
+        "fun f(x) {} switch (f()) { case f(1, 2): default: f(); }",
+        vec![
+            Lint::ArityMismatch("f".to_string(), 1, 0, Span { line: 1 }),
+            Lint::ArityMismatch("f".to_string(), 1, 2, Span { line: 1 }),
+            Lint::ArityMismatch("f".to_string(), 1, 0, Span { line: 1 }),
+        ]
+    )]
+    #[case::folded_repetition("while (\"ab\" * 2 == \"a\" + \"bab\") print 1;", vec![Lint::InfiniteLoop(Span { line: 1 })])]
+    #[case::repetition_error("while (\"ab\" * -1) print 1;", vec![])]
+    #[case::loop_empty_string("while (\"\") print 1;", vec![Lint::InfiniteLoop(Span { line: 1 })])]
+    #[case::folded_string_comparison("while (\"a\" < \"b\") print 1;", vec![Lint::InfiniteLoop(Span { line: 1 })])]
+    #[case::self_comparison(
+        "var a = 1;\nif (a == a) print a;",
+        vec![Lint::SelfComparison("a".to_string(), Span { line: 2 })]
+    )]
+    #[case::nested(
+        "fun f(b) { while (b < b) {} }",
+        vec![Lint::SelfComparison("b".to_string(), Span { line: 1 })]
+    )]
+    #[case::variable_condition("var a = true; while (a) a = false;", vec![])]
+    #[case::runtime_error("if (1 / 0) print 1;", vec![])]
+    #[case::different_variables("var a = 1; var b = 2; print a < b;", vec![])]
+    #[case::arity_mismatch(
+        "fun f(a, b) {} f(1);",
+        vec![Lint::ArityMismatch("f".to_string(), 2, 1, Span { line: 1 })]
+    )]
+    #[case::arity_before_declaration(
+        "f(); fun f(a) {}",
+        vec![Lint::ArityMismatch("f".to_string(), 1, 0, Span { line: 1 })]
+    )]
+    #[case::arity_nested_call(
+        "fun g(a) {} fun f() { print g(g(1), 2); }",
+        vec![Lint::ArityMismatch("g".to_string(), 1, 2, Span { line: 1 })]
+    )]
+    #[case::arity_matching("fun f(a, b) {} f(1, 2);", vec![])]
+    #[case::arity_redeclared("fun f(a) {} fun f(a, b) {} f(1, 2);", vec![])]
+    #[case::arity_reassigned("fun f(a) {} f = len; f(\"a\", 2);", vec![])]
+    #[case::arity_parameter("fun f(a) {} fun apply(f) { f(); }", vec![])]
+    #[case::arity_unknown("len(1, 2);", vec![])]
+    #[case::constant_assignment(
+        "const a = 1; { a = 2; }",
+        vec![Lint::ConstantAssignment("a".to_string(), Span { line: 1 })]
+    )]
+    #[case::shadowed_constant_assignment("const a = 1; { var a = 2; a = 3; }", vec![])]
+    #[case::constant_redeclared_as_variable("const a = 1; var a = 2; a = 3;", vec![])]
+    fn test_analyze(#[case] source: &str, #[case] expected: Vec<Lint>) -> Result<(), String> {
+        let program = lox::parse(source)?;
+
+        assert_eq!(Analyzer::analyze(&program.statements), expected);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::unused_local("{ var a = 1; }", vec![Lint::UnusedVariable("a".to_string(), Span { line: 1 })])]
+    #[case::assigned_only("{ var a = 1; a = 2; }", vec![Lint::UnusedVariable("a".to_string(), Span { line: 1 })])]
+    #[case::read_local("{ var a = 1; print a; }", vec![])]
+    #[case::unused_in_case(
+        "switch (1) { case 1: var a = 1; default: var b = 2; print b; }",
+        vec![Lint::UnusedVariable("a".to_string(), Span { line: 1 })]
+    )]
+    #[case::read_in_nested_block("{ var a = 1; { print a + 1; } }", vec![])]
+    #[case::unused_global("var a = 1;", vec![])]
+    #[case::unused_parameter("fun f(a) {}", vec![])]
+    #[case::unused_caught("try { throw 1; } catch (e) {}", vec![])]
+    #[case::unused_in_try(
+        "try { var a = 1; } catch (e) { var b = e; print b; }",
+        vec![Lint::UnusedVariable("a".to_string(), Span { line: 1 })]
+    )]
+    #[case::read_by_callee("fun g() { print a; } { var a = 1; g(); }", vec![])]
+    #[case::shadowed_global(
+        "var a = 1; { var a = 2; print a; }",
+        vec![Lint::ShadowedVariable("a".to_string(), Span { line: 1 })]
+    )]
+    #[case::shadowed_parameter(
+        "fun f(a) { var a = 2; print a; }",
+        vec![Lint::ShadowedVariable("a".to_string(), Span { line: 1 })]
+    )]
+    #[case::shadowed_caught(
+        "try {} catch (e) { var e = 2; print e; }",
+        vec![Lint::ShadowedVariable("e".to_string(), Span { line: 1 })]
+    )]
+    #[case::initializer_reads_outer(
+        "var a = 1; { var a = a + 1; }",
+        vec![
+            Lint::ShadowedVariable("a".to_string(), Span { line: 1 }),
+            Lint::UnusedVariable("a".to_string(), Span { line: 1 })
+        ]
+    )]
+    #[case::sibling_blocks("{ var a = 1; print a; } { var a = 2; print a; }", vec![])]
+    #[case::redeclared_global("var a = 1; var a = 2;", vec![])]
+    #[case::read_innermost(
+        "{ var a = 1; { var a = 2; { print a; } } }",
+        vec![
+            Lint::ShadowedVariable("a".to_string(), Span { line: 1 }),
+            Lint::UnusedVariable("a".to_string(), Span { line: 1 })
+        ]
+    )]
+    #[case::read_innermost_after_inner_scope(
+        "{ var a = 1; { var a = 2; print a; } print a; }",
+        vec![Lint::ShadowedVariable("a".to_string(), Span { line: 1 })]
+    )]
+    #[case::read_innermost_parameter(
+        "{ var a = 1; fun f(a) { print a; } f(2); }",
+        vec![Lint::UnusedVariable("a".to_string(), Span { line: 1 })]
+    )]
+    fn test_analyze_warnings(
+        #[case] source: &str,
+        #[case] expected: Vec<Lint>,
+    ) -> Result<(), String> {
+        let program = lox::parse(source)?;
+        let options = AnalyzerOptions {
+            warnings: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            Analyzer::analyze_with_options(&program.statements, options),
+            expected
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint_spans() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a program with lints on different lines
+        let source = "fun f(a) {}
+const c = 1;
+while (true) {}
+do {} while (true);
+if (false) {}
+f(1,
+  2);
+{
+  c = 2;
+  var c = 3;
+}
+switch (1) {
+  case 1:
+  case 1:
+}";
+        let program = lox::parse(source)?;
+        let options = AnalyzerOptions {
+            warnings: true,
+            ..Default::default()
+        };
+
+        ///////////////////////////////////////////////////////////////////////
+        // When analyzing it
+        let lints = Analyzer::analyze_with_options(&program.statements, options);
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then each lint points at the line of its loop, condition, assignment, declaration,
+        // case or call
+        assert_eq!(
+            lints,
+            vec![
+                Lint::InfiniteLoop(Span { line: 3 }),
+                Lint::InfiniteLoop(Span { line: 4 }),
+                Lint::ConstantFalseCondition(Span { line: 5 }),
+                Lint::ConstantAssignment("c".to_string(), Span { line: 9 }),
+                Lint::ShadowedVariable("c".to_string(), Span { line: 10 }),
+                Lint::DuplicateCase("1".to_string(), Span { line: 14 }),
+                Lint::ArityMismatch("f".to_string(), 1, 2, Span { line: 6 }),
+                Lint::UnusedVariable("c".to_string(), Span { line: 10 }),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_analyze_warnings_disabled() -> Result<(), String> {
+        let program = lox::parse("var a = 1; { var a = 2; var b = 3; }")?;
+
+        assert_eq!(Analyzer::analyze(&program.statements), vec![]);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::if_zero("if (0) print 1;", vec![Lint::ConstantFalseCondition(Span { line: 1 })])]
+    #[case::loop_empty_string("while (\"\") print 1;", vec![Lint::ConstantFalseCondition(Span { line: 1 })])]
+    #[case::loop_number("while (1) print 1;", vec![Lint::InfiniteLoop(Span { line: 1 })])]
+    fn test_analyze_permissive_truthiness(
+        #[case] source: &str,
+        #[case] expected: Vec<Lint>,
+    ) -> Result<(), String> {
+        let program = lox::parse(source)?;
+        let options = AnalyzerOptions {
+            truthiness: Truthiness::Permissive,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            Analyzer::analyze_with_options(&program.statements, options),
+            expected
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+use super::{
+    Diagnostic, Interpreter, LanguageVersion, Parser, ScanError, Scanner, SpannedToken, Stmt, Value,
+};
+
+// Output of the front end for a source: its tokens and its syntax tree
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub tokens: Vec<SpannedToken>,
+    pub statements: Vec<Stmt>,
+}
+
+// Scans the source into tokens, the last one being Eof
+pub fn tokenize(source: &str) -> Result<Vec<SpannedToken>, Diagnostic> {
+    Ok(Scanner::new(source.to_string()).scan_spanned_tokens()?)
+}
+
+// Scans and parses the source, without executing it. Sources without a version header are
+// parsed as the default language version
+pub fn parse(source: &str) -> Result<Program, Diagnostic> {
+    parse_with_version(source, LanguageVersion::default())
+}
+
+// Same as parse, for sources without a version header being of the given language version
+pub fn parse_with_version(source: &str, version: LanguageVersion) -> Result<Program, Diagnostic> {
+    // the version header of the source takes precedence, as when running it
+    let version = LanguageVersion::from_source(source)
+        .map_err(|e| Diagnostic::error("E0204", e, None))?
+        .unwrap_or(version);
+    let tokens = tokenize(source)?;
+
+    let mut parser = Parser::from_spanned_tokens(tokens.clone());
+    parser.set_language_version(version);
+    let statements = parser.parse()?;
+
+    Ok(Program { tokens, statements })
+}
+
+// Whether the source is the start of a program that more input could complete, e.g. with a
+// block or a string left open. A REPL keeps reading lines while its input is incomplete
+pub fn is_incomplete(source: &str) -> bool {
+    let tokens = match Scanner::new(source.to_string()).scan_spanned_tokens() {
+        Ok(tokens) => tokens,
+        Err(e) => return matches!(e, ScanError::UnterminatedString(_)),
+    };
+
+    Parser::from_spanned_tokens(tokens)
+        .parse()
+        .is_err_and(|e| e.is_unexpected_eof())
+}
+
+// Runs the source in a new interpreter with the default options. The result is the value of
+// the last expression statement, nil if there is none
+pub fn run(source: &str) -> Result<Value, String> {
+    let mut interpreter = Interpreter::new();
+    let result = interpreter.execute(source.to_string())?;
+
+    let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+    Ok(result_guard.clone())
+}
+
+// Syntax tree of the statements as JSON, for external tools consuming the parse tree
+#[cfg(feature = "serde")]
+pub fn ast_to_json(statements: &[Stmt]) -> Result<String, String> {
+    serde_json::to_string_pretty(statements).map_err(|e| e.to_string())
+}
+
+// Tokens as JSON, with the line each one was found at
+#[cfg(feature = "serde")]
+pub fn tokens_to_json(tokens: &[SpannedToken]) -> Result<String, String> {
+    serde_json::to_string_pretty(tokens).map_err(|e| e.to_string())
+}
+
+// Statements read back from the JSON produced by ast_to_json
+#[cfg(feature = "serde")]
+pub fn ast_from_json(json: &str) -> Result<Vec<Stmt>, String> {
+    serde_json::from_str(json).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rstest::*;
+
+    use crate::lox::{self, Expr, Interpreter, LanguageVersion, Stmt, Token, Value};
+
+    #[test]
+    fn test_tokenize() -> Result<(), String> {
+        let tokens = lox::tokenize("print 1;")?;
+
+        let tokens: Vec<Token> = tokens.into_iter().map(|t| t.token).collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Print,
+                Token::NumberLiteral(1.0),
+                Token::Semicolon,
+                Token::Eof
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse() -> Result<(), String> {
+        let program = lox::parse("print 1;")?;
+
+        assert_eq!(program.tokens.len(), 4);
+        assert_eq!(
+            program.statements,
+            vec![Stmt::Print(Box::new(Expr::LiteralNumber(1.0)))]
+        );
+
+        // the parsed program can be executed later on
+        let mut interpreter = Interpreter::new();
+        interpreter.execute_program(&program)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_error() {
+        assert!(lox::parse("print 1").is_err());
+    }
+
+    #[rstest]
+    #[case::default_version("var a = [1];", LanguageVersion::default(), None)]
+    #[case::classic("var a = [1];", LanguageVersion::Lox1, Some("E0201"))]
+    #[case::classic_header(
+        "// lox-version: 1\nvar a = [1];",
+        LanguageVersion::default(),
+        Some("E0201")
+    )]
+    #[case::extended_header("// lox-version: extended\nvar a = [1];", LanguageVersion::Lox1, None)]
+    #[case::unknown_header(
+        "// lox-version: 2\nvar a = 1;",
+        LanguageVersion::default(),
+        Some("E0204")
+    )]
+    fn test_parse_with_version(
+        #[case] source: &str,
+        #[case] version: LanguageVersion,
+        #[case] code: Option<&str>,
+    ) {
+        let result = lox::parse_with_version(source, version);
+
+        assert_eq!(result.err().map(|e| e.code), code);
+    }
+
+    #[rstest]
+    #[case::complete("print 1;", false)]
+    #[case::empty("", false)]
+    #[case::missing_semicolon("print 1", true)]
+    #[case::open_block("fun f() {\n  print 1;", true)]
+    #[case::open_call("print f(1,", true)]
+    #[case::open_string("print \"a\nb", true)]
+    #[case::open_raw_string("print \"\"\"a\"\"", true)]
+    #[case::unexpected_token("print );", false)]
+    #[case::unexpected_last_token("import 1", false)]
+    #[case::scan_error("print @", false)]
+    fn test_is_incomplete(#[case] source: &str, #[case] expected: bool) {
+        assert_eq!(lox::is_incomplete(source), expected);
+    }
+
+    #[test]
+    fn test_run() -> Result<(), String> {
+        assert_eq!(lox::run("len(\"abc\") * 2;")?, Value::Number(6.0));
+        assert_eq!(lox::run("var a = 1; a = a + 1;")?, Value::Number(2.0));
+        assert_eq!(lox::run("var a = 1; a; print a;")?, Value::Number(1.0));
+        assert_eq!(lox::run("var a = 1;")?, Value::Nil);
+        assert!(lox::run("1 + nil;").is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_ast_json_round_trip() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a parsed program using every kind of statement
+        let program = lox::parse(
+            "import \"m.lox\" as m; var a = [1, \"s\", nil]; fun f(x) { if (!x) print -x; else while (x < 1) x = x + 1; } a[0] = m.g(true or false);",
+        )?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // When serializing its statements to JSON and reading them back
+        let json = lox::ast_to_json(&program.statements)?;
+        let statements = lox::ast_from_json(&json)?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the statements are the same, including the operator spans
+        assert_eq!(statements, program.statements);
+        assert!(json.contains("\"BinaryOr\""), "unexpected JSON: {json}");
+
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_tokens_to_json() -> Result<(), String> {
+        let tokens = lox::tokenize("print\n\"a\";")?;
+
+        let json: serde_json::Value =
+            serde_json::from_str(&lox::tokens_to_json(&tokens)?).map_err(|e| e.to_string())?;
+
+        assert_eq!(
+            json[1],
+            serde_json::json!({"token": {"StringLiteral": "a"}, "span": {"line": 2}})
+        );
+        assert_eq!(json.as_array().map(Vec::len), Some(4));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,877 @@
+use super::{Expr, ExprVisitor, Span, Stmt, StmtVisitor, Symbol};
+
+// Layout of the printed AST
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AstStyle {
+    // every node on a single line as braced s-expressions, e.g. `{print {1 + 2}}`
+    #[default]
+    SExpression,
+    // one node per line, children indented under their parent
+    Tree,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AstPrinterOptions {
+    pub style: AstStyle,
+    // annotate the nodes that keep their location with its line, only used by the tree style
+    pub spans: bool,
+}
+
+// Renders parsed programs as text, e.g. for debugging the parser or dumping the AST from the CLI.
+// Visiting a node directly renders it as an s-expression
+pub struct AstPrinter {
+    options: AstPrinterOptions,
+}
+
+impl AstPrinter {
+    pub fn new() -> Self {
+        Self::with_options(AstPrinterOptions::default())
+    }
+
+    pub fn with_options(options: AstPrinterOptions) -> Self {
+        Self { options }
+    }
+
+    // Renders the statements one after the other, each ending with a new line
+    pub fn print(&mut self, statements: &[Stmt]) -> String {
+        statements
+            .iter()
+            .map(|stmt| self.print_stmt(stmt) + "\n")
+            .collect()
+    }
+
+    pub fn print_stmt(&mut self, stmt: &Stmt) -> String {
+        match self.options.style {
+            AstStyle::SExpression => stmt.accept(self),
+            AstStyle::Tree => stmt.accept(&mut self.tree_builder()).render(),
+        }
+    }
+
+    pub fn print_expr(&mut self, expr: &Expr) -> String {
+        match self.options.style {
+            AstStyle::SExpression => expr.accept(self),
+            AstStyle::Tree => expr.accept(&mut self.tree_builder()).render(),
+        }
+    }
+
+    fn tree_builder(&self) -> TreeBuilder {
+        TreeBuilder {
+            spans: self.options.spans,
+        }
+    }
+}
+
+impl Default for AstPrinter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// S-expression style
+
+impl ExprVisitor<String> for AstPrinter {
+    fn visit_assign(&mut self, left: &Symbol, right: &Box<Expr>, _span: &Span) -> String {
+        format!("{{{} = {}}}", left, right.accept(self))
+    }
+
+    fn visit_index_assign(
+        &mut self,
+        list: &Box<Expr>,
+        index: &Box<Expr>,
+        value: &Box<Expr>,
+    ) -> String {
+        format!(
+            "{{{}[{}] = {}}}",
+            list.accept(self),
+            index.accept(self),
+            value.accept(self)
+        )
+    }
+
+    fn visit_set(&mut self, object: &Box<Expr>, name: &Symbol, value: &Box<Expr>) -> String {
+        format!(
+            "{{{}.{} = {}}}",
+            object.accept(self),
+            name,
+            value.accept(self)
+        )
+    }
+
+    fn visit_binary_or(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) -> String {
+        format!("{{{} or {}}}", left.accept(self), right.accept(self))
+    }
+
+    fn visit_binary_and(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) -> String {
+        format!("{{{} and {}}}", left.accept(self), right.accept(self))
+    }
+
+    fn visit_binary_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) -> String {
+        format!("{{{} == {}}}", left.accept(self), right.accept(self))
+    }
+
+    fn visit_binary_not_equal(
+        &mut self,
+        left: &Box<Expr>,
+        right: &Box<Expr>,
+        _span: &Span,
+    ) -> String {
+        format!("{{{} != {}}}", left.accept(self), right.accept(self))
+    }
+
+    fn visit_binary_less(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) -> String {
+        format!("{{{} < {}}}", left.accept(self), right.accept(self))
+    }
+
+    fn visit_binary_less_equal(
+        &mut self,
+        left: &Box<Expr>,
+        right: &Box<Expr>,
+        _span: &Span,
+    ) -> String {
+        format!("{{{} <= {}}}", left.accept(self), right.accept(self))
+    }
+
+    fn visit_binary_greater(
+        &mut self,
+        left: &Box<Expr>,
+        right: &Box<Expr>,
+        _span: &Span,
+    ) -> String {
+        format!("{{{} > {}}}", left.accept(self), right.accept(self))
+    }
+
+    fn visit_binary_greater_equal(
+        &mut self,
+        left: &Box<Expr>,
+        right: &Box<Expr>,
+        _span: &Span,
+    ) -> String {
+        format!("{{{} >= {}}}", left.accept(self), right.accept(self))
+    }
+
+    fn visit_binary_add(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) -> String {
+        format!("{{{} + {}}}", left.accept(self), right.accept(self))
+    }
+
+    fn visit_binary_sub(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) -> String {
+        format!("{{{} - {}}}", left.accept(self), right.accept(self))
+    }
+
+    fn visit_binary_mul(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) -> String {
+        format!("{{{} * {}}}", left.accept(self), right.accept(self))
+    }
+
+    fn visit_binary_div(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) -> String {
+        format!("{{{} / {}}}", left.accept(self), right.accept(self))
+    }
+
+    fn visit_range(
+        &mut self,
+        start: &Box<Expr>,
+        end: &Box<Expr>,
+        inclusive: &bool,
+        _span: &Span,
+    ) -> String {
+        let operator = if *inclusive { "..=" } else { ".." };
+        format!("{{{} {operator} {}}}", start.accept(self), end.accept(self))
+    }
+
+    fn visit_unary_bang(&mut self, expr: &Box<Expr>) -> String {
+        format!("{{!{}}}", expr.accept(self))
+    }
+
+    fn visit_unary_minus(&mut self, expr: &Box<Expr>) -> String {
+        format!("{{-{}}}", expr.accept(self))
+    }
+
+    fn visit_call(&mut self, callee: &Box<Expr>, arguments: &Vec<Expr>, _span: &Span) -> String {
+        let mut call_str = format!("{{call {}(", callee.accept(self));
+
+        for (i, arg) in arguments.iter().enumerate() {
+            call_str.push_str(&arg.accept(self));
+
+            if i < arguments.len() - 1 {
+                call_str.push_str(", ");
+            }
+        }
+
+        call_str.push_str(")}");
+
+        call_str
+    }
+
+    fn visit_index(&mut self, list: &Box<Expr>, index: &Box<Expr>) -> String {
+        format!("{{{}[{}]}}", list.accept(self), index.accept(self))
+    }
+
+    fn visit_get(&mut self, object: &Box<Expr>, name: &Symbol) -> String {
+        format!("{{{}.{}}}", object.accept(self), name)
+    }
+
+    fn visit_list(&mut self, elements: &Vec<Expr>) -> String {
+        let mut list_str = String::from("[");
+
+        for (i, element) in elements.iter().enumerate() {
+            list_str.push_str(&element.accept(self));
+
+            if i < elements.len() - 1 {
+                list_str.push_str(", ");
+            }
+        }
+
+        list_str.push(']');
+
+        list_str
+    }
+
+    fn visit_literal_string(&mut self, value: &String) -> String {
+        format!("\"{}\"", value)
+    }
+
+    fn visit_literal_number(&mut self, value: &f64) -> String {
+        value.to_string()
+    }
+
+    fn visit_false(&mut self) -> String {
+        "false".to_string()
+    }
+
+    fn visit_true(&mut self) -> String {
+        "true".to_string()
+    }
+
+    fn visit_nil(&mut self) -> String {
+        "nil".to_string()
+    }
+
+    fn visit_identifier(&mut self, value: &Symbol) -> String {
+        value.to_string()
+    }
+}
+
+impl StmtVisitor<String> for AstPrinter {
+    fn visit_print(&mut self, expr: &Box<Expr>) -> String {
+        format!("{{print {}}}", expr.accept(self))
+    }
+
+    fn visit_expr(&mut self, expr: &Box<Expr>) -> String {
+        expr.accept(self)
+    }
+
+    fn visit_var_declaration(
+        &mut self,
+        name: &Symbol,
+        initializer: &Option<Box<Expr>>,
+        _span: &Span,
+    ) -> String {
+        match initializer {
+            Some(expr) => format!("{{var {} = {}}}", name, expr.accept(self)),
+            None => format!("{{var {}}}", name),
+        }
+    }
+
+    fn visit_multi_var_declaration(
+        &mut self,
+        declarations: &Vec<(Symbol, Option<Box<Expr>>, Span)>,
+    ) -> String {
+        let declarations = declarations
+            .iter()
+            .map(|(name, initializer, _)| match initializer {
+                Some(expr) => format!("{} = {}", name, expr.accept(self)),
+                None => name.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!("{{var {}}}", declarations)
+    }
+
+    fn visit_const_declaration(
+        &mut self,
+        name: &Symbol,
+        initializer: &Box<Expr>,
+        _span: &Span,
+    ) -> String {
+        format!("{{const {} = {}}}", name, initializer.accept(self))
+    }
+
+    fn visit_block(&mut self, stmts: &Vec<Stmt>) -> String {
+        let mut block = String::from("{");
+
+        for stmt in stmts {
+            block.push_str(&stmt.accept(self));
+        }
+
+        block.push('}');
+
+        block
+    }
+
+    fn visit_if(
+        &mut self,
+        condition: &Box<Expr>,
+        then_branch: &Box<Stmt>,
+        else_branch: &Option<Box<Stmt>>,
+        _span: &Span,
+    ) -> String {
+        let mut if_stmt = format!(
+            "{{if {} then {} ",
+            condition.accept(self),
+            then_branch.accept(self)
+        );
+
+        if let Some(else_branch) = else_branch {
+            if_stmt.push_str(&format!(" else {}", else_branch.accept(self)));
+        }
+
+        if_stmt.push('}');
+
+        if_stmt
+    }
+
+    fn visit_while(&mut self, condition: &Box<Expr>, body: &Box<Stmt>, _span: &Span) -> String {
+        format!(
+            "{{while {} then {}}}",
+            condition.accept(self),
+            body.accept(self)
+        )
+    }
+
+    fn visit_do_while(&mut self, body: &Box<Stmt>, condition: &Box<Expr>, _span: &Span) -> String {
+        format!(
+            "{{do {} while {}}}",
+            body.accept(self),
+            condition.accept(self)
+        )
+    }
+
+    fn visit_for_in(&mut self, name: &Symbol, collection: &Box<Expr>, body: &Box<Stmt>) -> String {
+        format!(
+            "{{for {name} in {} {}}}",
+            collection.accept(self),
+            body.accept(self)
+        )
+    }
+
+    fn visit_switch(
+        &mut self,
+        subject: &Box<Expr>,
+        cases: &Vec<(Box<Expr>, Vec<Stmt>, Span)>,
+        default: &Option<Vec<Stmt>>,
+    ) -> String {
+        let mut switch_stmt = format!("{{switch {}", subject.accept(self));
+
+        for (value, body, _) in cases {
+            switch_stmt.push_str(&format!(" {{case {}: ", value.accept(self)));
+            for stmt in body {
+                switch_stmt.push_str(&stmt.accept(self));
+            }
+            switch_stmt.push('}');
+        }
+
+        if let Some(default) = default {
+            switch_stmt.push_str(" {default: ");
+            for stmt in default {
+                switch_stmt.push_str(&stmt.accept(self));
+            }
+            switch_stmt.push('}');
+        }
+
+        switch_stmt.push('}');
+
+        switch_stmt
+    }
+
+    fn visit_function_declaration(
+        &mut self,
+        name: &Symbol,
+        arguments: &Vec<Symbol>,
+        body: &Box<Stmt>,
+    ) -> String {
+        let mut function_decl = format!("{{fun {}(", name);
+
+        for (i, arg) in arguments.iter().enumerate() {
+            function_decl.push_str(arg);
+
+            if i < arguments.len() - 1 {
+                function_decl.push_str(", ");
+            }
+        }
+
+        function_decl.push_str(") ");
+        function_decl.push_str(format!("{{ {} }}", body.accept(self)).as_str());
+
+        function_decl
+    }
+
+    fn visit_throw(&mut self, expr: &Box<Expr>, _span: &Span) -> String {
+        format!("{{throw {}}}", expr.accept(self))
+    }
+
+    fn visit_try(&mut self, body: &Vec<Stmt>, name: &Symbol, handler: &Vec<Stmt>) -> String {
+        format!(
+            "{{try {} catch {name} {}}}",
+            self.visit_block(body),
+            self.visit_block(handler)
+        )
+    }
+
+    fn visit_assert(
+        &mut self,
+        condition: &Box<Expr>,
+        message: &Option<Box<Expr>>,
+        _span: &Span,
+    ) -> String {
+        match message {
+            Some(message) => format!(
+                "{{assert {}, {}}}",
+                condition.accept(self),
+                message.accept(self)
+            ),
+            None => format!("{{assert {}}}", condition.accept(self)),
+        }
+    }
+
+    fn visit_import(&mut self, path: &String, alias: &Option<Symbol>) -> String {
+        match alias {
+            Some(alias) => format!("{{import \"{}\" as {}}}", path, alias),
+            None => format!("{{import \"{}\"}}", path),
+        }
+    }
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// Tree style
+
+// Node of the printed tree
+struct Node {
+    label: String,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn leaf(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            children: Vec::new(),
+        }
+    }
+
+    fn branch(label: impl Into<String>, children: Vec<Node>) -> Self {
+        Self {
+            label: label.into(),
+            children,
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut lines = Vec::new();
+        self.render_lines(0, &mut lines);
+        lines.join("\n")
+    }
+
+    fn render_lines(&self, depth: usize, lines: &mut Vec<String>) {
+        lines.push(format!("{}{}", "  ".repeat(depth), self.label));
+        for child in self.children.iter() {
+            child.render_lines(depth + 1, lines);
+        }
+    }
+}
+
+// Builds the tree of a node for the tree style
+struct TreeBuilder {
+    spans: bool,
+}
+
+impl TreeBuilder {
+    // Label of a node that keeps its location, followed by its line if spans are printed
+    fn located(&self, label: impl Into<String>, span: &Span) -> String {
+        match self.spans {
+            true => format!("{} ({span})", label.into()),
+            false => label.into(),
+        }
+    }
+
+    fn binary(&mut self, operator: &str, left: &Expr, right: &Expr, span: &Span) -> Node {
+        let label = self.located(operator, span);
+        Node::branch(label, vec![left.accept(self), right.accept(self)])
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) -> Vec<Node> {
+        exprs.iter().map(|expr| expr.accept(self)).collect()
+    }
+}
+
+impl ExprVisitor<Node> for TreeBuilder {
+    fn visit_assign(&mut self, left: &Symbol, right: &Box<Expr>, span: &Span) -> Node {
+        let label = self.located(format!("assign {left}"), span);
+        Node::branch(label, vec![right.accept(self)])
+    }
+
+    fn visit_index_assign(
+        &mut self,
+        list: &Box<Expr>,
+        index: &Box<Expr>,
+        value: &Box<Expr>,
+    ) -> Node {
+        Node::branch(
+            "index assign",
+            vec![list.accept(self), index.accept(self), value.accept(self)],
+        )
+    }
+
+    fn visit_set(&mut self, object: &Box<Expr>, name: &Symbol, value: &Box<Expr>) -> Node {
+        Node::branch(
+            format!("set {name}"),
+            vec![object.accept(self), value.accept(self)],
+        )
+    }
+
+    fn visit_binary_or(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> Node {
+        self.binary("or", left, right, span)
+    }
+
+    fn visit_binary_and(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> Node {
+        self.binary("and", left, right, span)
+    }
+
+    fn visit_binary_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> Node {
+        self.binary("==", left, right, span)
+    }
+
+    fn visit_binary_not_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> Node {
+        self.binary("!=", left, right, span)
+    }
+
+    fn visit_binary_less(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> Node {
+        self.binary("<", left, right, span)
+    }
+
+    fn visit_binary_less_equal(
+        &mut self,
+        left: &Box<Expr>,
+        right: &Box<Expr>,
+        span: &Span,
+    ) -> Node {
+        self.binary("<=", left, right, span)
+    }
+
+    fn visit_binary_greater(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> Node {
+        self.binary(">", left, right, span)
+    }
+
+    fn visit_binary_greater_equal(
+        &mut self,
+        left: &Box<Expr>,
+        right: &Box<Expr>,
+        span: &Span,
+    ) -> Node {
+        self.binary(">=", left, right, span)
+    }
+
+    fn visit_binary_add(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> Node {
+        self.binary("+", left, right, span)
+    }
+
+    fn visit_binary_sub(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> Node {
+        self.binary("-", left, right, span)
+    }
+
+    fn visit_binary_mul(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> Node {
+        self.binary("*", left, right, span)
+    }
+
+    fn visit_binary_div(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> Node {
+        self.binary("/", left, right, span)
+    }
+
+    fn visit_range(
+        &mut self,
+        start: &Box<Expr>,
+        end: &Box<Expr>,
+        inclusive: &bool,
+        span: &Span,
+    ) -> Node {
+        let operator = if *inclusive { "..=" } else { ".." };
+        self.binary(operator, start, end, span)
+    }
+
+    fn visit_unary_bang(&mut self, expr: &Box<Expr>) -> Node {
+        Node::branch("!", vec![expr.accept(self)])
+    }
+
+    fn visit_unary_minus(&mut self, expr: &Box<Expr>) -> Node {
+        Node::branch("-", vec![expr.accept(self)])
+    }
+
+    fn visit_call(&mut self, callee: &Box<Expr>, arguments: &Vec<Expr>, span: &Span) -> Node {
+        let mut children = vec![callee.accept(self)];
+        children.extend(self.exprs(arguments));
+        Node::branch(self.located("call", span), children)
+    }
+
+    fn visit_index(&mut self, list: &Box<Expr>, index: &Box<Expr>) -> Node {
+        Node::branch("index", vec![list.accept(self), index.accept(self)])
+    }
+
+    fn visit_get(&mut self, object: &Box<Expr>, name: &Symbol) -> Node {
+        Node::branch(format!("get {name}"), vec![object.accept(self)])
+    }
+
+    fn visit_list(&mut self, elements: &Vec<Expr>) -> Node {
+        Node::branch("list", self.exprs(elements))
+    }
+
+    fn visit_literal_string(&mut self, value: &String) -> Node {
+        Node::leaf(format!("\"{value}\""))
+    }
+
+    fn visit_literal_number(&mut self, value: &f64) -> Node {
+        Node::leaf(value.to_string())
+    }
+
+    fn visit_false(&mut self) -> Node {
+        Node::leaf("false")
+    }
+
+    fn visit_true(&mut self) -> Node {
+        Node::leaf("true")
+    }
+
+    fn visit_nil(&mut self) -> Node {
+        Node::leaf("nil")
+    }
+
+    fn visit_identifier(&mut self, value: &Symbol) -> Node {
+        Node::leaf(value.to_string())
+    }
+}
+
+impl StmtVisitor<Node> for TreeBuilder {
+    fn visit_print(&mut self, expr: &Box<Expr>) -> Node {
+        Node::branch("print", vec![expr.accept(self)])
+    }
+
+    fn visit_expr(&mut self, expr: &Box<Expr>) -> Node {
+        Node::branch("expr", vec![expr.accept(self)])
+    }
+
+    fn visit_var_declaration(
+        &mut self,
+        name: &Symbol,
+        initializer: &Option<Box<Expr>>,
+        span: &Span,
+    ) -> Node {
+        let children = initializer.iter().map(|expr| expr.accept(self)).collect();
+        Node::branch(self.located(format!("var {name}"), span), children)
+    }
+
+    fn visit_multi_var_declaration(
+        &mut self,
+        declarations: &Vec<(Symbol, Option<Box<Expr>>, Span)>,
+    ) -> Node {
+        let children = declarations
+            .iter()
+            .map(|(name, initializer, span)| self.visit_var_declaration(name, initializer, span))
+            .collect();
+        Node::branch("vars", children)
+    }
+
+    fn visit_const_declaration(
+        &mut self,
+        name: &Symbol,
+        initializer: &Box<Expr>,
+        span: &Span,
+    ) -> Node {
+        let label = self.located(format!("const {name}"), span);
+        Node::branch(label, vec![initializer.accept(self)])
+    }
+
+    fn visit_block(&mut self, stmts: &Vec<Stmt>) -> Node {
+        let children = stmts.iter().map(|stmt| stmt.accept(self)).collect();
+        Node::branch("block", children)
+    }
+
+    fn visit_if(
+        &mut self,
+        condition: &Box<Expr>,
+        then_branch: &Box<Stmt>,
+        else_branch: &Option<Box<Stmt>>,
+        span: &Span,
+    ) -> Node {
+        let mut children = vec![condition.accept(self), then_branch.accept(self)];
+        if let Some(else_branch) = else_branch {
+            children.push(Node::branch("else", vec![else_branch.accept(self)]));
+        }
+
+        Node::branch(self.located("if", span), children)
+    }
+
+    fn visit_while(&mut self, condition: &Box<Expr>, body: &Box<Stmt>, span: &Span) -> Node {
+        let label = self.located("while", span);
+        Node::branch(label, vec![condition.accept(self), body.accept(self)])
+    }
+
+    fn visit_do_while(&mut self, body: &Box<Stmt>, condition: &Box<Expr>, span: &Span) -> Node {
+        let label = self.located("do while", span);
+        Node::branch(label, vec![body.accept(self), condition.accept(self)])
+    }
+
+    fn visit_for_in(&mut self, name: &Symbol, collection: &Box<Expr>, body: &Box<Stmt>) -> Node {
+        Node::branch(
+            format!("for {name} in"),
+            vec![collection.accept(self), body.accept(self)],
+        )
+    }
+
+    fn visit_switch(
+        &mut self,
+        subject: &Box<Expr>,
+        cases: &Vec<(Box<Expr>, Vec<Stmt>, Span)>,
+        default: &Option<Vec<Stmt>>,
+    ) -> Node {
+        let mut children = vec![subject.accept(self)];
+
+        for (value, body, span) in cases {
+            let mut case_children = vec![value.accept(self)];
+            case_children.extend(body.iter().map(|stmt| stmt.accept(self)));
+            children.push(Node::branch(self.located("case", span), case_children));
+        }
+
+        if let Some(default) = default {
+            let default_children = default.iter().map(|stmt| stmt.accept(self)).collect();
+            children.push(Node::branch("default", default_children));
+        }
+
+        Node::branch("switch", children)
+    }
+
+    fn visit_function_declaration(
+        &mut self,
+        name: &Symbol,
+        arguments: &Vec<Symbol>,
+        body: &Box<Stmt>,
+    ) -> Node {
+        Node::branch(
+            format!(
+                "fun {name}({})",
+                arguments
+                    .iter()
+                    .map(|a| a.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            vec![body.accept(self)],
+        )
+    }
+
+    fn visit_throw(&mut self, expr: &Box<Expr>, span: &Span) -> Node {
+        Node::branch(self.located("throw", span), vec![expr.accept(self)])
+    }
+
+    fn visit_try(&mut self, body: &Vec<Stmt>, name: &Symbol, handler: &Vec<Stmt>) -> Node {
+        Node::branch(
+            "try",
+            vec![
+                self.visit_block(body),
+                Node::branch(
+                    format!("catch {name}"),
+                    handler.iter().map(|stmt| stmt.accept(self)).collect(),
+                ),
+            ],
+        )
+    }
+
+    fn visit_assert(
+        &mut self,
+        condition: &Box<Expr>,
+        message: &Option<Box<Expr>>,
+        span: &Span,
+    ) -> Node {
+        let label = self.located("assert", span);
+
+        let mut children = vec![condition.accept(self)];
+        if let Some(message) = message {
+            children.push(message.accept(self));
+        }
+
+        Node::branch(label, children)
+    }
+
+    fn visit_import(&mut self, path: &String, alias: &Option<Symbol>) -> Node {
+        match alias {
+            Some(alias) => Node::leaf(format!("import \"{path}\" as {alias}")),
+            None => Node::leaf(format!("import \"{path}\"")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rstest::*;
+
+    use crate::lox;
+
+    use super::{AstPrinter, AstPrinterOptions, AstStyle};
+
+    #[rstest]
+    #[case::sexpression(
+        AstStyle::SExpression,
+        false,
+        "print 1 + 2 * a;\nvar b;",
+        "{print {1 + {2 * a}}}\n{var b}\n"
+    )]
+    #[case::tree(
+        AstStyle::Tree,
+        false,
+        "print 1 + 2 * a;\nvar b;",
+        "print\n  +\n    1\n    *\n      2\n      a\nvar b\n"
+    )]
+    #[case::tree_spans(
+        AstStyle::Tree,
+        true,
+        "var x =\n1 - 2;",
+        "var x (line 1)\n  - (line 2)\n    1\n    2\n"
+    )]
+    #[case::tree_statement_spans(
+        AstStyle::Tree,
+        true,
+        "while (f(1))\n  x = 2;",
+        "while (line 1)\n  call (line 1)\n    f\n    1\n  expr\n    assign x (line 2)\n      2\n"
+    )]
+    #[case::tree_assert_span(
+        AstStyle::Tree,
+        true,
+        "\nassert a, \"message\";",
+        "assert (line 2)\n  a\n  \"message\"\n"
+    )]
+    #[case::tree_statements(
+        AstStyle::Tree,
+        false,
+        "fun f(a, b) { if (a) print b; else g(a)[0] = nil; }",
+        "fun f(a, b)\n  block\n    block\n      if\n        a\n        print\n          b\n        else\n          expr\n            index assign\n              call\n                g\n                a\n              0\n              nil\n"
+    )]
+    fn test_print(
+        #[case] style: AstStyle,
+        #[case] spans: bool,
+        #[case] source: &str,
+        #[case] expected: &str,
+    ) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a parsed program and a printer with the given options
+        let program = lox::parse(source)?;
+        let mut printer = AstPrinter::with_options(AstPrinterOptions { style, spans });
+
+        ///////////////////////////////////////////////////////////////////////
+        // When printing the program
+        let printed = printer.print(&program.statements);
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the statements are rendered in the given style
+        assert_eq!(printed, expected);
+
+        Ok(())
+    }
+}
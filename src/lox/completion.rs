@@ -0,0 +1,105 @@
+use std::collections::BTreeSet;
+
+use super::{Environment, Symbol, Token, Value};
+
+// Candidates completing the word that ends at the given byte position of a line, together with
+// the position where that word starts. Words after `name.` are completed with the declarations
+// of the module bound to name, other words with keywords and the variables in scope
+pub fn complete(
+    environment: &dyn Environment,
+    line: &str,
+    position: usize,
+) -> (usize, Vec<String>) {
+    let line = &line[..position.min(line.len())];
+    let start = word_start(line);
+    let word = &line[start..];
+
+    let candidates: BTreeSet<String> = match line[..start].strip_suffix('.') {
+        Some(before_dot) => {
+            let object = &before_dot[word_start(before_dot)..];
+            module_exports(environment, object)
+        }
+        None => Token::keywords()
+            .map(|keyword| keyword.to_string())
+            .chain(
+                environment
+                    .get_all_variables()
+                    .into_iter()
+                    .map(|(name, _)| name),
+            )
+            .collect(),
+    };
+
+    let candidates = candidates
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(word))
+        .collect();
+
+    (start, candidates)
+}
+
+// Start of the identifier the line ends with
+fn word_start(line: &str) -> usize {
+    line.char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_ascii_alphanumeric() || *c == '_')
+        .last()
+        .map_or(line.len(), |(i, _)| i)
+}
+
+fn module_exports(environment: &dyn Environment, name: &str) -> BTreeSet<String> {
+    let Some(value) = Symbol::find(name).and_then(|name| environment.get_variable(name)) else {
+        return BTreeSet::new();
+    };
+
+    let Ok(value_guard) = value.try_borrow() else {
+        return BTreeSet::new();
+    };
+
+    match &*value_guard {
+        Value::Module(module) => module.exports.keys().cloned().collect(),
+        _ => BTreeSet::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::path::PathBuf;
+
+    use rstest::*;
+
+    use crate::lox::Interpreter;
+
+    #[rstest]
+    #[case::keyword("wh", 0, vec!["while"])]
+    #[case::keyword_and_global("pr", 0, vec!["print", "progress"])]
+    #[case::native("print sq", 6, vec!["sqrt"])]
+    #[case::after_operator("1+pro", 2, vec!["progress"])]
+    #[case::module_export("c.inc", 2, vec!["increment"])]
+    #[case::module_all("print c.", 8, vec!["count", "increment"])]
+    #[case::not_a_module("progress.", 9, vec![])]
+    #[case::unknown("zzz", 0, vec![])]
+    fn test_complete(
+        #[case] line: &str,
+        #[case] expected_start: usize,
+        #[case] expected: Vec<&str>,
+    ) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter with a global variable and an imported module
+        let mut interpreter = Interpreter::new();
+        interpreter.set_module_search_paths(vec![PathBuf::from("test-data/modules")]);
+        interpreter.execute("var progress = 0; import \"counter.lox\" as c;".to_string())?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // When completing the end of a line
+        let (start, candidates) = interpreter.complete(line, line.len());
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the candidates complete the last word of the line
+        assert_eq!(start, expected_start);
+        assert_eq!(candidates, expected);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,278 @@
+use std::rc::Rc;
+
+use super::{new_value_box, Callable, Value};
+
+// Rust types that can be read from a Lox value, e.g. the arguments of the natives created with
+// NativeFunction::from_rust
+pub trait FromLox: Sized {
+    // None if the value is not of the expected type
+    fn from_lox(value: &Value) -> Option<Self>;
+
+    // Expected value, as reported by errors, e.g. "a number"
+    fn expected() -> String;
+}
+
+// Rust types that can be turned into a Lox value, e.g. the results of the natives created with
+// NativeFunction::from_rust
+pub trait IntoLox {
+    fn into_lox(self) -> Value;
+}
+
+// Error of a value that does not have the expected type, in the format used by the natives
+pub fn argument_error<T: FromLox>(function: &str, arg_name: &str, value: &Value) -> String {
+    format!(
+        "{function}() expects argument '{arg_name}' to be {}, got {}",
+        T::expected(),
+        value.type_name()
+    )
+}
+
+// Argument of a native converted to T, for the natives taking the arguments as values, e.g.
+// `let n: f64 = argument("sqrt", arguments, 0, "n")?;`
+pub fn argument<T: FromLox>(
+    function: &str,
+    arguments: &[Value],
+    index: usize,
+    arg_name: &str,
+) -> Result<T, String> {
+    let value = arguments
+        .get(index)
+        .ok_or(format!("{function}() is missing argument '{arg_name}'"))?;
+
+    T::from_lox(value).ok_or_else(|| argument_error::<T>(function, arg_name, value))
+}
+
+impl FromLox for Value {
+    fn from_lox(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+
+    fn expected() -> String {
+        "a value".to_string()
+    }
+}
+
+impl FromLox for f64 {
+    fn from_lox(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    fn expected() -> String {
+        "a number".to_string()
+    }
+}
+
+impl FromLox for bool {
+    fn from_lox(value: &Value) -> Option<Self> {
+        match value {
+            Value::Boolean(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    fn expected() -> String {
+        "a boolean".to_string()
+    }
+}
+
+impl FromLox for String {
+    fn from_lox(value: &Value) -> Option<Self> {
+        match value {
+            Value::String(s) => Some(s.clone()),
+            _ => None,
+        }
+    }
+
+    fn expected() -> String {
+        "a string".to_string()
+    }
+}
+
+impl FromLox for Rc<Box<dyn Callable>> {
+    fn from_lox(value: &Value) -> Option<Self> {
+        match value {
+            Value::Callable(callable) => Some(callable.clone()),
+            _ => None,
+        }
+    }
+
+    fn expected() -> String {
+        "a function".to_string()
+    }
+}
+
+// Lists whose elements all convert to T
+impl<T: FromLox> FromLox for Vec<T> {
+    fn from_lox(value: &Value) -> Option<Self> {
+        match value {
+            Value::List(elements) => elements
+                .iter()
+                .map(|element| T::from_lox(&*element.try_borrow().ok()?))
+                .collect(),
+            _ => None,
+        }
+    }
+
+    fn expected() -> String {
+        format!("a list where each element is {}", T::expected())
+    }
+}
+
+// Nil converts to None
+impl<T: FromLox> FromLox for Option<T> {
+    fn from_lox(value: &Value) -> Option<Self> {
+        match value {
+            Value::Nil => Some(None),
+            other => T::from_lox(other).map(Some),
+        }
+    }
+
+    fn expected() -> String {
+        format!("{} or nil", T::expected())
+    }
+}
+
+impl<T: Into<Value>> IntoLox for T {
+    fn into_lox(self) -> Value {
+        self.into()
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Self {
+        Value::Number(n)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Boolean(b)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Value::Nil
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(elements: Vec<T>) -> Self {
+        Value::List(
+            elements
+                .into_iter()
+                .map(|element| new_value_box(element.into()))
+                .collect(),
+        )
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::Nil, Into::into)
+    }
+}
+
+// Fallible conversions of an owned value, failing with the expected type
+macro_rules! impl_try_from_value {
+    ($($t:ty),*) => {
+        $(
+            impl TryFrom<Value> for $t {
+                type Error = String;
+
+                fn try_from(value: Value) -> Result<Self, Self::Error> {
+                    <$t>::from_lox(&value).ok_or(format!(
+                        "Expected {}, got {}",
+                        <$t>::expected(),
+                        value.type_name()
+                    ))
+                }
+            }
+        )*
+    };
+}
+
+impl_try_from_value!(f64, bool, String, Vec<Value>);
+
+#[cfg(test)]
+mod tests {
+
+    use rstest::*;
+
+    use crate::lox::Value;
+
+    use super::{argument, FromLox};
+
+    #[rstest]
+    #[case::number(Value::from(1.5), "1.5")]
+    #[case::boolean(Value::from(true), "true")]
+    #[case::str(Value::from("lox"), "lox")]
+    #[case::unit(Value::from(()), "nil")]
+    #[case::list(Value::from(vec![1.0, 2.0]), "[1, 2]")]
+    #[case::nested_list(Value::from(vec![vec!["a"], vec![]]), "[[a], []]")]
+    #[case::some(Value::from(Some(2.0)), "2")]
+    #[case::none(Value::from(None::<f64>), "nil")]
+    fn test_into_value(#[case] value: Value, #[case] expected: &str) {
+        assert_eq!(value.to_string(), expected);
+    }
+
+    #[test]
+    fn test_try_from_value() {
+        assert_eq!(f64::try_from(Value::Number(2.0)), Ok(2.0));
+        assert_eq!(bool::try_from(Value::Boolean(false)), Ok(false));
+        assert_eq!(String::try_from(Value::from("a")), Ok("a".to_string()));
+        assert_eq!(
+            Vec::<Value>::try_from(Value::from(vec![1.0])),
+            Ok(vec![Value::Number(1.0)])
+        );
+        assert_eq!(
+            f64::try_from(Value::Nil),
+            Err("Expected a number, got nil".to_string())
+        );
+    }
+
+    #[test]
+    fn test_argument() {
+        let arguments = [Value::Number(1.0), Value::from("a")];
+
+        assert_eq!(argument::<f64>("f", &arguments, 0, "n"), Ok(1.0));
+        assert_eq!(
+            argument::<f64>("f", &arguments, 1, "n"),
+            Err("f() expects argument 'n' to be a number, got string".to_string())
+        );
+        assert_eq!(
+            argument::<String>("f", &arguments, 2, "s"),
+            Err("f() is missing argument 's'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_from_lox() {
+        assert_eq!(
+            Vec::<f64>::from_lox(&Value::from(vec![1.0, 2.0])),
+            Some(vec![1.0, 2.0])
+        );
+        assert_eq!(Vec::<f64>::from_lox(&Value::from(vec!["a"])), None);
+        assert_eq!(Option::<String>::from_lox(&Value::Nil), Some(None));
+        assert_eq!(Option::<String>::from_lox(&Value::Number(1.0)), None);
+        assert_eq!(
+            Option::<Vec<bool>>::expected(),
+            "a list where each element is a boolean or nil"
+        );
+    }
+}
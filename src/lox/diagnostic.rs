@@ -0,0 +1,403 @@
+use std::fmt::Display;
+
+use super::{Lint, LoxError, ParseError, ScanError, Span};
+
+// Codes of the diagnostics: E00xx for the command line, E01xx for scanning, E02xx for parsing,
+// E03xx for running and W01xx for the warnings of the analyzer. Codes are kept once published,
+// tools may match on them
+
+// Whether a diagnostic stops the program
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+// Error or warning about a source, pointing at the line it was found at when known
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Option<Span>,
+}
+
+// Diagnostic as read by editors and other tools, see Diagnostic::to_json
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct DiagnosticRecord<'a> {
+    file: Option<&'a str>,
+    range: Option<SpanRange>,
+    severity: Severity,
+    code: &'a str,
+    message: &'a str,
+}
+
+// Source covered by a diagnostic, spans only locate lines so both ends are on the same one
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct SpanRange {
+    start: Span,
+    end: Span,
+}
+
+// ANSI escape sequences of the colored output
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+impl Diagnostic {
+    pub fn error(code: &'static str, message: impl Into<String>, span: Option<Span>) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            span,
+        }
+    }
+
+    pub fn warning(code: &'static str, message: impl Into<String>, span: Option<Span>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(code, message, span)
+        }
+    }
+
+    // Error of a running program. Its line is read from the message, which runtime errors end
+    // with, e.g. "Division by zero at line 3"
+    pub fn runtime(message: impl Into<String>) -> Self {
+        let message = message.into();
+        let span = message
+            .rsplit_once(" at line ")
+            .and_then(|(_, line)| line.parse().ok())
+            .map(|line| Span { line });
+
+        Self::error("E0301", message, span)
+    }
+
+    // The diagnostic followed by the line of the source it points at, underlined, e.g.
+    //
+    // error[E0201]: Expected ';' after expression.
+    //  --> script.lox:2
+    //   |
+    // 2 | print a
+    //   | ^^^^^^^
+    //
+    // Severity, code and underline are colored with ANSI escapes if color is set
+    pub fn render(&self, source: &str, file: &str, color: bool) -> String {
+        let paint = |style: &str, text: &str| match color {
+            true => format!("{style}{text}{RESET}"),
+            false => text.to_string(),
+        };
+
+        let severity_style = match self.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        };
+
+        let mut output = format!(
+            "{}{}",
+            paint(severity_style, &format!("{}[{}]", self.severity, self.code)),
+            paint(BOLD, &format!(": {}", self.message))
+        );
+
+        // spans of unknown or synthesized locations do not point at any line of the source
+        let line = self
+            .span
+            .filter(|span| span.line > 0)
+            .and_then(|span| Some((span.line, source.lines().nth(span.line as usize - 1)?)));
+
+        let Some((number, text)) = line else {
+            output.push_str(&format!("\n{} {file}", paint(BLUE, "-->")));
+            return output;
+        };
+
+        let gutter = " ".repeat(number.to_string().len());
+        let content = text.trim();
+        // the indentation is repeated as is for tabs to line the underline up
+        let indentation = &text[..text.len() - text.trim_start().len()];
+        let underline = "^".repeat(content.chars().count().max(1));
+
+        output.push_str(&format!("\n{gutter}{} {file}:{number}", paint(BLUE, "-->")));
+        output.push_str(&format!("\n{gutter} {}", paint(BLUE, "|")));
+        output.push_str(&format!("\n{} {text}", paint(BLUE, &format!("{number} |"))));
+        output.push_str(&format!(
+            "\n{gutter} {} {indentation}{}",
+            paint(BLUE, "|"),
+            paint(severity_style, &underline)
+        ));
+
+        output
+    }
+
+    // The diagnostic as a single line of JSON, with the file it was found in if any, e.g.
+    // {"file":"a.lox","range":{"start":{"line":2},"end":{"line":2}},"severity":"error",...}
+    // Unknown locations have a null range
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self, file: Option<&str>) -> Result<String, String> {
+        let record = DiagnosticRecord {
+            file,
+            range: self
+                .span
+                .filter(|span| span.line > 0)
+                .map(|span| SpanRange {
+                    start: span,
+                    end: span,
+                }),
+            severity: self.severity,
+            code: self.code,
+            message: &self.message,
+        };
+
+        serde_json::to_string(&record).map_err(|e| e.to_string())
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl From<Diagnostic> for String {
+    fn from(diagnostic: Diagnostic) -> Self {
+        diagnostic.message
+    }
+}
+
+impl From<ScanError> for Diagnostic {
+    fn from(error: ScanError) -> Self {
+        let code = match error {
+            ScanError::UnterminatedString(_) => "E0101",
+            ScanError::UnexpectedCharacter(_, _) => "E0102",
+            ScanError::NonAsciiCharacter(_, _) => "E0103",
+            ScanError::MalformedNumber(_, _) => "E0104",
+            ScanError::Limit(_) => "E0105",
+        };
+
+        Diagnostic::error(code, error.to_string(), error.get_span())
+    }
+}
+
+impl From<ParseError> for Diagnostic {
+    fn from(error: ParseError) -> Self {
+        let code = match (error.get_limit_exceeded(), error.is_unexpected_eof()) {
+            (Some(_), _) => "E0203",
+            (None, true) => "E0202",
+            (None, false) => "E0201",
+        };
+
+        Diagnostic::error(code, error.to_string(), Some(error.get_span()))
+    }
+}
+
+impl From<LoxError> for Diagnostic {
+    fn from(error: LoxError) -> Self {
+        match error {
+            LoxError::Script(message) => Diagnostic::runtime(message),
+            LoxError::BudgetExceeded(limit) => Diagnostic::error("E0302", limit.to_string(), None),
+        }
+    }
+}
+
+impl From<&Lint> for Diagnostic {
+    fn from(lint: &Lint) -> Self {
+        let code = match lint {
+            Lint::InfiniteLoop(_) => "W0101",
+            Lint::ConstantFalseCondition(_) => "W0102",
+            Lint::SelfComparison(_, _) => "W0103",
+            Lint::ArityMismatch(_, _, _, _) => "W0104",
+            Lint::ConstantAssignment(_, _) => "W0105",
+            Lint::UnusedVariable(_, _) => "W0106",
+            Lint::ShadowedVariable(_, _) => "W0107",
+            Lint::DuplicateCase(_, _) => "W0108",
+        };
+
+        Diagnostic::warning(code, lint.to_string(), Some(lint.get_span()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rstest::*;
+
+    use crate::lox::{Interpreter, InterpreterOptions, Lint, Span};
+
+    use super::Diagnostic;
+
+    #[rstest]
+    #[case::scan("print \"abc;", "E0101", 1)]
+    #[case::unexpected_character("var a = 1;\nprint a # 2;", "E0102", 2)]
+    #[case::syntax("var a = 1;\nprint a +;", "E0201", 2)]
+    #[case::unexpected_eof("fun f() {\n  print 1;\n", "E0202", 2)]
+    fn test_compile_errors(
+        #[case] source: &str,
+        #[case] code: &str,
+        #[case] line: u64,
+    ) -> Result<(), String> {
+        let diagnostic = Interpreter::new()
+            .parse(source.to_string())
+            .err()
+            .ok_or("Expected an error")?;
+
+        assert_eq!(diagnostic.code, code);
+        assert_eq!(diagnostic.span, Some(Span { line }));
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::division("Division by zero at line 3", Some(Span { line: 3 }))]
+    #[case::assertion("Assertion failed: a at line 12 at line 4", Some(Span { line: 4 }))]
+    #[case::no_line("Undefined variable 'a'", None)]
+    #[case::not_a_line("Uncaught exception: at line one", None)]
+    fn test_runtime_span(#[case] message: &str, #[case] span: Option<Span>) {
+        assert_eq!(Diagnostic::runtime(message).span, span);
+    }
+
+    #[test]
+    fn test_budget_exceeded() -> Result<(), String> {
+        let mut interpreter = Interpreter::with_options(InterpreterOptions {
+            max_steps: 100,
+            ..Default::default()
+        });
+
+        let error = interpreter
+            .execute("while (true) {}".to_string())
+            .err()
+            .ok_or("Expected an error")?;
+
+        let diagnostic = Diagnostic::from(error);
+        assert_eq!(diagnostic.code, "E0302");
+        assert_eq!(diagnostic.span, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_render() {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an error on an indented line
+        let source = "fun f() {\n    print 1 / 0;\n}\nf();";
+        let diagnostic = Diagnostic::runtime("Division by zero at line 2");
+
+        ///////////////////////////////////////////////////////////////////////
+        // When rendering it
+        let rendered = diagnostic.render(source, "script.lox", false);
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the line is shown with its content underlined
+        assert_eq!(
+            rendered,
+            "error[E0301]: Division by zero at line 2
+ --> script.lox:2
+  |
+2 |     print 1 / 0;
+  |     ^^^^^^^^^^^^"
+        );
+    }
+
+    #[rstest]
+    #[case::no_span(None)]
+    #[case::unknown_line(Some(Span { line: 0 }))]
+    #[case::past_the_end(Some(Span { line: 10 }))]
+    fn test_render_without_line(#[case] span: Option<Span>) {
+        let diagnostic = Diagnostic::warning("W0101", "Loop never ends", span);
+
+        assert_eq!(
+            diagnostic.render("while (true) {}", "script.lox", false),
+            "warning[W0101]: Loop never ends\n--> script.lox"
+        );
+    }
+
+    #[test]
+    fn test_render_color() {
+        let diagnostic = Diagnostic::from(&Lint::SelfComparison("a".to_string(), Span { line: 1 }));
+
+        assert_eq!(
+            diagnostic.render("a == a;", "-", true),
+            "\x1b[1;33mwarning[W0103]\x1b[0m\x1b[1m: Comparison of 'a' with itself at line 1\x1b[0m
+ \x1b[1;34m-->\x1b[0m -:1
+  \x1b[1;34m|\x1b[0m
+\x1b[1;34m1 |\x1b[0m a == a;
+  \x1b[1;34m|\x1b[0m \x1b[1;33m^^^^^^^\x1b[0m"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[rstest]
+    #[case::located(
+        Diagnostic::runtime("Division by zero at line 2"),
+        Some("a.lox"),
+        serde_json::json!({
+            "file": "a.lox",
+            "range": {"start": {"line": 2}, "end": {"line": 2}},
+            "severity": "error",
+            "code": "E0301",
+            "message": "Division by zero at line 2"
+        })
+    )]
+    #[case::lint(
+        Diagnostic::from(&Lint::UnusedVariable("a".to_string(), Span { line: 3 })),
+        Some("a.lox"),
+        serde_json::json!({
+            "file": "a.lox",
+            "range": {"start": {"line": 3}, "end": {"line": 3}},
+            "severity": "warning",
+            "code": "W0106",
+            "message": "Variable 'a' is never read"
+        })
+    )]
+    #[case::uncaught_exception(
+        Diagnostic::runtime("Uncaught exception: x at line 2"),
+        None,
+        serde_json::json!({
+            "file": null,
+            "range": {"start": {"line": 2}, "end": {"line": 2}},
+            "severity": "error",
+            "code": "E0301",
+            "message": "Uncaught exception: x at line 2"
+        })
+    )]
+    #[case::unlocated(
+        Diagnostic::warning("W0101", "Loop never ends", Some(Span { line: 0 })),
+        None,
+        serde_json::json!({
+            "file": null,
+            "range": null,
+            "severity": "warning",
+            "code": "W0101",
+            "message": "Loop never ends"
+        })
+    )]
+    fn test_to_json(
+        #[case] diagnostic: Diagnostic,
+        #[case] file: Option<&str>,
+        #[case] expected: serde_json::Value,
+    ) -> Result<(), String> {
+        let json = diagnostic.to_json(file)?;
+        assert!(!json.contains('\n'));
+
+        let record: serde_json::Value = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+        assert_eq!(record, expected);
+
+        Ok(())
+    }
+}
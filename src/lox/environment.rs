@@ -1,15 +1,23 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 
-use super::{new_value_box, Callable, Value, ValueBox};
-
-type ValueStack = Vec<std::collections::HashMap<String, ValueBox>>;
+use super::{new_value_box, visit_reachable, Callable, Symbol, Value, ValueBox};
 
 // TODO: need to sort out the memory layout of the variables stored in the environment
 //       till now, I clone the stored values everytime I access them, which is inneficient
 pub trait Environment: std::fmt::Display + std::fmt::Debug {
-    fn get_variable(&self, name: &str) -> Option<ValueBox>;
-    fn set_variable(&mut self, name: &str, value: Value) -> Result<ValueBox, String>;
-    fn define_variable(&mut self, name: &str, value: Value);
+    fn get_variable(&self, name: Symbol) -> Option<ValueBox>;
+    fn set_variable(&mut self, name: Symbol, value: Value) -> Result<ValueBox, String>;
+    fn define_variable(&mut self, name: Symbol, value: Value);
+    fn define_constant(&mut self, name: Symbol, value: Value);
+
+    // Whether the variable the name resolves to was declared as a constant
+    fn is_constant(&self, name: Symbol) -> bool;
+
+    // Variable declared in the scope the given number of levels above the current one, 0 being
+    // the current scope. Unlike get_variable, enclosing scopes are not searched
+    fn get_variable_at(&self, distance: usize, name: Symbol) -> Option<ValueBox>;
 
     fn push_variable_stack(&mut self);
     fn pop_variable_stack(&mut self);
@@ -17,123 +25,284 @@ pub trait Environment: std::fmt::Display + std::fmt::Debug {
     fn branch_push(&mut self);
     fn branch_pop(&mut self);
 
-    fn define_function(&mut self, name: &str, value: Box<dyn Callable>);
+    fn define_function(&mut self, name: Symbol, value: Box<dyn Callable>);
+
+    fn get_global_variables(&self) -> Vec<(String, ValueBox)>;
+
+    // Variables of every scope, including the ones shadowed and the ones of the outer branches
+    fn get_all_variables(&self) -> Vec<(String, ValueBox)>;
+
+    // Number of scopes, including the global one
+    fn get_scope_count(&self) -> usize;
+
+    // Copy of the scopes of the environment, sharing the boxes of the variables
+    fn duplicate(&self) -> Box<dyn Environment>;
+}
+
+type ScopeRef = Rc<RefCell<Scope>>;
+
+// Variables declared by a block or a function call. Names not found in a scope are looked up
+// in its parent, the global scope being the only one without a parent
+#[derive(Debug, Default)]
+struct Scope {
+    variables: HashMap<Symbol, ValueBox>,
+    // variables of the scope that can not be assigned
+    constants: HashSet<Symbol>,
+    parent: Option<ScopeRef>,
+}
+
+impl Scope {
+    fn new(parent: Option<ScopeRef>) -> ScopeRef {
+        Rc::new(RefCell::new(Scope {
+            variables: HashMap::new(),
+            constants: HashSet::new(),
+            parent,
+        }))
+    }
+
+    // Innermost scope of the chain declaring the name
+    fn resolve(scope: &ScopeRef, name: &Symbol) -> Option<ScopeRef> {
+        let mut scope = scope.clone();
+        loop {
+            if scope.borrow().variables.contains_key(name) {
+                return Some(scope);
+            }
+
+            let parent = scope.borrow().parent.clone()?;
+            scope = parent;
+        }
+    }
+
+    // The scope and its ancestors, innermost first
+    fn chain(scope: &ScopeRef) -> Vec<ScopeRef> {
+        let mut chain = vec![scope.clone()];
+        while let Some(parent) = chain.last().and_then(|s| s.borrow().parent.clone()) {
+            chain.push(parent);
+        }
+        chain
+    }
+
+    // Copy of the scope and its ancestors sharing the variable boxes. Scopes already copied,
+    // e.g. the global scope reached from several branches, are reused so they stay shared
+    fn copy(scope: &ScopeRef, copies: &mut HashMap<*const RefCell<Scope>, ScopeRef>) -> ScopeRef {
+        if let Some(copy) = copies.get(&Rc::as_ptr(scope)) {
+            return copy.clone();
+        }
+
+        let scope_ref = scope.borrow();
+        let parent = scope_ref.parent.as_ref().map(|p| Scope::copy(p, copies));
+        let copy = Rc::new(RefCell::new(Scope {
+            variables: scope_ref.variables.clone(),
+            constants: scope_ref.constants.clone(),
+            parent,
+        }));
+
+        copies.insert(Rc::as_ptr(scope), copy.clone());
+        copy
+    }
 }
 
 #[derive(Debug)]
 pub struct EnvironmentImpl {
-    global_variables: std::collections::HashMap<String, ValueBox>,
-    // current_stack: ValueStack,
+    global: ScopeRef,
+    // innermost scope of the current branch
+    current: ScopeRef,
 
-    // a stack of environments, used across function calls
-    branch_stack: Vec<ValueStack>,
+    // innermost scopes of the branches interrupted by branch_push, resumed by branch_pop
+    branches: Vec<ScopeRef>,
 }
 
 impl EnvironmentImpl {
     pub fn new() -> Self {
-        // TODO create an empty
-        // let branch_stack = vec![vec![std::collections::HashMap::new()]];
-        let branch_stack = vec![vec![]];
+        let global = Scope::new(None);
 
         Self {
-            global_variables: std::collections::HashMap::new(),
-            branch_stack: branch_stack,
+            current: global.clone(),
+            global,
+            branches: Vec::new(),
         }
     }
-}
 
-impl Environment for EnvironmentImpl {
-    fn get_variable(&self, name: &str) -> Option<ValueBox> {
-        // search in the current stack, if there is any created
-        if let Some(current_stack) = self.branch_stack.last() {
-            for scope in current_stack.iter().rev() {
-                if let Some(v) = scope.get(name) {
-                    return Some(v.to_owned());
+    // Distinct scopes of every branch, the global one first
+    fn all_scopes(&self) -> Vec<ScopeRef> {
+        let mut visited = HashSet::new();
+        let mut scopes = Vec::new();
+
+        for innermost in self.branches.iter().chain(std::iter::once(&self.current)) {
+            for scope in Scope::chain(innermost).into_iter().rev() {
+                if visited.insert(Rc::as_ptr(&scope)) {
+                    scopes.push(scope);
                 }
             }
         }
 
-        self.global_variables.get(name).map(|v| v.to_owned())
+        scopes
     }
+}
 
-    fn set_variable(&mut self, name: &str, value: Value) -> Result<ValueBox, String> {
-        // if there is a branch stack, try to set the variable value there
-        if let Some(current_stack) = self.branch_stack.last_mut() {
-            for scope in current_stack.iter_mut().rev() {
-                if let Some(v) = scope.get_mut(name) {
-                    let mut guard = v.try_write().map_err(|e| {
-                        format!("Error locking variable \"{name}\" for writing: {e:?}")
-                    })?;
-                    *guard.as_mut() = value;
-                    return Ok(v.to_owned());
-                }
-            }
-        }
+impl Environment for EnvironmentImpl {
+    fn get_variable(&self, name: Symbol) -> Option<ValueBox> {
+        let scope = Scope::resolve(&self.current, &name)?;
+        let variable = scope.borrow().variables.get(&name).cloned();
+        variable
+    }
 
-        // if the variable is not found in the current stack, try to set it in the global variables
-        if let Some(v) = self.global_variables.get_mut(name) {
-            let mut guard = v.try_write().map_err(|e| {
-                format!("Error locking global variable \"{name}\" for writing: {e:?}")
-            })?;
-            *guard.as_mut() = value;
-            return Ok(v.to_owned());
+    fn set_variable(&mut self, name: Symbol, value: Value) -> Result<ValueBox, String> {
+        let variable = self
+            .get_variable(name.clone())
+            .ok_or(format!("Undefined variable '{}'", name))?;
+
+        if self.is_constant(name.clone()) {
+            return Err(format!("Cannot assign to constant '{name}'"));
         }
 
-        Err(format!("Undefined variable '{}'", name))
+        *variable
+            .try_borrow_mut()
+            .map_err(|e| format!("Error borrowing variable \"{name}\" for writing: {e}"))? = value;
+
+        Ok(variable)
     }
 
-    fn define_variable(&mut self, name: &str, value: Value) {
-        if let Some(current_stack) = self.branch_stack.last_mut() {
-            if let Some(scope) = current_stack.last_mut() {
-                scope.insert(name.to_string(), new_value_box(value));
-                return;
-            }
-        }
+    fn define_variable(&mut self, name: Symbol, value: Value) {
+        let mut scope = self.current.borrow_mut();
+        scope.constants.remove(&name);
+        scope.variables.insert(name, new_value_box(value));
+    }
 
-        self.global_variables
-            .insert(name.to_string(), new_value_box(value));
+    fn define_constant(&mut self, name: Symbol, value: Value) {
+        let mut scope = self.current.borrow_mut();
+        scope.variables.insert(name.clone(), new_value_box(value));
+        scope.constants.insert(name);
+    }
+
+    fn is_constant(&self, name: Symbol) -> bool {
+        Scope::resolve(&self.current, &name)
+            .is_some_and(|scope| scope.borrow().constants.contains(&name))
+    }
 
-        // let current_stack = self.branch_stack.last_mut().unwrap();
-        // match current_stack.last_mut() {
-        //     Some(scope) => {
-        //         scope.insert(name.to_string(), value);
-        //     }
-        //     None => {
-        //         self.global_variables
-        //             .borrow_mut()
-        //             .insert(name.to_string(), value);
-        //     }
-        // }
+    fn get_variable_at(&self, distance: usize, name: Symbol) -> Option<ValueBox> {
+        let scope = Scope::chain(&self.current).into_iter().nth(distance)?;
+        let variable = scope.borrow().variables.get(&name).cloned();
+        variable
     }
 
     fn push_variable_stack(&mut self) {
-        let current_stack = self.branch_stack.last_mut().unwrap();
-        current_stack.push(std::collections::HashMap::new());
+        self.current = Scope::new(Some(self.current.clone()));
     }
 
     fn pop_variable_stack(&mut self) {
-        let current_stack = self.branch_stack.last_mut().unwrap();
-        if current_stack.len() > 1 {
-            current_stack.pop();
+        let parent = self.current.borrow().parent.clone();
+        if let Some(parent) = parent {
+            self.current = parent;
         }
     }
 
+    // Starts a chain of scopes on top of the global one, hiding the scopes of the current branch
     fn branch_push(&mut self) {
-        self.branch_stack
-            .push(vec![std::collections::HashMap::new()]);
+        let branch = std::mem::replace(&mut self.current, Scope::new(Some(self.global.clone())));
+        self.branches.push(branch);
     }
 
     fn branch_pop(&mut self) {
-        if self.branch_stack.len() > 1 {
-            self.branch_stack.pop();
+        if let Some(branch) = self.branches.pop() {
+            self.current = branch;
         }
     }
 
-    fn define_function(&mut self, name: &str, value: Box<dyn Callable>) {
-        self.global_variables.insert(
-            name.to_string(),
-            new_value_box(Value::Callable(Rc::new(value))),
-        );
+    fn define_function(&mut self, name: Symbol, value: Box<dyn Callable>) {
+        let mut global = self.global.borrow_mut();
+        global.constants.remove(&name);
+        global
+            .variables
+            .insert(name, new_value_box(Value::Callable(Rc::new(value))));
+    }
+
+    fn get_global_variables(&self) -> Vec<(String, ValueBox)> {
+        self.global
+            .borrow()
+            .variables
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_owned()))
+            .collect()
+    }
+
+    fn get_all_variables(&self) -> Vec<(String, ValueBox)> {
+        self.all_scopes()
+            .iter()
+            .flat_map(|scope| {
+                scope
+                    .borrow()
+                    .variables
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.to_owned()))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    fn get_scope_count(&self) -> usize {
+        self.all_scopes().len()
+    }
+
+    fn duplicate(&self) -> Box<dyn Environment> {
+        let mut copies = HashMap::new();
+
+        Box::new(EnvironmentImpl {
+            global: Scope::copy(&self.global, &mut copies),
+            current: Scope::copy(&self.current, &mut copies),
+            branches: self
+                .branches
+                .iter()
+                .map(|branch| Scope::copy(branch, &mut copies))
+                .collect(),
+        })
+    }
+}
+
+// State of an environment and of every value reachable from its variables. Restoring it undoes
+// the declarations and assignments made since it was captured, keeping the identity of the
+// boxes so values shared between variables stay shared.
+//
+// Capturing is not copy-on-write: it walks the whole heap, natives included, and records every
+// distinct box once with a shallow copy of its value. Elements of lists and maps are recorded as
+// boxes of their own rather than copied with their container, so the cost grows with the number
+// of values alive and the length of their strings, not with how often they are shared. This is
+// fine for interactive sessions, see the transactional benchmark for large heaps
+pub struct EnvironmentCheckpoint {
+    environment: Box<dyn Environment>,
+    values: Vec<(ValueBox, Value)>,
+}
+
+impl EnvironmentCheckpoint {
+    pub fn capture(environment: &dyn Environment) -> Result<Self, String> {
+        let mut values = Vec::new();
+        let mut visited = HashSet::new();
+
+        for (_, variable) in environment.get_all_variables() {
+            visit_reachable(&variable, &mut visited, &mut |value_box, value| {
+                values.push((value_box.clone(), value.clone()))
+            })?;
+        }
+
+        Ok(Self {
+            environment: environment.duplicate(),
+            values,
+        })
+    }
+
+    // Number of boxes recorded, the values reachable from the environment when captured
+    pub fn get_value_count(&self) -> usize {
+        self.values.len()
+    }
+
+    // Writes back the captured values and returns the captured environment
+    pub fn restore(self) -> Result<Box<dyn Environment>, String> {
+        for (value_box, value) in self.values {
+            let mut value_guard = value_box.try_borrow_mut().map_err(|e| e.to_string())?;
+            *value_guard = value;
+        }
+
+        Ok(self.environment)
     }
 }
 
@@ -147,9 +316,9 @@ impl std::fmt::Display for EnvironmentImpl {
 #[cfg(test)]
 mod tests {
 
-    use crate::lox::{Value, ValueBox};
+    use crate::lox::{new_value_box, Symbol, Value, ValueBox};
 
-    use super::Environment;
+    use super::{Environment, EnvironmentCheckpoint};
 
     trait ManipulateVariable {
         fn get_variable(&self, name: &str) -> Result<ValueBox, String>;
@@ -169,12 +338,12 @@ mod tests {
     impl ManipulateVariable for EnvironmentHolder {
         fn get_variable(&self, name: &str) -> Result<ValueBox, String> {
             self.environment
-                .get_variable(name)
+                .get_variable(Symbol::new(name))
                 .ok_or(format!("Variable not found: {name}"))
         }
 
         fn define_variable(&mut self, name: &str, value: Value) {
-            self.environment.define_variable(name, value);
+            self.environment.define_variable(Symbol::new(name), value);
         }
     }
 
@@ -183,27 +352,31 @@ mod tests {
         let mut env = super::EnvironmentImpl::new();
 
         // create a variable
-        env.define_variable("a", super::Value::Number(1.0));
+        env.define_variable(Symbol::new("a"), super::Value::Number(1.0));
 
-        let a1 = env.get_variable("a").ok_or("Variable 'a' not found")?;
+        let a1 = env
+            .get_variable(Symbol::new("a"))
+            .ok_or("Variable 'a' not found")?;
         let mut addr_a1: usize = 0;
-        if let Ok(mut guard) = a1.write() {
-            *guard.as_mut() = super::Value::Number(2.0);
-            addr_a1 = &*guard.as_ref() as *const Value as usize;
+        if let Ok(mut guard) = a1.try_borrow_mut() {
+            *guard = super::Value::Number(2.0);
+            addr_a1 = &*guard as *const Value as usize;
         }
         // let addr_a1 = &*a1 as *const Value as usize;
         // *a1.as_mut() = super::Value::Number(2.0);
 
-        let a2 = env.get_variable("a").ok_or("Variable 'a' not found")?;
+        let a2 = env
+            .get_variable(Symbol::new("a"))
+            .ok_or("Variable 'a' not found")?;
         let mut addr_a2: usize = 0;
-        if let Ok(mut guard) = a2.write() {
-            *guard.as_mut() = super::Value::Number(3.0);
-            addr_a2 = &*guard.as_ref() as *const Value as usize;
+        if let Ok(mut guard) = a2.try_borrow_mut() {
+            *guard = super::Value::Number(3.0);
+            addr_a2 = &*guard as *const Value as usize;
         }
 
-        // lock a1 again and check the value
-        if let Ok(guard) = a1.read() {
-            assert_eq!(*guard.as_ref(), super::Value::Number(3.0));
+        // borrow a1 again and check the value
+        if let Ok(guard) = a1.try_borrow() {
+            assert_eq!(*guard, super::Value::Number(3.0));
         }
         // assert_eq!(*a1, super::Value::Number(3.0));
         assert_eq!(addr_a1, addr_a2);
@@ -217,14 +390,14 @@ mod tests {
         env_holder.define_variable("a", Value::Number(1.0));
 
         let a1 = env_holder.get_variable("a")?;
-        if let Ok(a_guard) = a1.read() {
-            assert_eq!(*a_guard.as_ref(), Value::Number(1.0));
+        if let Ok(a_guard) = a1.try_borrow() {
+            assert_eq!(*a_guard, Value::Number(1.0));
         }
 
         let a2 = env_holder.get_variable("a")?;
 
-        let partial = if let Ok(a1_guard) = a1.try_read() {
-            match a1_guard.as_ref() {
+        let partial = if let Ok(a1_guard) = a1.try_borrow() {
+            match &*a1_guard {
                 Value::Number(ref a1_value) => *a1_value + 1.0,
                 _ => 0.0,
             }
@@ -233,14 +406,125 @@ mod tests {
         };
 
         // then write the value
-        if let Ok(mut a2_guard) = a2.try_write() {
-            *a2_guard.as_mut() = Value::Number(partial);
+        if let Ok(mut a2_guard) = a2.try_borrow_mut() {
+            *a2_guard = Value::Number(partial);
         }
 
-        if let Ok(a_guard) = a1.read() {
-            assert_eq!(*a_guard.as_ref(), Value::Number(2.0));
+        if let Ok(a_guard) = a1.try_borrow() {
+            assert_eq!(*a_guard, Value::Number(2.0));
         }
 
         Ok(())
     }
+
+    fn number(environment: &dyn Environment, name: &str) -> Option<f64> {
+        let variable = environment.get_variable(Symbol::new(name))?;
+        let value = variable.borrow().to_number();
+        value
+    }
+
+    #[test]
+    fn test_scope_chain() {
+        let mut env = super::EnvironmentImpl::new();
+        env.define_variable(Symbol::new("a"), Value::Number(1.0));
+
+        // inner scopes see and assign the variables of the enclosing ones
+        env.push_variable_stack();
+        env.define_variable(Symbol::new("b"), Value::Number(2.0));
+        env.push_variable_stack();
+        env.define_variable(Symbol::new("a"), Value::Number(3.0));
+        assert!(env
+            .set_variable(Symbol::new("b"), Value::Number(4.0))
+            .is_ok());
+
+        assert_eq!(number(&env, "a"), Some(3.0));
+        assert_eq!(
+            env.get_variable_at(1, Symbol::new("b"))
+                .map(|b| b.borrow().clone()),
+            Some(Value::Number(4.0))
+        );
+        assert!(env.get_variable_at(0, Symbol::new("b")).is_none());
+        assert_eq!(env.get_scope_count(), 3);
+
+        // popping every scope goes back to the global one
+        env.pop_variable_stack();
+        env.pop_variable_stack();
+        env.pop_variable_stack();
+        env.define_variable(Symbol::new("c"), Value::Number(5.0));
+
+        assert_eq!(number(&env, "a"), Some(1.0));
+        assert_eq!(number(&env, "b"), None);
+        assert_eq!(env.get_scope_count(), 1);
+        assert_eq!(env.get_global_variables().len(), 2);
+    }
+
+    #[test]
+    fn test_branches() {
+        let mut env = super::EnvironmentImpl::new();
+        env.define_variable(Symbol::new("global"), Value::Number(1.0));
+        env.push_variable_stack();
+        env.define_variable(Symbol::new("local"), Value::Number(2.0));
+
+        // a branch only sees the global scope
+        env.branch_push();
+        assert_eq!(number(&env, "global"), Some(1.0));
+        assert_eq!(number(&env, "local"), None);
+        assert_eq!(env.get_scope_count(), 3);
+        assert_eq!(env.get_all_variables().len(), 2);
+
+        env.branch_pop();
+        assert_eq!(number(&env, "local"), Some(2.0));
+    }
+
+    #[test]
+    fn test_duplicate() {
+        let mut env = super::EnvironmentImpl::new();
+        env.push_variable_stack();
+        env.define_variable(Symbol::new("a"), Value::Number(1.0));
+
+        let mut copy = env.duplicate();
+
+        // the variables are shared
+        assert!(env
+            .set_variable(Symbol::new("a"), Value::Number(2.0))
+            .is_ok());
+        assert_eq!(number(copy.as_ref(), "a"), Some(2.0));
+
+        // the scopes are independent
+        copy.define_variable(Symbol::new("b"), Value::Number(3.0));
+        copy.pop_variable_stack();
+        assert_eq!(number(&env, "b"), None);
+        assert_eq!(number(copy.as_ref(), "a"), None);
+        assert_eq!(env.get_scope_count(), 2);
+    }
+
+    #[test]
+    fn test_checkpoint_records_shared_values_once() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a list of a thousand elements held by a variable
+        let mut env = super::EnvironmentImpl::new();
+        let elements = (0..1000)
+            .map(|i| new_value_box(Value::Number(i as f64)))
+            .collect();
+        env.define_variable(Symbol::new("a"), Value::List(elements));
+        let single = EnvironmentCheckpoint::capture(&env)?.get_value_count();
+
+        ///////////////////////////////////////////////////////////////////////
+        // When more variables share the elements of the list
+        let list = env
+            .get_variable(Symbol::new("a"))
+            .ok_or("Variable not found: a")?;
+        let list = list.try_borrow().map_err(|e| e.to_string())?.clone();
+        for name in ["b", "c", "d"] {
+            env.define_variable(Symbol::new(name), list.clone());
+        }
+        let shared = EnvironmentCheckpoint::capture(&env)?.get_value_count();
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the elements are recorded once, only the boxes of the variables are added
+        assert_eq!(single, 1001);
+        assert_eq!(shared, single + 3);
+
+        Ok(())
+    }
 }
@@ -1,29 +1,45 @@
+use super::{Span, Symbol};
+
 #[derive(PartialEq, PartialOrd, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Expr {
-    // Assign
-    // TODO: left side should be an Expr once we need lvalues
-    Assign(String, Box<Expr>),
-
-    // Binary
-    BinaryOr(Box<Expr>, Box<Expr>),
-    BinaryAnd(Box<Expr>, Box<Expr>),
-    BinaryEqual(Box<Expr>, Box<Expr>),
-    BinaryNotEqual(Box<Expr>, Box<Expr>),
-    BinaryLess(Box<Expr>, Box<Expr>),
-    BinaryLessEqual(Box<Expr>, Box<Expr>),
-    BinaryGreater(Box<Expr>, Box<Expr>),
-    BinaryGreaterEqual(Box<Expr>, Box<Expr>),
-    BinaryAdd(Box<Expr>, Box<Expr>),
-    BinarySub(Box<Expr>, Box<Expr>),
-    BinaryMul(Box<Expr>, Box<Expr>),
-    BinaryDiv(Box<Expr>, Box<Expr>),
+    // Assign, one variant for each kind of target
+    Assign(Symbol, Box<Expr>, Span), // name, value, operator span
+    IndexAssign(Box<Expr>, Box<Expr>, Box<Expr>), // list, index, value
+    Set(Box<Expr>, Symbol, Box<Expr>), // object, property name, value
+
+    // Binary: left, right, operator span
+    BinaryOr(Box<Expr>, Box<Expr>, Span),
+    BinaryAnd(Box<Expr>, Box<Expr>, Span),
+    BinaryEqual(Box<Expr>, Box<Expr>, Span),
+    BinaryNotEqual(Box<Expr>, Box<Expr>, Span),
+    BinaryLess(Box<Expr>, Box<Expr>, Span),
+    BinaryLessEqual(Box<Expr>, Box<Expr>, Span),
+    BinaryGreater(Box<Expr>, Box<Expr>, Span),
+    BinaryGreaterEqual(Box<Expr>, Box<Expr>, Span),
+    BinaryAdd(Box<Expr>, Box<Expr>, Span),
+    BinarySub(Box<Expr>, Box<Expr>, Span),
+    BinaryMul(Box<Expr>, Box<Expr>, Span),
+    BinaryDiv(Box<Expr>, Box<Expr>, Span),
+
+    // Range: start, end, whether the end is included, operator span
+    Range(Box<Expr>, Box<Expr>, bool, Span),
 
     // Unary
     UnaryBang(Box<Expr>),
     UnaryMinus(Box<Expr>),
 
-    // Function call
-    Call(Box<Expr>, Vec<Expr>),
+    // Function call: callee, arguments, left parenthesis span
+    Call(Box<Expr>, Vec<Expr>, Span),
+
+    // List indexing
+    Index(Box<Expr>, Box<Expr>), // list, index
+
+    // Property access
+    Get(Box<Expr>, Symbol), // object, property name
+
+    // List literal
+    List(Vec<Expr>),
 
     // Terminal nodes
     LiteralString(String),
@@ -31,30 +47,44 @@ pub enum Expr {
     False,
     True,
     Nil,
-    Identifier(String),
+    Identifier(Symbol),
 }
 
 impl Expr {
     pub fn accept<T>(&self, visitor: &mut dyn ExprVisitor<T>) -> T {
         match self {
-            Expr::Assign(left, right) => visitor.visit_assign(left, right),
-            Expr::BinaryOr(left, right) => visitor.visit_binary_or(left, right),
-            Expr::BinaryAnd(left, right) => visitor.visit_binary_and(left, right),
-            Expr::BinaryEqual(left, right) => visitor.visit_binary_equal(left, right),
-            Expr::BinaryNotEqual(left, right) => visitor.visit_binary_not_equal(left, right),
-            Expr::BinaryLess(left, right) => visitor.visit_binary_less(left, right),
-            Expr::BinaryLessEqual(left, right) => visitor.visit_binary_less_equal(left, right),
-            Expr::BinaryGreater(left, right) => visitor.visit_binary_greater(left, right),
-            Expr::BinaryGreaterEqual(left, right) => {
-                visitor.visit_binary_greater_equal(left, right)
+            Expr::Assign(left, right, span) => visitor.visit_assign(left, right, span),
+            Expr::IndexAssign(list, index, value) => visitor.visit_index_assign(list, index, value),
+            Expr::Set(object, name, value) => visitor.visit_set(object, name, value),
+            Expr::BinaryOr(left, right, span) => visitor.visit_binary_or(left, right, span),
+            Expr::BinaryAnd(left, right, span) => visitor.visit_binary_and(left, right, span),
+            Expr::BinaryEqual(left, right, span) => visitor.visit_binary_equal(left, right, span),
+            Expr::BinaryNotEqual(left, right, span) => {
+                visitor.visit_binary_not_equal(left, right, span)
+            }
+            Expr::BinaryLess(left, right, span) => visitor.visit_binary_less(left, right, span),
+            Expr::BinaryLessEqual(left, right, span) => {
+                visitor.visit_binary_less_equal(left, right, span)
+            }
+            Expr::BinaryGreater(left, right, span) => {
+                visitor.visit_binary_greater(left, right, span)
+            }
+            Expr::BinaryGreaterEqual(left, right, span) => {
+                visitor.visit_binary_greater_equal(left, right, span)
+            }
+            Expr::BinaryAdd(left, right, span) => visitor.visit_binary_add(left, right, span),
+            Expr::BinarySub(left, right, span) => visitor.visit_binary_sub(left, right, span),
+            Expr::BinaryMul(left, right, span) => visitor.visit_binary_mul(left, right, span),
+            Expr::BinaryDiv(left, right, span) => visitor.visit_binary_div(left, right, span),
+            Expr::Range(start, end, inclusive, span) => {
+                visitor.visit_range(start, end, inclusive, span)
             }
-            Expr::BinaryAdd(left, right) => visitor.visit_binary_add(left, right),
-            Expr::BinarySub(left, right) => visitor.visit_binary_sub(left, right),
-            Expr::BinaryMul(left, right) => visitor.visit_binary_mul(left, right),
-            Expr::BinaryDiv(left, right) => visitor.visit_binary_div(left, right),
             Expr::UnaryBang(expr) => visitor.visit_unary_bang(expr),
             Expr::UnaryMinus(expr) => visitor.visit_unary_minus(expr),
-            Expr::Call(callee, arguments) => visitor.visit_call(callee, arguments),
+            Expr::Call(callee, arguments, span) => visitor.visit_call(callee, arguments, span),
+            Expr::Index(list, index) => visitor.visit_index(list, index),
+            Expr::Get(object, name) => visitor.visit_get(object, name),
+            Expr::List(elements) => visitor.visit_list(elements),
             Expr::LiteralString(value) => visitor.visit_literal_string(value),
             Expr::LiteralNumber(value) => visitor.visit_literal_number(value),
             Expr::False => visitor.visit_false(),
@@ -66,19 +96,29 @@ impl Expr {
 }
 
 pub trait ExprVisitor<T> {
-    fn visit_assign(&mut self, left: &String, right: &Box<Expr>) -> T;
-    fn visit_binary_or(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> T;
-    fn visit_binary_and(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> T;
-    fn visit_binary_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> T;
-    fn visit_binary_not_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> T;
-    fn visit_binary_less(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> T;
-    fn visit_binary_less_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> T;
-    fn visit_binary_greater(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> T;
-    fn visit_binary_greater_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> T;
-    fn visit_binary_add(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> T;
-    fn visit_binary_sub(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> T;
-    fn visit_binary_mul(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> T;
-    fn visit_binary_div(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> T;
+    fn visit_assign(&mut self, left: &Symbol, right: &Box<Expr>, span: &Span) -> T;
+    fn visit_index_assign(&mut self, list: &Box<Expr>, index: &Box<Expr>, value: &Box<Expr>) -> T;
+    fn visit_set(&mut self, object: &Box<Expr>, name: &Symbol, value: &Box<Expr>) -> T;
+    fn visit_binary_or(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> T;
+    fn visit_binary_and(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> T;
+    fn visit_binary_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> T;
+    fn visit_binary_not_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> T;
+    fn visit_binary_less(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> T;
+    fn visit_binary_less_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> T;
+    fn visit_binary_greater(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> T;
+    fn visit_binary_greater_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span)
+        -> T;
+    fn visit_binary_add(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> T;
+    fn visit_binary_sub(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> T;
+    fn visit_binary_mul(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> T;
+    fn visit_binary_div(&mut self, left: &Box<Expr>, right: &Box<Expr>, span: &Span) -> T;
+    fn visit_range(
+        &mut self,
+        start: &Box<Expr>,
+        end: &Box<Expr>,
+        inclusive: &bool,
+        span: &Span,
+    ) -> T;
 
     fn visit_unary_bang(&mut self, expr: &Box<Expr>) -> T;
     fn visit_unary_minus(&mut self, expr: &Box<Expr>) -> T;
@@ -88,14 +128,17 @@ pub trait ExprVisitor<T> {
     fn visit_false(&mut self) -> T;
     fn visit_true(&mut self) -> T;
     fn visit_nil(&mut self) -> T;
-    fn visit_identifier(&mut self, value: &String) -> T;
-    fn visit_call(&mut self, callee: &Box<Expr>, arguments: &Vec<Expr>) -> T;
+    fn visit_identifier(&mut self, value: &Symbol) -> T;
+    fn visit_call(&mut self, callee: &Box<Expr>, arguments: &Vec<Expr>, span: &Span) -> T;
+    fn visit_index(&mut self, list: &Box<Expr>, index: &Box<Expr>) -> T;
+    fn visit_get(&mut self, object: &Box<Expr>, name: &Symbol) -> T;
+    fn visit_list(&mut self, elements: &Vec<Expr>) -> T;
 }
 
 #[cfg(test)]
 mod tests {
 
-    use super::Expr;
+    use super::{Expr, Span};
 
     #[test]
     fn test_partial_eq_number() {
@@ -116,10 +159,12 @@ mod tests {
         let expr1 = Expr::BinaryAdd(
             Box::new(Expr::LiteralNumber(1.0)),
             Box::new(Expr::LiteralNumber(2.0)),
+            Span::default(),
         );
         let expr2 = Expr::BinaryAdd(
             Box::new(Expr::LiteralNumber(1.0)),
             Box::new(Expr::LiteralNumber(2.0)),
+            Span::default(),
         );
         assert_eq!(expr1, expr2);
     }
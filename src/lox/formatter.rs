@@ -0,0 +1,664 @@
+use super::{AstPrinter, Expr, ExprVisitor, Parser, Scanner, Span, Stmt, StmtVisitor, Symbol};
+
+const INDENT: &str = "    ";
+
+// Binding strength of the expressions, from loosest to tightest, used to decide where the
+// formatted source needs parentheses since the AST does not keep them
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Precedence {
+    Assignment,
+    Or,
+    And,
+    Equality,
+    Comparison,
+    Range,
+    Term,
+    Factor,
+    Unary,
+    Postfix,
+    Primary,
+}
+
+fn precedence(expr: &Expr) -> Precedence {
+    match expr {
+        Expr::Assign(..) | Expr::IndexAssign(..) | Expr::Set(..) => Precedence::Assignment,
+        Expr::BinaryOr(..) => Precedence::Or,
+        Expr::BinaryAnd(..) => Precedence::And,
+        Expr::BinaryEqual(..) | Expr::BinaryNotEqual(..) => Precedence::Equality,
+        Expr::BinaryLess(..)
+        | Expr::BinaryLessEqual(..)
+        | Expr::BinaryGreater(..)
+        | Expr::BinaryGreaterEqual(..) => Precedence::Comparison,
+        Expr::Range(..) => Precedence::Range,
+        Expr::BinaryAdd(..) | Expr::BinarySub(..) => Precedence::Term,
+        Expr::BinaryMul(..) | Expr::BinaryDiv(..) => Precedence::Factor,
+        Expr::UnaryBang(..) | Expr::UnaryMinus(..) => Precedence::Unary,
+        Expr::Call(..) | Expr::Index(..) | Expr::Get(..) => Precedence::Postfix,
+        _ => Precedence::Primary,
+    }
+}
+
+// Re-emits programs as canonical Lox source: one statement per line, blocks indented with four
+// spaces, single spaces around binary operators, blank lines around function declarations and
+// only the parentheses needed to keep the meaning of expressions
+pub struct Formatter {
+    depth: usize,
+}
+
+impl Formatter {
+    pub fn new() -> Self {
+        Self { depth: 0 }
+    }
+
+    // Formats the statements of a program, each one on its own line
+    pub fn format(&mut self, statements: &[Stmt]) -> String {
+        let mut formatted = String::new();
+
+        for (i, stmt) in statements.iter().enumerate() {
+            let is_function = |stmt: &Stmt| matches!(stmt, Stmt::FunctionDeclaration(..));
+            if i > 0 && (is_function(stmt) || is_function(&statements[i - 1])) {
+                formatted.push('\n');
+            }
+
+            formatted.push_str(&self.indentation());
+            formatted.push_str(&stmt.accept(self));
+            formatted.push('\n');
+        }
+
+        formatted
+    }
+
+    // Formats a source, failing if it does not parse. The comments before the first token, like
+    // the language version header, are kept. Comments anywhere else can't be placed back since
+    // the AST does not keep the location of statements, so sources having them are rejected
+    pub fn format_source(source: &str) -> Result<String, String> {
+        let scanner = Scanner::new(source.to_string());
+        let mut tokens = scanner.tokens();
+        let spanned_tokens = tokens.by_ref().collect::<Result<Vec<_>, _>>()?;
+
+        let first_line = spanned_tokens
+            .iter()
+            .find(|t| t.token != super::Token::Eof)
+            .map_or(u64::MAX, |t| t.span.line);
+        let (header, comments): (Vec<_>, Vec<_>) = tokens
+            .get_comments()
+            .iter()
+            .partition(|(span, _)| span.line < first_line);
+
+        if let Some((span, _)) = comments.first() {
+            return Err(format!(
+                "Can't format sources with comments after the first statement, found one at {span}"
+            ));
+        }
+
+        let statements = Parser::from_spanned_tokens(spanned_tokens)
+            .parse()
+            .map_err(|e| e.to_string())?;
+
+        let mut formatted = String::new();
+        for (_, comment) in header.iter() {
+            formatted.push_str(comment);
+            formatted.push('\n');
+        }
+        if !header.is_empty() && !statements.is_empty() {
+            formatted.push('\n');
+        }
+        formatted.push_str(&Formatter::new().format(&statements));
+
+        // the formatted source must parse to the same program, without comparing the locations
+        let reparsed = Parser::new(Scanner::new(formatted.clone()).scan_tokens()?)
+            .parse()
+            .map_err(|e| format!("Formatted source does not parse: {}", e.to_string()))?;
+        if AstPrinter::new().print(&reparsed) != AstPrinter::new().print(&statements) {
+            return Err("Formatting changed the meaning of the program".to_string());
+        }
+
+        Ok(formatted)
+    }
+
+    fn indentation(&self) -> String {
+        INDENT.repeat(self.depth)
+    }
+
+    // Operand of a binary expression, in parentheses if it binds looser than the operator. The
+    // right operand also needs them if it binds the same, since binary operators associate left
+    fn operand(&mut self, expr: &Expr, operator: Precedence, is_right: bool) -> String {
+        let operand = precedence(expr);
+        let formatted = expr.accept(self);
+
+        if operand < operator || (is_right && operand == operator) {
+            format!("({formatted})")
+        } else {
+            formatted
+        }
+    }
+
+    fn binary(
+        &mut self,
+        operator: &str,
+        left: &Expr,
+        right: &Expr,
+        precedence: Precedence,
+    ) -> String {
+        format!(
+            "{} {operator} {}",
+            self.operand(left, precedence, false),
+            self.operand(right, precedence, true)
+        )
+    }
+
+    fn list(&mut self, exprs: &[Expr]) -> String {
+        exprs
+            .iter()
+            .map(|expr| expr.accept(self))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    // Statement nested in an if or while, blocks open on the same line and other statements go
+    // on the next line, indented
+    fn body(&mut self, stmt: &Stmt) -> String {
+        if let Stmt::Block(_) = stmt {
+            return format!(" {}", stmt.accept(self));
+        }
+
+        self.depth += 1;
+        let body = format!("\n{}{}", self.indentation(), stmt.accept(self));
+        self.depth -= 1;
+        body
+    }
+}
+
+impl Default for Formatter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ExprVisitor<String> for Formatter {
+    fn visit_assign(&mut self, left: &Symbol, right: &Box<Expr>, _span: &Span) -> String {
+        format!("{left} = {}", right.accept(self))
+    }
+
+    fn visit_index_assign(
+        &mut self,
+        list: &Box<Expr>,
+        index: &Box<Expr>,
+        value: &Box<Expr>,
+    ) -> String {
+        format!(
+            "{}[{}] = {}",
+            self.operand(list, Precedence::Postfix, false),
+            index.accept(self),
+            value.accept(self)
+        )
+    }
+
+    fn visit_set(&mut self, object: &Box<Expr>, name: &Symbol, value: &Box<Expr>) -> String {
+        format!(
+            "{}.{name} = {}",
+            self.operand(object, Precedence::Postfix, false),
+            value.accept(self)
+        )
+    }
+
+    fn visit_binary_or(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) -> String {
+        self.binary("or", left, right, Precedence::Or)
+    }
+
+    fn visit_binary_and(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) -> String {
+        self.binary("and", left, right, Precedence::And)
+    }
+
+    fn visit_binary_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) -> String {
+        self.binary("==", left, right, Precedence::Equality)
+    }
+
+    fn visit_binary_not_equal(
+        &mut self,
+        left: &Box<Expr>,
+        right: &Box<Expr>,
+        _span: &Span,
+    ) -> String {
+        self.binary("!=", left, right, Precedence::Equality)
+    }
+
+    fn visit_binary_less(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) -> String {
+        self.binary("<", left, right, Precedence::Comparison)
+    }
+
+    fn visit_binary_less_equal(
+        &mut self,
+        left: &Box<Expr>,
+        right: &Box<Expr>,
+        _span: &Span,
+    ) -> String {
+        self.binary("<=", left, right, Precedence::Comparison)
+    }
+
+    fn visit_binary_greater(
+        &mut self,
+        left: &Box<Expr>,
+        right: &Box<Expr>,
+        _span: &Span,
+    ) -> String {
+        self.binary(">", left, right, Precedence::Comparison)
+    }
+
+    fn visit_binary_greater_equal(
+        &mut self,
+        left: &Box<Expr>,
+        right: &Box<Expr>,
+        _span: &Span,
+    ) -> String {
+        self.binary(">=", left, right, Precedence::Comparison)
+    }
+
+    fn visit_binary_add(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) -> String {
+        self.binary("+", left, right, Precedence::Term)
+    }
+
+    fn visit_binary_sub(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) -> String {
+        self.binary("-", left, right, Precedence::Term)
+    }
+
+    fn visit_binary_mul(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) -> String {
+        self.binary("*", left, right, Precedence::Factor)
+    }
+
+    fn visit_binary_div(&mut self, left: &Box<Expr>, right: &Box<Expr>, _span: &Span) -> String {
+        self.binary("/", left, right, Precedence::Factor)
+    }
+
+    fn visit_range(
+        &mut self,
+        start: &Box<Expr>,
+        end: &Box<Expr>,
+        inclusive: &bool,
+        _span: &Span,
+    ) -> String {
+        // ranges do not chain, a range bound is always grouped
+        let operator = if *inclusive { "..=" } else { ".." };
+        format!(
+            "{} {operator} {}",
+            self.operand(start, Precedence::Range, true),
+            self.operand(end, Precedence::Range, true)
+        )
+    }
+
+    fn visit_unary_bang(&mut self, expr: &Box<Expr>) -> String {
+        format!("!{}", self.operand(expr, Precedence::Unary, false))
+    }
+
+    fn visit_unary_minus(&mut self, expr: &Box<Expr>) -> String {
+        format!("-{}", self.operand(expr, Precedence::Unary, false))
+    }
+
+    fn visit_call(&mut self, callee: &Box<Expr>, arguments: &Vec<Expr>, _span: &Span) -> String {
+        format!(
+            "{}({})",
+            self.operand(callee, Precedence::Postfix, false),
+            self.list(arguments)
+        )
+    }
+
+    fn visit_index(&mut self, list: &Box<Expr>, index: &Box<Expr>) -> String {
+        format!(
+            "{}[{}]",
+            self.operand(list, Precedence::Postfix, false),
+            index.accept(self)
+        )
+    }
+
+    fn visit_get(&mut self, object: &Box<Expr>, name: &Symbol) -> String {
+        format!(
+            "{}.{name}",
+            self.operand(object, Precedence::Postfix, false)
+        )
+    }
+
+    fn visit_list(&mut self, elements: &Vec<Expr>) -> String {
+        format!("[{}]", self.list(elements))
+    }
+
+    fn visit_literal_string(&mut self, value: &String) -> String {
+        // only raw strings can contain quotes
+        if value.contains('"') {
+            return format!("\"\"\"{value}\"\"\"");
+        }
+
+        format!("\"{value}\"")
+    }
+
+    fn visit_literal_number(&mut self, value: &f64) -> String {
+        value.to_string()
+    }
+
+    fn visit_false(&mut self) -> String {
+        "false".to_string()
+    }
+
+    fn visit_true(&mut self) -> String {
+        "true".to_string()
+    }
+
+    fn visit_nil(&mut self) -> String {
+        "nil".to_string()
+    }
+
+    fn visit_identifier(&mut self, value: &Symbol) -> String {
+        value.to_string()
+    }
+}
+
+impl StmtVisitor<String> for Formatter {
+    fn visit_print(&mut self, expr: &Box<Expr>) -> String {
+        format!("print {};", expr.accept(self))
+    }
+
+    fn visit_expr(&mut self, expr: &Box<Expr>) -> String {
+        format!("{};", expr.accept(self))
+    }
+
+    fn visit_var_declaration(
+        &mut self,
+        name: &Symbol,
+        initializer: &Option<Box<Expr>>,
+        _span: &Span,
+    ) -> String {
+        match initializer {
+            Some(expr) => format!("var {name} = {};", expr.accept(self)),
+            None => format!("var {name};"),
+        }
+    }
+
+    fn visit_multi_var_declaration(
+        &mut self,
+        declarations: &Vec<(Symbol, Option<Box<Expr>>, Span)>,
+    ) -> String {
+        let declarations = declarations
+            .iter()
+            .map(|(name, initializer, _)| match initializer {
+                Some(expr) => format!("{name} = {}", expr.accept(self)),
+                None => name.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!("var {declarations};")
+    }
+
+    fn visit_const_declaration(
+        &mut self,
+        name: &Symbol,
+        initializer: &Box<Expr>,
+        _span: &Span,
+    ) -> String {
+        format!("const {name} = {};", initializer.accept(self))
+    }
+
+    fn visit_block(&mut self, stmts: &Vec<Stmt>) -> String {
+        if stmts.is_empty() {
+            return "{}".to_string();
+        }
+
+        self.depth += 1;
+        let body = self.format(stmts);
+        self.depth -= 1;
+
+        format!("{{\n{body}{}}}", self.indentation())
+    }
+
+    fn visit_if(
+        &mut self,
+        condition: &Box<Expr>,
+        then_branch: &Box<Stmt>,
+        else_branch: &Option<Box<Stmt>>,
+        _span: &Span,
+    ) -> String {
+        let mut formatted = format!("if ({}){}", condition.accept(self), self.body(then_branch));
+
+        if let Some(else_branch) = else_branch {
+            // a block closes on its own line, the else follows it; other statements end their
+            // line so the else starts a new one
+            match then_branch.as_ref() {
+                Stmt::Block(_) => formatted.push_str(" else"),
+                _ => formatted.push_str(&format!("\n{}else", self.indentation())),
+            }
+
+            match else_branch.as_ref() {
+                // else if chains stay on the same line
+                Stmt::If(..) => formatted.push_str(&format!(" {}", else_branch.accept(self))),
+                _ => formatted.push_str(&self.body(else_branch)),
+            }
+        }
+
+        formatted
+    }
+
+    fn visit_while(&mut self, condition: &Box<Expr>, body: &Box<Stmt>, _span: &Span) -> String {
+        format!("while ({}){}", condition.accept(self), self.body(body))
+    }
+
+    fn visit_for_in(&mut self, name: &Symbol, collection: &Box<Expr>, body: &Box<Stmt>) -> String {
+        format!(
+            "for ({name} in {}){}",
+            collection.accept(self),
+            self.body(body)
+        )
+    }
+
+    fn visit_do_while(&mut self, body: &Box<Stmt>, condition: &Box<Expr>, _span: &Span) -> String {
+        // the while follows a block on its closing line, other bodies end their line
+        let separator = match body.as_ref() {
+            Stmt::Block(_) => " ".to_string(),
+            _ => format!("\n{}", self.indentation()),
+        };
+
+        format!(
+            "do{}{separator}while ({});",
+            self.body(body),
+            condition.accept(self)
+        )
+    }
+
+    fn visit_switch(
+        &mut self,
+        subject: &Box<Expr>,
+        cases: &Vec<(Box<Expr>, Vec<Stmt>, Span)>,
+        default: &Option<Vec<Stmt>>,
+    ) -> String {
+        if cases.is_empty() && default.is_none() {
+            return format!("switch ({}) {{}}", subject.accept(self));
+        }
+
+        let mut formatted = format!("switch ({}) {{\n", subject.accept(self));
+
+        // cases are indented inside the switch, and their statements inside the cases
+        self.depth += 1;
+        for (value, body, _) in cases {
+            let label = format!("case {}:", value.accept(self));
+            formatted.push_str(&format!("{}{label}\n", self.indentation()));
+            self.depth += 1;
+            formatted.push_str(&self.format(body));
+            self.depth -= 1;
+        }
+
+        if let Some(default) = default {
+            formatted.push_str(&format!("{}default:\n", self.indentation()));
+            self.depth += 1;
+            formatted.push_str(&self.format(default));
+            self.depth -= 1;
+        }
+        self.depth -= 1;
+
+        formatted.push_str(&format!("{}}}", self.indentation()));
+        formatted
+    }
+
+    fn visit_function_declaration(
+        &mut self,
+        name: &Symbol,
+        arguments: &Vec<Symbol>,
+        body: &Box<Stmt>,
+    ) -> String {
+        // the parser wraps the body block of functions in another block
+        let body = match body.as_ref() {
+            Stmt::Block(stmts) if stmts.len() == 1 => &stmts[0],
+            other => other,
+        };
+
+        format!(
+            "fun {name}({}) {}",
+            arguments
+                .iter()
+                .map(|a| a.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            body.accept(self)
+        )
+    }
+
+    fn visit_throw(&mut self, expr: &Box<Expr>, _span: &Span) -> String {
+        format!("throw {};", expr.accept(self))
+    }
+
+    fn visit_try(&mut self, body: &Vec<Stmt>, name: &Symbol, handler: &Vec<Stmt>) -> String {
+        format!(
+            "try {} catch ({name}) {}",
+            self.visit_block(body),
+            self.visit_block(handler)
+        )
+    }
+
+    fn visit_assert(
+        &mut self,
+        condition: &Box<Expr>,
+        message: &Option<Box<Expr>>,
+        _span: &Span,
+    ) -> String {
+        match message {
+            Some(message) => format!(
+                "assert {}, {};",
+                condition.accept(self),
+                message.accept(self)
+            ),
+            None => format!("assert {};", condition.accept(self)),
+        }
+    }
+
+    fn visit_import(&mut self, path: &String, alias: &Option<Symbol>) -> String {
+        match alias {
+            Some(alias) => format!("import \"{path}\" as {alias};"),
+            None => format!("import \"{path}\";"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::path::PathBuf;
+
+    use rstest::*;
+
+    use super::Formatter;
+
+    #[rstest]
+    #[case::spacing("var   a=1+2 *3;print a;", "var a = 1 + 2 * 3;\nprint a;\n")]
+    #[case::multiple_variables("var a=1,b ,c=a+b;", "var a = 1, b, c = a + b;\n")]
+    #[case::constant("const  a=[1,2] ;", "const a = [1, 2];\n")]
+    #[case::parentheses(
+        "print (1 + 2) * (3 - (4 - 5)) / -(a);",
+        "print (1 + 2) * (3 - (4 - 5)) / -a;\n"
+    )]
+    #[case::redundant_parentheses("print ((a)) or (b and c);", "print a or b and c;\n")]
+    #[case::postfix("(f)(1,2)[0] = (a = b);", "f(1, 2)[0] = a = b;\n")]
+    #[case::property_assignment("a . b.c( ).d=5;", "a.b.c().d = 5;\n")]
+    #[case::range("for(i in 0..n+1)print i;", "for (i in 0 .. n + 1)\n    print i;\n")]
+    #[case::grouped_range("print (1..2)..=3 == r;", "print (1 .. 2) ..= 3 == r;\n")]
+    #[case::raw_string(
+        "print \"\"\"say \"hi\"\n\"\"\"+\"\";",
+        "print \"\"\"say \"hi\"\n\"\"\" + \"\";\n"
+    )]
+    #[case::grouped_assignment("print (a = 1) + 2;", "print (a = 1) + 2;\n")]
+    #[case::block(
+        "{var a; {print a;}}",
+        "{\n    var a;\n    {\n        print a;\n    }\n}\n"
+    )]
+    #[case::empty_block("while (x) {}", "while (x) {}\n")]
+    #[case::switch(
+        "switch(a){case 1:print a;a=2;case \"b\":default:{print nil;}}",
+        "switch (a) {\n    case 1:\n        print a;\n        a = 2;\n    case \"b\":\n    default:\n        {\n            print nil;\n        }\n}\n"
+    )]
+    #[case::for_in("for(x in [1,2])print x;", "for (x in [1, 2])\n    print x;\n")]
+    #[case::for_in_block(
+        "for (c in \"ab\") { print c; }",
+        "for (c in \"ab\") {\n    print c;\n}\n"
+    )]
+    #[case::do_while_block("do{a=a+1;}while(a<3);", "do {\n    a = a + 1;\n} while (a < 3);\n")]
+    #[case::do_while_statement(
+        "{do a=a+1;while(a<3);}",
+        "{\n    do\n        a = a + 1;\n    while (a < 3);\n}\n"
+    )]
+    #[case::try_catch(
+        "try{throw \"a\";}catch(e){print e;}",
+        "try {\n    throw \"a\";\n} catch (e) {\n    print e;\n}\n"
+    )]
+    #[case::assert("assert(a==1) ,\"message\";", "assert a == 1, \"message\";\n")]
+    #[case::empty_switch("switch (a) {  }", "switch (a) {}\n")]
+    #[case::if_else(
+        "if (a) print 1; else if (b) { print 2; } else print 3;",
+        "if (a)\n    print 1;\nelse if (b) {\n    print 2;\n} else\n    print 3;\n"
+    )]
+    #[case::function(
+        "var a = 1;fun f(x,y){return_value(x);} f(1,2);",
+        "var a = 1;\n\nfun f(x, y) {\n    return_value(x);\n}\n\nf(1, 2);\n"
+    )]
+    #[case::imports(
+        "import \"a.lox\";import \"b.lox\" as b;print b.c[1];",
+        "import \"a.lox\";\nimport \"b.lox\" as b;\nprint b.c[1];\n"
+    )]
+    #[case::version_header("// lox-version: 1\nprint 1;", "// lox-version: 1\n\nprint 1;\n")]
+    #[case::multiline_string("{ print \"a\nb\"; }", "{\n    print \"a\nb\";\n}\n")]
+    fn test_format_source(#[case] source: &str, #[case] expected: &str) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a source with non canonical formatting
+        // When formatting it
+        let formatted = Formatter::format_source(source)?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the result is the canonical source
+        assert_eq!(formatted, expected);
+
+        // and formatting it again leaves it unchanged
+        assert_eq!(Formatter::format_source(&formatted)?, formatted);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::comment("print 1; // one", "line 1")]
+    #[case::comment_in_block("print 1;\n{\n// nothing\n}", "line 3")]
+    fn test_format_source_comments(#[case] source: &str, #[case] line: &str) {
+        assert_eq!(
+            Formatter::format_source(source),
+            Err(format!(
+                "Can't format sources with comments after the first statement, found one at {line}"
+            ))
+        );
+    }
+
+    #[rstest]
+    fn test_format_programs(
+        #[files("test-data/interpreter/*.lox")] path: PathBuf,
+    ) -> Result<(), String> {
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+
+        let formatted = Formatter::format_source(&source)?;
+
+        assert_eq!(Formatter::format_source(&formatted)?, formatted);
+
+        Ok(())
+    }
+}
@@ -1,18 +1,40 @@
+use std::cell::RefCell;
 use std::fmt::Display;
+use std::rc::Rc;
 
-use crate::lox::new_value_box;
+use super::{
+    argument, new_value_box, CallContext, Callable, Environment, FromLox, IntoLox, Stmt, Symbol,
+    Value, ValueBox,
+};
 
-use super::{Callable, Stmt, Value, ValueBox};
+// Global environment of an imported module, shared by the functions it exports. It is taken
+// out while one of them executes
+pub type ModuleEnvironment = Rc<RefCell<Option<Box<dyn Environment>>>>;
+
+// Name and parameters of a callable, displayed as `<fn name(a, b)>` or `<native fn name()>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionSignature {
+    pub name: String,
+    pub arguments: Vec<String>,
+    pub native: bool,
+}
+
+impl Display for FunctionSignature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = if self.native { "native fn" } else { "fn" };
+        write!(f, "<{kind} {}({})>", self.name, self.arguments.join(", "))
+    }
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct FunctionImpl {
-    name: String,
-    arguments: Vec<String>,
+    name: Symbol,
+    arguments: Vec<Symbol>,
     body: Box<Stmt>,
 }
 
 impl FunctionImpl {
-    pub fn new(name: String, arguments: Vec<String>, body: Box<Stmt>) -> Self {
+    pub fn new(name: Symbol, arguments: Vec<Symbol>, body: Box<Stmt>) -> Self {
         Self {
             name,
             arguments,
@@ -22,26 +44,14 @@ impl FunctionImpl {
 }
 
 impl Callable for FunctionImpl {
-    fn call(&self) -> Result<ValueBox, String> {
-        println!("FunctionImpl::call(): {}", self.name);
-
-        // let mut environment = EnvironmentImpl::new();
-        // environment.push_variable_stack();
-
-        // for (name, value) in self.arguments.iter().zip(arguments.iter()) {
-        //     environment.define_variable(name, value.clone());
-        // }
-
-        // let result = self.body.accept(&mut Interpreter::new(&mut environment));
-
-        // environment.pop_variable_stack();
-
-        // result
-
-        Ok(new_value_box(Value::Nil))
+    fn call(&self, _arguments: &[Value]) -> Result<ValueBox, String> {
+        Err(format!(
+            "Function '{}' has a body and must be executed by the interpreter",
+            self.name
+        ))
     }
 
-    fn get_arg_name(&self, arg_number: usize) -> Result<String, String> {
+    fn get_arg_name(&self, arg_number: usize) -> Result<Symbol, String> {
         if arg_number >= self.arguments.len() {
             return Err(format!(
                 "Function '{}' has {} arguments, requested argument {}",
@@ -58,13 +68,330 @@ impl Callable for FunctionImpl {
         self.arguments.len()
     }
 
-    fn get_body(&self) -> &Box<Stmt> {
-        &self.body
+    fn get_body(&self) -> Option<&Box<Stmt>> {
+        Some(&self.body)
+    }
+
+    fn get_signature(&self) -> FunctionSignature {
+        FunctionSignature {
+            name: self.name.to_string(),
+            arguments: self.arguments.iter().map(|a| a.to_string()).collect(),
+            native: false,
+        }
     }
 }
 
 impl Display for FunctionImpl {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "<fn {}>", self.name)
+        write!(f, "{}", self.get_signature())
+    }
+}
+
+// Function exported by a module bound to an alias, executed in the environment of the module
+#[derive(Debug, Clone)]
+pub struct ModuleFunction {
+    function: Rc<Box<dyn Callable>>,
+    environment: ModuleEnvironment,
+}
+
+impl ModuleFunction {
+    pub fn new(function: Rc<Box<dyn Callable>>, environment: ModuleEnvironment) -> Self {
+        Self {
+            function,
+            environment,
+        }
+    }
+}
+
+impl Callable for ModuleFunction {
+    fn call(&self, arguments: &[Value]) -> Result<ValueBox, String> {
+        self.function.call(arguments)
+    }
+
+    fn call_in(
+        &self,
+        context: &mut dyn CallContext,
+        arguments: &[Value],
+    ) -> Result<ValueBox, String> {
+        self.function.call_in(context, arguments)
+    }
+
+    fn get_arg_name(&self, arg_number: usize) -> Result<Symbol, String> {
+        self.function.get_arg_name(arg_number)
+    }
+
+    fn get_arg_count(&self) -> usize {
+        self.function.get_arg_count()
+    }
+
+    fn get_body(&self) -> Option<&Box<Stmt>> {
+        self.function.get_body()
+    }
+
+    fn get_module_environment(&self) -> Option<ModuleEnvironment> {
+        Some(self.environment.clone())
+    }
+
+    fn get_signature(&self) -> FunctionSignature {
+        self.function.get_signature()
+    }
+}
+
+impl Display for ModuleFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.function)
+    }
+}
+
+// Signature of the Rust functions backing native callables
+pub type NativeFn = fn(&[Value]) -> Result<Value, String>;
+
+// Signature of the natives using the interpreter calling them, see CallContext
+pub type ContextNativeFn = fn(&mut dyn CallContext, &[Value]) -> Result<Value, String>;
+
+// Signature of the natives backed by Rust closures, see NativeFunction::from_rust
+pub type RustNativeFn = Rc<dyn Fn(&[Value]) -> Result<Value, String>>;
+
+// Same as RustNativeFn for the closures using the interpreter calling them, see
+// NativeFunction::from_rust_with_context
+pub type RustContextNativeFn = Rc<dyn Fn(&mut dyn CallContext, &[Value]) -> Result<Value, String>>;
+
+#[derive(Clone)]
+enum NativeBody {
+    Function(NativeFn),
+    ContextFunction(ContextNativeFn),
+    Closure(RustNativeFn),
+    ContextClosure(RustContextNativeFn),
+}
+
+impl std::fmt::Debug for NativeBody {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NativeBody::Function(function) => f.debug_tuple("Function").field(function).finish(),
+            NativeBody::ContextFunction(function) => {
+                f.debug_tuple("ContextFunction").field(function).finish()
+            }
+            NativeBody::Closure(_) => f.write_str("Closure"),
+            NativeBody::ContextClosure(_) => f.write_str("ContextClosure"),
+        }
+    }
+}
+
+// Rust closures with typed arguments that can back a native, Args being the tuple of the types
+// of their arguments. The arguments are converted with FromLox and the result with IntoLox
+pub trait IntoNative<Args> {
+    fn into_native(self, name: &str, arguments: &[Symbol]) -> RustNativeFn;
+}
+
+// Same as IntoNative for closures taking the interpreter calling them as first argument
+pub trait IntoContextNative<Args> {
+    fn into_context_native(self, name: &str, arguments: &[Symbol]) -> RustContextNativeFn;
+}
+
+macro_rules! impl_into_native {
+    ($($arg:ident),*) => {
+        impl<F, R, $($arg),*> IntoNative<($($arg,)*)> for F
+        where
+            F: Fn($($arg),*) -> Result<R, String> + 'static,
+            R: IntoLox,
+            $($arg: FromLox),*
+        {
+            #[allow(non_snake_case, unused_variables, unused_mut)]
+            fn into_native(self, name: &str, arguments: &[Symbol]) -> RustNativeFn {
+                let name = name.to_string();
+                let arg_names = arguments.to_vec();
+
+                Rc::new(move |values: &[Value]| {
+                    let mut indices = 0..;
+                    $(
+                        let index = indices.next().unwrap_or_default();
+                        let $arg = typed_argument::<$arg>(&name, &arg_names, values, index)?;
+                    )*
+
+                    self($($arg),*).map(IntoLox::into_lox)
+                })
+            }
+        }
+
+        impl<F, R, $($arg),*> IntoContextNative<($($arg,)*)> for F
+        where
+            F: Fn(&mut dyn CallContext, $($arg),*) -> Result<R, String> + 'static,
+            R: IntoLox,
+            $($arg: FromLox),*
+        {
+            #[allow(non_snake_case, unused_variables, unused_mut)]
+            fn into_context_native(self, name: &str, arguments: &[Symbol]) -> RustContextNativeFn {
+                let name = name.to_string();
+                let arg_names = arguments.to_vec();
+
+                Rc::new(move |context: &mut dyn CallContext, values: &[Value]| {
+                    let mut indices = 0..;
+                    $(
+                        let index = indices.next().unwrap_or_default();
+                        let $arg = typed_argument::<$arg>(&name, &arg_names, values, index)?;
+                    )*
+
+                    self(context, $($arg),*).map(IntoLox::into_lox)
+                })
+            }
+        }
+    };
+}
+
+impl_into_native!();
+impl_into_native!(A);
+impl_into_native!(A, B);
+impl_into_native!(A, B, C);
+impl_into_native!(A, B, C, D);
+
+fn typed_argument<T: FromLox>(
+    function: &str,
+    arg_names: &[Symbol],
+    values: &[Value],
+    index: usize,
+) -> Result<T, String> {
+    let arg_name = arg_names.get(index).map(|a| a.as_str()).unwrap_or_default();
+    argument(function, values, index, arg_name)
+}
+
+// Function implemented in Rust and exposed to Lox scripts
+#[derive(Debug, Clone)]
+pub struct NativeFunction {
+    name: String,
+    arguments: Vec<Symbol>,
+    function: NativeBody,
+}
+
+impl NativeFunction {
+    pub fn new(name: &str, arguments: &[&str], function: NativeFn) -> Self {
+        Self {
+            name: name.to_string(),
+            arguments: arguments.iter().map(|a| Symbol::new(a)).collect(),
+            function: NativeBody::Function(function),
+        }
+    }
+
+    // Creates a native that is given access to the interpreter calling it
+    pub fn with_context(name: &str, arguments: &[&str], function: ContextNativeFn) -> Self {
+        Self {
+            name: name.to_string(),
+            arguments: arguments.iter().map(|a| Symbol::new(a)).collect(),
+            function: NativeBody::ContextFunction(function),
+        }
+    }
+
+    // Creates a native from a Rust closure with typed arguments, e.g.
+    // `NativeFunction::from_rust("add", &["a", "b"], |a: f64, b: f64| Ok(a + b))`
+    pub fn from_rust<Args, F: IntoNative<Args>>(
+        name: &str,
+        arguments: &[&str],
+        function: F,
+    ) -> Self {
+        let arguments: Vec<Symbol> = arguments.iter().map(|a| Symbol::new(a)).collect();
+
+        Self {
+            name: name.to_string(),
+            function: NativeBody::Closure(function.into_native(name, &arguments)),
+            arguments,
+        }
+    }
+
+    // Same as from_rust for closures taking the interpreter calling them as first argument, e.g.
+    // to call back a function given as argument
+    pub fn from_rust_with_context<Args, F: IntoContextNative<Args>>(
+        name: &str,
+        arguments: &[&str],
+        function: F,
+    ) -> Self {
+        let arguments: Vec<Symbol> = arguments.iter().map(|a| Symbol::new(a)).collect();
+
+        Self {
+            name: name.to_string(),
+            function: NativeBody::ContextClosure(function.into_context_native(name, &arguments)),
+            arguments,
+        }
+    }
+
+    fn check_arity(&self, arguments: &[Value]) -> Result<(), String> {
+        if arguments.len() != self.arguments.len() {
+            return Err(format!(
+                "Expected {} arguments, but got {}",
+                self.arguments.len(),
+                arguments.len()
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+}
+
+impl Callable for NativeFunction {
+    fn call(&self, arguments: &[Value]) -> Result<ValueBox, String> {
+        self.check_arity(arguments)?;
+
+        match &self.function {
+            NativeBody::Function(function) => function(arguments).map(new_value_box),
+            NativeBody::ContextFunction(_) => Err(format!(
+                "Native function '{}' must be called by an interpreter",
+                self.name
+            )),
+            NativeBody::Closure(function) => function(arguments).map(new_value_box),
+            NativeBody::ContextClosure(_) => Err(format!(
+                "Native function '{}' must be called by an interpreter",
+                self.name
+            )),
+        }
+    }
+
+    fn call_in(
+        &self,
+        context: &mut dyn CallContext,
+        arguments: &[Value],
+    ) -> Result<ValueBox, String> {
+        self.check_arity(arguments)?;
+
+        match &self.function {
+            NativeBody::Function(function) => function(arguments).map(new_value_box),
+            NativeBody::ContextFunction(function) => {
+                function(context, arguments).map(new_value_box)
+            }
+            NativeBody::Closure(function) => function(arguments).map(new_value_box),
+            NativeBody::ContextClosure(function) => function(context, arguments).map(new_value_box),
+        }
+    }
+
+    fn get_arg_name(&self, arg_number: usize) -> Result<Symbol, String> {
+        self.arguments.get(arg_number).cloned().ok_or(format!(
+            "Function '{}' has {} arguments, requested argument {}",
+            self.name,
+            self.arguments.len(),
+            arg_number
+        ))
+    }
+
+    fn get_arg_count(&self) -> usize {
+        self.arguments.len()
+    }
+
+    fn get_body(&self) -> Option<&Box<Stmt>> {
+        None
+    }
+
+    fn get_signature(&self) -> FunctionSignature {
+        FunctionSignature {
+            name: self.name.clone(),
+            arguments: self.arguments.iter().map(|a| a.to_string()).collect(),
+            native: true,
+        }
+    }
+}
+
+impl Display for NativeFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.get_signature())
     }
 }
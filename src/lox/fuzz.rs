@@ -0,0 +1,82 @@
+use super::{parse, tokenize, AstPrinter, Formatter, Token};
+
+// Entry points for the fuzz targets: they take arbitrary bytes, errors are expected results.
+// They panic only when a property of the front end does not hold, which the fuzzer reports
+// together with the input
+
+// Scans the bytes, invalid UTF-8 is replaced as by String::from_utf8_lossy. The tokens end with
+// the only Eof and their lines never go backwards
+pub fn fuzz_scan(bytes: &[u8]) {
+    let source = String::from_utf8_lossy(bytes);
+
+    let Ok(tokens) = tokenize(&source) else {
+        return;
+    };
+
+    let eof_count = tokens.iter().filter(|t| t.token == Token::Eof).count();
+    assert_eq!(eof_count, 1, "Expected a single Eof token");
+    assert_eq!(tokens.last().map(|t| &t.token), Some(&Token::Eof));
+    assert!(
+        tokens.windows(2).all(|w| w[0].span.line <= w[1].span.line),
+        "Token lines go backwards"
+    );
+}
+
+// Parses the bytes as fuzz_scan scans them. A program that parses is printed, and formatted
+// back into a source that parses to the same statements, spans aside
+pub fn fuzz_parse(bytes: &[u8]) {
+    let source = String::from_utf8_lossy(bytes);
+
+    let Ok(program) = parse(&source) else {
+        return;
+    };
+
+    let printed = AstPrinter::new().print(&program.statements);
+
+    // sources with comments after the first statement are rejected by the formatter
+    let Ok(formatted) = Formatter::format_source(&source) else {
+        return;
+    };
+
+    // compared as printed, the spans of the reformatted source are on other lines
+    let reparsed = parse(&formatted).map(|program| AstPrinter::new().print(&program.statements));
+    assert_eq!(
+        reparsed,
+        Ok(printed),
+        "Formatted source parses differently:\n{formatted}"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rstest::*;
+
+    use super::{fuzz_parse, fuzz_scan};
+
+    #[rstest]
+    #[case::empty(b"")]
+    #[case::invalid_utf8(b"print \xff\xfe;")]
+    #[case::unterminated_string(b"print \"abc")]
+    #[case::unterminated_raw_string(b"print \"\"\"abc\n\"\";")]
+    #[case::unterminated_call(b"f(1, ")]
+    #[case::unterminated_block(b"{ var a = 1;")]
+    #[case::lone_operator(b"/")]
+    #[case::unexpected_tokens(b") } ] ; , .")]
+    #[case::trailing_dot(b"1.")]
+    #[case::keywords_only(b"switch case default do while try catch throw assert")]
+    #[case::deep_nesting(&[b'('; 10_000])]
+    #[case::deep_blocks(&[b'{'; 10_000])]
+    fn test_malformed_input(#[case] bytes: &[u8]) {
+        fuzz_scan(bytes);
+        fuzz_parse(bytes);
+    }
+
+    #[test]
+    fn test_valid_program() {
+        let source = b"var a = [1, 2]; fun f(x) { print -(x + a[0]) * 2; } f(3);";
+
+        fuzz_scan(source);
+        fuzz_parse(source);
+    }
+}
@@ -0,0 +1,131 @@
+use std::path::{Path, PathBuf};
+
+use super::{CapturedOutput, Interpreter, InterpreterOptions};
+
+// Golden output checks of the test suite: a program `foo.lox` is expected to print exactly the contents of the
+// `foo.expected` file next to it. A program ending with an error prints it as a last line
+// `error: <message>`, so failures can be golden too
+
+// Setting this variable rewrites the expected files with the actual output instead of comparing
+const UPDATE_EXPECTED_VARIABLE: &str = "LOX_UPDATE_EXPECTED";
+
+// File holding the expected output of the program
+pub fn expected_output_path(program: &Path) -> PathBuf {
+    program.with_extension("expected")
+}
+
+// Output of the program run with the given options, including its error if it fails. Imports
+// are looked up next to the program first
+pub fn run_capturing_output(program: &Path, options: InterpreterOptions) -> Result<String, String> {
+    let source = std::fs::read_to_string(program)
+        .map_err(|e| format!("Error reading '{}': {e}", program.display()))?;
+
+    let output = CapturedOutput::default();
+
+    let mut interpreter = Interpreter::with_options(options);
+    interpreter.set_output(Box::new(output.clone()));
+
+    let directory = program.parent().map(Path::to_path_buf).unwrap_or_default();
+    interpreter.set_module_search_paths(vec![directory, PathBuf::from(".")]);
+
+    let result = interpreter.execute(source);
+
+    let mut contents = output.contents();
+    if let Err(e) = result {
+        contents.push_str(&format!("error: {e}\n"));
+    }
+
+    Ok(contents)
+}
+
+// Runs the program and compares its output with the expected file, failing with the lines that
+// differ
+pub fn check_golden_output(program: &Path, options: InterpreterOptions) -> Result<(), String> {
+    let actual = run_capturing_output(program, options)?;
+    let expected_path = expected_output_path(program);
+
+    if std::env::var_os(UPDATE_EXPECTED_VARIABLE).is_some() {
+        return std::fs::write(&expected_path, actual)
+            .map_err(|e| format!("Error writing '{}': {e}", expected_path.display()));
+    }
+
+    let expected = std::fs::read_to_string(&expected_path)
+        .map_err(|e| format!("Error reading '{}': {e}", expected_path.display()))?;
+
+    if actual == expected {
+        return Ok(());
+    }
+
+    Err(format!(
+        "Output of '{}' differs from '{}':\n{}",
+        program.display(),
+        expected_path.display(),
+        diff_lines(&expected, &actual)
+    ))
+}
+
+// Line by line comparison, `-` for the expected lines and `+` for the actual ones that differ
+fn diff_lines(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    let mut diff = String::new();
+    for i in 0..expected.len().max(actual.len()) {
+        let (expected_line, actual_line) = (expected.get(i), actual.get(i));
+        if expected_line == actual_line {
+            continue;
+        }
+
+        diff.push_str(&format!("line {}:\n", i + 1));
+        if let Some(line) = expected_line {
+            diff.push_str(&format!("-{line}\n"));
+        }
+        if let Some(line) = actual_line {
+            diff.push_str(&format!("+{line}\n"));
+        }
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::path::PathBuf;
+
+    use rstest::*;
+
+    use crate::lox::InterpreterOptions;
+
+    use super::{check_golden_output, diff_lines, run_capturing_output};
+
+    #[rstest]
+    fn test_golden_output(
+        #[files("test-data/interpreter/*.lox")]
+        #[files("test-data/output/*.lox")]
+        program: PathBuf,
+    ) -> Result<(), String> {
+        check_golden_output(&program, InterpreterOptions::default())
+    }
+
+    #[test]
+    fn test_error_in_output() -> Result<(), String> {
+        let output = run_capturing_output(
+            &PathBuf::from("test-data/test_runner/syntax_error_test.lox"),
+            InterpreterOptions::default(),
+        )?;
+
+        assert_eq!(output, "error: Expected ';' after expression.\n");
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::same("a\nb\n", "a\nb\n", "")]
+    #[case::changed("a\nb\n", "a\nc\n", "line 2:\n-b\n+c\n")]
+    #[case::missing("a\nb\n", "a\n", "line 2:\n-b\n")]
+    #[case::extra("a\n", "a\nb\n", "line 2:\n+b\n")]
+    fn test_diff_lines(#[case] expected: &str, #[case] actual: &str, #[case] diff: &str) {
+        assert_eq!(diff_lines(expected, actual), diff);
+    }
+}
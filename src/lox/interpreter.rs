@@ -1,79 +1,499 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Display;
+use std::io::Write;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
 use super::{
-    new_value_box, value, Environment, ExprVisitor, Parser, Scanner, StmtVisitor, Value, ValueBox,
+    is_reachable_from, lox_add, lox_add_size, lox_compare, lox_equals, lox_iterate, lox_multiply,
+    lox_multiply_size, new_value_box, standard_environment, value, CallContext, Callable,
+    Comparison, Diagnostic, DisabledModuleLoader, Environment, EnvironmentCheckpoint,
+    EnvironmentImpl, ExprVisitor, FileModuleLoader, LanguageVersion, LimitExceeded, Limits,
+    ModuleEnvironment, ModuleFunction, ModuleLoader, NativeFunction, NativeSet, Parser,
+    PreludeSnapshot, Program, ScanError, Scanner, Span, SpannedToken, Stmt, StmtVisitor, Symbol,
+    Truthiness, Value, ValueBox, VALUE_BOX_SIZE,
 };
 
+// Default limits of nested calls and expressions, keep the tree walk well below the stack
+// size of the host thread
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 200;
+pub const DEFAULT_MAX_EXPRESSION_DEPTH: usize = 200;
+
+// Steps between two checks of the time budget, reading the clock on every step is too slow
+const DEADLINE_CHECK_INTERVAL: usize = 1024;
+
+// Options controlling which natives are available to the scripts run by an interpreter.
+//
+// The defaults trust the scripts: allow_io is set, so they can read stdin and read and write
+// files through the natives, and import modules from the filesystem. Use
+// InterpreterOptions::sandboxed for scripts from untrusted sources, along with the step, time
+// and memory budgets
+#[derive(Debug, Clone)]
+pub struct InterpreterOptions {
+    // register the standard library natives
+    pub stdlib: bool,
+    // register the host I/O natives (stdin and filesystem access), only used if stdlib is enabled.
+    // Without it imports are only served by the loader set with Interpreter::set_module_loader
+    pub allow_io: bool,
+    // caps on the size of the sources accepted by the interpreter
+    pub limits: Limits,
+    // language level of the sources without a version header
+    pub version: LanguageVersion,
+    // make the number native fail on values without a numeric form instead of returning nil
+    pub strict_conversions: bool,
+    // values considered true by conditions, the logical operators and the natives taking
+    // predicates
+    pub truthiness: Truthiness,
+    // nested function calls, natives included, allowed before failing with a stack overflow
+    pub max_call_depth: usize,
+    // nested subexpressions allowed within a function body before failing with a stack overflow
+    pub max_expression_depth: usize,
+    // statements, loop iterations, calls and subexpressions each execution can run, for
+    // bounding untrusted scripts
+    pub max_steps: usize,
+    // wall-clock time each execution can run, None for no time limit
+    pub max_duration: Option<Duration>,
+    // approximate bytes the values alive during each execution can take, globals and natives
+    // included, see Interpreter::heap_size
+    pub max_memory: usize,
+}
+
+impl InterpreterOptions {
+    // Default options without host I/O: the standard library is available, except for the
+    // natives reading stdin and files, and only the modules of the loader set by the embedder
+    // can be imported
+    pub fn sandboxed() -> Self {
+        Self {
+            allow_io: false,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for InterpreterOptions {
+    fn default() -> Self {
+        Self {
+            stdlib: true,
+            allow_io: true,
+            limits: Limits::default(),
+            version: LanguageVersion::default(),
+            strict_conversions: false,
+            truthiness: Truthiness::default(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_expression_depth: DEFAULT_MAX_EXPRESSION_DEPTH,
+            max_steps: usize::MAX,
+            max_duration: None,
+            max_memory: usize::MAX,
+        }
+    }
+}
+
+// Error ending an execution. Budgets running out are told apart from the errors of the script,
+// e.g. for an embedder to report a timeout instead of a faulty script
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoxError {
+    // scan, parse or runtime error of the script, uncaught exceptions included
+    Script(String),
+    // one of the step, time or memory budgets of the options ran out
+    BudgetExceeded(LimitExceeded),
+}
+
+impl Display for LoxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoxError::Script(message) => write!(f, "{message}"),
+            LoxError::BudgetExceeded(limit) => write!(f, "{limit}"),
+        }
+    }
+}
+
+impl From<String> for LoxError {
+    fn from(message: String) -> Self {
+        LoxError::Script(message)
+    }
+}
+
+impl From<LoxError> for String {
+    fn from(error: LoxError) -> Self {
+        error.to_string()
+    }
+}
+
 pub struct Interpreter {
     environment: Box<dyn Environment>,
+    options: InterpreterOptions,
+    // destination of print statements, shared with the interpreters of imported modules
+    output: Rc<RefCell<Box<dyn Write>>>,
+    // finds the sources of imported modules, shared with the interpreters of imported modules
+    module_loader: Rc<dyn ModuleLoader>,
+    // directory of the module being executed, its imports are looked up there first
+    module_directory: Option<PathBuf>,
+    // ids of the modules being imported, outermost first, used to detect import cycles
+    import_stack: Vec<String>,
+    // value of the throw statement being propagated as an error, taken by the catch handling it
+    thrown: Option<Value>,
+    // nesting of the calls being executed and of the expressions of the current call
+    call_depth: usize,
+    expression_depth: usize,
+    // budget of the current execution: steps run so far and the time it has to end by
+    steps: usize,
+    deadline: Option<Instant>,
+    // bytes of the values alive when last measured plus the ones created since, an estimate
+    // above the actual usage as values are released without being accounted
+    allocated: usize,
+    // budget exceeded by the last execution, if that is why it failed
+    limit_exceeded: Option<LimitExceeded>,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
+        Self::with_options(InterpreterOptions::default())
+    }
+
+    // Creates an interpreter without the standard library natives, for sandboxed embedding
+    pub fn without_stdlib() -> Self {
+        Self::with_options(InterpreterOptions {
+            stdlib: false,
+            allow_io: false,
+            ..Default::default()
+        })
+    }
+
+    // Creates an interpreter with the natives selected by the options. Only the first
+    // interpreter of a thread with a given set of natives registers them, the following ones
+    // restore a snapshot of its global environment, see standard_environment
+    pub fn with_options(options: InterpreterOptions) -> Self {
+        let environment = standard_environment(NativeSet::from_options(&options));
+        Self::from_environment(environment, options)
+    }
+
+    // Creates an interpreter whose global environment is restored from a prelude snapshot. The
+    // snapshot must have been taken with the natives selected by the options
+    pub fn from_snapshot(
+        snapshot: &PreludeSnapshot,
+        options: InterpreterOptions,
+    ) -> Result<Self, String> {
+        if snapshot.get_natives() != NativeSet::from_options(&options) {
+            return Err(
+                "Prelude snapshot was taken with other natives than the ones of the options"
+                    .to_string(),
+            );
+        }
+
+        Ok(Self::from_environment(snapshot.restore()?, options))
+    }
+
+    fn from_environment(environment: Box<dyn Environment>, options: InterpreterOptions) -> Self {
+        let module_loader = Self::file_module_loader(&options, vec![PathBuf::from(".")]);
+
         Self {
-            environment: Box::new(super::EnvironmentImpl::new()),
+            environment,
+            options,
+            output: Rc::new(RefCell::new(Box::new(std::io::stdout()))),
+            module_loader,
+            module_directory: None,
+            import_stack: Vec::new(),
+            thrown: None,
+            call_depth: 0,
+            expression_depth: 0,
+            steps: 0,
+            deadline: None,
+            allocated: 0,
+            limit_exceeded: None,
+        }
+    }
+
+    // Creates an interpreter writing the output of print statements to the given writer
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        let mut interpreter = Self::new();
+        interpreter.set_output(output);
+        interpreter
+    }
+
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = Rc::new(RefCell::new(output));
+    }
+
+    // Sets the directories where imported modules are looked up, in order of priority. Imports
+    // from the filesystem are refused if the options do not allow I/O
+    pub fn set_module_search_paths(&mut self, paths: Vec<PathBuf>) {
+        self.module_loader = Self::file_module_loader(&self.options, paths);
+    }
+
+    fn file_module_loader(
+        options: &InterpreterOptions,
+        search_paths: Vec<PathBuf>,
+    ) -> Rc<dyn ModuleLoader> {
+        match options.allow_io {
+            true => Rc::new(FileModuleLoader::new(search_paths)),
+            false => Rc::new(DisabledModuleLoader),
+        }
+    }
+
+    // Replaces the lookup of modules in the filesystem, e.g. to import modules embedded in
+    // the application, see ModuleLoaderChain
+    pub fn set_module_loader(&mut self, loader: Box<dyn ModuleLoader>) {
+        self.module_loader = Rc::from(loader);
+    }
+
+    // Completions of the word ending at the position of the line, see completion::complete
+    pub fn complete(&self, line: &str, position: usize) -> (usize, Vec<String>) {
+        super::complete(self.environment.as_ref(), line, position)
+    }
+
+    // Makes a native available to the scripts as a global function
+    pub fn register_native(&mut self, native: NativeFunction) {
+        self.environment
+            .define_function(Symbol::new(native.get_name()), Box::new(native));
+    }
+
+    // Global variables and functions, natives included, sorted by name. The values are copies,
+    // changing them does not change the variables
+    pub fn globals(&self) -> impl Iterator<Item = (String, Value)> {
+        let globals: BTreeMap<String, Value> = self
+            .environment
+            .get_global_variables()
+            .into_iter()
+            .filter_map(|(name, value)| {
+                // values are only borrowed while the interpreter is running
+                let value_guard = value.try_borrow().ok()?;
+                Some((name, value_guard.clone()))
+            })
+            .collect();
+
+        globals.into_iter()
+    }
+
+    // Copy of the value of a variable, None if it is not defined
+    pub fn get(&self, name: &str) -> Option<Value> {
+        let variable = Symbol::find(name).and_then(|name| self.environment.get_variable(name))?;
+        let value = variable.try_borrow().ok()?.clone();
+        Some(value)
+    }
+
+    // Assigns a variable, defining it as a global if it does not exist yet, e.g. to pass
+    // configuration to the scripts. Fails for constants
+    pub fn set(&mut self, name: &str, value: Value) -> Result<(), String> {
+        let name = Symbol::new(name);
+
+        match self.environment.get_variable(name.clone()) {
+            Some(_) => self.environment.set_variable(name, value).map(|_| ()),
+            None => {
+                self.environment.define_variable(name, value);
+                Ok(())
+            }
+        }
+    }
+
+    // Captures the current global environment of the interpreter
+    pub fn snapshot(&self) -> Result<PreludeSnapshot, String> {
+        PreludeSnapshot::capture(
+            self.environment.as_ref(),
+            NativeSet::from_options(&self.options),
+        )
+    }
+
+    // Same as execute, undoing the declarations and assignments of the source if it fails so
+    // the environment is left as it was, e.g. for each line of an interactive session. Every
+    // call copies the values alive beforehand, see EnvironmentCheckpoint for the cost
+    pub fn execute_transactional(&mut self, source: String) -> Result<ValueBox, LoxError> {
+        let checkpoint = EnvironmentCheckpoint::capture(self.environment.as_ref())?;
+
+        match self.execute(source) {
+            Ok(result) => Ok(result),
+            Err(e) => {
+                self.environment = checkpoint.restore()?;
+                Err(e)
+            }
         }
     }
 
-    pub fn execute(&mut self, source: String) -> Result<ValueBox, String> {
+    // Executes the source, the result is the value of its last expression statement, nil if
+    // there is none
+    pub fn execute(&mut self, source: String) -> Result<ValueBox, LoxError> {
+        let statements = self.parser(source)?.parse().map_err(|e| e.to_string())?;
+
+        self.execute_statements(&statements)
+            .map_err(|e| self.execution_error(e))
+    }
+
+    // Scans and parses the source as execute does, without running it, e.g. to tell the syntax
+    // errors of a script from its runtime errors. See execute_program
+    pub fn parse(&self, source: String) -> Result<Program, Diagnostic> {
+        let version = LanguageVersion::from_source(&source)
+            .map_err(|e| Diagnostic::error("E0204", e, None))?
+            .unwrap_or(self.options.version);
+        let tokens = self.scan(source)?;
+
+        let statements = self.parser_of_tokens(tokens.clone(), version).parse()?;
+
+        Ok(Program { tokens, statements })
+    }
+
+    // Evaluates a single expression, written without the trailing semicolon, e.g. `a + 1`
+    pub fn eval_expression(&mut self, source: &str) -> Result<Value, LoxError> {
+        let expr = self
+            .parser(source.to_string())?
+            .parse_single_expression()
+            .map_err(|e| e.to_string())?;
+
+        self.start_budget();
+        let result = self.evaluate(&expr).map_err(|e| self.execution_error(e))?;
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        Ok(result_guard.clone())
+    }
+
+    // Approximate bytes of heap used by the values reachable from the environment
+    pub fn heap_size(&self) -> Result<usize, String> {
+        let variables = self.environment.get_all_variables();
+        super::reachable_heap_size(variables.iter().map(|(_, value)| value))
+    }
+
+    // Step, time or memory budget exceeded by the last execution, if that is why it failed
+    pub fn get_limit_exceeded(&self) -> Option<&LimitExceeded> {
+        self.limit_exceeded.as_ref()
+    }
+
+    // Parser over the tokens of the source, configured with the options of the interpreter
+    fn parser(&self, source: String) -> Result<Parser, String> {
+        // the version header of the source takes precedence over the interpreter options
+        let version = LanguageVersion::from_source(&source)?.unwrap_or(self.options.version);
+        let tokens = self.scan(source)?;
+
+        Ok(self.parser_of_tokens(tokens, version))
+    }
+
+    fn scan(&self, source: String) -> Result<Vec<SpannedToken>, ScanError> {
         let mut scanner = Scanner::new(source);
-        let tokens = scanner.scan_tokens()?;
+        scanner.set_limits(self.options.limits.clone());
+        scanner.scan_spanned_tokens()
+    }
 
-        let mut parser = Parser::new(tokens);
-        let statements = parser.parse().map_err(|e| e.to_string())?;
+    fn parser_of_tokens(&self, tokens: Vec<SpannedToken>, version: LanguageVersion) -> Parser {
+        let mut parser = Parser::from_spanned_tokens(tokens);
+        parser.set_limits(self.options.limits.clone());
+        parser.set_language_version(version);
+        parser
+    }
 
-        match statements.len() {
-            1 => statements[0].accept(self),
-            _ => {
-                for stmt in statements {
-                    stmt.accept(self)?;
-                }
-                Ok(new_value_box(Value::Nil))
+    // Executes an already parsed program
+    pub fn execute_program(&mut self, program: &Program) -> Result<ValueBox, LoxError> {
+        self.execute_statements(&program.statements)
+            .map_err(|e| self.execution_error(e))
+    }
+
+    // Error of a failed execution, BudgetExceeded if it stopped because of a budget
+    fn execution_error(&self, message: String) -> LoxError {
+        match &self.limit_exceeded {
+            Some(limit) => LoxError::BudgetExceeded(limit.clone()),
+            None => LoxError::Script(message),
+        }
+    }
+
+    // The result is the value of the last expression statement, nil if there is none
+    fn execute_statements(&mut self, statements: &[Stmt]) -> Result<ValueBox, String> {
+        self.start_budget();
+
+        let mut result = new_value_box(Value::Nil);
+        for stmt in statements {
+            self.step()?;
+            let value = stmt.accept(self)?;
+            if let Stmt::Expr(_) = stmt {
+                result = value;
             }
         }
+
+        Ok(result)
     }
 }
 
 impl StmtVisitor<Result<ValueBox, String>> for Interpreter {
     fn visit_print(&mut self, expr: &Box<super::Expr>) -> Result<ValueBox, String> {
-        let value = expr.accept(self)?;
-        let value_guard = value.read().map_err(|e| e.to_string())?;
-        println!("{}", value_guard.as_ref());
+        let value = self.evaluate(expr)?;
+        let value_guard = value.try_borrow().map_err(|e| e.to_string())?;
+
+        let mut output = self
+            .output
+            .try_borrow_mut()
+            .map_err(|e| format!("Error accessing the output: {e}"))?;
+        writeln!(output, "{}", value_guard)
+            .map_err(|e| format!("Error writing to the output: {e}"))?;
+
         Ok(new_value_box(Value::Nil))
     }
 
     fn visit_expr(&mut self, expr: &Box<super::Expr>) -> Result<ValueBox, String> {
         // This is the only statement that I need to return a value
-        expr.accept(self)
+        self.evaluate(expr)
     }
 
     fn visit_var_declaration(
         &mut self,
-        name: &String,
+        name: &Symbol,
         initializer: &Option<Box<super::Expr>>,
+        _span: &Span,
     ) -> Result<ValueBox, String> {
+        self.check_redeclaration(name.clone())?;
+
         match initializer {
             Some(expr) => {
-                let value_result = expr.accept(self)?;
+                let value_result = self.evaluate(expr)?;
                 let value_owned = {
-                    let value_guard = value_result.read().map_err(|e| e.to_string())?;
-                    value_guard.as_ref().to_owned()
+                    let value_guard = value_result.try_borrow().map_err(|e| e.to_string())?;
+                    value_guard.clone()
                 };
 
-                self.environment.define_variable(name, value_owned);
-                self.environment.get_variable(name).ok_or(format!(
+                self.environment.define_variable(name.clone(), value_owned);
+                self.environment.get_variable(name.clone()).ok_or(format!(
                     "error defining variable \"{name}\". Variable not found after definition"
                 ))
             }
             None => {
-                self.environment.define_variable(name, Value::Nil);
+                self.environment.define_variable(name.clone(), Value::Nil);
                 Ok(new_value_box(Value::Nil))
             }
         }
     }
 
+    fn visit_multi_var_declaration(
+        &mut self,
+        declarations: &Vec<(Symbol, Option<Box<super::Expr>>, Span)>,
+    ) -> Result<ValueBox, String> {
+        // each initializer sees the variables declared before it
+        let mut result = new_value_box(Value::Nil);
+        for (name, initializer, span) in declarations {
+            result = self.visit_var_declaration(name, initializer, span)?;
+        }
+
+        Ok(result)
+    }
+
+    fn visit_const_declaration(
+        &mut self,
+        name: &Symbol,
+        initializer: &Box<super::Expr>,
+        _span: &Span,
+    ) -> Result<ValueBox, String> {
+        self.check_redeclaration(name.clone())?;
+
+        let value_result = self.evaluate(initializer)?;
+        let value_owned = {
+            let value_guard = value_result.try_borrow().map_err(|e| e.to_string())?;
+            value_guard.clone()
+        };
+
+        self.environment.define_constant(name.clone(), value_owned);
+        self.environment.get_variable(name.clone()).ok_or(format!(
+            "error defining constant \"{name}\". Constant not found after definition"
+        ))
+    }
+
     fn visit_block(&mut self, stmts: &Vec<super::Stmt>) -> Result<ValueBox, String> {
         self.environment.push_variable_stack();
         for stmt in stmts {
-            match stmt.accept(self) {
+            match self.step().and_then(|_| stmt.accept(self)) {
                 Ok(_) => {}
                 Err(e) => {
                     // ugly, better to have some form of RAII for popping the environment
@@ -93,14 +513,16 @@ impl StmtVisitor<Result<ValueBox, String>> for Interpreter {
         condition: &Box<super::Expr>,
         then_branch: &Box<super::Stmt>,
         else_branch: &Option<Box<super::Stmt>>,
+        _span: &Span,
     ) -> Result<ValueBox, String> {
-        // accept the condition and check if it is truthy, locking the result only for the condition evaluation
-        if condition
-            .accept(self)?
-            .read()
-            .map_err(|e| e.to_string())?
-            .is_truthy()
-        {
+        // accept the condition and check if it is truthy, borrowing the result only for the condition evaluation
+        let truthiness = self.options.truthiness;
+        if truthiness.is_truthy(
+            &*self
+                .evaluate(condition)?
+                .try_borrow()
+                .map_err(|e| e.to_string())?,
+        ) {
             then_branch.accept(self)
         } else {
             match else_branch {
@@ -114,16 +536,19 @@ impl StmtVisitor<Result<ValueBox, String>> for Interpreter {
         &mut self,
         condition: &Box<super::Expr>,
         body: &Box<super::Stmt>,
+        _span: &Span,
     ) -> Result<ValueBox, String> {
         // while the condition is truthy, execute the body
-        // Lock the result of the evaluation only while evaluating the condition of the while, then release
-        // the lock for running the body
-        while condition
-            .accept(self)?
-            .read()
-            .map_err(|e| e.to_string())?
-            .is_truthy()
-        {
+        // Borrow the result of the evaluation only while evaluating the condition of the while, then release
+        // the borrow for running the body
+        let truthiness = self.options.truthiness;
+        while truthiness.is_truthy(
+            &*self
+                .evaluate(condition)?
+                .try_borrow()
+                .map_err(|e| e.to_string())?,
+        ) {
+            self.step()?;
             match body.accept(self) {
                 Ok(_) => {}
                 Err(e) => return Err(e),
@@ -133,15 +558,263 @@ impl StmtVisitor<Result<ValueBox, String>> for Interpreter {
         Ok(new_value_box(Value::Nil))
     }
 
+    fn visit_do_while(
+        &mut self,
+        body: &Box<super::Stmt>,
+        condition: &Box<super::Expr>,
+        _span: &Span,
+    ) -> Result<ValueBox, String> {
+        // the body runs once before the condition is checked for the first time
+        let truthiness = self.options.truthiness;
+        loop {
+            self.step()?;
+            body.accept(self)?;
+
+            if !truthiness.is_truthy(
+                &*self
+                    .evaluate(condition)?
+                    .try_borrow()
+                    .map_err(|e| e.to_string())?,
+            ) {
+                break;
+            }
+        }
+
+        Ok(new_value_box(Value::Nil))
+    }
+
+    fn visit_for_in(
+        &mut self,
+        name: &Symbol,
+        collection: &Box<super::Expr>,
+        body: &Box<super::Stmt>,
+    ) -> Result<ValueBox, String> {
+        let collection = self.evaluate(collection)?;
+        let values = {
+            let collection_guard = collection.try_borrow().map_err(|e| e.to_string())?;
+            lox_iterate(&collection_guard)?.ok_or(format!(
+                "Cannot iterate over {}",
+                collection_guard.type_name()
+            ))?
+        };
+
+        // each iteration binds the loop variable in a scope of its own
+        for value in values {
+            self.step()?;
+            self.environment.push_variable_stack();
+            self.environment.define_variable(name.clone(), value);
+            let result = body.accept(self);
+            self.environment.pop_variable_stack();
+            result?;
+        }
+
+        Ok(new_value_box(Value::Nil))
+    }
+
+    fn visit_switch(
+        &mut self,
+        subject: &Box<super::Expr>,
+        cases: &Vec<(Box<super::Expr>, Vec<super::Stmt>, Span)>,
+        default: &Option<Vec<super::Stmt>>,
+    ) -> Result<ValueBox, String> {
+        // the subject is evaluated once, the case values in order until one equals it
+        let subject = self.evaluate(subject)?;
+
+        for (value, body, _) in cases {
+            let value = self.evaluate(value)?;
+            let matches = {
+                let subject_guard = subject.try_borrow().map_err(|e| e.to_string())?;
+                let value_guard = value.try_borrow().map_err(|e| e.to_string())?;
+                lox_equals(&subject_guard, &value_guard)
+            };
+
+            // only the matching case runs, there is no fall-through to the next one
+            if matches {
+                return self.visit_block(body);
+            }
+        }
+
+        match default {
+            Some(default) => self.visit_block(default),
+            None => Ok(new_value_box(Value::Nil)),
+        }
+    }
+
     fn visit_function_declaration(
         &mut self,
-        name: &String,
-        arguments: &Vec<String>,
+        name: &Symbol,
+        arguments: &Vec<Symbol>,
         body: &Box<super::Stmt>,
     ) -> Result<ValueBox, String> {
+        // functions are declared in the global scope, whatever the scope of the declaration
+        if self.environment.is_constant(name.clone()) {
+            return Err(format!("Cannot redeclare constant '{name}'"));
+        }
+
         let function = super::FunctionImpl::new(name.clone(), arguments.clone(), body.clone());
 
-        self.environment.define_function(name, Box::new(function));
+        self.environment
+            .define_function(name.clone(), Box::new(function));
+
+        Ok(new_value_box(Value::Nil))
+    }
+
+    fn visit_throw(&mut self, expr: &Box<super::Expr>, span: &Span) -> Result<ValueBox, String> {
+        let value = self.evaluate(expr)?;
+        let value_guard = value.try_borrow().map_err(|e| e.to_string())?;
+
+        // the value travels with the error up to the closest catch
+        self.thrown = Some(value_guard.clone());
+        Err(format!("Uncaught exception: {value_guard} at {span}"))
+    }
+
+    fn visit_try(
+        &mut self,
+        body: &Vec<super::Stmt>,
+        name: &Symbol,
+        handler: &Vec<super::Stmt>,
+    ) -> Result<ValueBox, String> {
+        self.thrown = None;
+
+        let error = match self.visit_block(body) {
+            Ok(result) => return Ok(result),
+            Err(error) => error,
+        };
+
+        // only thrown values are caught, runtime errors keep propagating
+        let Some(value) = self.thrown.take() else {
+            return Err(error);
+        };
+
+        self.environment.push_variable_stack();
+        self.environment.define_variable(name.clone(), value);
+        let result = self.visit_block(handler);
+        self.environment.pop_variable_stack();
+        result
+    }
+
+    fn visit_assert(
+        &mut self,
+        condition: &Box<super::Expr>,
+        message: &Option<Box<super::Expr>>,
+        span: &Span,
+    ) -> Result<ValueBox, String> {
+        let truthiness = self.options.truthiness;
+        if truthiness.is_truthy(
+            &*self
+                .evaluate(condition)?
+                .try_borrow()
+                .map_err(|e| e.to_string())?,
+        ) {
+            return Ok(new_value_box(Value::Nil));
+        }
+
+        // the message is only evaluated when the assertion fails
+        match message {
+            Some(message) => {
+                let message = self.evaluate(message)?;
+                let message_guard = message.try_borrow().map_err(|e| e.to_string())?;
+                Err(format!("Assertion failed: {message_guard} at {span}"))
+            }
+            None => Err(format!("Assertion failed at {span}")),
+        }
+    }
+
+    fn visit_import(&mut self, path: &String, alias: &Option<Symbol>) -> Result<ValueBox, String> {
+        let module_source = self
+            .module_loader
+            .load(path, self.module_directory.as_deref())?
+            .ok_or(format!("Module '{path}' not found"))?;
+
+        if self.import_stack.contains(&module_source.id) {
+            return Err(format!("Circular import of module '{path}'"));
+        }
+
+        // the module runs in its own interpreter, its imports are looked up relative to its own
+        // directory first
+        // the module runs on what is left of the budget of the importer
+        let mut options = self.options.clone();
+        options.max_steps = self.options.max_steps.saturating_sub(self.steps);
+        options.max_duration = self
+            .deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        options.max_memory = self.options.max_memory.saturating_sub(self.allocated);
+
+        let mut module = Interpreter::with_options(options);
+        module.output = self.output.clone();
+        let builtin_names: HashSet<String> = module
+            .environment
+            .get_global_variables()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+
+        module.module_loader = self.module_loader.clone();
+        module.module_directory = module_source.directory;
+        module.import_stack = self.import_stack.clone();
+        module.import_stack.push(module_source.id);
+
+        let result = module.execute(module_source.source);
+        self.steps = self.steps.saturating_add(module.steps);
+        self.allocated = self.allocated.saturating_add(module.allocated);
+        result.map_err(|e| {
+            // values thrown by the module can be caught by the importer
+            self.thrown = module.thrown.take();
+            self.limit_exceeded = module.limit_exceeded.take();
+            format!("Error in module '{path}': {e}")
+        })?;
+
+        // the top-level declarations of the module, natives are already defined here
+        let declarations: Vec<(String, ValueBox)> = module
+            .environment
+            .get_global_variables()
+            .into_iter()
+            .filter(|(name, _)| !builtin_names.contains(name))
+            .collect();
+
+        match alias {
+            // bind the module to its alias, its declarations are accessed as properties and
+            // share their storage with the module globals. The module environment is kept
+            // alive for its functions to see the module globals
+            Some(alias) => {
+                let environment: ModuleEnvironment = Rc::new(RefCell::new(Some(
+                    std::mem::replace(&mut module.environment, Box::new(EnvironmentImpl::new())),
+                )));
+
+                let mut exports = BTreeMap::new();
+                for (name, value) in declarations {
+                    let function = match &*value.try_borrow().map_err(|e| e.to_string())? {
+                        Value::Callable(function) if function.get_body().is_some() => {
+                            Some(ModuleFunction::new(function.clone(), environment.clone()))
+                        }
+                        _ => None,
+                    };
+
+                    let export = match function {
+                        Some(function) => {
+                            new_value_box(Value::Callable(Rc::new(Box::new(function))))
+                        }
+                        None => value,
+                    };
+                    exports.insert(name, export);
+                }
+
+                let module = value::Module {
+                    name: alias.to_string(),
+                    exports,
+                };
+                self.environment
+                    .define_variable(alias.clone(), Value::Module(Rc::new(module)));
+            }
+            // expose copies of the declarations of the module directly
+            None => {
+                for (name, value) in declarations {
+                    let value_guard = value.try_borrow().map_err(|e| e.to_string())?;
+                    self.environment
+                        .define_variable(Symbol::new(&name), value_guard.clone());
+                }
+            }
+        }
 
         Ok(new_value_box(Value::Nil))
     }
@@ -150,15 +823,26 @@ impl StmtVisitor<Result<ValueBox, String>> for Interpreter {
 impl ExprVisitor<Result<ValueBox, String>> for Interpreter {
     fn visit_assign(
         &mut self,
-        left: &String,
+        left: &Symbol,
         right: &Box<super::Expr>,
+        _span: &Span,
     ) -> Result<ValueBox, String> {
-        if let Some(left_variable) = self.environment.get_variable(left) {
-            let right_result = right.accept(self)?;
-            let right_guard = right_result.read().map_err(|e| e.to_string())?;
+        if let Some(left_variable) = self.environment.get_variable(left.clone()) {
+            if self.environment.is_constant(left.clone()) {
+                return Err(format!("Cannot assign to constant '{left}'"));
+            }
+
+            let right_result = self.evaluate(right)?;
 
-            let mut left_guard = left_variable.write().map_err(|e| e.to_string())?;
-            *left_guard.as_mut() = *right_guard.to_owned();
+            // copy the value before borrowing the variable, as they can be the same box in
+            // chained assignments like `a = a = 1`
+            let right_owned = {
+                let right_guard = right_result.try_borrow().map_err(|e| e.to_string())?;
+                right_guard.clone()
+            };
+
+            let mut left_guard = left_variable.try_borrow_mut().map_err(|e| e.to_string())?;
+            *left_guard = right_owned;
 
             Ok(left_variable.to_owned())
         } else {
@@ -166,24 +850,88 @@ impl ExprVisitor<Result<ValueBox, String>> for Interpreter {
         }
     }
 
+    fn visit_index_assign(
+        &mut self,
+        list: &Box<super::Expr>,
+        index: &Box<super::Expr>,
+        value: &Box<super::Expr>,
+    ) -> Result<ValueBox, String> {
+        // first, evaluate the list, the index and the value to assign
+        let list_result = self.evaluate(list)?;
+        let index_result = self.evaluate(index)?;
+        let value_result = self.evaluate(value)?;
+
+        // copy the value before borrowing the element, as they can be the same box
+        let value_owned = {
+            let value_guard = value_result.try_borrow().map_err(|e| e.to_string())?;
+            value_guard.clone()
+        };
+
+        let element = {
+            let list_guard = list_result.try_borrow().map_err(|e| e.to_string())?;
+            let index_guard = index_result.try_borrow().map_err(|e| e.to_string())?;
+
+            if let Value::Map(_) = &*list_guard {
+                return Err("Maps are read-only".to_string());
+            }
+
+            get_element(&list_guard, &index_guard)?
+        };
+
+        // a list holding itself could not be printed nor compared, the walk would never end
+        if is_reachable_from(&element, &value_owned)? {
+            return Err("Cannot store a list inside itself".to_string());
+        }
+
+        let mut element_guard = element.try_borrow_mut().map_err(|e| e.to_string())?;
+        *element_guard = value_owned;
+
+        Ok(element.to_owned())
+    }
+
+    fn visit_set(
+        &mut self,
+        object: &Box<super::Expr>,
+        name: &Symbol,
+        value: &Box<super::Expr>,
+    ) -> Result<ValueBox, String> {
+        let object_result = self.evaluate(object)?;
+        self.evaluate(value)?;
+
+        // modules are the only values with properties, their declarations can not be replaced
+        // from outside of the module
+        let object_guard = object_result.try_borrow().map_err(|e| e.to_string())?;
+        match &*object_guard {
+            Value::Module(module) => Err(format!(
+                "Cannot assign to '{name}', declarations of module '{}' are read-only",
+                module.name
+            )),
+            other => Err(format!(
+                "Only modules have properties, got {}",
+                other.type_name()
+            )),
+        }
+    }
+
     fn visit_binary_or(
         &mut self,
         left: &Box<super::Expr>,
         right: &Box<super::Expr>,
+        _span: &Span,
     ) -> Result<ValueBox, String> {
         // first, evaluate the left expression
-        let left_result = left.accept(self)?;
+        let left_result = self.evaluate(left)?;
 
-        // lock left result only to check if it is truthy, then release before evaluating right, if needed
+        // borrow left result only to check if it is truthy, then release before evaluating right, if needed
         let left_is_truthy = {
-            let left_guard = left_result.read().map_err(|e| e.to_string())?;
-            left_guard.is_truthy()
+            let left_guard = left_result.try_borrow().map_err(|e| e.to_string())?;
+            self.options.truthiness.is_truthy(&left_guard)
         };
 
         return if left_is_truthy {
             Ok(left_result)
         } else {
-            right.accept(self)
+            self.evaluate(right)
         };
     }
 
@@ -191,18 +939,19 @@ impl ExprVisitor<Result<ValueBox, String>> for Interpreter {
         &mut self,
         left: &Box<super::Expr>,
         right: &Box<super::Expr>,
+        _span: &Span,
     ) -> Result<ValueBox, String> {
         // first, evaluate the left expression
-        let left_result = left.accept(self)?;
+        let left_result = self.evaluate(left)?;
 
-        // lock left result only to check if it is truthy, then release before evaluating right, if needed
+        // borrow left result only to check if it is truthy, then release before evaluating right, if needed
         let left_is_truthy = {
-            let left_guard = left_result.read().map_err(|e| e.to_string())?;
-            left_guard.is_truthy()
+            let left_guard = left_result.try_borrow().map_err(|e| e.to_string())?;
+            self.options.truthiness.is_truthy(&left_guard)
         };
 
         return if left_is_truthy {
-            right.accept(self)
+            self.evaluate(right)
         } else {
             Ok(left_result)
         };
@@ -212,208 +961,146 @@ impl ExprVisitor<Result<ValueBox, String>> for Interpreter {
         &mut self,
         left: &Box<super::Expr>,
         right: &Box<super::Expr>,
+        _span: &Span,
     ) -> Result<ValueBox, String> {
         // first, evaluate the left and right expressions
-        let left_result = left.accept(self)?;
-        let right_result = right.accept(self)?;
+        let left_result = self.evaluate(left)?;
+        let right_result = self.evaluate(right)?;
 
-        let left_guard = left_result.read().map_err(|e| e.to_string())?;
-        let right_guard = right_result.read().map_err(|e| e.to_string())?;
+        let left_guard = left_result.try_borrow().map_err(|e| e.to_string())?;
+        let right_guard = right_result.try_borrow().map_err(|e| e.to_string())?;
 
         // then evaluate the comparison
-        match (left_guard.as_ref(), right_guard.as_ref()) {
-            (Value::Number(left), Value::Number(right)) => {
-                Ok(new_value_box(Value::Boolean(left == right)))
-            }
-            (Value::String(left), Value::String(right)) => {
-                Ok(new_value_box(Value::Boolean(left == right)))
-            }
-            (Value::Boolean(left), Value::Boolean(right)) => {
-                Ok(new_value_box(Value::Boolean(left == right)))
-            }
-            (Value::Nil, Value::Nil) => Ok(new_value_box(Value::Boolean(true))),
-            // TODO: compare objects
-            _ => Ok(new_value_box(Value::Boolean(false))),
-        }
+        let equal = lox_equals(&left_guard, &right_guard);
+        Ok(new_value_box(Value::Boolean(equal)))
     }
 
     fn visit_binary_not_equal(
         &mut self,
         left: &Box<super::Expr>,
         right: &Box<super::Expr>,
+        _span: &Span,
     ) -> Result<ValueBox, String> {
         // first, evaluate the left and right expressions
-        let left_result = left.accept(self)?;
-        let right_result = right.accept(self)?;
+        let left_result = self.evaluate(left)?;
+        let right_result = self.evaluate(right)?;
 
-        let left_guard = left_result.read().map_err(|e| e.to_string())?;
-        let right_guard = right_result.read().map_err(|e| e.to_string())?;
+        let left_guard = left_result.try_borrow().map_err(|e| e.to_string())?;
+        let right_guard = right_result.try_borrow().map_err(|e| e.to_string())?;
 
         // then evaluate the comparison
-        match (left_guard.as_ref(), right_guard.as_ref()) {
-            (Value::Number(left), Value::Number(right)) => {
-                Ok(new_value_box(Value::Boolean(left != right)))
-            }
-            (Value::String(left), Value::String(right)) => {
-                Ok(new_value_box(Value::Boolean(left != right)))
-            }
-            (Value::Boolean(left), Value::Boolean(right)) => {
-                Ok(new_value_box(Value::Boolean(left != right)))
-            }
-            (Value::Nil, Value::Nil) => Ok(new_value_box(Value::Boolean(false))),
-            // TODO: compare objects
-            _ => Ok(new_value_box(Value::Boolean(true))),
-        }
+        let equal = lox_equals(&left_guard, &right_guard);
+        Ok(new_value_box(Value::Boolean(!equal)))
     }
 
     fn visit_binary_less(
         &mut self,
         left: &Box<super::Expr>,
         right: &Box<super::Expr>,
+        span: &Span,
     ) -> Result<ValueBox, String> {
         // first, evaluate the left and right expressions
-        let left_result = left.accept(self)?;
-        let right_result = right.accept(self)?;
+        let left_result = self.evaluate(left)?;
+        let right_result = self.evaluate(right)?;
 
-        let left_guard = left_result.read().map_err(|e| e.to_string())?;
-        let right_guard = right_result.read().map_err(|e| e.to_string())?;
+        let left_guard = left_result.try_borrow().map_err(|e| e.to_string())?;
+        let right_guard = right_result.try_borrow().map_err(|e| e.to_string())?;
 
         // then evaluate the comparison
-        match (left_guard.as_ref(), right_guard.as_ref()) {
-            (Value::Number(left), Value::Number(right)) => {
-                Ok(new_value_box(Value::Boolean(left < right)))
-            }
-            (Value::String(left), Value::String(right)) => {
-                Ok(new_value_box(Value::Boolean(left < right)))
-            }
-            _ => Err(
-                "Less comparison can only be applied to operands both numbers or both strings"
-                    .to_string(),
-            ),
-        }
+        compare(Comparison::Less, &left_guard, &right_guard, span)
     }
 
     fn visit_binary_less_equal(
         &mut self,
         left: &Box<super::Expr>,
         right: &Box<super::Expr>,
+        span: &Span,
     ) -> Result<ValueBox, String> {
         // first, evaluate the left and right expressions
-        let left_result = left.accept(self)?;
-        let right_result = right.accept(self)?;
+        let left_result = self.evaluate(left)?;
+        let right_result = self.evaluate(right)?;
 
-        let left_guard = left_result.read().map_err(|e| e.to_string())?;
-        let right_guard = right_result.read().map_err(|e| e.to_string())?;
+        let left_guard = left_result.try_borrow().map_err(|e| e.to_string())?;
+        let right_guard = right_result.try_borrow().map_err(|e| e.to_string())?;
 
         // then evaluate the comparison
-        match (left_guard.as_ref(), right_guard.as_ref()) {
-            (Value::Number(left), Value::Number(right)) => Ok(new_value_box(Value::Boolean(left <= right))),
-            (Value::String(left), Value::String(right)) => Ok(new_value_box(Value::Boolean(left <= right))),
-            _ => Err(
-                "Less or equal comparison can only be applied to operands both numbers or both strings".to_string(),
-            ),
-        }
+        compare(Comparison::LessEqual, &left_guard, &right_guard, span)
     }
 
     fn visit_binary_greater(
         &mut self,
         left: &Box<super::Expr>,
         right: &Box<super::Expr>,
+        span: &Span,
     ) -> Result<ValueBox, String> {
         // first, evaluate the left and right expressions
-        let left_result = left.accept(self)?;
-        let right_result = right.accept(self)?;
+        let left_result = self.evaluate(left)?;
+        let right_result = self.evaluate(right)?;
 
-        let left_guard = left_result.read().map_err(|e| e.to_string())?;
-        let right_guard = right_result.read().map_err(|e| e.to_string())?;
+        let left_guard = left_result.try_borrow().map_err(|e| e.to_string())?;
+        let right_guard = right_result.try_borrow().map_err(|e| e.to_string())?;
 
         // then evaluate the comparison
-        match (left_guard.as_ref(), right_guard.as_ref()) {
-            (Value::Number(left), Value::Number(right)) => {
-                Ok(new_value_box(Value::Boolean(left > right)))
-            }
-            (Value::String(left), Value::String(right)) => {
-                Ok(new_value_box(Value::Boolean(left > right)))
-            }
-            _ => Err(
-                "Greater comparison can only be applied to operands both numbers or both strings"
-                    .to_string(),
-            ),
-        }
+        compare(Comparison::Greater, &left_guard, &right_guard, span)
     }
 
     fn visit_binary_greater_equal(
         &mut self,
         left: &Box<super::Expr>,
         right: &Box<super::Expr>,
+        span: &Span,
     ) -> Result<ValueBox, String> {
         // first, evaluate the left and right expressions
-        let left_result = left.accept(self)?;
-        let right_result = right.accept(self)?;
+        let left_result = self.evaluate(left)?;
+        let right_result = self.evaluate(right)?;
 
-        let left_guard = left_result.read().map_err(|e| e.to_string())?;
-        let right_guard = right_result.read().map_err(|e| e.to_string())?;
+        let left_guard = left_result.try_borrow().map_err(|e| e.to_string())?;
+        let right_guard = right_result.try_borrow().map_err(|e| e.to_string())?;
 
         // then evaluate the comparison
-        match (left_guard.as_ref(), right_guard.as_ref()) {
-            (Value::Number(left), Value::Number(right)) => Ok(new_value_box(Value::Boolean(left >= right))),
-            (Value::String(left), Value::String(right)) => Ok(new_value_box(Value::Boolean(left >= right))),
-            _ => Err(
-                "Greater or equal comparison can only be applied to operands both numbers or both strings".to_string(),
-            ),
-        }
+        compare(Comparison::GreaterEqual, &left_guard, &right_guard, span)
     }
 
     fn visit_binary_add(
         &mut self,
         left: &Box<super::Expr>,
         right: &Box<super::Expr>,
+        span: &Span,
     ) -> Result<ValueBox, String> {
         // first, evaluate the left and right expressions
-        let left_result = left.accept(self)?;
-        let right_result = right.accept(self)?;
-
-        let left_guard = left_result.read().map_err(|e| e.to_string())?;
-        let right_guard = right_result.read().map_err(|e| e.to_string())?;
-
-        // then evaluate the addition
-        match (left_guard.as_ref(), right_guard.as_ref()) {
-            (Value::Number(left), Value::Number(right)) => {
-                Ok(new_value_box(Value::Number(left + right)))
-            }
-            (Value::String(left), Value::String(right)) => {
-                Ok(new_value_box(Value::String(format!("{left}{right}"))))
-            }
-            (Value::String(left), Value::Number(right)) => Ok(new_value_box(Value::String(
-                left.to_owned() + &right.to_string(),
-            ))),
-            (Value::Number(left), Value::String(right)) => {
-                Ok(new_value_box(Value::String(left.to_string() + &right)))
-            }
-            _ => Err(
-                "Addition can only be applied to operands both numbers or both strings".to_string(),
-            ),
-        }
+        let left_result = self.evaluate(left)?;
+        let right_result = self.evaluate(right)?;
+
+        let left_guard = left_result.try_borrow().map_err(|e| e.to_string())?;
+        let right_guard = right_result.try_borrow().map_err(|e| e.to_string())?;
+
+        // then evaluate the addition, see lox_add for the coercion rules, once its result is
+        // known to fit in the memory budget
+        self.reserve(lox_add_size(&left_guard, &right_guard)?)?;
+        lox_add(&left_guard, &right_guard)?
+            .map(new_value_box)
+            .ok_or_else(|| binary_operator_error("+", &left_guard, &right_guard, span))
     }
 
     fn visit_binary_sub(
         &mut self,
         left: &Box<super::Expr>,
         right: &Box<super::Expr>,
+        span: &Span,
     ) -> Result<ValueBox, String> {
         // first, evaluate the left and right expressions
-        let left_result = left.accept(self)?;
-        let right_result = right.accept(self)?;
+        let left_result = self.evaluate(left)?;
+        let right_result = self.evaluate(right)?;
 
-        let left_guard = left_result.read().map_err(|e| e.to_string())?;
-        let right_guard = right_result.read().map_err(|e| e.to_string())?;
+        let left_guard = left_result.try_borrow().map_err(|e| e.to_string())?;
+        let right_guard = right_result.try_borrow().map_err(|e| e.to_string())?;
 
         // then evaluate the subtraction
-        match (left_guard.as_ref(), right_guard.as_ref()) {
+        match (&*left_guard, &*right_guard) {
             (Value::Number(left), Value::Number(right)) => {
                 Ok(new_value_box(Value::Number(left - right)))
             }
-            _ => Err("Subtraction can only be applied to numbers".to_string()),
+            (left, right) => Err(binary_operator_error("-", left, right, span)),
         }
     }
 
@@ -421,70 +1108,97 @@ impl ExprVisitor<Result<ValueBox, String>> for Interpreter {
         &mut self,
         left: &Box<super::Expr>,
         right: &Box<super::Expr>,
+        span: &Span,
     ) -> Result<ValueBox, String> {
         // first, evaluate the left and right expressions
-        let left_result = left.accept(self)?;
-        let right_result = right.accept(self)?;
-
-        let left_guard = left_result.read().map_err(|e| e.to_string())?;
-        let right_guard = right_result.read().map_err(|e| e.to_string())?;
-
-        // then evaluate the multiplication
-        match (left_guard.as_ref(), right_guard.as_ref()) {
-            (Value::Number(left), Value::Number(right)) => {
-                Ok(new_value_box(Value::Number(left * right)))
-            }
-            _ => Err("Multiplication can only be applied to numbers".to_string()),
-        }
+        let left_result = self.evaluate(left)?;
+        let right_result = self.evaluate(right)?;
+
+        let left_guard = left_result.try_borrow().map_err(|e| e.to_string())?;
+        let right_guard = right_result.try_borrow().map_err(|e| e.to_string())?;
+
+        // then evaluate the multiplication, numbers or a string repetition, once its result is
+        // known to fit in the memory budget
+        self.reserve(lox_multiply_size(&left_guard, &right_guard))?;
+        lox_multiply(&left_guard, &right_guard)
+            .map_err(|e| format!("{e} at {span}"))?
+            .map(new_value_box)
+            .ok_or_else(|| binary_operator_error("*", &left_guard, &right_guard, span))
     }
 
     fn visit_binary_div(
         &mut self,
         left: &Box<super::Expr>,
         right: &Box<super::Expr>,
+        span: &Span,
     ) -> Result<ValueBox, String> {
         // first, evaluate the left and right expressions
-        let left_result = left.accept(self)?;
-        let right_result = right.accept(self)?;
+        let left_result = self.evaluate(left)?;
+        let right_result = self.evaluate(right)?;
 
-        let left_guard = left_result.read().map_err(|e| e.to_string())?;
-        let right_guard = right_result.read().map_err(|e| e.to_string())?;
+        let left_guard = left_result.try_borrow().map_err(|e| e.to_string())?;
+        let right_guard = right_result.try_borrow().map_err(|e| e.to_string())?;
 
         // then evaluate the division
-        match (left_guard.as_ref(), right_guard.as_ref()) {
+        match (&*left_guard, &*right_guard) {
             (Value::Number(left), Value::Number(right)) => {
                 if *right == 0.0 {
-                    return Err("Division by zero".to_string());
+                    return Err(format!("Division by zero at {span}"));
                 }
                 Ok(new_value_box(Value::Number(left / right)))
             }
-            _ => Err("Division can only be applied to numbers".to_string()),
+            (left, right) => Err(binary_operator_error("/", left, right, span)),
+        }
+    }
+
+    fn visit_range(
+        &mut self,
+        start: &Box<super::Expr>,
+        end: &Box<super::Expr>,
+        inclusive: &bool,
+        span: &Span,
+    ) -> Result<ValueBox, String> {
+        let start_result = self.evaluate(start)?;
+        let end_result = self.evaluate(end)?;
+
+        let start_guard = start_result.try_borrow().map_err(|e| e.to_string())?;
+        let end_guard = end_result.try_borrow().map_err(|e| e.to_string())?;
+
+        match (&*start_guard, &*end_guard) {
+            (Value::Number(start), Value::Number(end)) => {
+                Ok(new_value_box(Value::Range(value::Range {
+                    start: *start,
+                    end: *end,
+                    inclusive: *inclusive,
+                })))
+            }
+            (start, end) => {
+                let operator = if *inclusive { "..=" } else { ".." };
+                Err(binary_operator_error(operator, start, end, span))
+            }
         }
     }
 
     fn visit_unary_bang(&mut self, expr: &Box<super::Expr>) -> Result<ValueBox, String> {
-        let expr_result = expr.accept(self)?;
-        let result_guard = expr_result.read().map_err(|e| e.to_string())?;
+        let expr_result = self.evaluate(expr)?;
+        let result_guard = expr_result.try_borrow().map_err(|e| e.to_string())?;
 
-        match result_guard.as_ref() {
-            Value::Boolean(boolean_value) => Ok(new_value_box(Value::Boolean(!boolean_value))),
-            Value::Number(_) => Err("Unary bang cannot be applied to a number".to_string()),
-            Value::String(_) => Err("Unary bang cannot be applied to a string".to_string()),
-            Value::Nil => Err("Unary bang cannot be applied to nil".to_string()),
-            Value::Callable(_s) => Err("Unary bang cannot be applied to a function".to_string()),
-        }
+        // negates the truthiness of any value, as conditions do
+        Ok(new_value_box(Value::Boolean(
+            !self.is_truthy(&result_guard),
+        )))
     }
 
     fn visit_unary_minus(&mut self, expr: &Box<super::Expr>) -> Result<ValueBox, String> {
-        let expr_result = expr.accept(self)?;
-        let result_guard = expr_result.read().map_err(|e| e.to_string())?;
+        let expr_result = self.evaluate(expr)?;
+        let result_guard = expr_result.try_borrow().map_err(|e| e.to_string())?;
 
-        match result_guard.as_ref() {
+        match &*result_guard {
             Value::Number(number_value) => Ok(new_value_box(Value::Number(-number_value))),
-            Value::String(_) => Err("Unary minus cannot be applied to a string".to_string()),
-            Value::Boolean(_) => Err("Unary minus cannot be applied to a boolean".to_string()),
-            Value::Nil => Err("Unary minus cannot be applied to nil".to_string()),
-            Value::Callable(_s) => Err("Unary minus cannot be applied to a function".to_string()),
+            other => Err(format!(
+                "Unary minus cannot be applied to {}",
+                other.type_name()
+            )),
         }
     }
 
@@ -492,56 +1206,86 @@ impl ExprVisitor<Result<ValueBox, String>> for Interpreter {
         &mut self,
         callee: &Box<super::Expr>,
         arguments: &Vec<super::Expr>,
+        _span: &Span,
     ) -> Result<ValueBox, String> {
-        // evaluate the callee expression
-        let callee_result = callee.accept(self)?;
-        let callee_guard = callee_result.read().map_err(|e| e.to_string())?;
-
-        match callee_guard.as_ref() {
-            Value::Callable(callable) => {
-                // validate if the number of arguments is correct
-                if callable.get_arg_count() != arguments.len() {
-                    return Err(format!(
-                        "Expected {} arguments, but got {}",
-                        callable.get_arg_count(),
-                        arguments.len()
-                    ));
-                }
+        // evaluate the callee expression, the callable is cloned to release the borrow of its box
+        // while it executes, the call can write to the variable holding it
+        let callee_result = self.evaluate(callee)?;
+        let callable = match &*callee_result.try_borrow().map_err(|e| e.to_string())? {
+            Value::Callable(callable) => callable.clone(),
+            other => {
+                return Err(format!(
+                    "Can only call functions and classes, got {}",
+                    other.type_name()
+                ))
+            }
+        };
 
-                // evaluate the arguments
-                let mut evaluated_arguments = Vec::new();
-                for arg in arguments {
-                    evaluated_arguments.push(arg.accept(self)?);
-                }
+        // validate if the number of arguments is correct
+        if callable.get_arg_count() != arguments.len() {
+            return Err(format!(
+                "Expected {} arguments, but got {}",
+                callable.get_arg_count(),
+                arguments.len()
+            ));
+        }
 
-                // create the environment to call the function
-                // self.environment.branch_push();
-                self.environment.push_variable_stack();
+        // evaluate the arguments
+        let mut evaluated_arguments = Vec::new();
+        for arg in arguments {
+            evaluated_arguments.push(self.evaluate(arg)?);
+        }
 
-                // bind the arguments to the new function environment
-                for (i, arg) in evaluated_arguments.iter().enumerate() {
-                    // TODO: pop environment if there is an error
-                    let arg_name = callable.get_arg_name(i)?;
+        self.call_callable(callable.as_ref().as_ref(), &evaluated_arguments)
+    }
 
-                    let arg_guard = arg
-                        .try_read()
-                        .map_err(|e| format!("Error reading argument {arg_name}: {e}"))?;
+    fn visit_index(
+        &mut self,
+        list: &Box<super::Expr>,
+        index: &Box<super::Expr>,
+    ) -> Result<ValueBox, String> {
+        // first, evaluate the list and the index expressions
+        let list_result = self.evaluate(list)?;
+        let index_result = self.evaluate(index)?;
 
-                    self.environment
-                        .define_variable(&arg_name, arg_guard.as_ref().to_owned());
-                }
+        let list_guard = list_result.try_borrow().map_err(|e| e.to_string())?;
+        let index_guard = index_result.try_borrow().map_err(|e| e.to_string())?;
 
-                let body = callable.get_body();
-                let body_result = body.accept(self);
+        get_element(&list_guard, &index_guard)
+    }
 
-                // self.environment.branch_pop();
-                self.environment.pop_variable_stack();
-                body_result
-            }
-            _ => Err("Can only call functions and classes".to_string()),
+    fn visit_get(&mut self, object: &Box<super::Expr>, name: &Symbol) -> Result<ValueBox, String> {
+        let object_result = self.evaluate(object)?;
+        let object_guard = object_result.try_borrow().map_err(|e| e.to_string())?;
+
+        match &*object_guard {
+            Value::Module(module) => module
+                .exports
+                .get(name.as_str())
+                .map(|e| e.to_owned())
+                .ok_or(format!(
+                    "Module '{}' has no declaration named '{name}'",
+                    module.name
+                )),
+            other => Err(format!(
+                "Only modules have properties, got {}",
+                other.type_name()
+            )),
         }
     }
 
+    fn visit_list(&mut self, elements: &Vec<super::Expr>) -> Result<ValueBox, String> {
+        // each element is stored in its own box, holding a copy of the evaluated value
+        let mut values = Vec::with_capacity(elements.len());
+        for element in elements {
+            let element_result = self.evaluate(element)?;
+            let element_guard = element_result.try_borrow().map_err(|e| e.to_string())?;
+            values.push(new_value_box(element_guard.clone()));
+        }
+
+        Ok(new_value_box(Value::List(values)))
+    }
+
     fn visit_literal_string(&mut self, value: &String) -> Result<ValueBox, String> {
         // FIXME: Is it possible to avoid the string clone?
         Ok(new_value_box(Value::String(value.clone())))
@@ -563,9 +1307,9 @@ impl ExprVisitor<Result<ValueBox, String>> for Interpreter {
         Ok(new_value_box(Value::Nil))
     }
 
-    fn visit_identifier(&mut self, value: &String) -> Result<ValueBox, String> {
+    fn visit_identifier(&mut self, value: &Symbol) -> Result<ValueBox, String> {
         // FIXME: need to avoid cloning the value
-        match self.environment.get_variable(value) {
+        match self.environment.get_variable(value.clone()) {
             Some(value) => Ok(value.clone()),
             None => Err(format!("Undefined variable '{}'", value)),
         }
@@ -576,12 +1320,336 @@ impl ExprVisitor<Result<ValueBox, String>> for Interpreter {
     }
 }
 
+// Boolean result of a comparison, or an error if the values can not be compared
+fn compare(
+    comparison: Comparison,
+    left: &Value,
+    right: &Value,
+    span: &Span,
+) -> Result<ValueBox, String> {
+    lox_compare(comparison, left, right)
+        .map(|result| new_value_box(Value::Boolean(result)))
+        .ok_or_else(|| binary_operator_error(comparison.symbol(), left, right, span))
+}
+
+// Error for a binary operator applied to operands of unsupported types
+fn binary_operator_error(operator: &str, left: &Value, right: &Value, span: &Span) -> String {
+    format!(
+        "'{operator}' not supported between {} and {} at {span}",
+        left.type_name(),
+        right.type_name()
+    )
+}
+
+impl Interpreter {
+    // Declarations can shadow constants of the enclosing scopes, but not replace the ones of the
+    // current scope
+    fn check_redeclaration(&self, name: Symbol) -> Result<(), String> {
+        if self.environment.get_variable_at(0, name.clone()).is_some()
+            && self.environment.is_constant(name.clone())
+        {
+            return Err(format!("Cannot redeclare constant '{name}'"));
+        }
+
+        Ok(())
+    }
+
+    // Each execution gets the full budget of the options
+    fn start_budget(&mut self) {
+        self.steps = 0;
+        self.allocated = 0;
+        self.deadline = self
+            .options
+            .max_duration
+            .map(|duration| Instant::now() + duration);
+        self.limit_exceeded = None;
+    }
+
+    // Counts a step of the current execution, failing once its budget is spent
+    fn step(&mut self) -> Result<(), String> {
+        self.steps += 1;
+
+        let limit = if self.steps > self.options.max_steps {
+            LimitExceeded::Steps(self.options.max_steps)
+        } else if self.steps % DEADLINE_CHECK_INTERVAL == 0
+            && self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            LimitExceeded::Duration(self.options.max_duration.unwrap_or_default())
+        } else {
+            return Ok(());
+        };
+
+        self.exceed(limit)
+    }
+
+    // Accounts a value created by the current execution, failing once the values alive take
+    // more memory than its budget
+    fn allocate(&mut self, value: &ValueBox) -> Result<(), String> {
+        if self.options.max_memory == usize::MAX {
+            return Ok(());
+        }
+
+        let size = VALUE_BOX_SIZE + value.try_borrow().map_err(|e| e.to_string())?.heap_size();
+        self.allocated = self.allocated.saturating_add(size);
+        if self.allocated <= self.options.max_memory {
+            return Ok(());
+        }
+
+        // the estimate includes the values released since the last measurement, only the ones
+        // still reachable and the new value count against the budget
+        self.allocated = self.heap_size()?.saturating_add(size);
+        if self.allocated <= self.options.max_memory {
+            return Ok(());
+        }
+
+        self.exceed(LimitExceeded::Memory(self.options.max_memory))
+    }
+
+    // Fails if a value of the given size can not be allocated without exceeding the memory
+    // budget. Checked before building the values whose size depends on the script, e.g. a
+    // repeated string, which could exhaust the memory of the host before allocate sees them
+    fn reserve(&mut self, size: usize) -> Result<(), String> {
+        if self.options.max_memory == usize::MAX
+            || self.allocated.saturating_add(size) <= self.options.max_memory
+        {
+            return Ok(());
+        }
+
+        // measured again for the values released since the last measurement, see allocate
+        self.allocated = self.heap_size()?;
+        if self.allocated.saturating_add(size) <= self.options.max_memory {
+            return Ok(());
+        }
+
+        self.exceed(LimitExceeded::Memory(self.options.max_memory))
+    }
+
+    fn exceed(&mut self, limit: LimitExceeded) -> Result<(), String> {
+        let message = limit.to_string();
+        self.limit_exceeded = Some(limit);
+        Err(message)
+    }
+
+    // Evaluates a subexpression, failing instead of exhausting the host stack on deep nesting
+    fn evaluate(&mut self, expr: &super::Expr) -> Result<ValueBox, String> {
+        if self.expression_depth >= self.options.max_expression_depth {
+            return Err(format!(
+                "Stack overflow: expressions nested more than {} levels deep",
+                self.options.max_expression_depth
+            ));
+        }
+
+        self.step()?;
+        self.expression_depth += 1;
+        let result = expr.accept(self);
+        self.expression_depth -= 1;
+
+        // the expressions creating strings, lists and the results of calls, the others give
+        // numbers, booleans or values that already exist
+        if let (
+            Ok(value),
+            super::Expr::LiteralString(_)
+            | super::Expr::BinaryAdd(..)
+            | super::Expr::BinaryMul(..)
+            | super::Expr::List(_)
+            | super::Expr::Call(..),
+        ) = (&result, expr)
+        {
+            self.allocate(value)?;
+        }
+
+        result
+    }
+
+    fn call_callable(
+        &mut self,
+        callable: &dyn Callable,
+        arguments: &[ValueBox],
+    ) -> Result<ValueBox, String> {
+        if self.call_depth >= self.options.max_call_depth {
+            return Err(format!(
+                "Stack overflow: more than {} nested calls",
+                self.options.max_call_depth
+            ));
+        }
+
+        self.step()?;
+
+        // the expressions of the callee are counted from its body
+        self.call_depth += 1;
+        let expression_depth = std::mem::take(&mut self.expression_depth);
+        let result = self.call_callable_nested(callable, arguments);
+        self.expression_depth = expression_depth;
+        self.call_depth -= 1;
+        result
+    }
+
+    fn call_callable_nested(
+        &mut self,
+        callable: &dyn Callable,
+        arguments: &[ValueBox],
+    ) -> Result<ValueBox, String> {
+        let body = match callable.get_body() {
+            Some(body) => body,
+            None => {
+                // native functions are called directly with copies of the arguments
+                let mut argument_values = Vec::with_capacity(arguments.len());
+                for arg in arguments.iter() {
+                    let arg_guard = arg.try_borrow().map_err(|e| e.to_string())?;
+                    argument_values.push(arg_guard.clone());
+                }
+
+                return callable.call_in(self, &argument_values);
+            }
+        };
+
+        // functions of imported modules run in the environment of their module, which
+        // is swapped in for the duration of the call
+        let module_environment = match callable.get_module_environment() {
+            Some(shared) => {
+                let environment = shared.borrow_mut().take().ok_or(format!(
+                    "Module of function {} is already executing",
+                    callable
+                ))?;
+                Some((
+                    shared,
+                    std::mem::replace(&mut self.environment, environment),
+                ))
+            }
+            None => None,
+        };
+
+        let body_result = self.call_body(callable, body, arguments);
+
+        if let Some((shared, caller_environment)) = module_environment {
+            let environment = std::mem::replace(&mut self.environment, caller_environment);
+            *shared.borrow_mut() = Some(environment);
+        }
+
+        body_result
+    }
+
+    fn call_body(
+        &mut self,
+        callable: &dyn Callable,
+        body: &Stmt,
+        arguments: &[ValueBox],
+    ) -> Result<ValueBox, String> {
+        // create the environment to call the function
+        // self.environment.branch_push();
+        self.environment.push_variable_stack();
+
+        // bind the arguments to the new function environment
+        for (i, arg) in arguments.iter().enumerate() {
+            // TODO: pop environment if there is an error
+            let arg_name = callable.get_arg_name(i)?;
+
+            let arg_guard = arg
+                .try_borrow()
+                .map_err(|e| format!("Error reading argument {arg_name}: {e}"))?;
+
+            self.environment
+                .define_variable(arg_name, arg_guard.clone());
+        }
+
+        let body_result = body.accept(self);
+
+        // self.environment.branch_pop();
+        self.environment.pop_variable_stack();
+        body_result
+    }
+}
+
+impl CallContext for Interpreter {
+    fn get_environment(&self) -> &dyn Environment {
+        self.environment.as_ref()
+    }
+
+    fn is_truthy(&self, value: &Value) -> bool {
+        self.options.truthiness.is_truthy(value)
+    }
+
+    fn reserve(&mut self, size: usize) -> Result<(), String> {
+        Interpreter::reserve(self, size)
+    }
+
+    fn call(&mut self, callee: &Value, arguments: &[Value]) -> Result<Value, String> {
+        let callable = match callee {
+            Value::Callable(callable) => callable.clone(),
+            other => {
+                return Err(format!(
+                    "Can only call functions and classes, got {}",
+                    other.type_name()
+                ))
+            }
+        };
+
+        if callable.get_arg_count() != arguments.len() {
+            return Err(format!(
+                "Expected {} arguments, but got {}",
+                callable.get_arg_count(),
+                arguments.len()
+            ));
+        }
+
+        let arguments: Vec<ValueBox> = arguments.iter().cloned().map(new_value_box).collect();
+        let result = self.call_callable(callable.as_ref().as_ref(), &arguments)?;
+
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        Ok(result_guard.clone())
+    }
+}
+
+// Element of a list at a position or entry of a map at a key
+fn get_element(container: &Value, index: &Value) -> Result<ValueBox, String> {
+    let elements = match (container, index) {
+        (Value::List(elements), _) => elements,
+        (Value::Map(entries), Value::String(key)) => {
+            return entries
+                .get(key)
+                .map(|e| e.to_owned())
+                .ok_or(format!("Undefined map key '{key}'"))
+        }
+        (Value::Map(_), other) => {
+            return Err(format!(
+                "Map key must be a string, got {}",
+                other.type_name()
+            ))
+        }
+        (other, _) => return Err(format!("Can only index lists, got {}", other.type_name())),
+    };
+
+    let position = match index {
+        Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => *n as usize,
+        other => {
+            return Err(format!(
+                "List index must be a non-negative integer, got {}",
+                other
+            ))
+        }
+    };
+
+    elements.get(position).map(|e| e.to_owned()).ok_or(format!(
+        "List index {} out of bounds for list of length {}",
+        position,
+        elements.len()
+    ))
+}
+
 #[cfg(test)]
 mod tests {
 
+    use std::cell::RefCell;
+    use std::io::Write;
     use std::path::PathBuf;
+    use std::rc::Rc;
 
-    use crate::lox::new_value_box;
+    use crate::lox::{
+        new_value_box, CallContext, Callable, Expr, InterpreterOptions, LimitExceeded, LoxError,
+        NativeFunction, Program, Stmt, Truthiness,
+    };
 
     use super::{Value, ValueBox};
     use rstest::*;
@@ -597,6 +1665,33 @@ mod tests {
         new_value_box(Value::Boolean(true))
     )]
     #[case::comparison_not_equal_nil("nil != nil;", new_value_box(Value::Boolean(false)))]
+    #[case::comparison_not_equal("1 != 2;", new_value_box(Value::Boolean(true)))]
+    #[case::bang("!true;", new_value_box(Value::Boolean(false)))]
+    #[case::bang_bang("!!true;", new_value_box(Value::Boolean(true)))]
+    #[case::bang_not_equal("!true != false;", new_value_box(Value::Boolean(false)))]
+    #[case::list_index("[1, 2, 3][1];", new_value_box(Value::Number(2.0)))]
+    #[case::list_equal("[1, \"a\"] == [1, \"a\"];", new_value_box(Value::Boolean(true)))]
+    #[case::list_not_equal("[1, 2] != [1];", new_value_box(Value::Boolean(true)))]
+    #[case::function_equal("fun f() {} var g = f; f == g;", new_value_box(Value::Boolean(true)))]
+    #[case::function_not_equal(
+        "fun f() {} var g = f; fun f() {} f != g;",
+        new_value_box(Value::Boolean(true))
+    )]
+    #[case::native_equal("[len, sqrt] == [len, sqrt];", new_value_box(Value::Boolean(true)))]
+    #[case::native_not_equal("len == sqrt;", new_value_box(Value::Boolean(false)))]
+    #[case::string_repeat("\"ab\" * 3;", new_value_box(Value::String("ababab".to_string())))]
+    #[case::string_repeat_count_first("2 * \"ab\";", new_value_box(Value::String("abab".to_string())))]
+    #[case::string_repeat_zero("\"ab\" * 0;", new_value_box(Value::String("".to_string())))]
+    #[case::raw_string_concat("\"\"\"a \"b\" c\"\"\" + \"!\";", new_value_box(Value::String("a \"b\" c!".to_string())))]
+    #[case::string_number_concat("\"n\" + 1.5;", new_value_box(Value::String("n1.5".to_string())))]
+    #[case::list_concat(
+        "[1] + [2, 3];",
+        new_value_box(Value::List(vec![
+            new_value_box(Value::Number(1.0)),
+            new_value_box(Value::Number(2.0)),
+            new_value_box(Value::Number(3.0)),
+        ]))
+    )]
     fn test_interpreter_expressions(
         #[case] source: String,
         #[case] expected: ValueBox,
@@ -611,30 +1706,1065 @@ mod tests {
 
         ///////////////////////////////////////////////////////////////////////
         // Then the result should be the expected value
-        let result_guard = result.try_read().map_err(|e| e.to_string())?;
-        let expected_guard = expected.try_read().map_err(|e| e.to_string())?;
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        let expected_guard = expected.try_borrow().map_err(|e| e.to_string())?;
         assert_eq!(*result_guard, *expected_guard);
 
         Ok(())
     }
 
     #[rstest]
-    fn test_from_file(
-        #[files("test-data/interpreter/*.lox")] base_path: PathBuf,
+    #[case::less_boolean_number(
+        "\n\ntrue < 2;",
+        "'<' not supported between boolean and number at line 3"
+    )]
+    #[case::greater_string_number(
+        "\"a\" > 1;",
+        "'>' not supported between string and number at line 1"
+    )]
+    #[case::less_equal_nil("nil <= nil;", "'<=' not supported between nil and nil at line 1")]
+    #[case::greater_equal_list("[1] >= [2];", "'>=' not supported between list and list at line 1")]
+    #[case::add_boolean("true + 1;", "'+' not supported between boolean and number at line 1")]
+    #[case::sub_string(
+        "\"a\" - \"b\";",
+        "'-' not supported between string and string at line 1"
+    )]
+    #[case::mul_nil("\n2 * nil;", "'*' not supported between number and nil at line 2")]
+    #[case::div_boolean("1 / false;", "'/' not supported between number and boolean at line 1")]
+    #[case::div_zero("1 / 0;", "Division by zero at line 1")]
+    #[case::mul_strings(
+        "\"a\" * \"b\";",
+        "'*' not supported between string and string at line 1"
+    )]
+    #[case::repeat_fraction(
+        "\"ab\" * 1.5;",
+        "Strings can only be repeated a non-negative integer number of times, got 1.5 at line 1"
+    )]
+    #[case::repeat_negative(
+        "-1 * \"ab\";",
+        "Strings can only be repeated a non-negative integer number of times, got -1 at line 1"
+    )]
+    #[case::repeat_beyond_host_memory(
+        "var s = \"ab\" * 1000000000000;",
+        "Repeated string is too long, strings can be repeated up to 1073741824 bytes at line 1"
+    )]
+    #[case::add_string_nil("\"a\" + nil;", "'+' not supported between string and nil at line 1")]
+    fn test_binary_operator_errors(
+        #[case] source: String,
+        #[case] expected_error: String,
+    ) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter and source code applying an operator to unsupported operands
+        let mut interpreter = super::Interpreter::new();
+
+        ///////////////////////////////////////////////////////////////////////
+        // When executing the source code
+        let result = interpreter.execute(source).map_err(String::from);
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the error reports the operator, the operand types and the location
+        match result {
+            Ok(_) => Err("Expected an error".to_string()),
+            Err(e) => {
+                assert_eq!(e, expected_error);
+                Ok(())
+            }
+        }
+    }
+
+    #[rstest]
+    #[case::global("x = 1;")]
+    #[case::block("{ x = 1; }")]
+    #[case::function("fun f() { x = 1; } f();")]
+    fn test_assign_undeclared(#[case] source: String) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given source code assigning a variable that was never declared
+        let mut interpreter = super::Interpreter::new();
+
+        ///////////////////////////////////////////////////////////////////////
+        // When executing the source code
+        let result = interpreter.execute(source).map_err(String::from);
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the assignment fails instead of implicitly defining a global
+        assert_eq!(result.err(), Some("Undefined variable 'x'".to_string()));
+        assert!(interpreter.execute("x;".to_string()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_host_variables() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given configuration set by the host
+        let mut interpreter = super::Interpreter::new();
+        interpreter.set("limit", Value::Number(2.0))?;
+        interpreter.set("name", Value::String("lox".to_string()))?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // When running a script using it
+        interpreter.execute("var result = name + \"!\"; limit = limit * 2;".to_string())?;
+        interpreter.set("name", Value::Nil)?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the host reads back the script results
+        assert_eq!(
+            interpreter.get("result"),
+            Some(Value::String("lox!".to_string()))
+        );
+        assert_eq!(interpreter.get("limit"), Some(Value::Number(4.0)));
+        assert_eq!(interpreter.get("name"), Some(Value::Nil));
+        assert_eq!(interpreter.get("undefined"), None);
+
+        let globals: Vec<(String, Value)> = interpreter.globals().collect();
+        assert!(globals.contains(&("limit".to_string(), Value::Number(4.0))));
+        assert!(globals.iter().any(|(name, _)| name == "len"));
+        assert!(globals.windows(2).all(|pair| pair[0].0 < pair[1].0));
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::numbers("add(1, 2);", Ok(Value::Number(3.0)))]
+    #[case::string("greet(\"lox\");", Ok(Value::String("hello lox".to_string())))]
+    #[case::list("sum([1, 2, 3]);", Ok(Value::Number(6.0)))]
+    #[case::optional("half(nil);", Ok(Value::Nil))]
+    #[case::optional_value("half(3);", Ok(Value::Number(1.5)))]
+    #[case::error_result("sum([]);", Err("sum() of an empty list".to_string()))]
+    #[case::wrong_type(
+        "add(\"1\", 2);",
+        Err("add() expects argument 'a' to be a number, got string".to_string())
+    )]
+    #[case::wrong_element_type(
+        "sum([1, \"2\"]);",
+        Err(
+            "sum() expects argument 'values' to be a list where each element is a number, got list"
+                .to_string()
+        )
+    )]
+    #[case::wrong_arity("add(1);", Err("Expected 2 arguments, but got 1".to_string()))]
+    #[case::callback("apply(len, \"abc\");", Ok(Value::Number(3.0)))]
+    #[case::callback_error(
+        "apply(1, nil);",
+        Err("apply() expects argument 'function' to be a function, got number".to_string())
+    )]
+    fn test_rust_natives(
+        #[case] source: &str,
+        #[case] expected: Result<Value, String>,
+    ) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given natives written as Rust closures with typed arguments
+        let mut interpreter = super::Interpreter::new();
+        interpreter.register_native(NativeFunction::from_rust(
+            "add",
+            &["a", "b"],
+            |a: f64, b: f64| Ok(a + b),
+        ));
+        interpreter.register_native(NativeFunction::from_rust(
+            "greet",
+            &["name"],
+            |name: String| Ok(format!("hello {name}")),
+        ));
+        interpreter.register_native(NativeFunction::from_rust(
+            "sum",
+            &["values"],
+            |values: Vec<f64>| match values.is_empty() {
+                true => Err("sum() of an empty list".to_string()),
+                false => Ok(values.iter().sum::<f64>()),
+            },
+        ));
+        interpreter.register_native(NativeFunction::from_rust(
+            "half",
+            &["n"],
+            |n: Option<f64>| Ok(n.map(|n| n / 2.0)),
+        ));
+        interpreter.register_native(NativeFunction::from_rust_with_context(
+            "apply",
+            &["function", "argument"],
+            |context: &mut dyn CallContext, function: Rc<Box<dyn Callable>>, argument: Value| {
+                context.call(&Value::Callable(function), &[argument])
+            },
+        ));
+
+        ///////////////////////////////////////////////////////////////////////
+        // When calling them from a script
+        let result = interpreter
+            .execute(source.to_string())
+            .map(|result| result.borrow().clone())
+            .map_err(String::from);
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the arguments and the result are converted
+        assert_eq!(result, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_host_set_constant() -> Result<(), String> {
+        let mut interpreter = super::Interpreter::new();
+        interpreter.execute("const limit = 1;".to_string())?;
+
+        assert_eq!(
+            interpreter.set("limit", Value::Number(2.0)),
+            Err("Cannot assign to constant 'limit'".to_string())
+        );
+        assert_eq!(interpreter.get("limit"), Some(Value::Number(1.0)));
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::arithmetic("a * 2 + 1", Ok(Value::Number(21.0)))]
+    #[case::call("len(name)", Ok(Value::Number(3.0)))]
+    #[case::assignment("a = 1", Ok(Value::Number(1.0)))]
+    #[case::statement("a;", Err("Expected end of expression.".to_string()))]
+    #[case::declaration("var b = 1", Err("Unexpected token while parsing primary: Var".to_string()))]
+    #[case::undefined("b", Err("Undefined variable 'b'".to_string()))]
+    fn test_eval_expression(
+        #[case] source: &str,
+        #[case] expected: Result<Value, String>,
+    ) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter with some variables
+        let mut interpreter = super::Interpreter::new();
+        interpreter.execute("var a = 10; var name = \"lox\";".to_string())?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // When evaluating an expression
+        let result = interpreter.eval_expression(source).map_err(String::from);
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then its value is returned
+        assert_eq!(result, expected);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::initializers("var a = 1, b, c = \"c\";", "[a, b, c];", "[1, nil, c]")]
+    #[case::previous_variable("var a = 1, b = a + 1;", "b;", "2")]
+    #[case::in_block("var r; { var a = 1, b = 2; r = a + b; }", "r;", "3")]
+    fn test_multi_var_declaration(
+        #[case] source: String,
+        #[case] expression: String,
+        #[case] expected: &str,
+    ) -> Result<(), String> {
+        let mut interpreter = super::Interpreter::new();
+
+        interpreter.execute(source)?;
+
+        let result = interpreter.execute(expression)?;
+        assert_eq!(result.borrow().to_string(), expected);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::global("const a = 1; a = 2;", "Cannot assign to constant 'a'")]
+    #[case::enclosing_block("const a = 1; { { a = 2; } }", "Cannot assign to constant 'a'")]
+    #[case::function(
+        "const a = 1; fun f() { a = 2; } f();",
+        "Cannot assign to constant 'a'"
+    )]
+    #[case::redeclared_var("const a = 1; var a = 2;", "Cannot redeclare constant 'a'")]
+    #[case::redeclared_const("const a = 1; const a = 2;", "Cannot redeclare constant 'a'")]
+    #[case::redeclared_function("const a = 1; fun a() {}", "Cannot redeclare constant 'a'")]
+    fn test_constant_errors(#[case] source: String, #[case] expected: &str) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter
+        let mut interpreter = super::Interpreter::new();
+
+        ///////////////////////////////////////////////////////////////////////
+        // When executing source code replacing a constant
+        let result = interpreter.execute(source).map_err(String::from);
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then it fails and the constant keeps its value
+        assert_eq!(result.err(), Some(expected.to_string()));
+        let a = interpreter.execute("a;".to_string())?;
+        assert_eq!(*a.borrow(), Value::Number(1.0));
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::read("const a = 1; var r = a + 1;", "r;", "2")]
+    #[case::shadowed_in_block("const a = 1; var r; { var a = 2; a = 3; r = a; }", "r;", "3")]
+    #[case::list_elements("const l = [1, 2]; l[0] = 3;", "l;", "[3, 2]")]
+    #[case::block_scope("{ const a = 1; } var a = 2; a = 3;", "a;", "3")]
+    fn test_constants(
+        #[case] source: String,
+        #[case] expression: String,
+        #[case] expected: &str,
     ) -> Result<(), String> {
+        let mut interpreter = super::Interpreter::new();
+
+        interpreter.execute(source)?;
+
+        let result = interpreter.execute(expression)?;
+        assert_eq!(result.borrow().to_string(), expected);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::counts("var r = 0; do r = r + 1; while (r < 3);", "3")]
+    #[case::body_runs_once("var r = 0; do { r = r + 1; } while (false);", "1")]
+    #[case::condition_after_body("var r = 5; do r = r * 2; while (r < 3);", "10")]
+    #[case::block_scope("var r = 1; do { var r = 2; } while (false);", "1")]
+    fn test_do_while(#[case] source: String, #[case] expected: &str) -> Result<(), String> {
+        let mut interpreter = super::Interpreter::new();
+
+        interpreter.execute(source)?;
+
+        let result = interpreter.execute("r;".to_string())?;
+        assert_eq!(result.borrow().to_string(), expected);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::list("var r = 0; for (x in [1, 2, 3]) r = r + x;", "6")]
+    #[case::empty_list("var r = 0; for (x in []) r = 1;", "0")]
+    #[case::string("var r = \"\"; for (c in \"abc\") r = c + r;", "cba")]
+    #[case::map_keys(
+        "var r = \"\"; for (k in memory_stats()) r = r + k + \" \";",
+        "bytes scopes values variables "
+    )]
+    #[case::nested(
+        "var r = 0; for (x in [1, 2]) for (y in [10, 20]) r = r + x * y;",
+        "90"
+    )]
+    #[case::copied_elements(
+        "var l = [1, 2]; var r = 0; for (x in l) { l[1] = 5; r = r + x; }",
+        "3"
+    )]
+    #[case::range("var r = 0; for (i in 1..4) r = r * 10 + i;", "123")]
+    #[case::inclusive_range("var r = 0; for (i in 1..=4) r = r * 10 + i;", "1234")]
+    #[case::range_expressions("var n = 2; var r = 0; for (i in n - 1..n * 2) r = r + i;", "6")]
+    #[case::empty_range("var r = 0; for (i in 5..1) r = 1;", "0")]
+    #[case::variable_scope("var x = 7; var r; for (x in [1]) {} r = x;", "7")]
+    fn test_for_in(#[case] source: String, #[case] expected: &str) -> Result<(), String> {
+        let mut interpreter = super::Interpreter::new();
+
+        interpreter.execute(source)?;
+
+        let result = interpreter.execute("r;".to_string())?;
+        assert_eq!(result.borrow().to_string(), expected);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::number("for (x in 3) print x;", "Cannot iterate over number")]
+    #[case::nil("for (x in nil) print x;", "Cannot iterate over nil")]
+    #[case::range_bounds(
+        "for (x in 1..\"a\") print x;",
+        "'..' not supported between number and string at line 1"
+    )]
+    #[case::inclusive_range_bounds(
+        "print nil..=1;",
+        "'..=' not supported between nil and number at line 1"
+    )]
+    #[case::missing_in("for (x [1]) print x;", "Expected 'in' after loop variable.")]
+    #[case::missing_variable("for (in [1]) print x;", "Expected identifier for the loop variable.")]
+    fn test_for_in_errors(#[case] source: String, #[case] expected: &str) {
+        let mut interpreter = super::Interpreter::new();
+
+        assert_eq!(
+            interpreter.execute(source).map_err(String::from).err(),
+            Some(expected.to_string())
+        );
+    }
+
+    #[rstest]
+    #[case::caught("var r; try { throw \"boom\"; r = 1; } catch (e) { r = e; }", "boom")]
+    #[case::not_thrown("var r = 0; try { r = 1; } catch (e) { r = 2; }", "1")]
+    #[case::any_value("var r; try { throw [1, 2]; } catch (e) { r = e[1]; }", "2")]
+    #[case::nested(
+        "var r = \"\"; try { try { throw 1; } catch (e) { r = r + e; throw 2; } } catch (e) { r = r + e; }",
+        "12"
+    )]
+    #[case::from_function("var r; fun f() { throw 3; } try { f(); } catch (e) { r = e; }", "3")]
+    #[case::through_native(
+        "var r; fun f(x) { throw x; } try { filter([4], f); } catch (e) { r = e; }",
+        "4"
+    )]
+    #[case::catch_scope(
+        "var e = 1; var r; try { throw 2; } catch (e) { r = e; } r = r + e;",
+        "3"
+    )]
+    fn test_exceptions(#[case] source: String, #[case] expected: &str) -> Result<(), String> {
+        let mut interpreter = super::Interpreter::new();
+
+        interpreter.execute(source)?;
+
+        let result = interpreter.execute("r;".to_string())?;
+        assert_eq!(result.borrow().to_string(), expected);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::uncaught("\nthrow \"boom\";", "Uncaught exception: boom at line 2")]
+    #[case::rethrown(
+        "try { throw 1; } catch (e) { throw e + 1; }",
+        "Uncaught exception: 2 at line 1"
+    )]
+    #[case::runtime_error("try { var a = nil + 1; } catch (e) { print e; }", "'+' not supported")]
+    fn test_exceptions_errors(#[case] source: String, #[case] expected: &str) {
+        let mut interpreter = super::Interpreter::new();
+
+        let result = interpreter.execute(source).map_err(String::from);
+
+        assert!(
+            result.as_ref().is_err_and(|e| e.contains(expected)),
+            "{result:?}"
+        );
+    }
+
+    #[rstest]
+    #[case::recursion("fun f() { f(); } f();", "Stack overflow: more than 200 nested calls")]
+    #[case::mutual_recursion(
+        "fun f() { g(); } fun g() { f(); } f();",
+        "Stack overflow: more than 200 nested calls"
+    )]
+    #[case::through_native(
+        "fun f(x) { filter([x], f); } f(1);",
+        "Stack overflow: more than 200 nested calls"
+    )]
+    fn test_stack_overflow(#[case] source: String, #[case] expected: &str) {
+        let mut interpreter = super::Interpreter::new();
+
+        let result = interpreter
+            .execute(source)
+            .map(|_| ())
+            .map_err(String::from);
+
+        assert_eq!(result, Err(expected.to_string()));
+    }
+
+    #[rstest]
+    #[case::itself("var l = [1]; l[0] = l;")]
+    #[case::nested("var l = [[1]]; l[0][0] = l;")]
+    #[case::in_other_list("var l = [1]; var m = [l]; l[0] = m;")]
+    fn test_list_inside_itself(#[case] source: String) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter writing its output to a buffer
+        let buffer = SharedBuffer::default();
+        let mut interpreter = super::Interpreter::with_output(Box::new(buffer.clone()));
+
+        ///////////////////////////////////////////////////////////////////////
+        // When storing a list inside itself
+        let result = interpreter
+            .execute(source)
+            .map(|_| ())
+            .map_err(String::from);
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the assignment fails, and the list can still be printed and compared
+        assert_eq!(result, Err("Cannot store a list inside itself".to_string()));
+
+        interpreter.execute("print l; print l == l + [];".to_string())?;
+        assert!(
+            buffer.contents().ends_with("]\ntrue\n"),
+            "{}",
+            buffer.contents()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_call_depth() -> Result<(), String> {
+        let mut interpreter = super::Interpreter::with_options(InterpreterOptions {
+            max_call_depth: 5,
+            ..Default::default()
+        });
+        interpreter
+            .execute("var n = 0; fun f(x) { if (x > 0) { n = n + 1; f(x - 1); } }".to_string())?;
+
+        // the top-level call and four nested ones
+        interpreter.execute("f(4);".to_string())?;
+        assert_eq!(
+            interpreter
+                .execute("f(5);".to_string())
+                .map(|_| ())
+                .map_err(String::from),
+            Err("Stack overflow: more than 5 nested calls".to_string())
+        );
+
+        // the depth is restored after the error
+        interpreter.execute("f(4);".to_string())?;
+        assert_eq!(interpreter.get("n"), Some(Value::Number(13.0)));
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::infinite_loop("while (true) {}")]
+    #[case::infinite_do_while("var a = 0; do a = a + 1; while (true);")]
+    #[case::recursion("fun f() { print 1; f(); } f();")]
+    #[case::not_caught("try { while (true) {} } catch (e) { print e; }")]
+    fn test_max_steps(#[case] source: String) {
+        let mut interpreter = super::Interpreter::with_options(InterpreterOptions {
+            max_steps: 100,
+            ..Default::default()
+        });
+        interpreter.set_output(Box::new(std::io::sink()));
+
+        let result = interpreter.execute(source).map(|_| ());
+
+        assert_eq!(
+            result,
+            Err(LoxError::BudgetExceeded(LimitExceeded::Steps(100)))
+        );
+        assert_eq!(
+            interpreter.get_limit_exceeded(),
+            Some(&LimitExceeded::Steps(100))
+        );
+    }
+
+    #[test]
+    fn test_max_steps_per_execution() -> Result<(), String> {
+        let mut interpreter = super::Interpreter::with_options(InterpreterOptions {
+            max_steps: 100,
+            ..Default::default()
+        });
+
+        // each execution has its own budget, the steps of the earlier ones do not count
+        for _ in 0..10 {
+            interpreter.execute("var i = 0; while (i < 10) i = i + 1;".to_string())?;
+        }
+        assert_eq!(interpreter.get_limit_exceeded(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_duration() {
+        let max_duration = std::time::Duration::from_millis(20);
+        let mut interpreter = super::Interpreter::with_options(InterpreterOptions {
+            max_duration: Some(max_duration),
+            ..Default::default()
+        });
+
+        let result = interpreter
+            .execute("while (true) {}".to_string())
+            .map(|_| ());
+
+        assert_eq!(
+            result,
+            Err(LoxError::BudgetExceeded(LimitExceeded::Duration(
+                max_duration
+            )))
+        );
+        assert_eq!(
+            interpreter.get_limit_exceeded(),
+            Some(&LimitExceeded::Duration(max_duration))
+        );
+    }
+
+    #[rstest]
+    #[case::large_string("var s = \"ab\" * 100000;")]
+    #[case::growing_string("var s = \"x\"; while (true) s = s + s;")]
+    #[case::growing_list("var l = [1]; while (true) l = l + l;")]
+    #[case::not_caught("try { var s = \"ab\" * 100000; } catch (e) { print e; }")]
+    #[case::repeat_beyond_host_memory("var s = \"ab\" * 1000000000000;")]
+    #[case::upper("var s = \"ab\" * 12000; var t = upper(s); var u = upper(s);")]
+    #[case::split("var s = \",\" * 20000; var l = split(s, \",\");")]
+    #[case::list("var l = list(0..1000000000000);")]
+    fn test_max_memory(#[case] source: String) {
+        let mut interpreter = super::Interpreter::with_options(InterpreterOptions {
+            max_memory: 64 * 1024,
+            ..Default::default()
+        });
+
+        let result = interpreter.execute(source).map(|_| ());
+
+        assert_eq!(
+            result,
+            Err(LoxError::BudgetExceeded(LimitExceeded::Memory(64 * 1024)))
+        );
+        assert_eq!(
+            result.map_err(String::from),
+            Err("Execution exceeds the memory limit of 65536 bytes.".to_string())
+        );
+        assert_eq!(
+            interpreter.get_limit_exceeded(),
+            Some(&LimitExceeded::Memory(64 * 1024))
+        );
+    }
+
+    #[test]
+    fn test_max_memory_released_values() -> Result<(), String> {
+        let mut interpreter = super::Interpreter::with_options(InterpreterOptions {
+            max_memory: 64 * 1024,
+            ..Default::default()
+        });
+
+        // the strings created by the loop add up to more than the budget, but only one of them
+        // is alive at a time
+        interpreter.execute(
+            "var i = 0; while (i < 1000) { var s = \"abc\" * 100; i = i + 1; }".to_string(),
+        )?;
+        assert_eq!(interpreter.get_limit_exceeded(), None);
+
+        let before = interpreter.heap_size()?;
+        interpreter.execute("var s = \"abc\" * 100;".to_string())?;
+        assert!(interpreter.heap_size()? >= before + 300);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_expression_depth() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a syntax tree nested deeper than the parser allows, e.g. built by a host
+        let mut expr = Expr::LiteralNumber(1.0);
+        for _ in 0..1000 {
+            expr = Expr::UnaryMinus(Box::new(expr));
+        }
+        let program = Program {
+            tokens: Vec::new(),
+            statements: vec![Stmt::Expr(Box::new(expr))],
+        };
+
+        ///////////////////////////////////////////////////////////////////////
+        // When executing it
+        let mut interpreter = super::Interpreter::new();
+        let result = interpreter
+            .execute_program(&program)
+            .map(|_| ())
+            .map_err(String::from);
+
         ///////////////////////////////////////////////////////////////////////
-        // Given the source code in the file
-        let input_source = std::fs::read_to_string(base_path).map_err(|e| e.to_string())?;
+        // Then it fails instead of exhausting the stack
+        assert_eq!(
+            result,
+            Err("Stack overflow: expressions nested more than 200 levels deep".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::passes("assert 1 < 2;", Ok(()))]
+    #[case::truthy("assert 0, \"zero is true\";", Ok(()))]
+    #[case::fails("var a = 1;\nassert a > 1;", Err("Assertion failed at line 2"))]
+    #[case::message(
+        "var a = 1;\n\nassert a > 1, \"a is \" + a;",
+        Err("Assertion failed: a is 1 at line 3")
+    )]
+    #[case::in_function(
+        "fun check(x) {\n  assert x, \"check\";\n}\ncheck(true);\ncheck(nil);",
+        Err("Assertion failed: check at line 2")
+    )]
+    #[case::message_not_evaluated("assert true, undefined_variable;", Ok(()))]
+    fn test_assert(#[case] source: &str, #[case] expected: Result<(), &str>) {
+        let mut interpreter = super::Interpreter::new();
+
+        let result = interpreter
+            .execute(source.to_string())
+            .map(|_| ())
+            .map_err(String::from);
+
+        assert_eq!(result, expected.map_err(|e| e.to_string()));
+    }
+
+    #[rstest]
+    #[case::first_case(
+        "var r; switch (1) { case 1: r = \"one\"; case 2: r = \"two\"; }",
+        "one"
+    )]
+    #[case::no_fall_through(
+        "var r = \"\"; switch (1) { case 1: r = r + \"a\"; case 2: r = r + \"b\"; }",
+        "a"
+    )]
+    #[case::later_case("var r; switch (\"b\") { case \"a\": r = 1; case \"b\": r = 2; }", "2")]
+    #[case::default(
+        "var r; switch (3) { case 1: r = 1; default: r = \"other\"; }",
+        "other"
+    )]
+    #[case::no_match("var r = 0; switch (3) { case 1: r = 1; }", "0")]
+    #[case::empty_case("var r = 0; switch (1) { case 1: default: r = 1; }", "0")]
+    #[case::strict_equality("var r; switch (0) { case false: r = 1; default: r = 2; }", "2")]
+    #[case::list_value("var r; switch ([1]) { case [1]: r = 1; }", "1")]
+    #[case::subject_evaluated_once(
+        "var n = 0; fun next() { n = n + 1; } var r; switch (next()) { case 1: case 2: } r = n;",
+        "1"
+    )]
+    #[case::lazy_case_values(
+        "var n = 0; fun count() { n = n + 1; } var r; switch (1) { case 1: case count(): } r = n;",
+        "0"
+    )]
+    #[case::case_scope("var r = 1; switch (1) { case 1: var r = 2; }", "1")]
+    fn test_switch(#[case] source: String, #[case] expected: &str) -> Result<(), String> {
+        let mut interpreter = super::Interpreter::new();
+
+        interpreter.execute(source)?;
 
-        // and given an interpreters
+        let result = interpreter.execute("r;".to_string())?;
+        assert_eq!(result.borrow().to_string(), expected);
 
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::if_zero_strict(Truthiness::Strict, "var r = 1; if (0) r = 2;", "2")]
+    #[case::if_empty_string_strict(Truthiness::Strict, "var r = 1; if (\"\") r = 2;", "2")]
+    #[case::if_nil_strict(Truthiness::Strict, "var r = 1; if (nil) r = 2;", "1")]
+    #[case::or_strict(Truthiness::Strict, "var r = 0 or 1;", "0")]
+    #[case::boolean_strict(Truthiness::Strict, "var r = boolean(\"\");", "true")]
+    #[case::filter_strict(Truthiness::Strict, "var r = filter([0, 1], number);", "[0, 1]")]
+    #[case::bang_nil_strict(Truthiness::Strict, "var r = !nil;", "true")]
+    #[case::bang_zero_strict(Truthiness::Strict, "var r = !0;", "false")]
+    #[case::bang_empty_string_strict(Truthiness::Strict, "var r = !\"\";", "false")]
+    #[case::if_zero_permissive(Truthiness::Permissive, "var r = 1; if (0) r = 2;", "1")]
+    #[case::if_empty_string_permissive(Truthiness::Permissive, "var r = 1; if (\"\") r = 2;", "1")]
+    #[case::while_permissive(Truthiness::Permissive, "var r = 3; while (r) r = r - 1;", "0")]
+    #[case::or_permissive(Truthiness::Permissive, "var r = 0 or 1;", "1")]
+    #[case::boolean_permissive(Truthiness::Permissive, "var r = boolean(\"\");", "false")]
+    #[case::filter_permissive(Truthiness::Permissive, "var r = filter([0, 1], number);", "[1]")]
+    #[case::bang_nil_permissive(Truthiness::Permissive, "var r = !nil;", "true")]
+    #[case::bang_zero_permissive(Truthiness::Permissive, "var r = !0;", "true")]
+    #[case::bang_empty_string_permissive(Truthiness::Permissive, "var r = !\"\";", "true")]
+    fn test_truthiness(
+        #[case] truthiness: Truthiness,
+        #[case] source: String,
+        #[case] expected: &str,
+    ) -> Result<(), String> {
+        let mut interpreter = super::Interpreter::with_options(InterpreterOptions {
+            truthiness,
+            ..Default::default()
+        });
+
+        interpreter.execute(source)?;
+
+        let result = interpreter.execute("r;".to_string())?;
+        assert_eq!(result.borrow().to_string(), expected);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::read_enclosing_block("var r; { var a = \"outer\"; { { r = a; } } }", "r;", "outer")]
+    #[case::assign_enclosing_block("var r; { var a = 1; { { a = 2; } r = a; } }", "r;", "2")]
+    #[case::shadowed_in_block("var r; { var a = 1; { var a = 2; a = 3; } r = a; }", "r;", "1")]
+    #[case::shadowed_global("var a = 1; { var a = 2; { a = 3; } }", "a;", "1")]
+    #[case::function_in_block(
+        "var r; { var a = 1; fun f() { a = a + 1; } f(); f(); r = a; }",
+        "r;",
+        "3"
+    )]
+    #[case::loop_body(
+        "var r; { var total = 0; var i = 0; while (i < 4) { { total = total + i; } i = i + 1; } r = total; }",
+        "r;",
+        "6"
+    )]
+    #[case::global_after_block("{ var a = 1; { a = 2; } } var a = \"global\";", "a;", "global")]
+    fn test_enclosing_scopes(
+        #[case] source: String,
+        #[case] expression: String,
+        #[case] expected: &str,
+    ) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given source code using variables declared in enclosing blocks
         let mut interpreter = super::Interpreter::new();
 
         ///////////////////////////////////////////////////////////////////////
         // When executing the source code
-        // Then there should be no error
-        _ = interpreter.execute(input_source)?;
+        interpreter.execute(source)?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then reads and assignments resolve to the innermost declaration
+        let result = interpreter.execute(expression)?;
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(result_guard.to_string(), expected);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::chained("var a; var b; a = b = 5;", "[a, b];", "[5, 5]")]
+    #[case::same_variable("var a = 1; a = a = 2;", "a;", "2")]
+    #[case::index("var l = [0]; var a; a = l[0] = 3;", "[a, l];", "[3, [3]]")]
+    #[case::value("var a; var b;", "a = b = \"x\";", "x")]
+    fn test_chained_assignment(
+        #[case] setup: String,
+        #[case] source: String,
+        #[case] expected: &str,
+    ) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter executing chained assignments
+        let mut interpreter = super::Interpreter::new();
+        interpreter.execute(setup)?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // When reading the assigned values
+        let result = interpreter.execute(source)?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then every target holds the rightmost value
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(result_guard.to_string(), expected);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::runtime_error_mid_block("{ a = 2; items[0] = 3; var c = 1; undefined(); }")]
+    #[case::declaration("var b = 1; var a = \"other\"; b + nil;")]
+    #[case::list_element("items[1] = 10; items[5];")]
+    #[case::function_call("fun f() { a = 5; nil(); } f();")]
+    #[case::parse_error("a = 2; print (;")]
+    #[case::scan_error("a = 2; @")]
+    fn test_execute_transactional(#[case] source: String) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter with some variables, one of them sharing a list with another
+        let mut interpreter = super::Interpreter::new();
+        interpreter.execute("var a = 1; var items = [1, 2]; var alias = items;".to_string())?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // When executing source that fails after changing the variables
+        let result = interpreter.execute_transactional(source);
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the variables are left as they were before the execution
+        assert!(result.is_err());
+        let state = interpreter.execute("[a, items, alias];".to_string())?;
+        let state_guard = state.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(state_guard.to_string(), "[1, [1, 2], [1, 2]]");
+        assert!(interpreter.execute("b;".to_string()).is_err());
+        assert!(interpreter.execute("c;".to_string()).is_err());
+
+        // and list elements are still shared between the variables
+        interpreter.execute("items[0] = 7;".to_string())?;
+        let shared = interpreter.execute("alias[0];".to_string())?;
+        let shared_guard = shared.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(*shared_guard, Value::Number(7.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_execute_transactional_commits() -> Result<(), String> {
+        let mut interpreter = super::Interpreter::new();
+
+        interpreter.execute_transactional("var a = 1; { a = a + 1; }".to_string())?;
+
+        let result = interpreter.execute("a;".to_string())?;
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(*result_guard, Value::Number(2.0));
+
+        Ok(())
+    }
+
+    // Writer appending to a buffer that can be inspected after handing the writer to an interpreter
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl SharedBuffer {
+        fn contents(&self) -> String {
+            String::from_utf8_lossy(&self.0.borrow()).to_string()
+        }
+    }
+
+    #[test]
+    fn test_print_output() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter writing its output to a buffer
+        let buffer = SharedBuffer::default();
+        let mut interpreter = super::Interpreter::with_output(Box::new(buffer.clone()));
+
+        ///////////////////////////////////////////////////////////////////////
+        // When executing print statements, including from an imported module
+        interpreter.set_module_search_paths(vec![PathBuf::from("test-data/modules")]);
+        interpreter.execute(
+            "import \"greeting.lox\"; print 1 + 2; print [\"a\", nil]; greet(\"lox\");".to_string(),
+        )?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the printed values are captured in the buffer
+        assert_eq!(buffer.contents(), "3\n[a, nil]\nhello lox\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_search_paths() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter looking up modules in the test modules directory
+        let mut interpreter = super::Interpreter::new();
+        interpreter.set_module_search_paths(vec![PathBuf::from("test-data/modules")]);
+
+        ///////////////////////////////////////////////////////////////////////
+        // When importing a module that imports another module relative to itself
+        interpreter.execute("import \"nested.lox\";".to_string())?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the declarations of both modules are available
+        let result = interpreter.execute("greeting + \" \" + nested_name;".to_string())?;
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(*result_guard, Value::String("hello nested".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_language_version() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter accepting only classic Lox by default
+        let mut interpreter = super::Interpreter::with_options(super::InterpreterOptions {
+            version: super::LanguageVersion::Lox1,
+            ..Default::default()
+        });
+
+        ///////////////////////////////////////////////////////////////////////
+        // When running sources using lists with and without a version header
+        let without_header = interpreter.execute("var a = [1];".to_string());
+        let with_header = interpreter.execute("// lox-version: extended\nvar a = [1];".to_string());
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then only the source declaring the extended version is accepted
+        assert_eq!(
+            without_header.map_err(String::from).err(),
+            Some("Lists are not supported in Lox 1.".to_string())
+        );
+        assert!(with_header.is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_then_execute() -> Result<(), String> {
+        let mut interpreter = super::Interpreter::with_options(super::InterpreterOptions {
+            version: super::LanguageVersion::Lox1,
+            ..Default::default()
+        });
+
+        // parsing follows the options of the interpreter and runs nothing
+        assert_eq!(
+            interpreter.parse("var a = [1];".to_string()).err(),
+            Some(super::Diagnostic::error(
+                "E0201",
+                "Lists are not supported in Lox 1.",
+                Some(super::Span { line: 1 })
+            ))
+        );
+        let program = interpreter.parse("var a = 1; a = a + nil;".to_string())?;
+        assert!(interpreter.get("a").is_none());
+
+        assert_eq!(
+            interpreter
+                .execute_program(&program)
+                .map_err(String::from)
+                .err(),
+            Some("'+' not supported between number and nil at line 1".to_string())
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::global("c.count;", Value::Number(0.0))]
+    #[case::function_updating_global("c.increment(); c.increment(); c.count;", Value::Number(2.0))]
+    #[case::type_name("typeof(c);", Value::String("module".to_string()))]
+    fn test_import_as(#[case] source: &str, #[case] expected: Value) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter importing a module bound to an alias
+        let mut interpreter = super::Interpreter::new();
+        interpreter.set_module_search_paths(vec![PathBuf::from("test-data/modules")]);
+        interpreter.execute("import \"counter.lox\" as c;".to_string())?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // When executing statements accessing the module, one at a time
+        let mut result = new_value_box(Value::Nil);
+        for statement in source.split_inclusive(';') {
+            result = interpreter.execute(statement.to_string())?;
+        }
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the declarations are reached through the alias
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(*result_guard, expected);
 
         Ok(())
     }
+
+    #[test]
+    fn test_import_as_function_globals() -> Result<(), String> {
+        // functions called through the alias see the globals of their module
+        let buffer = SharedBuffer::default();
+        let mut interpreter = super::Interpreter::with_output(Box::new(buffer.clone()));
+        interpreter.set_module_search_paths(vec![PathBuf::from("test-data/modules")]);
+
+        interpreter.execute("import \"greeting.lox\" as g; g.greet(\"bob\");".to_string())?;
+
+        assert_eq!(buffer.contents(), "hello bob\n");
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::missing_declaration("c.missing;", "Module 'c' has no declaration named 'missing'")]
+    #[case::not_a_module("var a = 1; a.count;", "Only modules have properties, got number")]
+    #[case::assign_declaration(
+        "c.count = 1;",
+        "Cannot assign to 'count', declarations of module 'c' are read-only"
+    )]
+    #[case::assign_not_a_module(
+        "var a = [1]; a[0].count = 1;",
+        "Only modules have properties, got number"
+    )]
+    #[case::missing_alias("import \"counter.lox\" as;", "Expected module name after 'as'.")]
+    #[case::not_exposed("count;", "Undefined variable 'count'")]
+    fn test_import_as_errors(#[case] source: &str, #[case] expected: &str) -> Result<(), String> {
+        let mut interpreter = super::Interpreter::new();
+        interpreter.set_module_search_paths(vec![PathBuf::from("test-data/modules")]);
+        interpreter.execute("import \"counter.lox\" as c;".to_string())?;
+
+        assert_eq!(
+            interpreter
+                .execute(source.to_string())
+                .map_err(String::from)
+                .err(),
+            Some(expected.to_string())
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::missing("import \"missing.lox\";", "Module 'missing.lox' not found")]
+    #[case::cycle(
+        "import \"cycle_a.lox\";",
+        "Error in module 'cycle_a.lox': Error in module 'cycle_b.lox': Circular import of module 'cycle_a.lox'"
+    )]
+    fn test_import_errors(
+        #[case] source: String,
+        #[case] expected_error: String,
+    ) -> Result<(), String> {
+        let mut interpreter = super::Interpreter::new();
+        interpreter.set_module_search_paths(vec![PathBuf::from("test-data/modules")]);
+
+        match interpreter.execute(source).map_err(String::from) {
+            Ok(_) => Err("Expected an error".to_string()),
+            Err(e) => {
+                assert!(e.starts_with(&expected_error), "unexpected error: {e}");
+                Ok(())
+            }
+        }
+    }
 }
@@ -0,0 +1,62 @@
+use std::fmt::Display;
+use std::time::Duration;
+
+// Caps on the size of the input accepted by the scanner and the parser, bounding the resources
+// used by untrusted sources before execution begins
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limits {
+    pub max_tokens: usize,
+    pub max_ast_nodes: usize,
+    // applies to both function declarations and calls
+    pub max_function_parameters: usize,
+    pub max_string_literal_length: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_tokens: usize::MAX,
+            max_ast_nodes: usize::MAX,
+            max_function_parameters: 255,
+            max_string_literal_length: usize::MAX,
+        }
+    }
+}
+
+// Limit exceeded by the input or by its execution, holding the configured maximum
+#[derive(Debug, Clone, PartialEq)]
+pub enum LimitExceeded {
+    Tokens(usize),
+    AstNodes(usize),
+    FunctionParameters(usize),
+    StringLiteralLength(usize),
+    // budgets of the interpreter options
+    Steps(usize),
+    Duration(Duration),
+    // approximate bytes of the values alive
+    Memory(usize),
+}
+
+impl Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitExceeded::Tokens(max) => write!(f, "Source exceeds the limit of {max} tokens."),
+            LimitExceeded::AstNodes(max) => {
+                write!(f, "Source exceeds the limit of {max} syntax tree nodes.")
+            }
+            LimitExceeded::FunctionParameters(max) => {
+                write!(f, "Function exceeds the limit of {max} parameters.")
+            }
+            LimitExceeded::StringLiteralLength(max) => {
+                write!(f, "String literal exceeds the limit of {max} characters.")
+            }
+            LimitExceeded::Steps(max) => write!(f, "Execution exceeds the limit of {max} steps."),
+            LimitExceeded::Duration(max) => {
+                write!(f, "Execution exceeds the time limit of {max:?}.")
+            }
+            LimitExceeded::Memory(max) => {
+                write!(f, "Execution exceeds the memory limit of {max} bytes.")
+            }
+        }
+    }
+}
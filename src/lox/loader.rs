@@ -0,0 +1,268 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+// Module found by a loader
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleSource {
+    // identity of the module, two imports resolving to the same id import the same module
+    pub id: String,
+    pub source: String,
+    // directory the imports of the module are looked up in first, if it lives in a file
+    pub directory: Option<PathBuf>,
+}
+
+// Finds the source of the modules named by import statements
+pub trait ModuleLoader {
+    // Module named by the import path, None if the loader does not have it. The directory is
+    // the one of the module containing the import, None for the main script and in-memory
+    // modules
+    fn load(&self, path: &str, directory: Option<&Path>) -> Result<Option<ModuleSource>, String>;
+}
+
+// Looks up modules as files, relative to the importing module first and then in the search
+// paths in order
+pub struct FileModuleLoader {
+    search_paths: Vec<PathBuf>,
+}
+
+impl FileModuleLoader {
+    pub fn new(search_paths: Vec<PathBuf>) -> Self {
+        Self { search_paths }
+    }
+}
+
+impl ModuleLoader for FileModuleLoader {
+    fn load(&self, path: &str, directory: Option<&Path>) -> Result<Option<ModuleSource>, String> {
+        let directories = directory
+            .into_iter()
+            .chain(self.search_paths.iter().map(|p| p.as_path()));
+
+        for directory in directories {
+            let candidate = directory.join(path);
+            if !candidate.is_file() {
+                continue;
+            }
+
+            let candidate = candidate
+                .canonicalize()
+                .map_err(|e| format!("Error resolving module '{path}': {e}"))?;
+            let source = std::fs::read_to_string(&candidate)
+                .map_err(|e| format!("Error reading module '{path}': {e}"))?;
+
+            return Ok(Some(ModuleSource {
+                id: candidate.display().to_string(),
+                source,
+                directory: candidate.parent().map(|d| d.to_path_buf()),
+            }));
+        }
+
+        Ok(None)
+    }
+}
+
+// Modules registered by the embedder, e.g. scripts shipped inside the binary, looked up by
+// their exact name
+#[derive(Default)]
+pub struct MemoryModuleLoader {
+    modules: HashMap<String, String>,
+}
+
+impl MemoryModuleLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Registers the source of a module, replacing any module with the same name
+    pub fn register(&mut self, name: &str, source: &str) {
+        self.modules.insert(name.to_string(), source.to_string());
+    }
+}
+
+impl ModuleLoader for MemoryModuleLoader {
+    fn load(&self, path: &str, _directory: Option<&Path>) -> Result<Option<ModuleSource>, String> {
+        Ok(self.modules.get(path).map(|source| ModuleSource {
+            id: format!("memory:{path}"),
+            source: source.clone(),
+            directory: None,
+        }))
+    }
+}
+
+// Refuses every import, used in place of the filesystem when the options do not allow I/O
+pub struct DisabledModuleLoader;
+
+impl ModuleLoader for DisabledModuleLoader {
+    fn load(&self, path: &str, _directory: Option<&Path>) -> Result<Option<ModuleSource>, String> {
+        Err(format!(
+            "Cannot import module '{path}', filesystem access is disabled"
+        ))
+    }
+}
+
+// Tries each loader in order, the first one having the module wins
+#[derive(Default)]
+pub struct ModuleLoaderChain {
+    loaders: Vec<Box<dyn ModuleLoader>>,
+}
+
+impl ModuleLoaderChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Adds a loader to fall back to when the previous ones do not have a module
+    pub fn then(mut self, loader: Box<dyn ModuleLoader>) -> Self {
+        self.loaders.push(loader);
+        self
+    }
+}
+
+impl ModuleLoader for ModuleLoaderChain {
+    fn load(&self, path: &str, directory: Option<&Path>) -> Result<Option<ModuleSource>, String> {
+        for loader in self.loaders.iter() {
+            if let Some(module) = loader.load(path, directory)? {
+                return Ok(Some(module));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::path::PathBuf;
+
+    use rstest::*;
+
+    use crate::lox::{Interpreter, InterpreterOptions};
+
+    use super::*;
+
+    fn embedded_loader() -> ModuleLoaderChain {
+        let mut memory = MemoryModuleLoader::new();
+        memory.register("config.lox", "var name = \"embedded\";");
+        memory.register(
+            "app.lox",
+            "import \"config.lox\"; var title = name + \" app\";",
+        );
+        // shadows the module of the same name in the filesystem
+        memory.register("greeting.lox", "var greeting = \"hi\";");
+        // declares a global after the scope opened by a function call is closed
+        memory.register(
+            "late.lox",
+            "fun touch() {} touch(); var late = \"after call\";",
+        );
+
+        ModuleLoaderChain::new()
+            .then(Box::new(memory))
+            .then(Box::new(FileModuleLoader::new(vec![PathBuf::from(
+                "test-data/modules",
+            )])))
+    }
+
+    #[rstest]
+    #[case::memory("import \"config.lox\";", "name;", "embedded")]
+    #[case::memory_imports_memory("import \"app.lox\";", "title;", "embedded app")]
+    #[case::memory_first("import \"greeting.lox\";", "greeting;", "hi")]
+    #[case::file_fallback("import \"counter.lox\" as c;", "c.count;", "0")]
+    #[case::alias("import \"config.lox\" as config;", "config.name;", "embedded")]
+    #[case::declared_after_call("import \"late.lox\" as l;", "l.late;", "after call")]
+    fn test_import_from_loader(
+        #[case] import: &str,
+        #[case] expression: &str,
+        #[case] expected: &str,
+    ) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter loading modules from memory before the filesystem
+        let mut interpreter = Interpreter::new();
+        interpreter.set_module_loader(Box::new(embedded_loader()));
+
+        ///////////////////////////////////////////////////////////////////////
+        // When importing a module
+        interpreter.execute(import.to_string())?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then its declarations come from the first loader having it
+        let result = interpreter.execute(expression.to_string())?;
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(result_guard.to_string(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_loader_errors() {
+        let mut memory = MemoryModuleLoader::new();
+        memory.register("a.lox", "import \"b.lox\";");
+        memory.register("b.lox", "import \"a.lox\";");
+
+        let mut interpreter = Interpreter::new();
+        interpreter.set_module_loader(Box::new(memory));
+
+        assert_eq!(
+            interpreter
+                .execute("import \"c.lox\";".to_string())
+                .err()
+                .map(String::from),
+            Some("Module 'c.lox' not found".to_string())
+        );
+        assert_eq!(
+            interpreter.execute("import \"a.lox\";".to_string()).err().map(String::from),
+            Some(
+                "Error in module 'a.lox': Error in module 'b.lox': Circular import of module 'a.lox'"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_import_without_io() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter not allowed to do I/O, looking modules up in a directory
+        let mut interpreter = Interpreter::with_options(InterpreterOptions {
+            allow_io: false,
+            ..Default::default()
+        });
+        interpreter.set_module_search_paths(vec![PathBuf::from("test-data/modules")]);
+
+        ///////////////////////////////////////////////////////////////////////
+        // When importing a module from the filesystem
+        let result = interpreter.execute("import \"counter.lox\";".to_string());
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the import is refused, while modules of the embedder can still be imported
+        assert_eq!(
+            result.err().map(String::from),
+            Some("Cannot import module 'counter.lox', filesystem access is disabled".to_string())
+        );
+
+        let mut memory = MemoryModuleLoader::new();
+        memory.register("config.lox", "var name = \"embedded\";");
+        interpreter.set_module_loader(Box::new(memory));
+        interpreter.execute("import \"config.lox\";".to_string())?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_file_loader_relative_directory() -> Result<(), String> {
+        let loader = FileModuleLoader::new(vec![]);
+
+        assert_eq!(loader.load("greeting.lox", None)?, None);
+
+        let module = loader
+            .load("greeting.lox", Some(Path::new("test-data/modules")))?
+            .ok_or("Expected the module to be found")?;
+        assert!(module.id.ends_with("greeting.lox"));
+        assert_eq!(
+            module.directory,
+            Path::new("test-data/modules").canonicalize().ok()
+        );
+
+        Ok(())
+    }
+}
@@ -1,32 +1,143 @@
-use super::{Expr, ExprVisitor, Stmt, StmtVisitor, Token};
+use std::cell::Cell;
+
+use super::{
+    Expr, LanguageVersion, LimitExceeded, Limits, Span, SpannedToken, Stmt, Symbol, Token,
+};
 
 pub struct Statement {}
 
+// The message is boxed to keep the results of the recursive descent small, deeply nested inputs
+// would overflow the stack otherwise
 pub struct ParseError {
-    message: String,
+    message: Box<str>,
+    kind: ParseErrorKind,
+    // token the parser was looking at, the last one past the end of the input
+    span: Span,
+}
+
+enum ParseErrorKind {
+    Syntax,
+    // the input ends before the statement being parsed, more input could complete it
+    UnexpectedEof,
+    // the input exceeds one of the parser limits
+    Limit(LimitExceeded),
+}
+
+impl ParseError {
+    pub fn get_limit_exceeded(&self) -> Option<&LimitExceeded> {
+        match &self.kind {
+            ParseErrorKind::Limit(limit) => Some(limit),
+            _ => None,
+        }
+    }
+
+    // Location of the error, line 0 if the tokens were parsed without spans
+    pub fn get_span(&self) -> Span {
+        self.span
+    }
+
+    pub fn is_unexpected_eof(&self) -> bool {
+        matches!(self.kind, ParseErrorKind::UnexpectedEof)
+    }
 }
 
 impl ToString for ParseError {
     fn to_string(&self) -> String {
-        self.message.clone()
+        self.message.to_string()
     }
 }
 
+// Default limit of nested statements and expressions, keeps the recursive descent well
+// below the stack size of the host thread
+pub const DEFAULT_MAX_NESTING_DEPTH: usize = 100;
+
 pub struct Parser {
     tokens: Vec<Token>,
+    spans: Vec<Span>,
     current: usize,
+    depth: usize,
+    max_depth: usize,
+    limits: Limits,
+    node_count: usize,
+    version: LanguageVersion,
+    // set once the parser looks at the end of the stream, errors found after that are caused by
+    // the input ending early
+    end_reached: Cell<bool>,
+    // token read past the end of the stream, whether or not the stream ends with EOF
+    eof: Token,
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Parser {
-        Parser { tokens, current: 0 }
+    // Parser over a stream of tokens, which may end with the EOF token emitted by the scanner
+    pub fn new(tokens: impl IntoIterator<Item = Token>) -> Parser {
+        Parser {
+            tokens: tokens.into_iter().collect(),
+            spans: Vec::new(),
+            current: 0,
+            depth: 0,
+            max_depth: DEFAULT_MAX_NESTING_DEPTH,
+            limits: Limits::default(),
+            node_count: 0,
+            version: LanguageVersion::default(),
+            end_reached: Cell::new(false),
+            eof: Token::Eof,
+        }
+    }
+
+    pub fn from_spanned_tokens(spanned_tokens: impl IntoIterator<Item = SpannedToken>) -> Parser {
+        let (tokens, spans) = spanned_tokens
+            .into_iter()
+            .map(|t| (t.token, t.span))
+            .unzip();
+
+        Parser {
+            tokens,
+            spans,
+            current: 0,
+            depth: 0,
+            max_depth: DEFAULT_MAX_NESTING_DEPTH,
+            limits: Limits::default(),
+            node_count: 0,
+            version: LanguageVersion::default(),
+            end_reached: Cell::new(false),
+            eof: Token::Eof,
+        }
+    }
+
+    pub fn set_max_nesting_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth;
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn set_language_version(&mut self, version: LanguageVersion) {
+        self.version = version;
+    }
+
+    // Parses the tokens as a single expression, written without the semicolon of a statement
+    pub fn parse_single_expression(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.parse_expression()?;
+
+        if !self.is_at_end() {
+            return Err(self.error("Expected end of expression."));
+        }
+
+        Ok(expr)
     }
 
     pub fn parse(&mut self) -> Result<Vec<Stmt>, ParseError> {
         let mut statements = Vec::new();
 
         while !self.is_at_end() {
-            let expr = self.parse_statement()?;
+            let expr = self.parse_statement().map_err(|e| match e.kind {
+                ParseErrorKind::Syntax if self.end_reached.get() => ParseError {
+                    kind: ParseErrorKind::UnexpectedEof,
+                    ..e
+                },
+                _ => e,
+            })?;
             statements.push(expr);
         }
 
@@ -36,18 +147,63 @@ impl Parser {
     ///////////////////////////////////////////////////////////////////////////
     // Statement parsing
     fn parse_statement(&mut self) -> Result<Stmt, ParseError> {
+        self.count_node()?;
+        self.enter_nesting()?;
+        let stmt = self.parse_statement_nested();
+        self.depth -= 1;
+        stmt
+    }
+
+    fn parse_statement_nested(&mut self) -> Result<Stmt, ParseError> {
         match self.peek() {
             Token::Print => self.parse_statement_print(),
             Token::Var => self.parse_statement_var_declaration(),
+            Token::Const => {
+                self.require_extension("Constants")?;
+                self.parse_statement_const_declaration()
+            }
             Token::LeftBrace => self.parse_statement_block(),
             Token::If => self.parse_statement_if(),
             Token::While => self.parse_statement_while(),
+            Token::Do => {
+                self.require_extension("Do-while loops")?;
+                self.parse_statement_do_while()
+            }
+            Token::For => {
+                self.require_extension("For-in loops")?;
+                self.parse_statement_for_in()
+            }
+            Token::Switch => {
+                self.require_extension("Switch statements")?;
+                self.parse_statement_switch()
+            }
             Token::Fun => self.parse_statement_function_declaration(),
+            Token::Throw => {
+                self.require_extension("Exceptions")?;
+                self.parse_statement_throw()
+            }
+            Token::Try => {
+                self.require_extension("Exceptions")?;
+                self.parse_statement_try()
+            }
+            Token::Assert => {
+                self.require_extension("Assert statements")?;
+                self.parse_statement_assert()
+            }
+            Token::Import => {
+                self.require_extension("Imports")?;
+                self.parse_statement_import()
+            }
             _ => self.parse_statement_expression(),
         }
     }
 
     fn parse_statement_block(&mut self) -> Result<Stmt, ParseError> {
+        Ok(Stmt::Block(self.parse_block()?))
+    }
+
+    // Statements between braces, the cursor is at the left brace
+    fn parse_block(&mut self) -> Result<Vec<Stmt>, ParseError> {
         self.advance(); // consume the left brace token
 
         let mut statements = Vec::new();
@@ -58,12 +214,10 @@ impl Parser {
         }
 
         if !self.match_token(vec![Token::RightBrace]) {
-            return Err(ParseError {
-                message: "Expected '}' after block.".to_string(),
-            });
+            return Err(self.error("Expected '}' after block."));
         }
 
-        Ok(Stmt::Block(statements))
+        Ok(statements)
     }
 
     fn parse_statement_print(&mut self) -> Result<Stmt, ParseError> {
@@ -72,9 +226,7 @@ impl Parser {
         let expr = self.parse_expression()?;
 
         if !self.match_token(vec![Token::Semicolon]) {
-            return Err(ParseError {
-                message: "Expected ';' after expression.".to_string(),
-            });
+            return Err(self.error("Expected ';' after expression."));
         }
 
         Ok(Stmt::Print(Box::new(expr)))
@@ -84,9 +236,7 @@ impl Parser {
         let expr = self.parse_expression()?;
 
         if !self.match_token(vec![Token::Semicolon]) {
-            return Err(ParseError {
-                message: "Expected ';' after expression.".to_string(),
-            });
+            return Err(self.error("Expected ';' after expression."));
         }
 
         Ok(Stmt::Expr(Box::new(expr)))
@@ -95,14 +245,34 @@ impl Parser {
     fn parse_statement_var_declaration(&mut self) -> Result<Stmt, ParseError> {
         self.advance(); // consume the var token
 
+        let mut declarations = vec![self.parse_var_declarator()?];
+        while self.match_token(vec![Token::Comma]) {
+            self.require_extension("Multiple variable declarations")?;
+            declarations.push(self.parse_var_declarator()?);
+        }
+
+        if !self.match_token(vec![Token::Semicolon]) {
+            return Err(self.error("Expected ';' after variable declaration."));
+        }
+
+        // a single variable keeps the plain declaration statement
+        if declarations.len() == 1 {
+            let (identifier, initializer, span) = declarations.remove(0);
+            return Ok(Stmt::VarDeclaration(identifier, initializer, span));
+        }
+
+        Ok(Stmt::MultiVarDeclaration(declarations))
+    }
+
+    // Name, optional initializer and name span of one of the variables of a var statement
+    fn parse_var_declarator(&mut self) -> Result<(Symbol, Option<Box<Expr>>, Span), ParseError> {
         let identifier = match self.advance() {
             Token::Identifier(s) => s.clone(),
             _ => {
-                return Err(ParseError {
-                    message: "Expected identifier after var.".to_string(),
-                });
+                return Err(self.error("Expected identifier after var."));
             }
         };
+        let span = self.previous_span();
 
         let initializer = if self.match_token(vec![Token::Equal]) {
             Some(Box::new(self.parse_expression()?))
@@ -110,30 +280,46 @@ impl Parser {
             None
         };
 
+        Ok((identifier, initializer, span))
+    }
+
+    fn parse_statement_const_declaration(&mut self) -> Result<Stmt, ParseError> {
+        self.advance(); // consume the const token
+
+        let identifier = match self.advance() {
+            Token::Identifier(s) => s.clone(),
+            _ => {
+                return Err(self.error("Expected identifier after const."));
+            }
+        };
+        let span = self.previous_span();
+
+        // constants can not be assigned later, so the initializer is required
+        if !self.match_token(vec![Token::Equal]) {
+            return Err(self.error(format!("Expected '=' after constant '{identifier}'.")));
+        }
+
+        let initializer = Box::new(self.parse_expression()?);
+
         if !self.match_token(vec![Token::Semicolon]) {
-            return Err(ParseError {
-                message: "Expected ';' after variable declaration.".to_string(),
-            });
+            return Err(self.error("Expected ';' after constant declaration."));
         }
 
-        Ok(Stmt::VarDeclaration(identifier.clone(), initializer))
+        Ok(Stmt::ConstDeclaration(identifier, initializer, span))
     }
 
     fn parse_statement_if(&mut self) -> Result<Stmt, ParseError> {
         self.advance(); // consume the if token
+        let span = self.previous_span();
 
         if !self.match_token(vec![Token::LeftParenthesis]) {
-            return Err(ParseError {
-                message: "Expected '(' after if.".to_string(),
-            });
+            return Err(self.error("Expected '(' after if."));
         }
 
         let condition = Box::new(self.parse_expression()?);
 
         if !self.match_token(vec![Token::RightParenthesis]) {
-            return Err(ParseError {
-                message: "Expected ')' after if condition.".to_string(),
-            });
+            return Err(self.error("Expected ')' after if condition."));
         }
 
         let then_branch = Box::new(self.parse_statement()?);
@@ -144,29 +330,221 @@ impl Parser {
             None
         };
 
-        Ok(Stmt::If(condition, then_branch, else_branch))
+        Ok(Stmt::If(condition, then_branch, else_branch, span))
     }
 
     fn parse_statement_while(&mut self) -> Result<Stmt, ParseError> {
         self.advance(); // consume the while token
+        let span = self.previous_span();
 
         if !self.match_token(vec![Token::LeftParenthesis]) {
-            return Err(ParseError {
-                message: "Expected '(' after while.".to_string(),
-            });
+            return Err(self.error("Expected '(' after while."));
         }
 
         let condition = Box::new(self.parse_expression()?);
 
         if !self.match_token(vec![Token::RightParenthesis]) {
-            return Err(ParseError {
-                message: "Expected ')' after while condition.".to_string(),
-            });
+            return Err(self.error("Expected ')' after while condition."));
         }
 
         let body = Box::new(self.parse_statement()?);
 
-        Ok(Stmt::While(condition, body))
+        Ok(Stmt::While(condition, body, span))
+    }
+
+    fn parse_statement_throw(&mut self) -> Result<Stmt, ParseError> {
+        self.advance(); // consume the throw token
+        let span = self.previous_span();
+
+        let expr = self.parse_expression()?;
+
+        if !self.match_token(vec![Token::Semicolon]) {
+            return Err(self.error("Expected ';' after thrown value."));
+        }
+
+        Ok(Stmt::Throw(Box::new(expr), span))
+    }
+
+    // `assert condition;` or `assert condition, message;`
+    fn parse_statement_assert(&mut self) -> Result<Stmt, ParseError> {
+        self.advance(); // consume the assert token
+        let span = self.previous_span();
+
+        let condition = self.parse_expression()?;
+
+        let message = match self.match_token(vec![Token::Comma]) {
+            true => Some(Box::new(self.parse_expression()?)),
+            false => None,
+        };
+
+        if !self.match_token(vec![Token::Semicolon]) {
+            return Err(self.error("Expected ';' after assertion."));
+        }
+
+        Ok(Stmt::Assert(Box::new(condition), message, span))
+    }
+
+    fn parse_statement_try(&mut self) -> Result<Stmt, ParseError> {
+        self.advance(); // consume the try token
+
+        let body = self.parse_required_block("Expected '{' after try.")?;
+
+        if !self.match_token(vec![Token::Catch]) {
+            return Err(self.error("Expected 'catch' after try block."));
+        }
+
+        if !self.match_token(vec![Token::LeftParenthesis]) {
+            return Err(self.error("Expected '(' after catch."));
+        }
+
+        let name = match self.advance() {
+            Token::Identifier(s) => s.clone(),
+            _ => {
+                return Err(self.error("Expected identifier for the caught value."));
+            }
+        };
+
+        if !self.match_token(vec![Token::RightParenthesis]) {
+            return Err(self.error("Expected ')' after catch variable."));
+        }
+
+        let handler = self.parse_required_block("Expected '{' after catch.")?;
+
+        Ok(Stmt::Try(body, name, handler))
+    }
+
+    // Statements of a block that must follow, e.g. the bodies of try and catch
+    fn parse_required_block(&mut self, message: &str) -> Result<Vec<Stmt>, ParseError> {
+        if !self.check(&Token::LeftBrace) {
+            return Err(self.error(message.to_string()));
+        }
+
+        self.parse_block()
+    }
+
+    fn parse_statement_do_while(&mut self) -> Result<Stmt, ParseError> {
+        self.advance(); // consume the do token
+        let span = self.previous_span();
+
+        let body = Box::new(self.parse_statement()?);
+
+        if !self.match_token(vec![Token::While]) {
+            return Err(self.error("Expected 'while' after do body."));
+        }
+
+        if !self.match_token(vec![Token::LeftParenthesis]) {
+            return Err(self.error("Expected '(' after while."));
+        }
+
+        let condition = Box::new(self.parse_expression()?);
+
+        if !self.match_token(vec![Token::RightParenthesis]) {
+            return Err(self.error("Expected ')' after while condition."));
+        }
+
+        if !self.match_token(vec![Token::Semicolon]) {
+            return Err(self.error("Expected ';' after do-while condition."));
+        }
+
+        Ok(Stmt::DoWhile(body, condition, span))
+    }
+
+    fn parse_statement_for_in(&mut self) -> Result<Stmt, ParseError> {
+        self.advance(); // consume the for token
+
+        if !self.match_token(vec![Token::LeftParenthesis]) {
+            return Err(self.error("Expected '(' after for."));
+        }
+
+        let name = match self.advance() {
+            Token::Identifier(s) => s.clone(),
+            _ => {
+                return Err(self.error("Expected identifier for the loop variable."));
+            }
+        };
+
+        if !self.match_token(vec![Token::In]) {
+            return Err(self.error("Expected 'in' after loop variable."));
+        }
+
+        let collection = Box::new(self.parse_expression()?);
+
+        if !self.match_token(vec![Token::RightParenthesis]) {
+            return Err(self.error("Expected ')' after for collection."));
+        }
+
+        let body = Box::new(self.parse_statement()?);
+
+        Ok(Stmt::ForIn(name, collection, body))
+    }
+
+    fn parse_statement_switch(&mut self) -> Result<Stmt, ParseError> {
+        self.advance(); // consume the switch token
+
+        if !self.match_token(vec![Token::LeftParenthesis]) {
+            return Err(self.error("Expected '(' after switch."));
+        }
+
+        let subject = Box::new(self.parse_expression()?);
+
+        if !self.match_token(vec![Token::RightParenthesis]) {
+            return Err(self.error("Expected ')' after switch value."));
+        }
+
+        if !self.match_token(vec![Token::LeftBrace]) {
+            return Err(self.error("Expected '{' before switch cases."));
+        }
+
+        let mut cases = Vec::new();
+        let mut default = None;
+
+        while !self.is_at_end() && !self.check(&Token::RightBrace) {
+            if default.is_some() {
+                return Err(
+                    self.error("Expected '}' after the default case, it must be the last case.")
+                );
+            }
+
+            if self.match_token(vec![Token::Case]) {
+                let span = self.previous_span();
+                let value = Box::new(self.parse_expression()?);
+
+                if !self.match_token(vec![Token::Colon]) {
+                    return Err(self.error("Expected ':' after case value."));
+                }
+
+                cases.push((value, self.parse_switch_case_body()?, span));
+            } else if self.match_token(vec![Token::Default]) {
+                if !self.match_token(vec![Token::Colon]) {
+                    return Err(self.error("Expected ':' after default."));
+                }
+
+                default = Some(self.parse_switch_case_body()?);
+            } else {
+                return Err(self.error("Expected 'case' or 'default' in switch."));
+            }
+        }
+
+        if !self.match_token(vec![Token::RightBrace]) {
+            return Err(self.error("Expected '}' after switch cases."));
+        }
+
+        Ok(Stmt::Switch(subject, cases, default))
+    }
+
+    // Statements of a case, up to the next case or the end of the switch
+    fn parse_switch_case_body(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        let mut statements = Vec::new();
+
+        while !self.is_at_end()
+            && !self.check(&Token::Case)
+            && !self.check(&Token::Default)
+            && !self.check(&Token::RightBrace)
+        {
+            statements.push(self.parse_statement()?);
+        }
+
+        Ok(statements)
     }
 
     fn parse_statement_function_declaration(&mut self) -> Result<Stmt, ParseError> {
@@ -175,39 +553,41 @@ impl Parser {
         let name = match self.advance() {
             Token::Identifier(s) => s.clone(),
             _ => {
-                return Err(ParseError {
-                    message: "Expected identifier after fun.".to_string(),
-                });
+                return Err(self.error("Expected identifier after fun."));
             }
         };
 
         if !self.match_token(vec![Token::LeftParenthesis]) {
-            return Err(ParseError {
-                message: "Expected '(' after function name.".to_string(),
-            });
+            return Err(self.error("Expected '(' after function name."));
         }
 
         let mut arguments = Vec::new();
 
         while !self.is_at_end() && !self.check(&Token::RightParenthesis) {
             match self.advance() {
+                Token::Identifier(s) if arguments.contains(s) => {
+                    let message = format!("Duplicate parameter '{s}' in function '{name}'.");
+                    return Err(self.error(message));
+                }
                 Token::Identifier(s) => arguments.push(s.clone()),
                 _ => {
-                    return Err(ParseError {
-                        message: "Expected identifier in function arguments.".to_string(),
-                    });
+                    return Err(self.error("Expected identifier in function arguments."));
                 }
             }
 
+            if arguments.len() > self.limits.max_function_parameters {
+                return Err(self.limit_error(LimitExceeded::FunctionParameters(
+                    self.limits.max_function_parameters,
+                )));
+            }
+
             if !self.match_token(vec![Token::Comma]) {
                 break;
             }
         }
 
         if !self.match_token(vec![Token::RightParenthesis]) {
-            return Err(ParseError {
-                message: "Expected ')' after function arguments.".to_string(),
-            });
+            return Err(self.error("Expected ')' after function arguments."));
         }
 
         let body = Box::new(self.parse_statement()?);
@@ -221,23 +601,60 @@ impl Parser {
         ))
     }
 
+    fn parse_statement_import(&mut self) -> Result<Stmt, ParseError> {
+        self.advance(); // consume the import token
+
+        let path = match self.advance() {
+            Token::StringLiteral(s) => s.clone(),
+            _ => {
+                return Err(self.error("Expected module path string after import."));
+            }
+        };
+
+        // optional alias binding the module to a single variable, `as` is not a reserved word
+        let alias = match self.peek() {
+            Token::Identifier(word) if word == "as" => {
+                self.advance();
+                match self.advance() {
+                    Token::Identifier(name) => Some(name.clone()),
+                    _ => {
+                        return Err(self.error("Expected module name after 'as'."));
+                    }
+                }
+            }
+            _ => None,
+        };
+
+        if !self.match_token(vec![Token::Semicolon]) {
+            return Err(self.error("Expected ';' after import."));
+        }
+
+        Ok(Stmt::Import(path, alias))
+    }
+
     ///////////////////////////////////////////////////////////////////////////
     // Expression parsing
     fn parse_expression(&mut self) -> Result<Expr, ParseError> {
-        self.parse_expression_assignment()
+        self.enter_nesting()?;
+        let expr = self.parse_expression_assignment();
+        self.depth -= 1;
+        expr
     }
 
     fn parse_expression_assignment(&mut self) -> Result<Expr, ParseError> {
         let expr = self.parse_expression_or()?;
 
         if self.match_token(vec![Token::Equal]) {
-            let value = self.parse_expression_or()?;
+            let span = self.previous_span();
+            self.count_node()?;
+            // assignment is right-associative, `a = b = 1` assigns 1 to b and then to a
+            let value = self.parse_expression()?;
 
             match expr {
-                Expr::Identifier(s) => Ok(Expr::Assign(s, Box::new(value))),
-                _ => Err(ParseError {
-                    message: "Invalid assignment target.".to_string(),
-                }),
+                Expr::Identifier(s) => Ok(Expr::Assign(s, Box::new(value), span)),
+                Expr::Index(list, index) => Ok(Expr::IndexAssign(list, index, Box::new(value))),
+                Expr::Get(object, name) => Ok(Expr::Set(object, name, Box::new(value))),
+                _ => Err(self.error("Invalid assignment target.")),
             }
         } else {
             Ok(expr)
@@ -248,15 +665,17 @@ impl Parser {
         let mut left_expr = self.parse_expression_and()?;
 
         while self.match_token(vec![Token::Or]) {
-            let operator = self.previous().clone();
+            let operator = self.previous()?.clone();
+            let span = self.previous_span();
+            self.count_node()?;
             let right_expr = self.parse_expression_and()?;
 
             left_expr = match operator {
-                Token::Or => Expr::BinaryOr(Box::new(left_expr), Box::new(right_expr)),
+                Token::Or => Expr::BinaryOr(Box::new(left_expr), Box::new(right_expr), span),
                 _ => {
-                    return Err(ParseError {
-                        message: format!("Unexpected token while parsing or: {:?}", operator),
-                    });
+                    return Err(
+                        self.error(format!("Unexpected token while parsing or: {:?}", operator))
+                    );
                 }
             };
         }
@@ -268,15 +687,18 @@ impl Parser {
         let mut left_expr = self.parse_expression_equality()?;
 
         while self.match_token(vec![Token::And]) {
-            let operator = self.previous().clone();
+            let operator = self.previous()?.clone();
+            let span = self.previous_span();
+            self.count_node()?;
             let right_expr = self.parse_expression_equality()?;
 
             left_expr = match operator {
-                Token::And => Expr::BinaryAnd(Box::new(left_expr), Box::new(right_expr)),
+                Token::And => Expr::BinaryAnd(Box::new(left_expr), Box::new(right_expr), span),
                 _ => {
-                    return Err(ParseError {
-                        message: format!("Unexpected token while parsing and: {:?}", operator),
-                    });
+                    return Err(self.error(format!(
+                        "Unexpected token while parsing and: {:?}",
+                        operator
+                    )));
                 }
             };
         }
@@ -288,16 +710,23 @@ impl Parser {
         let mut left_expr = self.parse_expression_comparison()?;
 
         while self.match_token(vec![Token::EqualEqual, Token::BangEqual]) {
-            let operator = self.previous().clone();
+            let operator = self.previous()?.clone();
+            let span = self.previous_span();
+            self.count_node()?;
             let right_expr = self.parse_expression_comparison()?;
 
             left_expr = match operator {
-                Token::EqualEqual => Expr::BinaryEqual(Box::new(left_expr), Box::new(right_expr)),
-                Token::BangEqual => Expr::BinaryNotEqual(Box::new(left_expr), Box::new(right_expr)),
+                Token::EqualEqual => {
+                    Expr::BinaryEqual(Box::new(left_expr), Box::new(right_expr), span)
+                }
+                Token::BangEqual => {
+                    Expr::BinaryNotEqual(Box::new(left_expr), Box::new(right_expr), span)
+                }
                 _ => {
-                    return Err(ParseError {
-                        message: format!("Unexpected token while parsing equality: {:?}", operator),
-                    });
+                    return Err(self.error(format!(
+                        "Unexpected token while parsing equality: {:?}",
+                        operator
+                    )));
                 }
             };
         }
@@ -306,7 +735,7 @@ impl Parser {
     }
 
     fn parse_expression_comparison(&mut self) -> Result<Expr, ParseError> {
-        let mut left_expr = self.parse_expression_add_sub()?;
+        let mut left_expr = self.parse_expression_range()?;
 
         while self.match_token(vec![
             Token::Less,
@@ -314,25 +743,27 @@ impl Parser {
             Token::Greater,
             Token::GreaterEqual,
         ]) {
-            let operator = self.previous().clone();
-            let right_expr = self.parse_expression_add_sub()?;
+            let operator = self.previous()?.clone();
+            let span = self.previous_span();
+            self.count_node()?;
+            let right_expr = self.parse_expression_range()?;
 
             left_expr = match operator {
-                Token::Less => Expr::BinaryLess(Box::new(left_expr), Box::new(right_expr)),
+                Token::Less => Expr::BinaryLess(Box::new(left_expr), Box::new(right_expr), span),
                 Token::LessEqual => {
-                    Expr::BinaryLessEqual(Box::new(left_expr), Box::new(right_expr))
+                    Expr::BinaryLessEqual(Box::new(left_expr), Box::new(right_expr), span)
+                }
+                Token::Greater => {
+                    Expr::BinaryGreater(Box::new(left_expr), Box::new(right_expr), span)
                 }
-                Token::Greater => Expr::BinaryGreater(Box::new(left_expr), Box::new(right_expr)),
                 Token::GreaterEqual => {
-                    Expr::BinaryGreaterEqual(Box::new(left_expr), Box::new(right_expr))
+                    Expr::BinaryGreaterEqual(Box::new(left_expr), Box::new(right_expr), span)
                 }
                 _ => {
-                    return Err(ParseError {
-                        message: format!(
-                            "Unexpected token while parsing comparison: {:?}",
-                            operator
-                        ),
-                    });
+                    return Err(self.error(format!(
+                        "Unexpected token while parsing comparison: {:?}",
+                        operator
+                    )));
                 }
             };
         }
@@ -340,20 +771,40 @@ impl Parser {
         Ok(left_expr)
     }
 
+    // Ranges do not chain, `a..b..c` is an error
+    fn parse_expression_range(&mut self) -> Result<Expr, ParseError> {
+        let start = self.parse_expression_add_sub()?;
+
+        if !self.match_token(vec![Token::DotDot, Token::DotDotEqual]) {
+            return Ok(start);
+        }
+
+        let inclusive = *self.previous()? == Token::DotDotEqual;
+        let span = self.previous_span();
+        self.count_node()?;
+        self.require_extension("Ranges")?;
+        let end = self.parse_expression_add_sub()?;
+
+        Ok(Expr::Range(Box::new(start), Box::new(end), inclusive, span))
+    }
+
     fn parse_expression_add_sub(&mut self) -> Result<Expr, ParseError> {
         let mut left_expr = self.parse_expression_mul_div()?;
 
         while self.match_token(vec![Token::Plus, Token::Minus]) {
-            let operator = self.previous().clone();
+            let operator = self.previous()?.clone();
+            let span = self.previous_span();
+            self.count_node()?;
             let right_expr = self.parse_expression_mul_div()?;
 
             left_expr = match operator {
-                Token::Plus => Expr::BinaryAdd(Box::new(left_expr), Box::new(right_expr)),
-                Token::Minus => Expr::BinarySub(Box::new(left_expr), Box::new(right_expr)),
+                Token::Plus => Expr::BinaryAdd(Box::new(left_expr), Box::new(right_expr), span),
+                Token::Minus => Expr::BinarySub(Box::new(left_expr), Box::new(right_expr), span),
                 _ => {
-                    return Err(ParseError {
-                        message: format!("Unexpected token while parsing add/sub: {:?}", operator),
-                    });
+                    return Err(self.error(format!(
+                        "Unexpected token while parsing add/sub: {:?}",
+                        operator
+                    )));
                 }
             };
         }
@@ -365,16 +816,19 @@ impl Parser {
         let mut left_expr = self.parse_expression_unary()?;
 
         while self.match_token(vec![Token::Star, Token::Slash]) {
-            let operator = self.previous().clone();
+            let operator = self.previous()?.clone();
+            let span = self.previous_span();
+            self.count_node()?;
             let right_expr = self.parse_expression_unary()?;
 
             left_expr = match operator {
-                Token::Star => Expr::BinaryMul(Box::new(left_expr), Box::new(right_expr)),
-                Token::Slash => Expr::BinaryDiv(Box::new(left_expr), Box::new(right_expr)),
+                Token::Star => Expr::BinaryMul(Box::new(left_expr), Box::new(right_expr), span),
+                Token::Slash => Expr::BinaryDiv(Box::new(left_expr), Box::new(right_expr), span),
                 _ => {
-                    return Err(ParseError {
-                        message: format!("Unexpected token while parsing mul/div: {:?}", operator),
-                    });
+                    return Err(self.error(format!(
+                        "Unexpected token while parsing mul/div: {:?}",
+                        operator
+                    )));
                 }
             };
         }
@@ -383,31 +837,64 @@ impl Parser {
     }
 
     fn parse_expression_unary(&mut self) -> Result<Expr, ParseError> {
+        // at the end there is no token left for the operand, advancing would read the previous
+        // token again
+        if self.is_at_end() {
+            return Err(self.error("Unexpected end of input."));
+        }
+
         self.advance(); // FIXME: check if here I need to advance
 
-        match self.previous() {
+        match self.previous()? {
             Token::Bang => {
-                let expr = self.parse_expression_unary()?;
-                Ok(Expr::UnaryBang(Box::new(expr)))
+                self.count_node()?;
+                self.enter_nesting()?;
+                let expr = self.parse_expression_unary();
+                self.depth -= 1;
+                Ok(Expr::UnaryBang(Box::new(expr?)))
             }
             Token::Minus => {
-                let expr = self.parse_expression_unary()?;
-                Ok(Expr::UnaryMinus(Box::new(expr)))
+                self.count_node()?;
+                self.enter_nesting()?;
+                let expr = self.parse_expression_unary();
+                self.depth -= 1;
+                Ok(Expr::UnaryMinus(Box::new(expr?)))
             }
             _ => self.parse_expression_call(),
         }
     }
 
     fn parse_expression_call(&mut self) -> Result<Expr, ParseError> {
-        let callee = self.parse_expression_primary()?;
-
-        if !self.match_token(vec![Token::LeftParenthesis]) {
-            return Ok(callee);
+        let mut expr = self.parse_expression_primary()?;
+
+        // calls, index and property accesses can be chained, e.g. get_list()[0]
+        while self.match_token(vec![Token::LeftParenthesis, Token::LeftBracket, Token::Dot]) {
+            self.count_node()?;
+            expr = match self.previous()? {
+                Token::LeftParenthesis => self.parse_expression_call_arguments(expr)?,
+                Token::Dot => match self.advance() {
+                    Token::Identifier(name) => Expr::Get(Box::new(expr), name.clone()),
+                    _ => {
+                        return Err(self.error("Expected property name after '.'."));
+                    }
+                },
+                _ => {
+                    self.require_extension("Lists")?;
+                    self.parse_expression_index(expr)?
+                }
+            };
         }
 
+        Ok(expr)
+    }
+
+    fn parse_expression_call_arguments(&mut self, callee: Expr) -> Result<Expr, ParseError> {
+        // the left parenthesis has already been consumed
+        let span = self.previous_span();
+
         // match for empty argument list
         if self.match_token(vec![Token::RightParenthesis]) {
-            return Ok(Expr::Call(Box::new(callee), Vec::new()));
+            return Ok(Expr::Call(Box::new(callee), Vec::new(), span));
         }
 
         let mut arguments = Vec::new();
@@ -415,35 +902,56 @@ impl Parser {
         loop {
             arguments.push(self.parse_expression()?);
 
+            if arguments.len() > self.limits.max_function_parameters {
+                return Err(self.limit_error(LimitExceeded::FunctionParameters(
+                    self.limits.max_function_parameters,
+                )));
+            }
+
             if !self.match_token(vec![Token::Comma]) {
                 break;
             }
         }
 
         if !self.match_token(vec![Token::RightParenthesis]) {
-            return Err(ParseError {
-                message: "Expected ')' for closing function call.".to_string(),
-            });
+            return Err(self.error("Expected ')' for closing function call."));
+        }
+
+        Ok(Expr::Call(Box::new(callee), arguments, span))
+    }
+
+    fn parse_expression_index(&mut self, list: Expr) -> Result<Expr, ParseError> {
+        // the left bracket has already been consumed
+
+        let index = self.parse_expression()?;
+
+        if !self.match_token(vec![Token::RightBracket]) {
+            return Err(self.error("Expected ']' after index."));
         }
 
-        Ok(Expr::Call(Box::new(callee), arguments))
+        Ok(Expr::Index(Box::new(list), Box::new(index)))
     }
 
     fn parse_expression_primary(&mut self) -> Result<Expr, ParseError> {
-        match self.previous() {
+        self.count_node()?;
+
+        match self.previous()? {
             Token::NumberLiteral(n) => Ok(Expr::LiteralNumber(*n)),
             Token::StringLiteral(s) => Ok(Expr::LiteralString(s.clone())),
+            Token::RawStringLiteral(s) => {
+                self.require_extension("Raw strings")?;
+                Ok(Expr::LiteralString(s.clone()))
+            }
             Token::Identifier(s) => Ok(Expr::Identifier(s.clone())),
             Token::False => Ok(Expr::False),
             Token::True => Ok(Expr::True),
             Token::Nil => Ok(Expr::Nil),
             Token::LeftParenthesis => self.parse_expression_parenthesis(),
-            _ => Err(ParseError {
-                message: format!(
-                    "Unexpected token while parsing primary: {:?}",
-                    self.previous()
-                ),
-            }),
+            Token::LeftBracket => {
+                self.require_extension("Lists")?;
+                self.parse_expression_list()
+            }
+            other => Err(self.error(format!("Unexpected token while parsing primary: {other:?}"))),
         }
     }
 
@@ -453,240 +961,165 @@ impl Parser {
         let expr = self.parse_expression()?;
 
         if !self.match_token(vec![Token::RightParenthesis]) {
-            return Err(ParseError {
-                message: "Expected ')' after expression.".to_string(),
-            });
+            return Err(self.error("Expected ')' after expression."));
         }
 
         Ok(expr)
     }
 
-    ///////////////////////////////////////////////////////////////////////////
-    // Auxiliary methods
-    fn is_at_end(&self) -> bool {
-        self.current >= self.tokens.len() || self.peek() == &Token::Eof
-    }
+    fn parse_expression_list(&mut self) -> Result<Expr, ParseError> {
+        // the left bracket has already been consumed
 
-    fn peek(&self) -> &Token {
-        &self.tokens[self.current]
-    }
-
-    fn advance(&mut self) -> &Token {
-        if !self.is_at_end() {
-            self.current += 1;
+        // match for empty list
+        if self.match_token(vec![Token::RightBracket]) {
+            return Ok(Expr::List(Vec::new()));
         }
-        &self.tokens[self.current - 1]
-    }
 
-    fn previous(&self) -> &Token {
-        &self.tokens[self.current - 1]
-    }
+        let mut elements = Vec::new();
 
-    fn check(&self, token: &Token) -> bool {
-        if self.is_at_end() {
-            return false;
-        }
-        self.peek() == token
-    }
+        loop {
+            elements.push(self.parse_expression()?);
 
-    fn match_token(&mut self, tokens: Vec<Token>) -> bool {
-        // I would need a variadic function here
-        if tokens.iter().any(|token| self.check(token)) {
-            self.advance();
-            return true;
+            if !self.match_token(vec![Token::Comma]) {
+                break;
+            }
         }
 
-        false
-    }
-}
-
-struct AstPrinter {}
-
-impl ExprVisitor<String> for AstPrinter {
-    fn visit_assign(&mut self, left: &String, right: &Box<Expr>) -> String {
-        format!("{{{} = {}}}", left, right.accept(self))
-    }
-
-    fn visit_binary_or(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> String {
-        format!("{{{} or {}}}", left.accept(self), right.accept(self))
-    }
-
-    fn visit_binary_and(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> String {
-        format!("{{{} and {}}}", left.accept(self), right.accept(self))
-    }
-
-    fn visit_binary_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> String {
-        format!("{{{} == {}}}", left.accept(self), right.accept(self))
-    }
-
-    fn visit_binary_not_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> String {
-        format!("{{{} != {}}}", left.accept(self), right.accept(self))
-    }
-
-    fn visit_binary_less(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> String {
-        format!("{{{} < {}}}", left.accept(self), right.accept(self))
-    }
-
-    fn visit_binary_less_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> String {
-        format!("{{{} <= {}}}", left.accept(self), right.accept(self))
-    }
-
-    fn visit_binary_greater(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> String {
-        format!("{{{} > {}}}", left.accept(self), right.accept(self))
-    }
-
-    fn visit_binary_greater_equal(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> String {
-        format!("{{{} >= {}}}", left.accept(self), right.accept(self))
-    }
-
-    fn visit_binary_add(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> String {
-        format!("{{{} + {}}}", left.accept(self), right.accept(self))
-    }
+        if !self.match_token(vec![Token::RightBracket]) {
+            return Err(self.error("Expected ']' after list elements."));
+        }
 
-    fn visit_binary_sub(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> String {
-        format!("{{{} - {}}}", left.accept(self), right.accept(self))
+        Ok(Expr::List(elements))
     }
 
-    fn visit_binary_mul(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> String {
-        format!("{{{} * {}}}", left.accept(self), right.accept(self))
-    }
+    ///////////////////////////////////////////////////////////////////////////
+    // Auxiliary methods
 
-    fn visit_binary_div(&mut self, left: &Box<Expr>, right: &Box<Expr>) -> String {
-        format!("{{{} / {}}}", left.accept(self), right.accept(self))
-    }
+    // Accounts for one more node of the syntax tree
+    fn count_node(&mut self) -> Result<(), ParseError> {
+        self.node_count += 1;
 
-    fn visit_unary_bang(&mut self, expr: &Box<Expr>) -> String {
-        format!("{{!{}}}", expr.accept(self))
-    }
+        if self.node_count > self.limits.max_ast_nodes {
+            return Err(self.limit_error(LimitExceeded::AstNodes(self.limits.max_ast_nodes)));
+        }
 
-    fn visit_unary_minus(&mut self, expr: &Box<Expr>) -> String {
-        format!("{{-{}}}", expr.accept(self))
+        Ok(())
     }
 
-    fn visit_call(&mut self, callee: &Box<Expr>, arguments: &Vec<Expr>) -> String {
-        let mut call_str = format!("{{call {}(", callee.accept(self));
-
-        for (i, arg) in arguments.iter().enumerate() {
-            call_str.push_str(&arg.accept(self));
-
-            if i < arguments.len() - 1 {
-                call_str.push_str(", ");
-            }
+    // Accounts for one more level of nesting, the caller must decrease the depth once done
+    fn enter_nesting(&mut self) -> Result<(), ParseError> {
+        if self.depth >= self.max_depth {
+            return Err(self.error(format!(
+                "Maximum nesting depth of {} exceeded.",
+                self.max_depth
+            )));
         }
 
-        call_str.push_str(")}");
-
-        call_str
-    }
-
-    fn visit_literal_string(&mut self, value: &String) -> String {
-        format!("\"{}\"", value)
+        self.depth += 1;
+        Ok(())
     }
 
-    fn visit_literal_number(&mut self, value: &f64) -> String {
-        value.to_string()
-    }
+    // Fails if the feature is an extension not available in the language version being parsed
+    fn require_extension(&self, feature: &str) -> Result<(), ParseError> {
+        if self.version == LanguageVersion::Lox1 {
+            return Err(self.error(format!("{feature} are not supported in {}.", self.version)));
+        }
 
-    fn visit_false(&mut self) -> String {
-        "false".to_string()
+        Ok(())
     }
 
-    fn visit_true(&mut self) -> String {
-        "true".to_string()
+    // Syntax error at the token being looked at
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        ParseError {
+            message: message.into().into_boxed_str(),
+            kind: ParseErrorKind::Syntax,
+            span: self.current_span(),
+        }
     }
 
-    fn visit_nil(&mut self) -> String {
-        "nil".to_string()
+    fn limit_error(&self, limit: LimitExceeded) -> ParseError {
+        ParseError {
+            message: limit.to_string().into_boxed_str(),
+            kind: ParseErrorKind::Limit(limit),
+            span: self.current_span(),
+        }
     }
 
-    fn visit_identifier(&mut self, value: &String) -> String {
-        value.clone()
-    }
-}
+    // Span of the token being looked at, at the end that of the last token before it, as EOF
+    // may be on a line of its own
+    fn current_span(&self) -> Span {
+        let index = match self.tokens.get(self.current) {
+            None | Some(Token::Eof) => self.current.saturating_sub(1),
+            Some(_) => self.current,
+        };
 
-impl StmtVisitor<String> for AstPrinter {
-    fn visit_print(&mut self, expr: &Box<Expr>) -> String {
-        format!("{{print {}}}", expr.accept(self))
+        self.spans
+            .get(index)
+            .or(self.spans.last())
+            .copied()
+            .unwrap_or_default()
     }
 
-    fn visit_expr(&mut self, expr: &Box<Expr>) -> String {
-        expr.accept(self)
+    fn is_at_end(&self) -> bool {
+        self.peek() == &Token::Eof
     }
 
-    fn visit_var_declaration(&mut self, name: &String, initializer: &Option<Box<Expr>>) -> String {
-        match initializer {
-            Some(expr) => format!("{{var {} = {}}}", name, expr.accept(self)),
-            None => format!("{{var {}}}", name),
+    // Past the last token the stream reads as EOF, for token streams not ending with it
+    fn peek(&self) -> &Token {
+        let token = self.tokens.get(self.current).unwrap_or(&self.eof);
+        if token == &Token::Eof {
+            self.end_reached.set(true);
         }
+        token
     }
 
-    fn visit_block(&mut self, stmts: &Vec<Stmt>) -> String {
-        let mut block = String::from("{");
-
-        for stmt in stmts {
-            block.push_str(&stmt.accept(self));
+    // Consumes the next token and returns it, at the end the last consumed token is returned
+    // again, EOF if there is none
+    fn advance(&mut self) -> &Token {
+        if !self.is_at_end() {
+            self.current += 1;
         }
-
-        block.push_str("}");
-
-        block
+        self.previous().unwrap_or(&self.eof)
     }
 
-    fn visit_if(
-        &mut self,
-        condition: &Box<Expr>,
-        then_branch: &Box<Stmt>,
-        else_branch: &Option<Box<Stmt>>,
-    ) -> String {
-        let mut if_stmt = format!(
-            "{{if {} then {} ",
-            condition.accept(self),
-            then_branch.accept(self)
-        );
-
-        if let Some(else_branch) = else_branch {
-            if_stmt.push_str(&format!(" else {}", else_branch.accept(self)));
-        }
-
-        if_stmt.push_str("}");
-
-        if_stmt
+    // Last consumed token, an error if no token was consumed because the input is empty
+    fn previous(&self) -> Result<&Token, ParseError> {
+        self.current
+            .checked_sub(1)
+            .and_then(|i| self.tokens.get(i))
+            .ok_or_else(|| self.error("Unexpected end of input."))
     }
 
-    fn visit_while(&mut self, condition: &Box<Expr>, body: &Box<Stmt>) -> String {
-        format!(
-            "{{while {} then {}}}",
-            condition.accept(self),
-            body.accept(self)
-        )
+    // Location of the previous token, unknown if the parser was created without spans
+    fn previous_span(&self) -> Span {
+        self.current
+            .checked_sub(1)
+            .and_then(|i| self.spans.get(i))
+            .copied()
+            .unwrap_or_default()
     }
 
-    fn visit_function_declaration(
-        &mut self,
-        name: &String,
-        arguments: &Vec<String>,
-        body: &Box<Stmt>,
-    ) -> String {
-        let mut function_decl = format!("{{fun {}(", name);
-
-        for (i, arg) in arguments.iter().enumerate() {
-            function_decl.push_str(arg);
-
-            if i < arguments.len() - 1 {
-                function_decl.push_str(", ");
-            }
+    fn check(&self, token: &Token) -> bool {
+        if self.is_at_end() {
+            return false;
         }
+        self.peek() == token
+    }
 
-        function_decl.push_str(") ");
-        function_decl.push_str(format!("{{ {} }}", body.accept(self)).as_str());
+    fn match_token(&mut self, tokens: Vec<Token>) -> bool {
+        // I would need a variadic function here
+        if tokens.iter().any(|token| self.check(token)) {
+            self.advance();
+            return true;
+        }
 
-        function_decl
+        false
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::lox::{scanner, Token};
+    use crate::lox::{scanner, AstPrinter, Token};
     use rstest::*;
 
     use super::*;
@@ -766,7 +1199,8 @@ mod tests {
             statements[0],
             Stmt::Expr(Box::new(Expr::BinaryAdd(
                 Box::new(Expr::LiteralNumber(1.0)),
-                Box::new(Expr::LiteralNumber(2.0))
+                Box::new(Expr::LiteralNumber(2.0)),
+                Span::default()
             )))
         );
 
@@ -802,14 +1236,309 @@ mod tests {
                 Box::new(Expr::LiteralNumber(1.0)),
                 Box::new(Expr::BinaryDiv(
                     Box::new(Expr::LiteralNumber(2.0)),
-                    Box::new(Expr::LiteralNumber(3.0))
-                ))
+                    Box::new(Expr::LiteralNumber(3.0)),
+                    Span::default()
+                )),
+                Span::default()
             )),)
         );
 
         Ok(())
     }
 
+    #[rstest]
+    #[case::parentheses("(", "1", ")")]
+    #[case::blocks("{", "", "}")]
+    #[case::unary("-", "1", "")]
+    #[case::lists("[", "1", "]")]
+    fn test_deep_nesting_error(
+        #[case] open: &str,
+        #[case] inner: &str,
+        #[case] close: &str,
+    ) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a source nested 100k levels deep
+        let depth = 100_000;
+        let source = format!("{}{}{};", open.repeat(depth), inner, close.repeat(depth));
+        let tokens = scanner::Scanner::new(source).scan_tokens()?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // When parsing the tokens
+        let mut parser = Parser::new(tokens);
+        let result = parser.parse();
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then parsing fails cleanly instead of overflowing the stack
+        match result {
+            Ok(_) => Err("Expected a nesting depth error".to_string()),
+            Err(e) => {
+                assert_eq!(
+                    e.to_string(),
+                    format!("Maximum nesting depth of {DEFAULT_MAX_NESTING_DEPTH} exceeded.")
+                );
+                Ok(())
+            }
+        }
+    }
+
+    #[rstest]
+    // 2 statements, 2 assignments, 2 additions and 6 primaries: 12 nodes
+    #[case::ast_nodes(
+        "a = 1 + 2; b = c + 3;",
+        Limits { max_ast_nodes: 11, ..Default::default() },
+        LimitExceeded::AstNodes(11)
+    )]
+    #[case::declaration_parameters(
+        "fun f(a, b, c) {}",
+        Limits { max_function_parameters: 2, ..Default::default() },
+        LimitExceeded::FunctionParameters(2)
+    )]
+    #[case::call_arguments(
+        "f(1, 2, 3);",
+        Limits { max_function_parameters: 2, ..Default::default() },
+        LimitExceeded::FunctionParameters(2)
+    )]
+    fn test_parse_limits(
+        #[case] source: String,
+        #[case] limits: Limits,
+        #[case] expected: LimitExceeded,
+    ) -> Result<(), String> {
+        let tokens = scanner::Scanner::new(source).scan_tokens()?;
+
+        let mut parser = Parser::new(tokens);
+        parser.set_limits(limits);
+
+        match parser.parse() {
+            Ok(_) => Err("Expected a limit error".to_string()),
+            Err(e) => {
+                assert_eq!(e.get_limit_exceeded(), Some(&expected));
+                assert_eq!(e.to_string(), expected.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_within_limits() -> Result<(), String> {
+        let tokens = scanner::Scanner::new("a = 1 + 2; b = c + 3;".to_string()).scan_tokens()?;
+
+        let mut parser = Parser::new(tokens);
+        parser.set_limits(Limits {
+            max_ast_nodes: 12,
+            ..Default::default()
+        });
+
+        assert_eq!(parser.parse().map_err(|e| e.to_string())?.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_configurable_nesting_depth() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an expression nested 10 levels deep
+        let source = format!("{}1{};", "(".repeat(10), ")".repeat(10));
+        let tokens = scanner::Scanner::new(source).scan_tokens()?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // When parsing with a limit below and above the nesting of the expression
+        let mut parser = Parser::new(tokens.clone());
+        parser.set_max_nesting_depth(5);
+        let limited = parser.parse();
+
+        let mut parser = Parser::new(tokens);
+        parser.set_max_nesting_depth(20);
+        let unlimited = parser.parse();
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then only the parser with the larger limit succeeds
+        assert!(limited.is_err());
+        assert!(unlimited.is_ok());
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::list("var a = [1, 2];", "Lists are not supported in Lox 1.")]
+    #[case::index("a[0];", "Lists are not supported in Lox 1.")]
+    #[case::import("import \"lib.lox\";", "Imports are not supported in Lox 1.")]
+    #[case::constant("const a = 1;", "Constants are not supported in Lox 1.")]
+    #[case::multiple_variables(
+        "var a, b;",
+        "Multiple variable declarations are not supported in Lox 1."
+    )]
+    #[case::switch(
+        "switch (a) { default: print a; }",
+        "Switch statements are not supported in Lox 1."
+    )]
+    #[case::do_while("do a = 1; while (a);", "Do-while loops are not supported in Lox 1.")]
+    #[case::for_in("for (a in b) print a;", "For-in loops are not supported in Lox 1.")]
+    #[case::range("print 1..2;", "Ranges are not supported in Lox 1.")]
+    #[case::raw_string("print \"\"\"a\"\"\";", "Raw strings are not supported in Lox 1.")]
+    #[case::throw("throw 1;", "Exceptions are not supported in Lox 1.")]
+    #[case::assert("assert true;", "Assert statements are not supported in Lox 1.")]
+    #[case::try_catch("try {} catch (e) {}", "Exceptions are not supported in Lox 1.")]
+    fn test_classic_version(#[case] source: String, #[case] expected: &str) -> Result<(), String> {
+        let tokens = scanner::Scanner::new(source).scan_tokens()?;
+
+        // the extended level accepts the source
+        let mut parser = Parser::new(tokens.clone());
+        parser.parse().map_err(|e| e.to_string())?;
+
+        let mut parser = Parser::new(tokens);
+        parser.set_language_version(LanguageVersion::Lox1);
+
+        match parser.parse() {
+            Ok(_) => Err("Expected a language version error".to_string()),
+            Err(e) => {
+                assert_eq!(e.to_string(), expected);
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_scan_and_parse_operators() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a source using every operator, including the Eof token emitted by the scanner
+        let source = "a = !b == c != d < e <= f > g >= h + i - j * k / -l or m and n;";
+        let tokens = scanner::Scanner::new(source.to_string()).scan_tokens()?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // When parsing the scanned tokens
+        let statements = Parser::new(tokens).parse().map_err(|e| e.to_string())?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the operators of the scanner are the ones matched by the parser
+        let mut printer = AstPrinter::new();
+        assert_eq!(statements.len(), 1);
+        assert_eq!(
+            statements[0].accept(&mut printer),
+            "{a = {{{{!b} == c} != {{{{d < e} <= f} > g} >= {{h + i} - {{j * k} / {-l}}}}} or {m and n}}}"
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::empty("", 0)]
+    #[case::comment("// nothing", 0)]
+    #[case::statements("var a = 1;\nprint a;", 2)]
+    fn test_parse_token_stream(
+        #[case] source: &str,
+        #[case] expected_count: usize,
+    ) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given the stream of spanned tokens of the scanner, ending with Eof
+        let scanner = scanner::Scanner::new(source.to_string());
+        let tokens = scanner.tokens().collect::<Result<Vec<_>, _>>()?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // When parsing the tokens as they are streamed
+        let statements = Parser::from_spanned_tokens(tokens)
+            .parse()
+            .map_err(|e| e.to_string())?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then Eof ends the input
+        assert_eq!(statements.len(), expected_count);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::missing_semicolon("print 1", true)]
+    #[case::missing_operand("1 +", true)]
+    #[case::open_block("{", true)]
+    #[case::open_loop_body("while (true) { print 1;", true)]
+    #[case::missing_name("var", true)]
+    #[case::unexpected_token("print 1 1;", false)]
+    #[case::bad_last_token("fun 1", false)]
+    fn test_parse_unexpected_eof(
+        #[case] source: &str,
+        #[case] unexpected_eof: bool,
+    ) -> Result<(), String> {
+        let tokens = scanner::Scanner::new(source.to_string()).scan_tokens()?;
+
+        // only errors at the end of the input could be fixed by reading more of it
+        let error = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(
+            error.is_unexpected_eof(),
+            unexpected_eof,
+            "{}",
+            error.message
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::at_token("print 1 +\n  ;", 2)]
+    #[case::missing_semicolon("var a = 1\nprint a;", 2)]
+    #[case::past_the_end("{\n  print 1;\n", 2)]
+    #[case::limit("fun f(\na, b, c) {}", 2)]
+    fn test_error_span(#[case] source: &str, #[case] line: u64) -> Result<(), String> {
+        let tokens = scanner::Scanner::new(source.to_string()).scan_spanned_tokens()?;
+
+        let mut parser = Parser::from_spanned_tokens(tokens);
+        parser.set_limits(Limits {
+            max_function_parameters: 2,
+            ..Default::default()
+        });
+
+        let error = parser.parse().unwrap_err();
+        assert_eq!(error.get_span(), Span { line }, "{}", error.message);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::repeated("fun f(a, a) {}", "Duplicate parameter 'a' in function 'f'.")]
+    #[case::not_adjacent("fun f(a, b, a) {}", "Duplicate parameter 'a' in function 'f'.")]
+    fn test_duplicate_parameters(
+        #[case] source: &str,
+        #[case] expected: &str,
+    ) -> Result<(), String> {
+        let tokens = scanner::Scanner::new(source.to_string()).scan_tokens()?;
+
+        let error = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(&*error.message, expected);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::missing_colon("switch (a) { case 1 print a; }", "Expected ':' after case value.")]
+    #[case::missing_default_colon("switch (a) { default print a; }", "Expected ':' after default.")]
+    #[case::statement_before_case(
+        "switch (a) { print a; }",
+        "Expected 'case' or 'default' in switch."
+    )]
+    #[case::case_after_default(
+        "switch (a) { default: print a; case 1: print 1; }",
+        "Expected '}' after the default case, it must be the last case."
+    )]
+    #[case::missing_brace("switch (a) case 1: print a;", "Expected '{' before switch cases.")]
+    #[case::unclosed("switch (a) { case 1: print a;", "Expected '}' after switch cases.")]
+    #[case::do_missing_while("do print a; (a);", "Expected 'while' after do body.")]
+    #[case::do_missing_semicolon("do print a; while (a)", "Expected ';' after do-while condition.")]
+    #[case::throw_missing_semicolon("throw 1", "Expected ';' after thrown value.")]
+    #[case::assert_missing_semicolon("assert a \"message\";", "Expected ';' after assertion.")]
+    #[case::try_missing_block("try print a; catch (e) {}", "Expected '{' after try.")]
+    #[case::try_missing_catch("try {} print a;", "Expected 'catch' after try block.")]
+    #[case::catch_missing_paren("try {} catch e {}", "Expected '(' after catch.")]
+    #[case::catch_missing_name("try {} catch (1) {}", "Expected identifier for the caught value.")]
+    #[case::catch_unclosed("try {} catch (e {}", "Expected ')' after catch variable.")]
+    #[case::catch_missing_block("try {} catch (e) print e;", "Expected '{' after catch.")]
+    fn test_switch_errors(#[case] source: &str, #[case] expected: &str) -> Result<(), String> {
+        let tokens = scanner::Scanner::new(source.to_string()).scan_tokens()?;
+
+        let error = Parser::new(tokens).parse().unwrap_err();
+        assert_eq!(&*error.message, expected);
+
+        Ok(())
+    }
+
     #[rstest]
     // #[case("nil;", "nil")]
     // #[case("\"my literal\";", "\"my literal\"")]
@@ -817,6 +1546,45 @@ mod tests {
     // #[case("(1.0 + 2.0) / 3.0;", "{{1 + 2} / 3}")]
     // #[case("var a = 2 + 2;", "{var a = {2 + 2}}")]
     #[case("say_hello();", "{call say_hello()}")]
+    #[case("[1, 2, 3];", "[1, 2, 3]")]
+    #[case("a[0];", "{a[0]}")]
+    #[case("a[0] = 1;", "{a[0] = 1}")]
+    #[case("a = b = 1;", "{a = {b = 1}}")]
+    #[case("a[0] = b = c;", "{a[0] = {b = c}}")]
+    #[case("a = b[0] = 1 + 2;", "{a = {b[0] = {1 + 2}}}")]
+    #[case("a[0][1];", "{{a[0]}[1]}")]
+    #[case("import \"lib/math.lox\";", "{import \"lib/math.lox\"}")]
+    #[case("const a = 1 + 2;", "{const a = {1 + 2}}")]
+    #[case("var a = 1, b, c = a;", "{var a = 1, b, c = a}")]
+    #[case(
+        "switch (a) { case 1: print a; a = 2; case \"b\": default: print nil; }",
+        "{switch a {case 1: {print a}{a = 2}} {case \"b\": } {default: {print nil}}}"
+    )]
+    #[case("switch (a + 1) {}", "{switch {a + 1}}")]
+    #[case(
+        "do { a = a + 1; } while (a < 3);",
+        "{do {{a = {a + 1}}} while {a < 3}}"
+    )]
+    #[case("throw a + 1;", "{throw {a + 1}}")]
+    #[case("for (x in [1, 2]) print x;", "{for x in [1, 2] {print x}}")]
+    #[case("a < 0..n + 1 == b;", "{{a < {0 .. {n + 1}}} == b}")]
+    #[case("print 1..=a[0];", "{print {1 ..= {a[0]}}}")]
+    #[case("for (c in s) { n = n + 1; }", "{for c in s {{n = {n + 1}}}}")]
+    #[case("assert a == 1;", "{assert {a == 1}}")]
+    #[case("assert a, \"a is \" + a;", "{assert a, {\"a is \" + a}}")]
+    #[case(
+        "try { f(); } catch (e) { print e; }",
+        "{try {{call f()}} catch e {{print e}}}"
+    )]
+    #[case(
+        "import \"lib/math.lox\" as math;",
+        "{import \"lib/math.lox\" as math}"
+    )]
+    #[case("math.sqrt(2);", "{call {math.sqrt}(2)}")]
+    #[case("a.b.c[0];", "{{{a.b}.c}[0]}")]
+    #[case("a.b.c().d = 5;", "{{call {{a.b}.c}()}.d = 5}")]
+    #[case("a.b = c[0] = 1;", "{a.b = {c[0] = 1}}")]
+    #[case("f()[0].b = 1;", "{{{call f()}[0]}.b = 1}")]
     fn test_ast_printer(
         #[case] source: String,
         #[case] expected_ast: String,
@@ -824,17 +1592,10 @@ mod tests {
         ///////////////////////////////////////////////////////////////////////
         // Given the tokens produced by the scanner
         let mut scanner = scanner::Scanner::new(source);
-        let tokens = scanner
-            .scan_tokens()?
-            .into_iter()
-            .filter(|t| t != &Token::Eof)
-            .collect();
-
-        println!("{:?}", tokens);
+        let tokens = scanner.scan_tokens()?;
 
         ///////////////////////////////////////////////////////////////////////
         // When parsing the tokens
-        // FIXME: parser does no support EOF token
         let mut parser = Parser::new(tokens);
         let statements = parser.parse().map_err(|e| e.to_string())?;
 
@@ -843,7 +1604,7 @@ mod tests {
         assert_eq!(statements.len(), 1);
 
         // and when printing the AST
-        let mut ast_printer = AstPrinter {};
+        let mut ast_printer = AstPrinter::new();
         let ast_string = statements[0].accept(&mut ast_printer);
 
         // the resulting string should be equal to the expected
@@ -851,4 +1612,87 @@ mod tests {
 
         Ok(())
     }
+
+    #[rstest]
+    #[case::empty(vec![], "Unexpected end of input.")]
+    #[case::only_eof(vec![Token::Eof], "Unexpected end of input.")]
+    #[case::unary_without_operand(vec![Token::Minus], "Unexpected end of input.")]
+    #[case::unclosed_call(
+        vec![Token::Identifier(Symbol::new("f")), Token::LeftParenthesis],
+        "Unexpected end of input."
+    )]
+    #[case::missing_operand(
+        vec![Token::NumberLiteral(1.0), Token::Plus, Token::Eof],
+        "Unexpected end of input."
+    )]
+    fn test_truncated_expression(#[case] tokens: Vec<Token>, #[case] expected: &str) {
+        let error = Parser::new(tokens).parse_single_expression().unwrap_err();
+
+        assert_eq!(error.to_string(), expected);
+    }
+
+    // Deterministic xorshift64 generator of token sequences, so failures are reproducible
+    struct TokenSequences {
+        state: u64,
+    }
+
+    impl TokenSequences {
+        fn below(&mut self, n: usize) -> usize {
+            self.state ^= self.state << 13;
+            self.state ^= self.state >> 7;
+            self.state ^= self.state << 17;
+            (self.state % n as u64) as usize
+        }
+
+        // Tokens in any order, EOF may be missing or appear before the end
+        fn sequence(&mut self, tokens: &[Token]) -> Vec<Token> {
+            let length = self.below(30);
+            (0..length)
+                .map(|_| tokens[self.below(tokens.len())].clone())
+                .collect()
+        }
+    }
+
+    #[test]
+    fn test_random_token_sequences() {
+        let mut tokens: Vec<Token> = Token::keywords().filter_map(Token::from_keyword).collect();
+        tokens.extend([
+            Token::LeftParenthesis,
+            Token::RightParenthesis,
+            Token::LeftBrace,
+            Token::RightBrace,
+            Token::LeftBracket,
+            Token::RightBracket,
+            Token::Comma,
+            Token::Dot,
+            Token::Colon,
+            Token::Semicolon,
+            Token::Minus,
+            Token::Plus,
+            Token::Bang,
+            Token::Equal,
+            Token::EqualEqual,
+            Token::Less,
+            Token::Identifier(Symbol::new("a")),
+            Token::NumberLiteral(1.0),
+            Token::StringLiteral("s".to_string()),
+            Token::Eof,
+        ]);
+
+        let mut sequences = TokenSequences {
+            state: 0x9e37_79b9_7f4a_7c15,
+        };
+
+        // the results do not matter, parsing any sequence must return instead of panicking
+        for _ in 0..5_000 {
+            let sequence = sequences.sequence(&tokens);
+
+            let _ = Parser::new(sequence.clone()).parse();
+            let _ = Parser::new(sequence.clone()).parse_single_expression();
+
+            let mut classic = Parser::new(sequence);
+            classic.set_language_version(LanguageVersion::Lox1);
+            let _ = classic.parse();
+        }
+    }
 }
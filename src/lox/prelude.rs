@@ -0,0 +1,286 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use super::{
+    new_value_box, stdlib, Environment, EnvironmentImpl, Interpreter, InterpreterOptions, Symbol,
+    Value,
+};
+
+// Natives of the standard library registered in a global environment, as selected by the
+// options of the interpreter
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NativeSet {
+    pub stdlib: bool,
+    pub allow_io: bool,
+    pub strict_conversions: bool,
+}
+
+impl NativeSet {
+    pub fn from_options(options: &InterpreterOptions) -> Self {
+        Self {
+            stdlib: options.stdlib,
+            allow_io: options.stdlib && options.allow_io,
+            strict_conversions: options.stdlib && options.strict_conversions,
+        }
+    }
+
+    pub fn register(&self, environment: &mut dyn Environment) {
+        if self.stdlib {
+            stdlib::register(environment);
+        }
+
+        if self.allow_io {
+            stdlib::io::register(environment);
+        }
+
+        if self.strict_conversions {
+            stdlib::types::register_strict_conversions(environment);
+        }
+    }
+}
+
+thread_local! {
+    // snapshots of the global environment of each set of natives, taken the first time an
+    // interpreter with that set is created in the thread
+    static STANDARD_PRELUDES: RefCell<HashMap<NativeSet, PreludeSnapshot>> =
+        RefCell::new(HashMap::new());
+}
+
+// Global environment holding the given natives. They are only registered the first time in each
+// thread, later environments are restored from a snapshot of the first one
+pub fn standard_environment(natives: NativeSet) -> Box<dyn Environment> {
+    STANDARD_PRELUDES.with(|preludes| {
+        let mut preludes = preludes.borrow_mut();
+
+        if let Some(environment) = preludes.get(&natives).and_then(|s| s.restore().ok()) {
+            return environment;
+        }
+
+        let mut environment = Box::new(EnvironmentImpl::new());
+        natives.register(environment.as_mut());
+
+        if let Ok(snapshot) = PreludeSnapshot::capture(environment.as_ref(), natives) {
+            preludes.insert(natives, snapshot);
+        }
+
+        environment
+    })
+}
+
+// Global environment captured after running a prelude. Restoring it gives a new interpreter
+// the prelude definitions without scanning, parsing and executing the prelude source again.
+#[derive(Debug, Clone)]
+pub struct PreludeSnapshot {
+    globals: HashMap<String, Value>,
+    // natives of the interpreter the snapshot was taken from, interpreters restored from it
+    // must be created with the same ones, see Interpreter::from_snapshot
+    natives: NativeSet,
+}
+
+impl PreludeSnapshot {
+    // Runs the prelude source in a fresh interpreter with the given options and captures the
+    // resulting globals
+    pub fn build(source: String, options: InterpreterOptions) -> Result<Self, String> {
+        let mut interpreter = Interpreter::with_options(options);
+        interpreter.execute(source)?;
+        interpreter.snapshot()
+    }
+
+    pub fn capture(environment: &dyn Environment, natives: NativeSet) -> Result<Self, String> {
+        let mut globals = HashMap::new();
+
+        for (name, value) in environment.get_global_variables() {
+            let value_guard = value
+                .try_borrow()
+                .map_err(|e| format!("Error reading global variable \"{name}\": {e}"))?;
+            globals.insert(name, deep_copy(&value_guard)?);
+        }
+
+        Ok(Self { globals, natives })
+    }
+
+    // Creates a new environment holding a copy of the captured globals. Every call returns
+    // an independent environment, changes made to it are not visible to other restores.
+    pub fn restore(&self) -> Result<Box<dyn Environment>, String> {
+        let mut environment = EnvironmentImpl::new();
+
+        for (name, value) in self.globals.iter() {
+            environment.define_variable(Symbol::new(name), deep_copy(value)?);
+        }
+
+        Ok(Box::new(environment))
+    }
+
+    pub fn get_global_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.globals.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn get_natives(&self) -> NativeSet {
+        self.natives
+    }
+}
+
+// Copies a value, including the boxes of list elements, so that the copy shares no storage
+// with the original
+fn deep_copy(value: &Value) -> Result<Value, String> {
+    match value {
+        Value::List(elements) => {
+            let mut copied = Vec::with_capacity(elements.len());
+            for element in elements {
+                let element_guard = element.try_borrow().map_err(|e| e.to_string())?;
+                copied.push(new_value_box(deep_copy(&element_guard)?));
+            }
+            Ok(Value::List(copied))
+        }
+        Value::Map(entries) => {
+            let mut copied = std::collections::BTreeMap::new();
+            for (key, entry) in entries {
+                let entry_guard = entry.try_borrow().map_err(|e| e.to_string())?;
+                copied.insert(key.clone(), new_value_box(deep_copy(&entry_guard)?));
+            }
+            Ok(Value::Map(copied))
+        }
+        other => Ok(other.to_owned()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::PreludeSnapshot;
+    use crate::lox::{Interpreter, InterpreterOptions, LimitExceeded, LoxError, Value};
+
+    #[test]
+    fn test_restore_globals() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a snapshot built from a prelude defining variables and a function
+        let snapshot = PreludeSnapshot::build(
+            "var answer = 42; var items = [1, 2]; fun show(x) { print x; }".to_string(),
+            InterpreterOptions::default(),
+        )?;
+
+        let names = snapshot.get_global_names();
+        for name in ["answer", "items", "show"] {
+            assert!(names.contains(&name.to_string()), "missing global {name}");
+        }
+
+        ///////////////////////////////////////////////////////////////////////
+        // When an interpreter is restored from the snapshot
+        let mut interpreter = Interpreter::from_snapshot(&snapshot, InterpreterOptions::default())?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the prelude definitions are available
+        let result = interpreter.execute("answer + items[1];".to_string())?;
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(*result_guard, Value::Number(44.0));
+
+        interpreter.execute("show(answer);".to_string())?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restored_interpreters_are_independent() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given two interpreters restored from the same snapshot
+        let snapshot = PreludeSnapshot::build(
+            "var items = [1, 2];".to_string(),
+            InterpreterOptions::default(),
+        )?;
+        let mut first = Interpreter::from_snapshot(&snapshot, InterpreterOptions::default())?;
+        let mut second = Interpreter::from_snapshot(&snapshot, InterpreterOptions::default())?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // When the first interpreter modifies a prelude list
+        first.execute("items[0] = 10;".to_string())?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the second interpreter still sees the original value
+        let result = second.execute("items[0];".to_string())?;
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(*result_guard, Value::Number(1.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_keeps_options() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a snapshot and options bounding the steps of an execution
+        let snapshot =
+            PreludeSnapshot::build("var n = 0;".to_string(), InterpreterOptions::default())?;
+        let options = InterpreterOptions {
+            max_steps: 100,
+            ..Default::default()
+        };
+
+        ///////////////////////////////////////////////////////////////////////
+        // When an interpreter restored with the options runs an endless loop
+        let mut interpreter = Interpreter::from_snapshot(&snapshot, options)?;
+        let result = interpreter
+            .execute("while (true) { n = n + 1; }".to_string())
+            .map(|_| ());
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the budget of the options stops it
+        assert_eq!(
+            result,
+            Err(LoxError::BudgetExceeded(LimitExceeded::Steps(100)))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_with_other_natives() -> Result<(), String> {
+        let snapshot =
+            PreludeSnapshot::build("var n = 0;".to_string(), InterpreterOptions::default())?;
+
+        let result = Interpreter::from_snapshot(
+            &snapshot,
+            InterpreterOptions {
+                allow_io: false,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            result.err(),
+            Some(
+                "Prelude snapshot was taken with other natives than the ones of the options"
+                    .to_string()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_standard_environments_are_independent() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter replacing a native, created after another one so its natives
+        // are restored from the snapshot of the thread
+        let mut first = Interpreter::new();
+        let mut second = Interpreter::new();
+        second.execute("var sqrt = 0;".to_string())?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // When calling the native in a third interpreter and in the first one
+        let mut third = Interpreter::new();
+        let results = [
+            first.execute("sqrt(4);".to_string())?,
+            third.execute("sqrt(4);".to_string())?,
+        ];
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then both still call the native
+        for result in results {
+            let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+            assert_eq!(*result_guard, Value::Number(2.0));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::BTreeSet;
+use std::time::Instant;
+
+use super::{AstPrinter, AstPrinterOptions, AstStyle, InterpreterOptions, Session, Token};
+
+// Commands of the interactive session, written as lines starting with ':' instead of Lox code
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplCommand {
+    Help,
+    // global variables and functions declared in the session
+    Env,
+    // syntax tree or tokens of the last Lox input
+    Ast(AstStyle),
+    Tokens,
+    // toggles reporting how long each input takes to run
+    Time,
+    // drops everything declared in the session
+    Reset,
+}
+
+const HELP: &str = "\
+:help          show this help
+:env           list the globals declared in the session
+:ast [tree]    print the syntax tree of the last input
+:tokens        print the tokens of the last input
+:time          toggle timing the inputs
+:reset         start over with a new session";
+
+impl ReplCommand {
+    // Command of the line, None if the line is Lox code
+    pub fn parse(line: &str) -> Option<Result<Self, String>> {
+        let line = line.trim().strip_prefix(':')?;
+
+        let command = match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["help"] => Ok(ReplCommand::Help),
+            ["env"] => Ok(ReplCommand::Env),
+            ["ast"] => Ok(ReplCommand::Ast(AstStyle::SExpression)),
+            ["ast", "tree"] => Ok(ReplCommand::Ast(AstStyle::Tree)),
+            ["tokens"] => Ok(ReplCommand::Tokens),
+            ["time"] => Ok(ReplCommand::Time),
+            ["reset"] => Ok(ReplCommand::Reset),
+            _ => Err(format!("Unknown command ':{line}', see :help")),
+        };
+
+        Some(command)
+    }
+}
+
+// Interactive session: Lox inputs run in a session kept between them, and commands inspect it
+pub struct Repl {
+    session: Session,
+    options: InterpreterOptions,
+    // globals defined before any input, e.g. the natives, left out of :env
+    builtins: BTreeSet<String>,
+    last_input: Option<String>,
+    timing: bool,
+}
+
+impl Repl {
+    pub fn new(options: InterpreterOptions) -> Self {
+        let session = Session::with_options(options.clone());
+        let builtins = session
+            .get_interpreter()
+            .globals()
+            .map(|(name, _)| name)
+            .collect();
+
+        Self {
+            session,
+            options,
+            builtins,
+            last_input: None,
+            timing: false,
+        }
+    }
+
+    pub fn get_session(&self) -> &Session {
+        &self.session
+    }
+
+    pub fn get_session_mut(&mut self) -> &mut Session {
+        &mut self.session
+    }
+
+    // Whether the input can be run as is: commands always, Lox code once its statements are
+    // complete, see lox::is_incomplete
+    pub fn is_complete(&self, input: &str) -> bool {
+        ReplCommand::parse(input).is_some() || !super::is_incomplete(input)
+    }
+
+    // Runs a command or Lox code, the result is the text to show besides what the code prints.
+    // Failing code leaves the session as it was
+    pub fn eval(&mut self, input: &str) -> Result<String, String> {
+        match ReplCommand::parse(input) {
+            Some(command) => self.run_command(command?),
+            None => self.run_code(input),
+        }
+    }
+
+    fn run_code(&mut self, input: &str) -> Result<String, String> {
+        self.last_input = Some(input.to_string());
+
+        let start = Instant::now();
+        let result = self.session.eval_transactional(input);
+        let elapsed = start.elapsed();
+
+        let timing = match self.timing {
+            true => format!("time: {elapsed:?}"),
+            false => String::new(),
+        };
+
+        result.map(|_| timing).map_err(String::from)
+    }
+
+    fn run_command(&mut self, command: ReplCommand) -> Result<String, String> {
+        match command {
+            ReplCommand::Help => Ok(HELP.to_string()),
+            ReplCommand::Env => Ok(self.env()),
+            ReplCommand::Ast(style) => self.ast(style),
+            ReplCommand::Tokens => self.tokens(),
+            ReplCommand::Time => {
+                self.timing = !self.timing;
+                Ok(format!("timing {}", if self.timing { "on" } else { "off" }))
+            }
+            ReplCommand::Reset => {
+                *self = Self {
+                    timing: self.timing,
+                    ..Self::new(self.options.clone())
+                };
+                Ok("session reset".to_string())
+            }
+        }
+    }
+
+    fn env(&self) -> String {
+        self.session
+            .get_interpreter()
+            .globals()
+            .filter(|(name, _)| !self.builtins.contains(name))
+            .map(|(name, value)| format!("{name} = {value}"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn last_input(&self) -> Result<&str, String> {
+        self.last_input
+            .as_deref()
+            .ok_or("No input to inspect yet".to_string())
+    }
+
+    fn ast(&self, style: AstStyle) -> Result<String, String> {
+        let program = super::parse(self.last_input()?)?;
+
+        let mut printer = AstPrinter::with_options(AstPrinterOptions {
+            style,
+            spans: false,
+        });
+        Ok(printer.print(&program.statements).trim_end().to_string())
+    }
+
+    fn tokens(&self) -> Result<String, String> {
+        let tokens = super::tokenize(self.last_input()?)?;
+
+        let lines: Vec<String> = tokens
+            .iter()
+            .filter(|t| t.token != Token::Eof)
+            .map(|t| format!("{}: {}", t.span, t.token))
+            .collect();
+        Ok(lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rstest::*;
+
+    use crate::lox::{AstStyle, CapturedOutput, InterpreterOptions};
+
+    use super::{Repl, ReplCommand};
+
+    fn repl() -> (Repl, CapturedOutput) {
+        let output = CapturedOutput::default();
+        let mut repl = Repl::new(InterpreterOptions::default());
+        repl.get_session_mut()
+            .get_interpreter_mut()
+            .set_output(Box::new(output.clone()));
+
+        (repl, output)
+    }
+
+    #[rstest]
+    #[case::help(":help", Some(Ok(ReplCommand::Help)))]
+    #[case::spaces("  :ast   tree ", Some(Ok(ReplCommand::Ast(AstStyle::Tree))))]
+    #[case::ast(":ast", Some(Ok(ReplCommand::Ast(AstStyle::SExpression))))]
+    #[case::unknown(":quit", Some(Err("Unknown command ':quit', see :help".to_string())))]
+    #[case::extra_argument(":env all", Some(Err("Unknown command ':env all', see :help".to_string())))]
+    #[case::code("print 1;", None)]
+    fn test_parse_command(
+        #[case] line: &str,
+        #[case] expected: Option<Result<ReplCommand, String>>,
+    ) {
+        assert_eq!(ReplCommand::parse(line), expected);
+    }
+
+    #[test]
+    fn test_env() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a session declaring a variable and a function
+        let (mut repl, output) = repl();
+        repl.eval("var a = [1, 2];")?;
+        repl.eval("fun twice(x) { print x * 2; }")?;
+        repl.eval("twice(a[1]);")?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // When listing the environment
+        let env = repl.eval(":env")?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then only the globals of the session are listed, without the natives
+        assert_eq!(env, "a = [1, 2]\ntwice = <fn twice(x)>");
+        assert_eq!(output.contents(), "4\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_last_input() -> Result<(), String> {
+        let (mut repl, _) = repl();
+        assert_eq!(
+            repl.eval(":ast"),
+            Err("No input to inspect yet".to_string())
+        );
+
+        // failed inputs can be inspected too
+        assert!(repl.eval("var a = 1 + ;").is_err());
+        assert_eq!(
+            repl.eval(":tokens")?,
+            "line 1: var\nline 1: a\nline 1: =\nline 1: 1\nline 1: +\nline 1: ;"
+        );
+
+        repl.eval("print 1 +\n  2;")?;
+        assert_eq!(repl.eval(":ast")?, "{print {1 + 2}}");
+        assert_eq!(repl.eval(":ast tree")?, "print\n  +\n    1\n    2");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_and_reset() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a session with timing enabled
+        let (mut repl, _) = repl();
+        assert_eq!(repl.eval(":time")?, "timing on");
+        assert!(repl.eval("var a = 1;")?.starts_with("time: "));
+
+        ///////////////////////////////////////////////////////////////////////
+        // When resetting it
+        assert_eq!(repl.eval(":reset")?, "session reset");
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then its declarations are gone, while the timing stays enabled
+        assert!(repl.eval("a;").is_err());
+        assert_eq!(repl.eval(":env")?, "");
+        assert_eq!(repl.eval(":time")?, "timing off");
+        assert_eq!(repl.eval("var b = 1;")?, "");
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::command(":env", true)]
+    #[case::code("print 1;", true)]
+    #[case::open_block("{ print 1;", false)]
+    fn test_is_complete(#[case] input: &str, #[case] expected: bool) {
+        assert_eq!(repl().0.is_complete(input), expected);
+    }
+}
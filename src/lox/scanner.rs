@@ -1,373 +1,426 @@
-use super::Token;
+use std::fmt::Display;
+
+use super::{LimitExceeded, Limits, Span, SpannedToken, Symbol, Token};
+
+// Error found while scanning, located at the start of the offending token
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScanError {
+    UnterminatedString(Span),
+    UnexpectedCharacter(char, Span),
+    NonAsciiCharacter(char, Span),
+    MalformedNumber(String, Span),
+    Limit(LimitExceeded),
+}
+
+impl ScanError {
+    pub fn get_span(&self) -> Option<Span> {
+        match self {
+            ScanError::UnterminatedString(span)
+            | ScanError::UnexpectedCharacter(_, span)
+            | ScanError::NonAsciiCharacter(_, span)
+            | ScanError::MalformedNumber(_, span) => Some(*span),
+            ScanError::Limit(_) => None,
+        }
+    }
+}
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScanError::UnterminatedString(span) => {
+                write!(f, "Unterminated string starting at {span}")
+            }
+            ScanError::UnexpectedCharacter(c, span) => {
+                write!(f, "Unexpected character '{}' at {span}", c.escape_debug())
+            }
+            ScanError::NonAsciiCharacter(c, span) => write!(
+                f,
+                "Unexpected character '{c}' at {span}, only string literals and comments can contain non-ASCII characters"
+            ),
+            ScanError::MalformedNumber(number, span) => {
+                write!(f, "Malformed number '{number}' at {span}")
+            }
+            ScanError::Limit(limit) => write!(f, "{limit}"),
+        }
+    }
+}
+
+impl From<ScanError> for String {
+    fn from(error: ScanError) -> Self {
+        error.to_string()
+    }
+}
 
 pub struct Scanner {
     source: String,
+    limits: Limits,
 }
 
-struct ScanInfo {
+// Position of the scanner over the bytes of the source. Non-ASCII characters can only appear
+// inside string literals and comments, so tokens always start and end at character boundaries
+struct Cursor<'a> {
+    source: &'a str,
+    // index of the next byte to consume
+    current: usize,
+    // index of the first byte of the token being scanned
+    start: usize,
+    // line of the next byte to consume, starting at 0
     line: u64,
-    line_offset: u64,
+    // line of the first byte of the token being scanned
+    start_line: u64,
 }
 
-impl Scanner {
-    pub fn new(source: String) -> Scanner {
-        Scanner { source: source }
+impl<'a> Cursor<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            current: 0,
+            start: 0,
+            line: 0,
+            start_line: 0,
+        }
     }
 
-    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, String> {
-        let mut tokens: Vec<Token> = Vec::new();
+    fn is_at_end(&self) -> bool {
+        self.current >= self.source.len()
+    }
 
-        if !self.source.is_ascii() {
-            return Err("Source is not ASCII".to_string());
-        }
+    // Marks the next byte as the start of a new token
+    fn begin_token(&mut self) {
+        self.start = self.current;
+        self.start_line = self.line;
+    }
 
-        let mut char_iterator = self.source.chars();
-        let mut scan_info = ScanInfo {
-            line: 0,
-            line_offset: 0,
-        };
+    // Consumes the next byte, the caller must check the cursor is not at the end
+    fn advance(&mut self) -> char {
+        let c = self.source.as_bytes()[self.current] as char;
+        self.current += 1;
 
-        while let Some(c) = char_iterator.nth(0) {
-            Scanner::match_root(c, &mut char_iterator, &mut tokens, &mut scan_info);
+        if c == '\n' {
+            self.line += 1;
         }
 
-        tokens.push(Token::Eof);
+        c
+    }
 
-        return Ok(tokens);
+    fn peek(&self) -> Option<char> {
+        self.source.as_bytes().get(self.current).map(|b| *b as char)
     }
 
-    #[inline(always)]
-    fn match_root(
-        c: char,
-        char_iterator: &mut std::str::Chars,
-        tokens: &mut Vec<Token>,
-        scan_info: &mut ScanInfo,
-    ) {
-        match c {
-            '(' => {
-                tokens.push(Token::LeftParenthesis);
-            }
-            ')' => {
-                tokens.push(Token::RightParenthesis);
-            }
-            '{' => {
-                tokens.push(Token::LeftBrace);
-            }
-            '}' => {
-                tokens.push(Token::RightBrace);
-            }
-            ',' => {
-                tokens.push(Token::Comma);
-            }
-            '.' => {
-                tokens.push(Token::Dot);
-            }
-            ';' => {
-                tokens.push(Token::Semicolon);
-            }
-            '+' => {
-                tokens.push(Token::Plus);
-            }
-            '-' => {
-                tokens.push(Token::Minus);
-            }
-            '*' => {
-                tokens.push(Token::Star);
-            }
-            '/' => {
-                Scanner::match_divide(char_iterator, tokens, scan_info);
-            }
-            '=' => {
-                Scanner::match_assign(char_iterator, tokens, scan_info);
-            }
-            '<' => {
-                Scanner::match_less(char_iterator, tokens, scan_info);
-            }
-            '>' => {
-                Scanner::match_greater(char_iterator, tokens, scan_info);
-            }
-            '"' => {
-                Scanner::match_string_literal(char_iterator, tokens, scan_info);
-            }
-            '!' => {
-                Scanner::match_bang(char_iterator, tokens, scan_info);
-            }
-            '\n' => {
-                scan_info.line += 1;
-                scan_info.line_offset = 0;
-            }
-            ' ' => {
-                scan_info.line_offset = 0;
-            }
-            digit if digit.is_ascii_digit() => {
-                Scanner::match_number_literal(digit, char_iterator, tokens, scan_info);
-            }
-            alpha if alpha.is_ascii_alphabetic() => {
-                Scanner::match_identifier(alpha, char_iterator, tokens, scan_info);
-            }
-            other => {
-                // FIXME: return error, unrecognized character
+    // Byte after the next one, without consuming either
+    fn peek_next(&self) -> Option<char> {
+        self.source
+            .as_bytes()
+            .get(self.current + 1)
+            .map(|b| *b as char)
+    }
+
+    // Consumes the next bytes only if they are the expected ones
+    fn match_str(&mut self, expected: &str) -> bool {
+        if self.source.as_bytes()[self.current..].starts_with(expected.as_bytes()) {
+            for _ in 0..expected.len() {
+                self.advance();
             }
+            return true;
         }
+
+        false
     }
 
-    #[inline(always)]
-    fn match_assign(
-        char_iterator: &mut std::str::Chars,
-        tokens: &mut Vec<Token>,
-        scan_info: &mut ScanInfo,
-    ) {
-        match char_iterator.nth(0) {
-            Some('=') => {
-                tokens.push(Token::EqualEqual);
-            }
-            Some(other) => {
-                tokens.push(Token::Equal);
-                Scanner::match_root(other, char_iterator, tokens, scan_info);
-            }
-            None => {
-                tokens.push(Token::Equal);
-            }
+    // Consumes the next byte only if it is the expected one
+    fn match_next(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.advance();
+            return true;
         }
+
+        false
     }
 
-    #[inline(always)]
-    fn match_less(
-        char_iterator: &mut std::str::Chars,
-        tokens: &mut Vec<Token>,
-        scan_info: &mut ScanInfo,
-    ) {
-        match char_iterator.nth(0) {
-            Some('=') => {
-                tokens.push(Token::LessEqual);
-            }
-            Some(other) => {
-                tokens.push(Token::Less);
-                Scanner::match_root(other, char_iterator, tokens, scan_info);
-            }
-            None => {
-                tokens.push(Token::Less);
-            }
+    // Text of the token being scanned
+    fn lexeme(&self) -> &str {
+        &self.source[self.start..self.current]
+    }
+
+    // Character the token being scanned starts with
+    fn start_char(&self) -> char {
+        self.source[self.start..].chars().next().unwrap_or_default()
+    }
+
+    // Location of the token being scanned
+    fn span(&self) -> Span {
+        Span {
+            line: self.start_line + 1,
         }
     }
 
-    #[inline(always)]
-    fn match_greater(
-        char_iterator: &mut std::str::Chars,
-        tokens: &mut Vec<Token>,
-        scan_info: &mut ScanInfo,
-    ) {
-        match char_iterator.nth(0) {
-            Some('=') => {
-                tokens.push(Token::GreaterEqual);
-            }
-            Some(other) => {
-                tokens.push(Token::Greater);
-                Scanner::match_root(other, char_iterator, tokens, scan_info);
-            }
-            None => {
-                tokens.push(Token::Greater);
-            }
+    fn spanned(&self, token: Token) -> SpannedToken {
+        SpannedToken {
+            token,
+            span: self.span(),
         }
     }
+}
 
-    #[inline(always)]
-    fn match_bang(
-        char_iterator: &mut std::str::Chars,
-        tokens: &mut Vec<Token>,
-        scan_info: &mut ScanInfo,
-    ) {
-        match char_iterator.nth(0) {
-            Some('=') => {
-                tokens.push(Token::BangEqual);
-            }
-            Some(other) => {
-                tokens.push(Token::Bang);
-                Scanner::match_root(other, char_iterator, tokens, scan_info);
+// Iterator over the tokens of a source, see Scanner::tokens
+pub struct Tokens<'a> {
+    cursor: Cursor<'a>,
+    limits: &'a Limits,
+    // number of tokens yielded so far, not counting EOF
+    count: usize,
+    // line comments skipped so far, with their location
+    comments: Vec<(Span, String)>,
+    finished: bool,
+}
+
+impl<'a> Tokens<'a> {
+    // Line comments found up to the last token yielded, they are not part of the tokens
+    pub fn get_comments(&self) -> &[(Span, String)] {
+        &self.comments
+    }
+
+    fn next_token(&mut self) -> Result<SpannedToken, ScanError> {
+        while !self.cursor.is_at_end() {
+            self.cursor.begin_token();
+            match Scanner::scan_token(&mut self.cursor)? {
+                Some(token) => {
+                    self.count += 1;
+                    self.check_limits(&token)?;
+                    return Ok(self.cursor.spanned(token));
+                }
+                None if self.cursor.lexeme().starts_with("//") => {
+                    let comment = self.cursor.lexeme().trim_end().to_string();
+                    self.comments.push((self.cursor.span(), comment));
+                }
+                None => {}
             }
-            None => {
-                tokens.push(Token::Bang);
+        }
+
+        self.finished = true;
+        self.cursor.begin_token();
+        Ok(self.cursor.spanned(Token::Eof))
+    }
+
+    // Checks the limits against the tokens scanned so far and the last scanned token
+    fn check_limits(&self, token: &Token) -> Result<(), ScanError> {
+        if self.count > self.limits.max_tokens {
+            return Err(ScanError::Limit(LimitExceeded::Tokens(
+                self.limits.max_tokens,
+            )));
+        }
+
+        if let Token::StringLiteral(s) | Token::RawStringLiteral(s) = token {
+            if s.chars().count() > self.limits.max_string_literal_length {
+                return Err(ScanError::Limit(LimitExceeded::StringLiteralLength(
+                    self.limits.max_string_literal_length,
+                )));
             }
         }
+
+        Ok(())
     }
+}
 
-    #[inline(always)]
-    fn match_divide(
-        char_iterator: &mut std::str::Chars,
-        tokens: &mut Vec<Token>,
-        scan_info: &mut ScanInfo,
-    ) {
-        match char_iterator.nth(0) {
-            Some('/') => {
-                // line comment
-                Scanner::match_line_comment(char_iterator, scan_info)
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Result<SpannedToken, ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let result = self.next_token();
+        if result.is_err() {
+            self.finished = true;
+        }
+
+        Some(result)
+    }
+}
+
+impl Scanner {
+    pub fn new(source: String) -> Scanner {
+        Scanner {
+            source: source,
+            limits: Limits::default(),
+        }
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn scan_tokens(&mut self) -> Result<Vec<Token>, ScanError> {
+        let tokens = self.scan_spanned_tokens()?;
+        Ok(tokens.into_iter().map(|t| t.token).collect())
+    }
+
+    pub fn scan_spanned_tokens(&mut self) -> Result<Vec<SpannedToken>, ScanError> {
+        self.tokens().collect()
+    }
+
+    // Lazily scans the source, yielding the tokens one by one. The iterator ends after the
+    // EOF token or after the first error
+    pub fn tokens(&self) -> Tokens<'_> {
+        Tokens {
+            cursor: Cursor::new(&self.source),
+            limits: &self.limits,
+            count: 0,
+            comments: Vec::new(),
+            finished: false,
+        }
+    }
+
+    // Scans the token starting at the cursor, if any. Whitespace and comments produce no token
+    fn scan_token(cursor: &mut Cursor) -> Result<Option<Token>, ScanError> {
+        let token = match cursor.advance() {
+            '(' => Some(Token::LeftParenthesis),
+            ')' => Some(Token::RightParenthesis),
+            '{' => Some(Token::LeftBrace),
+            '}' => Some(Token::RightBrace),
+            '[' => Some(Token::LeftBracket),
+            ']' => Some(Token::RightBracket),
+            ',' => Some(Token::Comma),
+            '.' => {
+                if cursor.match_next('.') {
+                    Some(Scanner::one_or_two(
+                        cursor,
+                        Token::DotDot,
+                        Token::DotDotEqual,
+                    ))
+                } else {
+                    Some(Token::Dot)
+                }
             }
-            Some(other) => {
-                tokens.push(Token::Slash);
-                Scanner::match_root(other, char_iterator, tokens, scan_info);
+            ':' => Some(Token::Colon),
+            ';' => Some(Token::Semicolon),
+            '+' => Some(Token::Plus),
+            '-' => Some(Token::Minus),
+            '*' => Some(Token::Star),
+            '/' => {
+                if cursor.match_next('/') {
+                    Scanner::skip_line_comment(cursor);
+                    None
+                } else {
+                    Some(Token::Slash)
+                }
             }
-            None => {
-                tokens.push(Token::Slash);
+            '=' => Some(Scanner::one_or_two(cursor, Token::Equal, Token::EqualEqual)),
+            '<' => Some(Scanner::one_or_two(cursor, Token::Less, Token::LessEqual)),
+            '>' => Some(Scanner::one_or_two(
+                cursor,
+                Token::Greater,
+                Token::GreaterEqual,
+            )),
+            '!' => Some(Scanner::one_or_two(cursor, Token::Bang, Token::BangEqual)),
+            '"' => {
+                if cursor.match_str("\"\"") {
+                    Some(Scanner::scan_raw_string_literal(cursor)?)
+                } else {
+                    Some(Scanner::scan_string_literal(cursor)?)
+                }
             }
+            ' ' | '\t' | '\r' | '\n' => None,
+            digit if digit.is_ascii_digit() => Some(Scanner::scan_number_literal(cursor)?),
+            alpha if alpha.is_ascii_alphabetic() => Some(Scanner::scan_identifier(cursor)),
+            other if !other.is_ascii() => {
+                return Err(ScanError::NonAsciiCharacter(
+                    cursor.start_char(),
+                    cursor.span(),
+                ));
+            }
+            other => {
+                return Err(ScanError::UnexpectedCharacter(other, cursor.span()));
+            }
+        };
+
+        Ok(token)
+    }
+
+    // Token of a single character operator, or of the two characters one if followed by '='
+    fn one_or_two(cursor: &mut Cursor, single: Token, with_equal: Token) -> Token {
+        if cursor.match_next('=') {
+            with_equal
+        } else {
+            single
         }
     }
 
-    #[inline(always)]
-    fn match_line_comment(char_iterator: &mut std::str::Chars, scan_info: &mut ScanInfo) {
+    fn skip_line_comment(cursor: &mut Cursor) {
         // consume characters until the end of the line is reached, or no more chars are available
-        while let Some(c) = char_iterator.nth(0) {
-            match c {
-                '\n' => {
-                    scan_info.line += 1;
-                    scan_info.line_offset = 0;
-                    break;
-                }
-                _ => {}
+        while let Some(c) = cursor.peek() {
+            cursor.advance();
+            if c == '\n' {
+                break;
             }
         }
     }
 
-    #[inline(always)]
-    fn match_string_literal(
-        char_iterator: &mut std::str::Chars,
-        tokens: &mut Vec<Token>,
-        _scan_info: &mut ScanInfo,
-    ) {
-        let mut str_buffer = String::with_capacity(128);
+    fn scan_string_literal(cursor: &mut Cursor) -> Result<Token, ScanError> {
         // consume characters until the end of the string is reached, or no more chars are available
-        while let Some(c) = char_iterator.nth(0) {
-            match c {
-                '"' => {
-                    // end of string
-                    tokens.push(Token::StringLiteral(str_buffer));
-                    break;
-                }
-                other => {
-                    str_buffer.push(other);
-                }
+        while let Some(c) = cursor.peek() {
+            cursor.advance();
+            if c == '"' {
+                // the lexeme includes both quotes
+                let lexeme = cursor.lexeme();
+                return Ok(Token::StringLiteral(
+                    lexeme[1..lexeme.len() - 1].to_string(),
+                ));
             }
         }
 
-        // FIXME: end of file reached, but string is not closed, return error
+        // end of file reached, but string is not closed
+        Err(ScanError::UnterminatedString(cursor.span()))
     }
 
-    #[inline(always)]
-    fn match_number_literal(
-        first: char,
-        char_iterator: &mut std::str::Chars,
-        tokens: &mut Vec<Token>,
-        _scan_info: &mut ScanInfo,
-    ) {
-        let mut number_buffer = String::with_capacity(32);
-        number_buffer.push(first);
+    // String between triple quotes, after the opening ones. It can span lines and contain
+    // single or double quotes, it ends at the first three quotes in a row
+    fn scan_raw_string_literal(cursor: &mut Cursor) -> Result<Token, ScanError> {
+        while !cursor.is_at_end() {
+            if cursor.match_str("\"\"\"") {
+                let lexeme = cursor.lexeme();
+                return Ok(Token::RawStringLiteral(
+                    lexeme[3..lexeme.len() - 3].to_string(),
+                ));
+            }
+            cursor.advance();
+        }
 
-        let mut decimal_point_scanned = false;
+        Err(ScanError::UnterminatedString(cursor.span()))
+    }
 
-        // consume characters until the end of the number is reached, or no more chars are available
-        while let Some(c) = char_iterator.nth(0) {
-            match c {
-                digit if digit.is_ascii_digit() => {
-                    number_buffer.push(digit);
-                }
-                '.' => {
-                    if decimal_point_scanned {
-                        // TODO: return error
-                    }
-
-                    // decimal point
-                    number_buffer.push('.');
-                    decimal_point_scanned = true;
-                }
-                other => {
-                    // end of number
-                    match number_buffer.parse::<f64>() {
-                        Ok(n) => tokens.push(Token::NumberLiteral(n)),
-                        Err(_e) => {
-                            // TODO: return error
-                        }
-                    }
-
-                    Scanner::match_root(other, char_iterator, tokens, _scan_info);
-
-                    // FIXME: This is ugly. Needed to avoid the code bellow for EOF
-                    return;
-                }
+    fn scan_number_literal(cursor: &mut Cursor) -> Result<Token, ScanError> {
+        // consume digits and decimal points until the end of the number is reached, a range
+        // operator ends the number, e.g. in 1..10
+        while let Some(c) = cursor.peek() {
+            if !c.is_ascii_digit() && c != '.' || c == '.' && cursor.peek_next() == Some('.') {
+                break;
             }
+            cursor.advance();
         }
 
-        // EOF reached, try to parse the number
-        match number_buffer.parse::<f64>() {
-            Ok(n) => tokens.push(Token::NumberLiteral(n)),
-            Err(_e) => {
-                // TODO: return error
-            }
-        }
+        // e.g. with several decimal points
+        cursor
+            .lexeme()
+            .parse::<f64>()
+            .map(Token::NumberLiteral)
+            .map_err(|_| ScanError::MalformedNumber(cursor.lexeme().to_string(), cursor.span()))
     }
 
-    #[inline(always)]
-    fn match_identifier(
-        first: char,
-        char_iterator: &mut std::str::Chars,
-        tokens: &mut Vec<Token>,
-        _scan_info: &mut ScanInfo,
-    ) {
-        let mut identifier_buffer = String::with_capacity(64);
-        identifier_buffer.push(first);
-
-        // consume characters until the end of the identifier is reached, or no more chars are available
-        while let Some(c) = char_iterator.nth(0) {
-            match c {
-                c if c.is_ascii_alphanumeric() || c == '_' => {
-                    identifier_buffer.push(c);
-                }
-                other => {
-                    match identifier_buffer.as_str() {
-                        "and" => tokens.push(Token::And),
-                        "class" => tokens.push(Token::Class),
-                        "else" => tokens.push(Token::Else),
-                        "false" => tokens.push(Token::False),
-                        "fun" => tokens.push(Token::Fun),
-                        "for" => tokens.push(Token::For),
-                        "if" => tokens.push(Token::If),
-                        "nil" => tokens.push(Token::Nil),
-                        "or" => tokens.push(Token::Or),
-                        "print" => tokens.push(Token::Print),
-                        "return" => tokens.push(Token::Return),
-                        "super" => tokens.push(Token::Super),
-                        "this" => tokens.push(Token::This),
-                        "true" => tokens.push(Token::True),
-                        "var" => tokens.push(Token::Var),
-                        "while" => tokens.push(Token::While),
-                        other => tokens.push(Token::Identifier(other.to_string())),
-                    }
-
-                    Scanner::match_root(other, char_iterator, tokens, _scan_info);
-                    return;
-                }
+    fn scan_identifier(cursor: &mut Cursor) -> Token {
+        while let Some(c) = cursor.peek() {
+            if !c.is_ascii_alphanumeric() && c != '_' {
+                break;
             }
+            cursor.advance();
         }
 
-        // FIXME: this is ugly, repeated code
-        // in case of EOF reached during the character scanning
-        match identifier_buffer.as_str() {
-            "and" => tokens.push(Token::And),
-            "class" => tokens.push(Token::Class),
-            "else" => tokens.push(Token::Else),
-            "false" => tokens.push(Token::False),
-            "fun" => tokens.push(Token::Fun),
-            "for" => tokens.push(Token::For),
-            "if" => tokens.push(Token::If),
-            "nil" => tokens.push(Token::Nil),
-            "or" => tokens.push(Token::Or),
-            "print" => tokens.push(Token::Print),
-            "return" => tokens.push(Token::Return),
-            "super" => tokens.push(Token::Super),
-            "this" => tokens.push(Token::This),
-            "true" => tokens.push(Token::True),
-            "var" => tokens.push(Token::Var),
-            "while" => tokens.push(Token::While),
-            other => tokens.push(Token::Identifier(other.to_string())),
-        }
+        Scanner::identifier_or_keyword(cursor.lexeme())
+    }
+
+    #[inline(always)]
+    fn identifier_or_keyword(word: &str) -> Token {
+        Token::from_keyword(word).unwrap_or_else(|| Token::Identifier(Symbol::new(word)))
     }
 }
 
@@ -416,13 +469,285 @@ mod tests {
         }
     }
 
+    #[rstest]
+    #[case::exclusive("1..10", vec![Token::NumberLiteral(1.0), Token::DotDot, Token::NumberLiteral(10.0)])]
+    #[case::inclusive("0.5..=2", vec![Token::NumberLiteral(0.5), Token::DotDotEqual, Token::NumberLiteral(2.0)])]
+    #[case::property("a.b..c", vec![
+        Token::Identifier(Symbol::new("a")),
+        Token::Dot,
+        Token::Identifier(Symbol::new("b")),
+        Token::DotDot,
+        Token::Identifier(Symbol::new("c")),
+    ])]
+    fn test_scan_range(
+        #[case] source: &str,
+        #[case] mut expected: Vec<Token>,
+    ) -> Result<(), String> {
+        let tokens = Scanner::new(source.to_string()).scan_tokens()?;
+
+        expected.push(Token::Eof);
+        assert_eq!(tokens, expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_spanned_tokens() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a source string spanning several lines
+        let source = String::from("var a\n// comment\n= 1;\n");
+
+        ///////////////////////////////////////////////////////////////////////
+        // When the source is scanned with spans
+        let mut scanner = Scanner::new(source);
+        let tokens = scanner.scan_spanned_tokens()?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then each token reports the line it was found at
+        let lines: Vec<u64> = tokens.iter().map(|t| t.span.line).collect();
+        assert_eq!(lines, vec![1, 1, 3, 3, 3, 4]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_multiline_string_spans() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a string literal spanning two lines
+        let source = String::from("print \"a\nb\";\nprint 1;");
+
+        ///////////////////////////////////////////////////////////////////////
+        // When the source is scanned with spans
+        let tokens = Scanner::new(source).scan_spanned_tokens()?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the literal is reported at the line it starts and later tokens account for its lines
+        assert_eq!(tokens[1].token, Token::StringLiteral("a\nb".to_string()));
+        let lines: Vec<u64> = tokens.iter().map(|t| t.span.line).collect();
+        assert_eq!(lines, vec![1, 1, 2, 3, 3, 3, 3]);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::quotes(r#""""say "hi" or 'bye'""""#, r#"say "hi" or 'bye'"#)]
+    #[case::no_escapes(r#""""a\n\"b""""#, r#"a\n\"b"#)]
+    #[case::multiline("\"\"\"\n  line 1\n  line 2\n\"\"\"", "\n  line 1\n  line 2\n")]
+    #[case::leading_quotes(r#""""""a""""#, r#"""a"#)]
+    #[case::empty(r#""""""""#, "")]
+    fn test_scan_raw_string(#[case] source: &str, #[case] expected: &str) -> Result<(), String> {
+        let tokens = Scanner::new(source.to_string()).scan_tokens()?;
+
+        assert_eq!(
+            tokens,
+            vec![Token::RawStringLiteral(expected.to_string()), Token::Eof]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_raw_string_spans() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a raw string literal spanning three lines
+        let source = String::from("print \"\"\"a\nb\nc\"\"\";\nprint 1;");
+
+        ///////////////////////////////////////////////////////////////////////
+        // When the source is scanned with spans
+        let tokens = Scanner::new(source).scan_spanned_tokens()?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the literal is reported at the line it starts and later tokens account for its lines
+        let lines: Vec<u64> = tokens.iter().map(|t| t.span.line).collect();
+        assert_eq!(lines, vec![1, 1, 3, 4, 4, 4, 4]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_utf8() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a source with non-ASCII characters in a comment and a string literal
+        let source = String::from("// café ☕\nprint \"héllo ✓\";");
+
+        ///////////////////////////////////////////////////////////////////////
+        // When the source is scanned
+        let tokens = Scanner::new(source).scan_tokens()?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the literal keeps its characters
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Print,
+                Token::StringLiteral("héllo ✓".to_string()),
+                Token::Semicolon,
+                Token::Eof
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::identifier("var a = 1;\nvar café = 2;", 'é', 2)]
+    #[case::first_char("\n\nπ = 3;", 'π', 3)]
+    fn test_scan_utf8_outside_literals(
+        #[case] source: &str,
+        #[case] character: char,
+        #[case] line: u64,
+    ) {
+        let result = Scanner::new(source.to_string()).scan_tokens();
+
+        assert_eq!(
+            result.map_err(|e| e.to_string()),
+            Err(format!(
+                "Unexpected character '{character}' at line {line}, only string literals and comments can contain non-ASCII characters"
+            ))
+        );
+    }
+
+    #[rstest]
+    #[case::hash("var a = 1; # comment", ScanError::UnexpectedCharacter('#', Span { line: 1 }), "Unexpected character '#' at line 1")]
+    #[case::at("\n\tprint @a;", ScanError::UnexpectedCharacter('@', Span { line: 2 }), "Unexpected character '@' at line 2")]
+    #[case::control("a\u{0}", ScanError::UnexpectedCharacter('\u{0}', Span { line: 1 }), "Unexpected character '\\0' at line 1")]
+    #[case::several_points(
+        "print 1.2.3;",
+        ScanError::MalformedNumber("1.2.3".to_string(), Span { line: 1 }),
+        "Malformed number '1.2.3' at line 1"
+    )]
+    fn test_scan_errors(
+        #[case] source: &str,
+        #[case] expected: ScanError,
+        #[case] expected_message: &str,
+    ) {
+        let result = Scanner::new(source.to_string()).scan_tokens();
+
+        assert_eq!(result, Err(expected.clone()));
+        assert_eq!(expected.to_string(), expected_message);
+    }
+
+    #[test]
+    fn test_tokens_iterator() {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a source with an error after some valid tokens
+        let scanner = Scanner::new("print a;\n@".to_string());
+
+        ///////////////////////////////////////////////////////////////////////
+        // When iterating its tokens
+        let mut tokens = scanner.tokens();
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the valid tokens are yielded before the error is found
+        assert_eq!(
+            tokens.next().map(|t| t.map(|t| t.token)),
+            Some(Ok(Token::Print))
+        );
+        assert_eq!(
+            tokens.next(),
+            Some(Ok(SpannedToken {
+                token: Token::Identifier(Symbol::new("a")),
+                span: Span { line: 1 }
+            }))
+        );
+        assert_eq!(
+            tokens.next().map(|t| t.map(|t| t.token)),
+            Some(Ok(Token::Semicolon))
+        );
+        assert_eq!(
+            tokens.next(),
+            Some(Err(ScanError::UnexpectedCharacter('@', Span { line: 2 })))
+        );
+
+        // and the iteration stops after the error
+        assert_eq!(tokens.next(), None);
+
+        // while a valid source ends with EOF
+        let scanner = Scanner::new("1".to_string());
+        let tokens: Vec<_> = scanner.tokens().map(|t| t.map(|t| t.token)).collect();
+        assert_eq!(tokens, vec![Ok(Token::NumberLiteral(1.0)), Ok(Token::Eof)]);
+    }
+
+    #[rstest]
+    #[case::at_end("print \"hello", 1)]
+    #[case::empty("\"", 1)]
+    #[case::multiline("var a = 1;\nprint \"hello\nworld;\n", 2)]
+    #[case::after_closed("print \"a\";\nprint \"b\";\n\n\"", 4)]
+    #[case::raw("print 1;\nprint \"\"\"a\nb\"\";", 2)]
+    fn test_scan_unterminated_string(#[case] source: &str, #[case] line: u64) {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a source whose last string literal is never closed
+        let mut scanner = Scanner::new(source.to_string());
+
+        ///////////////////////////////////////////////////////////////////////
+        // When the source is scanned
+        let result = scanner.scan_tokens();
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the error reports the line where the string started
+        let expected = ScanError::UnterminatedString(Span { line });
+        assert_eq!(result, Err(expected.clone()));
+        assert_eq!(expected.get_span(), Some(Span { line }));
+        assert_eq!(
+            expected.to_string(),
+            format!("Unterminated string starting at line {line}")
+        );
+    }
+
+    #[rstest]
+    #[case::bang("!", Token::Bang)]
+    #[case::equal("=", Token::Equal)]
+    #[case::less("<", Token::Less)]
+    #[case::greater(">", Token::Greater)]
+    #[case::slash("/", Token::Slash)]
+    fn test_scan_long_operator_runs(
+        #[case] operator: &str,
+        #[case] expected: Token,
+    ) -> Result<(), String> {
+        // long runs of operators must not grow the stack of the scanner
+        let source = format!("{operator} ").repeat(100_000);
+        let tokens = Scanner::new(source).scan_tokens()?;
+
+        assert_eq!(tokens.len(), 100_001);
+        assert!(tokens[..100_000].iter().all(|t| *t == expected));
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::tokens(
+        "var a = 1;",
+        Limits { max_tokens: 4, ..Default::default() },
+        LimitExceeded::Tokens(4)
+    )]
+    #[case::string_literal(
+        "print \"four\"; print \"five!\";",
+        Limits { max_string_literal_length: 4, ..Default::default() },
+        LimitExceeded::StringLiteralLength(4)
+    )]
+    fn test_scan_limits(
+        #[case] source: String,
+        #[case] limits: Limits,
+        #[case] expected: LimitExceeded,
+    ) {
+        let mut scanner = Scanner::new(source);
+        scanner.set_limits(limits);
+
+        assert_eq!(scanner.scan_tokens(), Err(ScanError::Limit(expected)));
+    }
+
     #[rstest]
     #[case::left_parenthesis("(", Token::LeftParenthesis)]
     #[case::right_parenthesis(")", Token::RightParenthesis)]
     #[case::left_brace("{", Token::LeftBrace)]
     #[case::right_brace("}", Token::RightBrace)]
+    #[case::left_bracket("[", Token::LeftBracket)]
+    #[case::right_bracket("]", Token::RightBracket)]
     #[case::comma(",", Token::Comma)]
     #[case::dot(".", Token::Dot)]
+    #[case::dot_dot("..", Token::DotDot)]
+    #[case::dot_dot_equal("..=", Token::DotDotEqual)]
+    #[case::colon(":", Token::Colon)]
     #[case::semicolon(";", Token::Semicolon)]
     #[case::plus("+", Token::Plus)]
     #[case::minus("-", Token::Minus)]
@@ -437,24 +762,36 @@ mod tests {
     #[case::greater_equal(">=", Token::GreaterEqual)]
     #[case::bang_equal("!=", Token::BangEqual)]
     #[case::and("and", Token::And)]
+    #[case::case("case", Token::Case)]
+    #[case::catch("catch", Token::Catch)]
     #[case::class("class", Token::Class)]
+    #[case::assert("assert", Token::Assert)]
+    #[case::const_("const", Token::Const)]
+    #[case::default("default", Token::Default)]
+    #[case::kw_do("do", Token::Do)]
     #[case::kw_else("else", Token::Else)]
     #[case::kw_false("false", Token::False)]
     #[case::fun("fun", Token::Fun)]
     #[case::kw_for("for", Token::For)]
     #[case::kw_if("if", Token::If)]
+    #[case::import("import", Token::Import)]
+    #[case::kw_in("in", Token::In)]
     #[case::nil("nil", Token::Nil)]
     #[case::or("or", Token::Or)]
     #[case::print("print", Token::Print)]
     #[case::kw_return("return", Token::Return)]
     #[case::super("super", Token::Super)]
+    #[case::switch("switch", Token::Switch)]
     #[case::this("this", Token::This)]
+    #[case::throw("throw", Token::Throw)]
     #[case::kw_true("true", Token::True)]
+    #[case::kw_try("try", Token::Try)]
     #[case::var("var", Token::Var)]
     #[case::kw_while("while", Token::While)]
     #[case::string_literal("\"my literal\"", Token::StringLiteral("my literal".to_string()))]
+    #[case::raw_string_literal("\"\"\"my \"literal\"\"\"", Token::RawStringLiteral("my \"literal".to_string()))]
     #[case::number_literal("0.5", Token::NumberLiteral(0.5))]
-    #[case::identifier("my_identifier", Token::Identifier("my_identifier".to_string()))]
+    #[case::identifier("my_identifier", Token::Identifier(Symbol::new("my_identifier")))]
     fn test_single_token(#[case] source: String, #[case] expected: Token) -> Result<(), String> {
         ///////////////////////////////////////////////////////////////////////
         // Given the source string as parameter
@@ -504,6 +841,153 @@ mod tests {
         Ok(())
     }
 
+    #[rstest]
+    fn test_scan_programs(
+        #[files("test-data/interpreter/*.lox")] path: PathBuf,
+    ) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a full Lox program
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // When the program is scanned
+        let tokens = Scanner::new(source).scan_tokens()?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then its statements are delimited by punctuation and the tokens end with EOF
+        assert!(tokens.contains(&Token::Semicolon));
+        assert_eq!(tokens.last(), Some(&Token::Eof));
+
+        Ok(())
+    }
+
+    // Deterministic xorshift64 generator, so failures of the round-trip test are reproducible
+    struct TokenGenerator {
+        state: u64,
+    }
+
+    impl TokenGenerator {
+        fn next(&mut self) -> u64 {
+            self.state ^= self.state << 13;
+            self.state ^= self.state >> 7;
+            self.state ^= self.state << 17;
+            self.state
+        }
+
+        fn below(&mut self, n: u64) -> u64 {
+            self.next() % n
+        }
+
+        fn word(&mut self, first_chars: &str, chars: &str, max_len: u64) -> String {
+            let first_chars: Vec<char> = first_chars.chars().collect();
+            let chars: Vec<char> = chars.chars().collect();
+
+            let mut word = String::new();
+            word.push(first_chars[self.below(first_chars.len() as u64) as usize]);
+            for _ in 0..self.below(max_len) {
+                word.push(chars[self.below(chars.len() as u64) as usize]);
+            }
+            word
+        }
+
+        fn token(&mut self) -> Token {
+            let fixed_tokens = [
+                Token::LeftParenthesis,
+                Token::RightParenthesis,
+                Token::LeftBrace,
+                Token::RightBrace,
+                Token::LeftBracket,
+                Token::RightBracket,
+                Token::Comma,
+                Token::Dot,
+                Token::Colon,
+                Token::Semicolon,
+                Token::Plus,
+                Token::Minus,
+                Token::Star,
+                Token::Slash,
+                Token::Equal,
+                Token::Less,
+                Token::Greater,
+                Token::Bang,
+                Token::EqualEqual,
+                Token::LessEqual,
+                Token::GreaterEqual,
+                Token::BangEqual,
+                Token::DotDot,
+                Token::DotDotEqual,
+            ];
+
+            let keywords: Vec<&str> = Token::keywords().collect();
+
+            match self.below(6) {
+                0 => fixed_tokens[self.below(fixed_tokens.len() as u64) as usize].clone(),
+                1 => {
+                    let keyword = keywords[self.below(keywords.len() as u64) as usize];
+                    Token::from_keyword(keyword).unwrap()
+                }
+                2 => {
+                    let lowercase = "abcdefghijklmnopqrstuvwxyz";
+                    let identifier =
+                        self.word(lowercase, "abcdefghijklmnopqrstuvwxyz0123456789_", 8);
+                    Token::from_keyword(&identifier)
+                        .unwrap_or_else(|| Token::Identifier(Symbol::new(&identifier)))
+                }
+                3 => Token::StringLiteral(
+                    self.word(" ", "abc XYZ 019 +-*/=(){};.,!<>_\n", 12)
+                        .trim_start()
+                        .to_string(),
+                ),
+                // quotes are kept apart, three in a row or a last one would close the literal
+                4 => Token::RawStringLiteral(
+                    self.word(" ", "abc \"'\n;", 12)
+                        .trim_end_matches('"')
+                        .replace('"', "\" "),
+                ),
+                _ => {
+                    let numerator = self.below(100_000) as f64;
+                    let denominator = [1.0, 2.0, 4.0, 10.0, 100.0][self.below(5) as usize];
+                    Token::NumberLiteral(numerator / denominator)
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_display_scan_round_trip() -> Result<(), String> {
+        let mut generator = TokenGenerator {
+            state: 0x2545_f491_4f6c_dd1d,
+        };
+
+        for iteration in 0..500 {
+            ///////////////////////////////////////////////////////////////////
+            // Given a random sequence of tokens
+            let length = generator.below(50);
+            let mut tokens: Vec<Token> = (0..length).map(|_| generator.token()).collect();
+
+            ///////////////////////////////////////////////////////////////////
+            // When the tokens are displayed, separated by spaces, and scanned again
+            let source = tokens
+                .iter()
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            let mut scanner = Scanner::new(source.clone());
+            let scanned_tokens = scanner.scan_tokens()?;
+
+            ///////////////////////////////////////////////////////////////////
+            // Then the scanned tokens are the original sequence followed by EOF
+            tokens.push(Token::Eof);
+            assert_eq!(
+                scanned_tokens, tokens,
+                "round trip failed at iteration {iteration} for source: {source}"
+            );
+        }
+
+        Ok(())
+    }
+
     fn read_expected_tokens(path: PathBuf) -> Result<Vec<Token>, String> {
         // raw file content
         let expecteed_tokens_source = match std::fs::read_to_string(path) {
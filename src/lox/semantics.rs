@@ -0,0 +1,410 @@
+use super::{format_number, list_heap_size, new_value_box, Value};
+
+// Rules of the language shared by everything that evaluates values, the interpreter at runtime
+// and the analyzer when folding constants, so both agree on the result of an operation
+
+// Comparison operators between two values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Comparison {
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl Comparison {
+    // Operator as written in the source, e.g. for the errors
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Comparison::Less => "<",
+            Comparison::LessEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterEqual => ">=",
+        }
+    }
+}
+
+// Values of different types are never equal. Lists and maps compare their elements, functions
+// and modules are only equal to themselves
+pub fn lox_equals(left: &Value, right: &Value) -> bool {
+    left == right
+}
+
+// Result of comparing two numbers or two strings, None for any other pair of values, which can
+// not be compared. Comparisons involving NaN are false
+pub fn lox_compare(comparison: Comparison, left: &Value, right: &Value) -> Option<bool> {
+    let ordering = match (left, right) {
+        (Value::Number(left), Value::Number(right)) => left.partial_cmp(right),
+        (Value::String(left), Value::String(right)) => Some(left.cmp(right)),
+        _ => return None,
+    };
+
+    Some(ordering.is_some_and(|ordering| match comparison {
+        Comparison::Less => ordering.is_lt(),
+        Comparison::LessEqual => ordering.is_le(),
+        Comparison::Greater => ordering.is_gt(),
+        Comparison::GreaterEqual => ordering.is_ge(),
+    }))
+}
+
+// Result of `left + right`, None if the operands can not be added:
+// - two numbers are summed
+// - two strings are concatenated
+// - a string and a number are concatenated, the number formatted as by print
+// - two lists are concatenated into a new list, holding copies of their elements
+// Any other pair is an error, e.g. strings are not concatenated with booleans or nil
+pub fn lox_add(left: &Value, right: &Value) -> Result<Option<Value>, String> {
+    let result = match (left, right) {
+        (Value::Number(left), Value::Number(right)) => Value::Number(left + right),
+        (Value::String(left), Value::String(right)) => Value::String(format!("{left}{right}")),
+        (Value::String(left), Value::Number(right)) => {
+            Value::String(left.to_owned() + &format_number(*right))
+        }
+        (Value::Number(left), Value::String(right)) => Value::String(format_number(*left) + right),
+        (Value::List(left), Value::List(right)) => {
+            let mut elements = Vec::with_capacity(left.len() + right.len());
+            for element in left.iter().chain(right.iter()) {
+                let element_guard = element.try_borrow().map_err(|e| e.to_string())?;
+                elements.push(new_value_box(element_guard.clone()));
+            }
+            Value::List(elements)
+        }
+        _ => return Ok(None),
+    };
+
+    Ok(Some(result))
+}
+
+// Approximate bytes of heap the result of `left + right` takes, worked out without building it
+// so the memory budget can be checked first. 0 for the results not taking any heap
+pub fn lox_add_size(left: &Value, right: &Value) -> Result<usize, String> {
+    let size = match (left, right) {
+        (Value::String(left), Value::String(right)) => left.len().saturating_add(right.len()),
+        // formatted numbers are short, the length of the longest is enough
+        (Value::String(s), Value::Number(_)) | (Value::Number(_), Value::String(s)) => {
+            s.len().saturating_add(32)
+        }
+        (Value::List(left), Value::List(right)) => {
+            // the elements are copied along with the contents of the strings they hold
+            let mut size = list_heap_size(left.len().saturating_add(right.len()));
+            for element in left.iter().chain(right.iter()) {
+                let element_guard = element.try_borrow().map_err(|e| e.to_string())?;
+                size = size.saturating_add(element_guard.heap_size());
+            }
+            size
+        }
+        _ => 0,
+    };
+
+    Ok(size)
+}
+
+// Approximate bytes of heap the result of `left * right` takes, see lox_add_size
+pub fn lox_multiply_size(left: &Value, right: &Value) -> usize {
+    match (left, right) {
+        // the conversion saturates, and invalid counts are left for lox_multiply to reject
+        (Value::String(s), Value::Number(count)) | (Value::Number(count), Value::String(s)) => {
+            (s.len() as f64 * count.max(0.0)) as usize
+        }
+        _ => 0,
+    }
+}
+
+// Result of `left * right`, None if the operands can not be multiplied. Two numbers are
+// multiplied, and a string and a number in either order repeat the string, e.g. "ab" * 3 is
+// "ababab". The count must be a non-negative integer
+pub fn lox_multiply(left: &Value, right: &Value) -> Result<Option<Value>, String> {
+    match (left, right) {
+        (Value::Number(left), Value::Number(right)) => Ok(Some(Value::Number(left * right))),
+        (Value::String(s), Value::Number(count)) | (Value::Number(count), Value::String(s)) => {
+            repeat(s, *count).map(|repeated| Some(Value::String(repeated)))
+        }
+        _ => Ok(None),
+    }
+}
+
+// Longest string a repetition can create, 1 GiB, so a script can not exhaust the memory of the
+// host with a single expression when no memory budget is set
+const MAX_REPEAT_LENGTH: usize = 1 << 30;
+
+fn repeat(s: &str, count: f64) -> Result<String, String> {
+    if count < 0.0 || count.fract() != 0.0 || !count.is_finite() {
+        return Err(format!(
+            "Strings can only be repeated a non-negative integer number of times, got {}",
+            format_number(count)
+        ));
+    }
+
+    // the length check also rejects counts too large for usize. Lengths below the cap can still
+    // fail to be allocated, which is reported as well rather than aborting the host
+    let length = s.len() as f64 * count;
+    if length > MAX_REPEAT_LENGTH as f64 {
+        return Err(format!(
+            "Repeated string is too long, strings can be repeated up to {MAX_REPEAT_LENGTH} bytes"
+        ));
+    }
+
+    let length = length as usize;
+    let mut repeated: Vec<u8> = Vec::new();
+    repeated
+        .try_reserve_exact(length)
+        .map_err(|_| format!("Not enough memory to repeat a string to {length} bytes"))?;
+
+    // doubling the copies made so far, as str::repeat does
+    if length > 0 {
+        repeated.extend_from_slice(s.as_bytes());
+    }
+    while repeated.len() < length {
+        repeated.extend_from_within(..repeated.len().min(length - repeated.len()));
+    }
+
+    String::from_utf8(repeated).map_err(|e| e.to_string())
+}
+
+// Values a for-in loop goes through, None for the values that can not be iterated:
+// - the elements of a list
+// - the keys of a map, in order
+// - the characters of a string, as strings of one character
+// - the numbers of a range, computed one at a time
+// The values are copies taken before the loop starts, changing the collection in the body does
+// not change the iterations
+pub fn lox_iterate(value: &Value) -> Result<Option<Box<dyn Iterator<Item = Value>>>, String> {
+    let values: Vec<Value> = match value {
+        Value::List(elements) => {
+            let mut values = Vec::with_capacity(elements.len());
+            for element in elements {
+                let element_guard = element.try_borrow().map_err(|e| e.to_string())?;
+                values.push(element_guard.clone());
+            }
+            values
+        }
+        Value::Map(entries) => entries
+            .keys()
+            .map(|key| Value::from(key.as_str()))
+            .collect(),
+        Value::String(s) => s.chars().map(|c| Value::String(c.to_string())).collect(),
+        Value::Range(range) => return Ok(Some(Box::new(range.values().map(Value::Number)))),
+        _ => return Ok(None),
+    };
+
+    Ok(Some(Box::new(values.into_iter())))
+}
+
+// Values considered true by conditions and the logical operators
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Truthiness {
+    // only nil and false are falsy, as in the Lox of the book
+    #[default]
+    Strict,
+    // 0, the empty string, functions and empty lists and maps are falsy too, as in the first
+    // releases of this interpreter
+    Permissive,
+}
+
+impl Truthiness {
+    pub fn is_truthy(&self, value: &Value) -> bool {
+        match self {
+            Truthiness::Strict => is_truthy(value),
+            Truthiness::Permissive => match value {
+                Value::Boolean(b) => *b,
+                Value::Number(n) => *n != 0.0,
+                Value::String(s) => !s.is_empty(),
+                Value::Nil => false,
+                Value::Callable(_) => false,
+                Value::List(l) => !l.is_empty(),
+                Value::Map(m) => !m.is_empty(),
+                Value::Module(_) => true,
+                Value::Range(r) => !r.is_empty(),
+            },
+        }
+    }
+}
+
+// Canonical truthiness: only nil and false are falsy
+pub fn is_truthy(value: &Value) -> bool {
+    !matches!(value, Value::Nil | Value::Boolean(false))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::BTreeMap;
+
+    use rstest::*;
+
+    use crate::lox::{new_value_box, Range, Value};
+
+    use super::{
+        is_truthy, lox_add, lox_compare, lox_equals, lox_iterate, lox_multiply, Comparison,
+        Truthiness,
+    };
+
+    fn range(start: f64, end: f64, inclusive: bool) -> Value {
+        Value::Range(Range {
+            start,
+            end,
+            inclusive,
+        })
+    }
+
+    fn list(elements: &[f64]) -> Value {
+        Value::List(
+            elements
+                .iter()
+                .map(|n| new_value_box(Value::Number(*n)))
+                .collect(),
+        )
+    }
+
+    #[rstest]
+    #[case::numbers(Value::Number(1.0), Value::Number(1.0), true)]
+    #[case::different_numbers(Value::Number(1.0), Value::Number(2.0), false)]
+    #[case::nan(Value::Number(f64::NAN), Value::Number(f64::NAN), false)]
+    #[case::strings(Value::from("a"), Value::from("a"), true)]
+    #[case::booleans(Value::Boolean(false), Value::Boolean(false), true)]
+    #[case::nil(Value::Nil, Value::Nil, true)]
+    #[case::lists(list(&[1.0, 2.0]), list(&[1.0, 2.0]), true)]
+    #[case::different_lists(list(&[1.0]), list(&[1.0, 2.0]), false)]
+    #[case::maps(Value::Map(BTreeMap::new()), Value::Map(BTreeMap::new()), true)]
+    #[case::number_and_string(Value::Number(1.0), Value::from("1"), false)]
+    #[case::zero_and_false(Value::Number(0.0), Value::Boolean(false), false)]
+    #[case::nil_and_false(Value::Nil, Value::Boolean(false), false)]
+    #[case::empty_list_and_nil(list(&[]), Value::Nil, false)]
+    fn test_lox_equals(#[case] left: Value, #[case] right: Value, #[case] expected: bool) {
+        assert_eq!(lox_equals(&left, &right), expected);
+        assert_eq!(lox_equals(&right, &left), expected);
+    }
+
+    #[rstest]
+    #[case::less(Comparison::Less, Value::Number(1.0), Value::Number(2.0), Some(true))]
+    #[case::less_equal(
+        Comparison::LessEqual,
+        Value::Number(2.0),
+        Value::Number(2.0),
+        Some(true)
+    )]
+    #[case::greater(
+        Comparison::Greater,
+        Value::Number(1.0),
+        Value::Number(2.0),
+        Some(false)
+    )]
+    #[case::greater_equal(
+        Comparison::GreaterEqual,
+        Value::Number(2.0),
+        Value::Number(1.0),
+        Some(true)
+    )]
+    #[case::strings(Comparison::Less, Value::from("a"), Value::from("b"), Some(true))]
+    #[case::string_prefix(Comparison::Greater, Value::from("ab"), Value::from("a"), Some(true))]
+    #[case::nan(
+        Comparison::LessEqual,
+        Value::Number(f64::NAN),
+        Value::Number(1.0),
+        Some(false)
+    )]
+    #[case::number_and_string(Comparison::Less, Value::Number(1.0), Value::from("2"), None)]
+    #[case::booleans(Comparison::Less, Value::Boolean(false), Value::Boolean(true), None)]
+    #[case::nil(Comparison::GreaterEqual, Value::Nil, Value::Nil, None)]
+    fn test_lox_compare(
+        #[case] comparison: Comparison,
+        #[case] left: Value,
+        #[case] right: Value,
+        #[case] expected: Option<bool>,
+    ) {
+        assert_eq!(lox_compare(comparison, &left, &right), expected);
+    }
+
+    #[rstest]
+    #[case::numbers(Value::Number(1.0), Value::Number(2.5), Some(Value::Number(3.5)))]
+    #[case::strings(Value::from("a"), Value::from("b"), Some(Value::from("ab")))]
+    #[case::string_number(Value::from("n"), Value::Number(2.0), Some(Value::from("n2")))]
+    #[case::number_string(Value::Number(0.5), Value::from("x"), Some(Value::from("0.5x")))]
+    #[case::lists(list(&[1.0]), list(&[2.0]), Some(list(&[1.0, 2.0])))]
+    #[case::string_boolean(Value::from("a"), Value::Boolean(true), None)]
+    #[case::string_nil(Value::from("a"), Value::Nil, None)]
+    #[case::list_number(list(&[1.0]), Value::Number(2.0), None)]
+    fn test_lox_add(#[case] left: Value, #[case] right: Value, #[case] expected: Option<Value>) {
+        assert_eq!(lox_add(&left, &right), Ok(expected));
+    }
+
+    #[rstest]
+    #[case::numbers(Value::Number(2.0), Value::Number(2.5), Ok(Some(Value::Number(5.0))))]
+    #[case::string_count(Value::from("ab"), Value::Number(3.0), Ok(Some(Value::from("ababab"))))]
+    #[case::count_string(Value::Number(2.0), Value::from("ab"), Ok(Some(Value::from("abab"))))]
+    #[case::zero_count(Value::from("ab"), Value::Number(0.0), Ok(Some(Value::from(""))))]
+    #[case::strings(Value::from("a"), Value::from("b"), Ok(None))]
+    #[case::list(list(&[1.0]), Value::Number(2.0), Ok(None))]
+    #[case::negative_count(
+        Value::from("a"),
+        Value::Number(-1.0),
+        Err("Strings can only be repeated a non-negative integer number of times, got -1")
+    )]
+    #[case::infinite_count(
+        Value::from("a"),
+        Value::Number(f64::INFINITY),
+        Err("Strings can only be repeated a non-negative integer number of times, got inf")
+    )]
+    #[case::too_long(
+        Value::from("ab"),
+        Value::Number(1e300),
+        Err("Repeated string is too long, strings can be repeated up to 1073741824 bytes")
+    )]
+    #[case::beyond_host_memory(
+        Value::from("ab"),
+        Value::Number(1e12),
+        Err("Repeated string is too long, strings can be repeated up to 1073741824 bytes")
+    )]
+    fn test_lox_multiply(
+        #[case] left: Value,
+        #[case] right: Value,
+        #[case] expected: Result<Option<Value>, &str>,
+    ) {
+        assert_eq!(
+            lox_multiply(&left, &right),
+            expected.map_err(|e| e.to_string())
+        );
+    }
+
+    #[rstest]
+    #[case::list(list(&[1.0, 2.0]), Some(vec![Value::Number(1.0), Value::Number(2.0)]))]
+    #[case::empty_list(list(&[]), Some(vec![]))]
+    #[case::map(
+        Value::Map(BTreeMap::from([
+            ("b".to_string(), new_value_box(Value::Nil)),
+            ("a".to_string(), new_value_box(Value::Nil)),
+        ])),
+        Some(vec![Value::from("a"), Value::from("b")])
+    )]
+    #[case::string(Value::from("añ"), Some(vec![Value::from("a"), Value::from("ñ")]))]
+    #[case::range(range(1.0, 3.0, false), Some(vec![Value::Number(1.0), Value::Number(2.0)]))]
+    #[case::inclusive_range(
+        range(1.0, 3.0, true),
+        Some(vec![Value::Number(1.0), Value::Number(2.0), Value::Number(3.0)])
+    )]
+    #[case::number(Value::Number(3.0), None)]
+    #[case::nil(Value::Nil, None)]
+    fn test_lox_iterate(#[case] value: Value, #[case] expected: Option<Vec<Value>>) {
+        let values = lox_iterate(&value).map(|values| values.map(Iterator::collect));
+        assert_eq!(values, Ok(expected));
+    }
+
+    #[rstest]
+    #[case::nil(Value::Nil, false, false)]
+    #[case::true_(Value::Boolean(true), true, true)]
+    #[case::false_(Value::Boolean(false), false, false)]
+    #[case::zero(Value::Number(0.0), true, false)]
+    #[case::number(Value::Number(-1.0), true, true)]
+    #[case::empty_string(Value::from(""), true, false)]
+    #[case::string(Value::from("a"), true, true)]
+    #[case::empty_list(list(&[]), true, false)]
+    #[case::list(list(&[0.0]), true, true)]
+    #[case::empty_map(Value::Map(BTreeMap::new()), true, false)]
+    #[case::range(range(0.0, 1.0, false), true, true)]
+    #[case::empty_range(range(1.0, 1.0, false), true, false)]
+    fn test_is_truthy(#[case] value: Value, #[case] strict: bool, #[case] permissive: bool) {
+        assert_eq!(is_truthy(&value), strict);
+        assert_eq!(Truthiness::Strict.is_truthy(&value), strict);
+        assert_eq!(Truthiness::Permissive.is_truthy(&value), permissive);
+    }
+}
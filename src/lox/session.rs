@@ -0,0 +1,116 @@
+use super::{Interpreter, InterpreterOptions, LoxError, Value};
+
+// Interpreter kept alive across several sources, e.g. by a REPL or a host applying script
+// updates. Results are returned as plain values, detached from the interpreter storage
+pub struct Session {
+    interpreter: Interpreter,
+}
+
+impl Session {
+    pub fn new() -> Self {
+        Self::from_interpreter(Interpreter::new())
+    }
+
+    pub fn with_options(options: InterpreterOptions) -> Self {
+        Self::from_interpreter(Interpreter::with_options(options))
+    }
+
+    pub fn from_interpreter(interpreter: Interpreter) -> Self {
+        Self { interpreter }
+    }
+
+    pub fn get_interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    pub fn get_interpreter_mut(&mut self) -> &mut Interpreter {
+        &mut self.interpreter
+    }
+
+    // Executes the source, the changes made before an error are kept
+    pub fn eval(&mut self, source: &str) -> Result<Value, LoxError> {
+        let result = self.interpreter.execute(source.to_string())?;
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        Ok(result_guard.clone())
+    }
+
+    // Evaluates a single expression, e.g. to read a result of the scripts run so far
+    pub fn eval_expression(&mut self, source: &str) -> Result<Value, LoxError> {
+        self.interpreter.eval_expression(source)
+    }
+
+    // Executes the source as a whole: if it fails, the variables of the session are left as
+    // they were before, see Interpreter::execute_transactional
+    pub fn eval_transactional(&mut self, source: &str) -> Result<Value, LoxError> {
+        let result = self.interpreter.execute_transactional(source.to_string())?;
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        Ok(result_guard.clone())
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rstest::*;
+
+    use crate::lox::Value;
+
+    use super::Session;
+
+    #[rstest]
+    #[case::runtime_error("limit = 0; rules[0] = \"deny\"; var extra = 1; fail();")]
+    #[case::parse_error("limit = 0; var extra = ;")]
+    #[case::nested_function(
+        "fun update() { limit = limit * 2; rules[1] = nil; } update(); update(); rules[5];"
+    )]
+    fn test_eval_transactional_rollback(#[case] update: &str) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a session with some configuration
+        let mut session = Session::new();
+        session.eval("var limit = 10; var rules = [\"allow\", \"log\"];")?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // When applying an update that fails part way
+        let result = session.eval_transactional(update);
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then none of its changes are applied
+        assert!(result.is_err());
+        assert_eq!(session.eval("limit;")?, Value::Number(10.0));
+        assert_eq!(session.eval("rules;")?.to_string(), "[allow, log]");
+        assert!(session.eval("extra;").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_transactional_commit() -> Result<(), String> {
+        let mut session = Session::new();
+        session.eval("var limit = 10;")?;
+
+        assert_eq!(
+            session.eval_transactional("limit = limit + 5;")?,
+            Value::Number(15.0)
+        );
+        assert_eq!(session.eval("limit;")?, Value::Number(15.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_eval_keeps_partial_changes() -> Result<(), String> {
+        let mut session = Session::new();
+        session.eval("var limit = 10;")?;
+
+        assert!(session.eval("limit = 0; fail();").is_err());
+        assert_eq!(session.eval("limit;")?, Value::Number(0.0));
+
+        Ok(())
+    }
+}
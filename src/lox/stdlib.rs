@@ -0,0 +1,54 @@
+use super::{Environment, Value};
+
+pub mod io;
+pub mod list;
+pub mod math;
+pub mod memory;
+pub mod string;
+pub mod types;
+
+// Registers the natives of the standard library modules in the environment. The host I/O
+// natives are not included, see io::register
+pub fn register(environment: &mut dyn Environment) {
+    list::register(environment);
+    math::register(environment);
+    memory::register(environment);
+    string::register(environment);
+    types::register(environment);
+}
+
+///////////////////////////////////////////////////////////////////////////////
+// Argument validation shared by the natives
+
+fn expect_string<'a>(
+    function: &str,
+    arg_name: &str,
+    value: &'a Value,
+) -> Result<&'a String, String> {
+    match value {
+        Value::String(s) => Ok(s),
+        other => Err(format!(
+            "{function}() expects argument '{arg_name}' to be a string, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn expect_number(function: &str, arg_name: &str, value: &Value) -> Result<f64, String> {
+    match value {
+        Value::Number(n) => Ok(*n),
+        other => Err(format!(
+            "{function}() expects argument '{arg_name}' to be a number, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn expect_index(function: &str, arg_name: &str, value: &Value) -> Result<usize, String> {
+    match value {
+        Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Ok(*n as usize),
+        other => Err(format!(
+            "{function}() expects argument '{arg_name}' to be a non-negative integer, got {other}"
+        )),
+    }
+}
@@ -0,0 +1,118 @@
+use std::io::BufRead;
+
+use super::expect_string;
+use crate::lox::{Environment, NativeFunction, Symbol, Value};
+
+// Registers the natives accessing stdin and the filesystem of the host
+pub fn register(environment: &mut dyn Environment) {
+    let natives = [
+        NativeFunction::new("read_line", &[], read_line),
+        NativeFunction::new("read_file", &["path"], read_file),
+        NativeFunction::new("write_file", &["path", "content"], write_file),
+    ];
+
+    for native in natives {
+        environment.define_function(Symbol::new(native.get_name()), Box::new(native));
+    }
+}
+
+// Next line from stdin without the line terminator, nil once stdin is exhausted
+pub fn read_line(_arguments: &[Value]) -> Result<Value, String> {
+    let mut line = String::new();
+
+    let read = std::io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .map_err(|e| format!("read_line() failed: {e}"))?;
+
+    if read == 0 {
+        return Ok(Value::Nil);
+    }
+
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+
+    Ok(Value::String(line))
+}
+
+pub fn read_file(arguments: &[Value]) -> Result<Value, String> {
+    let path = expect_string("read_file", "path", &arguments[0])?;
+
+    std::fs::read_to_string(path)
+        .map(Value::String)
+        .map_err(|e| format!("read_file() failed to read \"{path}\": {e}"))
+}
+
+// Writes the content to the file, replacing it if it exists
+pub fn write_file(arguments: &[Value]) -> Result<Value, String> {
+    let path = expect_string("write_file", "path", &arguments[0])?;
+    let content = expect_string("write_file", "content", &arguments[1])?;
+
+    std::fs::write(path, content)
+        .map(|_| Value::Nil)
+        .map_err(|e| format!("write_file() failed to write \"{path}\": {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rstest::*;
+
+    use crate::lox::{Interpreter, InterpreterOptions, Value};
+
+    #[test]
+    fn test_write_then_read_file() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a path in the temporary directory
+        let path = std::env::temp_dir().join(format!("lox_io_test_{}.txt", std::process::id()));
+        let path = Value::String(path.to_string_lossy().to_string());
+
+        ///////////////////////////////////////////////////////////////////////
+        // When writing a string to the file and reading it back
+        super::write_file(&[path.clone(), Value::String("hello\nlox".to_string())])?;
+        let content = super::read_file(&[path.clone()]);
+
+        if let Value::String(p) = &path {
+            _ = std::fs::remove_file(p);
+        }
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the content read is the content written
+        assert_eq!(content?, Value::String("hello\nlox".to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_missing_file() {
+        let result = super::read_file(&[Value::String("test-data/missing.txt".to_string())]);
+        assert!(result.is_err());
+    }
+
+    #[rstest]
+    #[case::allow_io(InterpreterOptions { allow_io: false, ..Default::default() })]
+    #[case::sandboxed(InterpreterOptions::sandboxed())]
+    fn test_io_disabled(#[case] options: InterpreterOptions) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter with I/O disabled
+        let mut interpreter = Interpreter::with_options(options);
+
+        ///////////////////////////////////////////////////////////////////////
+        // When calling an I/O native
+        let result = interpreter.execute("read_file(\"input.txt\");".to_string());
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the native is not defined, while the rest of the standard library is
+        assert_eq!(
+            result.err().map(String::from),
+            Some("Undefined variable 'read_file'".to_string())
+        );
+        interpreter.execute("len(\"abc\");".to_string())?;
+
+        Ok(())
+    }
+}
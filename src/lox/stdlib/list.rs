@@ -0,0 +1,154 @@
+use crate::lox::{
+    list_heap_size, new_value_box, CallContext, Environment, NativeFunction, Symbol, Value,
+    ValueBox,
+};
+
+pub fn register(environment: &mut dyn Environment) {
+    let natives = [
+        NativeFunction::with_context("map", &["list", "function"], map),
+        NativeFunction::with_context("filter", &["list", "function"], filter),
+    ];
+
+    for native in natives {
+        environment.define_function(Symbol::new(native.get_name()), Box::new(native));
+    }
+}
+
+// New list with the result of calling the function on each element of the list
+pub fn map(context: &mut dyn CallContext, arguments: &[Value]) -> Result<Value, String> {
+    let elements = expect_list("map", &arguments[0])?;
+    context.reserve(list_heap_size(elements.len()))?;
+
+    let mut mapped = Vec::with_capacity(elements.len());
+    for element in elements {
+        let value = context.call(&arguments[1], &[element])?;
+        mapped.push(new_value_box(value));
+    }
+
+    Ok(Value::List(mapped))
+}
+
+// New list with the elements of the list for which the function returns a truthy value
+pub fn filter(context: &mut dyn CallContext, arguments: &[Value]) -> Result<Value, String> {
+    let elements = expect_list("filter", &arguments[0])?;
+
+    let mut filtered = Vec::new();
+    for element in elements {
+        let keep = context.call(&arguments[1], std::slice::from_ref(&element))?;
+        if context.is_truthy(&keep) {
+            filtered.push(new_value_box(element));
+        }
+    }
+
+    Ok(Value::List(filtered))
+}
+
+// Copies of the elements of a list argument, the borrows of the elements are released before
+// calling back into the interpreter
+fn expect_list(function: &str, value: &Value) -> Result<Vec<Value>, String> {
+    match value {
+        Value::List(elements) => elements.iter().map(element_value).collect(),
+        other => Err(format!(
+            "{function}() expects argument 'list' to be a list, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+fn element_value(element: &ValueBox) -> Result<Value, String> {
+    let element_guard = element.try_borrow().map_err(|e| e.to_string())?;
+    Ok(element_guard.clone())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rstest::*;
+
+    use crate::lox::{Interpreter, Value};
+
+    #[rstest]
+    #[case::map_native("", "map([\"a\", \"bc\"], len);", "[1, 2]")]
+    #[case::map_empty("", "map([], len);", "[]")]
+    #[case::map_function("fun f(x) {}", "map([1, 2], f);", "[nil, nil]")]
+    #[case::map_function_globals(
+        "var sum = 0; fun add(x) { sum = sum + x; } map([1, 2, 3], add);",
+        "sum;",
+        "6"
+    )]
+    #[case::filter_native("", "filter([0, nil, \"a\", false], boolean);", "[0, a]")]
+    #[case::filter_function("fun f(x) {}", "filter([1, 2], f);", "[]")]
+    #[case::nested(
+        "var items; fun keep(l) { items = filter(l, boolean); } map([[nil], [\"a\", nil]], keep);",
+        "items;",
+        "[a]"
+    )]
+    fn test_list_natives(
+        #[case] setup: &str,
+        #[case] source: &str,
+        #[case] expected: &str,
+    ) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter with some declarations
+        let mut interpreter = Interpreter::new();
+        interpreter.execute(setup.to_string())?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // When calling a list native with a function
+        let result = interpreter.execute(source.to_string())?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the function is called back for each element
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(result_guard.to_string(), expected);
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::not_a_list(
+        "map(1, len);",
+        "map() expects argument 'list' to be a list, got number"
+    )]
+    #[case::not_callable("filter([1], 2);", "Can only call functions and classes, got number")]
+    #[case::arity("fun f(a, b) {} map([1], f);", "Expected 2 arguments, but got 1")]
+    #[case::callback_error(
+        "map([1], len);",
+        "len() expects argument 's' to be a string or a list, got number"
+    )]
+    fn test_list_natives_errors(#[case] source: &str, #[case] expected: &str) {
+        let mut interpreter = Interpreter::new();
+
+        assert_eq!(
+            interpreter
+                .execute(source.to_string())
+                .err()
+                .map(String::from),
+            Some(expected.to_string())
+        );
+    }
+
+    #[test]
+    fn test_map_assigns_callee() -> Result<(), String> {
+        // the callback can update the variable holding the list being mapped
+        let mut interpreter = Interpreter::new();
+
+        interpreter.execute(
+            "var items = [1, 2]; fun reset(x) { items = nil; } var result = map(items, reset);"
+                .to_string(),
+        )?;
+
+        let result = interpreter.execute("result;".to_string())?;
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(result_guard.to_string(), "[nil, nil]");
+        assert_eq!(
+            *interpreter
+                .execute("items;".to_string())?
+                .try_borrow()
+                .map_err(|e| e.to_string())?,
+            Value::Nil
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,174 @@
+use std::cell::Cell;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::expect_number;
+use crate::lox::{Environment, NativeFunction, Symbol, Value};
+
+pub fn register(environment: &mut dyn Environment) {
+    let natives = [
+        NativeFunction::new("sqrt", &["x"], sqrt),
+        NativeFunction::new("abs", &["x"], abs),
+        NativeFunction::new("floor", &["x"], floor),
+        NativeFunction::new("ceil", &["x"], ceil),
+        NativeFunction::new("min", &["a", "b"], min),
+        NativeFunction::new("max", &["a", "b"], max),
+        NativeFunction::new("pow", &["base", "exponent"], pow),
+        NativeFunction::new("random", &[], random),
+    ];
+
+    for native in natives {
+        environment.define_function(Symbol::new(native.get_name()), Box::new(native));
+    }
+}
+
+pub fn sqrt(arguments: &[Value]) -> Result<Value, String> {
+    let x = expect_number("sqrt", "x", &arguments[0])?;
+
+    if x < 0.0 {
+        return Err(format!("sqrt() of negative number {x}"));
+    }
+
+    Ok(Value::Number(x.sqrt()))
+}
+
+pub fn abs(arguments: &[Value]) -> Result<Value, String> {
+    let x = expect_number("abs", "x", &arguments[0])?;
+    Ok(Value::Number(x.abs()))
+}
+
+pub fn floor(arguments: &[Value]) -> Result<Value, String> {
+    let x = expect_number("floor", "x", &arguments[0])?;
+    Ok(Value::Number(x.floor()))
+}
+
+pub fn ceil(arguments: &[Value]) -> Result<Value, String> {
+    let x = expect_number("ceil", "x", &arguments[0])?;
+    Ok(Value::Number(x.ceil()))
+}
+
+pub fn min(arguments: &[Value]) -> Result<Value, String> {
+    let a = expect_number("min", "a", &arguments[0])?;
+    let b = expect_number("min", "b", &arguments[1])?;
+    Ok(Value::Number(a.min(b)))
+}
+
+pub fn max(arguments: &[Value]) -> Result<Value, String> {
+    let a = expect_number("max", "a", &arguments[0])?;
+    let b = expect_number("max", "b", &arguments[1])?;
+    Ok(Value::Number(a.max(b)))
+}
+
+pub fn pow(arguments: &[Value]) -> Result<Value, String> {
+    let base = expect_number("pow", "base", &arguments[0])?;
+    let exponent = expect_number("pow", "exponent", &arguments[1])?;
+    Ok(Value::Number(base.powf(exponent)))
+}
+
+thread_local! {
+    // xorshift64 state, seeded from the clock the first time random() is called
+    static RANDOM_STATE: Cell<u64> = const { Cell::new(0) };
+}
+
+// Pseudo-random number in the range [0, 1). Not suitable for cryptographic use
+pub fn random(_arguments: &[Value]) -> Result<Value, String> {
+    let next = RANDOM_STATE.with(|state| {
+        let mut x = state.get();
+        if x == 0 {
+            x = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or_default()
+                | 1;
+        }
+
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        state.set(x);
+        x
+    });
+
+    // use the 53 most significant bits as the mantissa of a number in [0, 1)
+    Ok(Value::Number((next >> 11) as f64 / (1u64 << 53) as f64))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rstest::*;
+
+    use super::{abs, ceil, floor, max, min, pow, random, sqrt};
+
+    use crate::lox::{Interpreter, Value};
+
+    fn number(n: f64) -> Value {
+        Value::Number(n)
+    }
+
+    #[rstest]
+    #[case::sqrt(sqrt, vec![number(9.0)], number(3.0))]
+    #[case::abs(abs, vec![number(-2.5)], number(2.5))]
+    #[case::floor(floor, vec![number(1.7)], number(1.0))]
+    #[case::floor_negative(floor, vec![number(-1.2)], number(-2.0))]
+    #[case::ceil(ceil, vec![number(1.2)], number(2.0))]
+    #[case::min(min, vec![number(1.0), number(-1.0)], number(-1.0))]
+    #[case::max(max, vec![number(1.0), number(-1.0)], number(1.0))]
+    #[case::pow(pow, vec![number(2.0), number(10.0)], number(1024.0))]
+    fn test_math_natives(
+        #[case] native: fn(&[Value]) -> Result<Value, String>,
+        #[case] arguments: Vec<Value>,
+        #[case] expected: Value,
+    ) -> Result<(), String> {
+        assert_eq!(native(&arguments)?, expected);
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::sqrt_negative(sqrt, vec![number(-1.0)])]
+    #[case::abs_string(abs, vec![Value::String("1".to_string())])]
+    #[case::min_nil(min, vec![number(1.0), Value::Nil])]
+    #[case::pow_boolean(pow, vec![Value::Boolean(true), number(1.0)])]
+    fn test_math_natives_errors(
+        #[case] native: fn(&[Value]) -> Result<Value, String>,
+        #[case] arguments: Vec<Value>,
+    ) {
+        assert!(native(&arguments).is_err());
+    }
+
+    #[test]
+    fn test_random_range() -> Result<(), String> {
+        for _ in 0..1000 {
+            match random(&[])? {
+                Value::Number(n) => assert!((0.0..1.0).contains(&n), "{n} out of range"),
+                other => return Err(format!("Expected a number, got {other}")),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_interpreter_without_stdlib() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter created without the standard library
+        let mut interpreter = Interpreter::without_stdlib();
+
+        ///////////////////////////////////////////////////////////////////////
+        // When calling a native
+        let result = interpreter.execute("sqrt(4);".to_string());
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the native is not defined
+        assert_eq!(
+            result.err().map(String::from),
+            Some("Undefined variable 'sqrt'".to_string())
+        );
+
+        // while the default interpreter has it registered
+        let result = Interpreter::new().execute("sqrt(4);".to_string())?;
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(*result_guard, number(2.0));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::{BTreeMap, HashSet};
+
+use crate::lox::{
+    new_value_box, visit_reachable, CallContext, Environment, NativeFunction, Symbol, Value,
+    VALUE_BOX_SIZE,
+};
+
+pub fn register(environment: &mut dyn Environment) {
+    environment.define_function(
+        Symbol::new("memory_stats"),
+        Box::new(NativeFunction::with_context(
+            "memory_stats",
+            &[],
+            memory_stats,
+        )),
+    );
+}
+
+// Counts of the runtime structures alive in the environment of the calling interpreter:
+// distinct values reachable from any scope, including list and map elements, variables
+// bound in any scope and number of scopes, and the approximate bytes of heap the values take
+pub fn memory_stats(context: &mut dyn CallContext, _arguments: &[Value]) -> Result<Value, String> {
+    let environment = context.get_environment();
+    let variables = environment.get_all_variables();
+
+    let mut visited = HashSet::new();
+    let mut bytes = 0;
+    for (_, value) in variables.iter() {
+        visit_reachable(value, &mut visited, &mut |_, value| {
+            bytes += VALUE_BOX_SIZE + value.heap_size();
+        })?;
+    }
+
+    let stats = [
+        ("values", visited.len()),
+        ("bytes", bytes),
+        ("variables", variables.len()),
+        ("scopes", environment.get_scope_count()),
+    ];
+
+    Ok(Value::Map(BTreeMap::from_iter(stats.map(
+        |(name, count)| (name.to_string(), new_value_box(Value::Number(count as f64))),
+    ))))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::lox::{Interpreter, Value};
+
+    #[test]
+    fn test_memory_stats() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter and the stats before running a script
+        let mut interpreter = Interpreter::new();
+        let stats = |interpreter: &mut Interpreter, key: &str| -> Result<f64, String> {
+            let result = interpreter.execute(format!("memory_stats()[\"{key}\"];"))?;
+            let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+            match &*result_guard {
+                Value::Number(n) => Ok(*n),
+                other => Err(format!("Expected a number, got {other}")),
+            }
+        };
+
+        let values = stats(&mut interpreter, "values")?;
+        let variables = stats(&mut interpreter, "variables")?;
+        let scopes = stats(&mut interpreter, "scopes")?;
+        let bytes = stats(&mut interpreter, "bytes")?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // When declaring a list of two elements and a number
+        interpreter.execute("var items = [1, 2]; var count = 2;".to_string())?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the stats account for the new variables and their values
+        assert_eq!(stats(&mut interpreter, "values")?, values + 4.0);
+        assert_eq!(stats(&mut interpreter, "variables")?, variables + 2.0);
+        assert_eq!(stats(&mut interpreter, "scopes")?, scopes);
+        assert!(stats(&mut interpreter, "bytes")? > bytes);
+
+        // And the scopes opened by blocks are counted
+        interpreter.execute(
+            "var outer; var inner; fun nested() { outer = memory_stats(); { inner = memory_stats(); } } nested();"
+                .to_string(),
+        )?;
+        let result = interpreter.execute("inner[\"scopes\"] - outer[\"scopes\"];".to_string())?;
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(*result_guard, Value::Number(1.0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_stats_map() -> Result<(), String> {
+        let mut interpreter = Interpreter::new();
+
+        let result = interpreter.execute("typeof(memory_stats());".to_string())?;
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(*result_guard, Value::String("map".to_string()));
+
+        assert_eq!(
+            interpreter
+                .execute("memory_stats()[\"heap\"];".to_string())
+                .err()
+                .map(String::from),
+            Some("Undefined map key 'heap'".to_string())
+        );
+        assert_eq!(
+            interpreter
+                .execute("memory_stats()[0];".to_string())
+                .err()
+                .map(String::from),
+            Some("Map key must be a string, got number".to_string())
+        );
+        assert_eq!(
+            interpreter
+                .execute("memory_stats()[\"values\"] = 1;".to_string())
+                .err()
+                .map(String::from),
+            Some("Maps are read-only".to_string())
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+use super::{expect_index, expect_string};
+use crate::lox::{
+    list_heap_size, new_value_box, CallContext, Environment, NativeFunction, Symbol, Value,
+};
+
+pub fn register(environment: &mut dyn Environment) {
+    let natives = [
+        NativeFunction::new("len", &["s"], len),
+        NativeFunction::new("substr", &["s", "start", "end"], substr),
+        NativeFunction::with_context("upper", &["s"], upper),
+        NativeFunction::with_context("lower", &["s"], lower),
+        NativeFunction::with_context("split", &["s", "separator"], split),
+        NativeFunction::new("char_at", &["s", "index"], char_at),
+    ];
+
+    for native in natives {
+        environment.define_function(Symbol::new(native.get_name()), Box::new(native));
+    }
+}
+
+// Number of characters of a string, or number of elements of a list
+pub fn len(arguments: &[Value]) -> Result<Value, String> {
+    match &arguments[0] {
+        Value::String(s) => Ok(Value::Number(s.chars().count() as f64)),
+        Value::List(l) => Ok(Value::Number(l.len() as f64)),
+        other => Err(format!(
+            "len() expects argument 's' to be a string or a list, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+// Characters of the string in the range [start, end)
+pub fn substr(arguments: &[Value]) -> Result<Value, String> {
+    let s = expect_string("substr", "s", &arguments[0])?;
+    let start = expect_index("substr", "start", &arguments[1])?;
+    let end = expect_index("substr", "end", &arguments[2])?;
+
+    let length = s.chars().count();
+    if start > end || end > length {
+        return Err(format!(
+            "substr() range {start}..{end} out of bounds for string of length {length}"
+        ));
+    }
+
+    Ok(Value::String(
+        s.chars().skip(start).take(end - start).collect(),
+    ))
+}
+
+pub fn upper(context: &mut dyn CallContext, arguments: &[Value]) -> Result<Value, String> {
+    let s = expect_string("upper", "s", &arguments[0])?;
+    context.reserve(s.len())?;
+    Ok(Value::String(s.to_uppercase()))
+}
+
+pub fn lower(context: &mut dyn CallContext, arguments: &[Value]) -> Result<Value, String> {
+    let s = expect_string("lower", "s", &arguments[0])?;
+    context.reserve(s.len())?;
+    Ok(Value::String(s.to_lowercase()))
+}
+
+// List of the substrings of s separated by separator
+pub fn split(context: &mut dyn CallContext, arguments: &[Value]) -> Result<Value, String> {
+    let s = expect_string("split", "s", &arguments[0])?;
+    let separator = expect_string("split", "separator", &arguments[1])?;
+
+    if separator.is_empty() {
+        return Err("split() expects argument 'separator' to be a non-empty string".to_string());
+    }
+
+    let parts = s.matches(separator.as_str()).count() + 1;
+    context.reserve(list_heap_size(parts).saturating_add(s.len()))?;
+
+    Ok(Value::List(
+        s.split(separator.as_str())
+            .map(|part| new_value_box(Value::String(part.to_string())))
+            .collect(),
+    ))
+}
+
+// Single character string at the given index
+pub fn char_at(arguments: &[Value]) -> Result<Value, String> {
+    let s = expect_string("char_at", "s", &arguments[0])?;
+    let index = expect_index("char_at", "index", &arguments[1])?;
+
+    match s.chars().nth(index) {
+        Some(c) => Ok(Value::String(c.to_string())),
+        None => Err(format!(
+            "char_at() index {index} out of bounds for string of length {}",
+            s.chars().count()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rstest::*;
+
+    use crate::lox::{new_value_box, Interpreter, Value};
+
+    fn string(s: &str) -> Value {
+        Value::String(s.to_string())
+    }
+
+    #[rstest]
+    #[case::empty(string(""), Value::Number(0.0))]
+    #[case::string(string("hello"), Value::Number(5.0))]
+    #[case::list(Value::List(vec![new_value_box(Value::Nil)]), Value::Number(1.0))]
+    fn test_len(#[case] s: Value, #[case] expected: Value) -> Result<(), String> {
+        assert_eq!(super::len(&[s])?, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_len_invalid_argument() {
+        assert!(super::len(&[Value::Number(1.0)]).is_err());
+    }
+
+    #[rstest]
+    #[case::whole("hello", 0.0, 5.0, Ok(string("hello")))]
+    #[case::middle("hello", 1.0, 3.0, Ok(string("el")))]
+    #[case::empty("hello", 2.0, 2.0, Ok(string("")))]
+    #[case::end_out_of_bounds("hello", 0.0, 6.0, Err(()))]
+    #[case::start_after_end("hello", 3.0, 2.0, Err(()))]
+    #[case::fractional_index("hello", 0.5, 2.0, Err(()))]
+    fn test_substr(
+        #[case] s: &str,
+        #[case] start: f64,
+        #[case] end: f64,
+        #[case] expected: Result<Value, ()>,
+    ) {
+        let result = super::substr(&[string(s), Value::Number(start), Value::Number(end)]);
+        assert_eq!(result.map_err(|_| ()), expected);
+    }
+
+    #[test]
+    fn test_upper() -> Result<(), String> {
+        let mut context = Interpreter::new();
+        assert_eq!(
+            super::upper(&mut context, &[string("Hello 1")])?,
+            string("HELLO 1")
+        );
+        assert!(super::upper(&mut context, &[Value::Nil]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_lower() -> Result<(), String> {
+        let mut context = Interpreter::new();
+        assert_eq!(
+            super::lower(&mut context, &[string("Hello 1")])?,
+            string("hello 1")
+        );
+        assert!(super::lower(&mut context, &[Value::Boolean(true)]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_split() -> Result<(), String> {
+        let mut context = Interpreter::new();
+        let expected = Value::List(vec![
+            new_value_box(string("a")),
+            new_value_box(string("b")),
+            new_value_box(string("")),
+        ]);
+        assert_eq!(
+            super::split(&mut context, &[string("a, b, "), string(", ")])?,
+            expected
+        );
+        assert!(super::split(&mut context, &[string("a"), string("")]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_char_at() -> Result<(), String> {
+        assert_eq!(
+            super::char_at(&[string("lox"), Value::Number(1.0)])?,
+            string("o")
+        );
+        assert!(super::char_at(&[string("lox"), Value::Number(3.0)]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_natives_registered_in_interpreter() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter
+        let mut interpreter = Interpreter::new();
+
+        ///////////////////////////////////////////////////////////////////////
+        // When calling the string natives from Lox code
+        let result =
+            interpreter.execute("upper(substr(\"lox rust\", 0, 3)) + len(\"ab\");".to_string())?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the natives are executed
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(*result_guard, string("LOX2"));
+
+        Ok(())
+    }
+}
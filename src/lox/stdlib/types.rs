@@ -0,0 +1,190 @@
+use crate::lox::{
+    list_heap_size, lox_iterate, new_value_box, CallContext, Environment, NativeFunction, Symbol,
+    Value,
+};
+
+pub fn register(environment: &mut dyn Environment) {
+    let natives = [
+        NativeFunction::new("typeof", &["value"], type_of),
+        NativeFunction::new("number", &["value"], number),
+        NativeFunction::with_context("boolean", &["value"], boolean),
+        NativeFunction::new("string", &["value"], string),
+        NativeFunction::with_context("list", &["value"], list),
+    ];
+
+    for native in natives {
+        environment.define_function(Symbol::new(native.get_name()), Box::new(native));
+    }
+}
+
+// Replaces the number native by one failing on values without a numeric form instead of
+// returning nil, see InterpreterOptions::strict_conversions
+pub fn register_strict_conversions(environment: &mut dyn Environment) {
+    environment.define_function(
+        Symbol::new("number"),
+        Box::new(NativeFunction::new("number", &["value"], number_strict)),
+    );
+}
+
+// Name of the type of the value, see Value::type_name
+pub fn type_of(arguments: &[Value]) -> Result<Value, String> {
+    Ok(Value::String(arguments[0].type_name().to_string()))
+}
+
+// Numeric form of the value following Value::to_number, nil if it has none
+pub fn number(arguments: &[Value]) -> Result<Value, String> {
+    Ok(arguments[0].to_number().map_or(Value::Nil, Value::Number))
+}
+
+// Same as number, failing if the value has no numeric form
+pub fn number_strict(arguments: &[Value]) -> Result<Value, String> {
+    match (&arguments[0], arguments[0].to_number()) {
+        (_, Some(n)) => Ok(Value::Number(n)),
+        (Value::String(s), None) => {
+            Err(format!("number() cannot convert string '{s}' to a number"))
+        }
+        (other, None) => Err(format!(
+            "number() cannot convert {} to a number",
+            other.type_name()
+        )),
+    }
+}
+
+// Truthiness of the value, the same used by conditions. Note boolean("false") is true
+pub fn boolean(context: &mut dyn CallContext, arguments: &[Value]) -> Result<Value, String> {
+    Ok(Value::Boolean(context.is_truthy(&arguments[0])))
+}
+
+// Text of the value as printed by the print statement
+pub fn string(arguments: &[Value]) -> Result<Value, String> {
+    Ok(Value::String(arguments[0].to_string()))
+}
+
+// List of the values a for-in loop goes through, e.g. list(1..4) is [1, 2, 3]
+pub fn list(context: &mut dyn CallContext, arguments: &[Value]) -> Result<Value, String> {
+    // ranges are the only values whose list can be much larger than themselves
+    if let Value::Range(range) = &arguments[0] {
+        context.reserve(list_heap_size(range.len().try_into().unwrap_or(usize::MAX)))?;
+    }
+
+    match lox_iterate(&arguments[0])? {
+        Some(values) => Ok(Value::List(values.map(new_value_box).collect())),
+        None => Err(format!(
+            "list() cannot convert {} to a list",
+            arguments[0].type_name()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rstest::*;
+
+    use crate::lox::{Interpreter, InterpreterOptions, Value};
+
+    #[rstest]
+    #[case::number("1", "number")]
+    #[case::string("\"a\"", "string")]
+    #[case::boolean("true", "boolean")]
+    #[case::nil("nil", "nil")]
+    #[case::list("[1, 2]", "list")]
+    #[case::range("0..2", "range")]
+    #[case::native_function("len", "function")]
+    #[case::typeof_result("typeof(1)", "string")]
+    fn test_typeof(#[case] expression: &str, #[case] expected: &str) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter
+        let mut interpreter = Interpreter::new();
+
+        ///////////////////////////////////////////////////////////////////////
+        // When calling typeof on the expression
+        let result = interpreter.execute(format!("typeof({expression});"))?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the result is the type name of the value
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(*result_guard, Value::String(expected.to_string()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_typeof_user_function() -> Result<(), String> {
+        let mut interpreter = Interpreter::new();
+        interpreter.execute("fun f() { print 1; }".to_string())?;
+
+        let result = interpreter.execute("typeof(f);".to_string())?;
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(*result_guard, Value::String("function".to_string()));
+
+        Ok(())
+    }
+
+    #[rstest]
+    #[case::number_of_number("number(1.5)", "1.5")]
+    #[case::number_of_string("number(\"1.5\")", "1.5")]
+    #[case::number_of_negative("number(\" -2 \")", "-2")]
+    #[case::number_of_boolean("number(true) + number(false)", "1")]
+    #[case::number_of_word("number(\"abc\")", "nil")]
+    #[case::number_of_exponent("number(\"1e3\")", "nil")]
+    #[case::number_of_trailing_point("number(\"1.\")", "nil")]
+    #[case::number_of_nil("number(nil)", "nil")]
+    #[case::boolean_of_zero("boolean(0)", "true")]
+    #[case::boolean_of_string("boolean(\"false\")", "true")]
+    #[case::boolean_of_nil("boolean(nil)", "false")]
+    #[case::string_of_number("string(2) + \"!\"", "2!")]
+    #[case::string_of_list("string([1, nil])", "[1, nil]")]
+    #[case::round_trip("number(string(0.25))", "0.25")]
+    #[case::list_of_range("list(1..4)", "[1, 2, 3]")]
+    #[case::list_of_inclusive_range("list(0.5..=2)", "[0.5, 1.5]")]
+    #[case::list_of_empty_range("list(3..1)", "[]")]
+    #[case::list_of_string("list(\"ab\")", "[a, b]")]
+    #[case::list_copy("list([1, [2]])", "[1, [2]]")]
+    #[case::string_of_range("string(1..=3)", "1..=3")]
+    fn test_conversions(#[case] expression: &str, #[case] expected: &str) -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given an interpreter
+        let mut interpreter = Interpreter::new();
+
+        ///////////////////////////////////////////////////////////////////////
+        // When converting a value
+        let result = interpreter.execute(format!("{expression};"))?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the result follows the conversion rules
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(result_guard.to_string(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_strict_conversions() -> Result<(), String> {
+        let mut interpreter = Interpreter::with_options(InterpreterOptions {
+            strict_conversions: true,
+            ..Default::default()
+        });
+
+        let result = interpreter.execute("number(\"2\");".to_string())?;
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(*result_guard, Value::Number(2.0));
+
+        assert_eq!(
+            interpreter
+                .execute("number(\"abc\");".to_string())
+                .err()
+                .map(String::from),
+            Some("number() cannot convert string 'abc' to a number".to_string())
+        );
+        assert_eq!(
+            interpreter
+                .execute("number(nil);".to_string())
+                .err()
+                .map(String::from),
+            Some("number() cannot convert nil to a number".to_string())
+        );
+
+        Ok(())
+    }
+}
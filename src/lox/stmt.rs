@@ -1,14 +1,29 @@
-use super::Expr;
+use super::{Expr, Span, Symbol};
 
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Stmt {
     Print(Box<Expr>),
     Expr(Box<Expr>),
-    VarDeclaration(String, Option<Box<Expr>>),
+    VarDeclaration(Symbol, Option<Box<Expr>>, Span), // name, initializer, name span
+    MultiVarDeclaration(Vec<(Symbol, Option<Box<Expr>>, Span)>), // `var a = 1, b;`, in order
+    ConstDeclaration(Symbol, Box<Expr>, Span),       // name, initializer, name span
     Block(Vec<Stmt>),
-    If(Box<Expr>, Box<Stmt>, Option<Box<Stmt>>),
-    While(Box<Expr>, Box<Stmt>),
-    FunctionDeclaration(String, Vec<String>, Box<Stmt>), // name, arguments, body
+    If(Box<Expr>, Box<Stmt>, Option<Box<Stmt>>, Span), // condition, then, else, if span
+    While(Box<Expr>, Box<Stmt>, Span),                 // condition, body, while keyword span
+    DoWhile(Box<Stmt>, Box<Expr>, Span),               // body, condition, do keyword span
+    ForIn(Symbol, Box<Expr>, Box<Stmt>),               // loop variable, collection, body
+    // subject, cases with their case keyword span, default
+    Switch(
+        Box<Expr>,
+        Vec<(Box<Expr>, Vec<Stmt>, Span)>,
+        Option<Vec<Stmt>>,
+    ),
+    FunctionDeclaration(Symbol, Vec<Symbol>, Box<Stmt>), // name, arguments, body
+    Throw(Box<Expr>, Span),                              // thrown value, throw keyword span
+    Try(Vec<Stmt>, Symbol, Vec<Stmt>),                   // body, name of the caught value, handler
+    Assert(Box<Expr>, Option<Box<Expr>>, Span),          // condition, message, assert keyword span
+    Import(String, Option<Symbol>),                      // module path, alias
 }
 
 impl Stmt {
@@ -16,17 +31,32 @@ impl Stmt {
         match self {
             Stmt::Print(expr) => visitor.visit_print(expr),
             Stmt::Expr(expr) => visitor.visit_expr(expr),
-            Stmt::VarDeclaration(name, initializer) => {
-                visitor.visit_var_declaration(name, initializer)
+            Stmt::VarDeclaration(name, initializer, span) => {
+                visitor.visit_var_declaration(name, initializer, span)
+            }
+            Stmt::MultiVarDeclaration(declarations) => {
+                visitor.visit_multi_var_declaration(declarations)
+            }
+            Stmt::ConstDeclaration(name, initializer, span) => {
+                visitor.visit_const_declaration(name, initializer, span)
             }
             Stmt::Block(stmts) => visitor.visit_block(stmts),
-            Stmt::If(condition, then_branch, else_branch) => {
-                visitor.visit_if(condition, then_branch, else_branch)
+            Stmt::If(condition, then_branch, else_branch, span) => {
+                visitor.visit_if(condition, then_branch, else_branch, span)
             }
-            Stmt::While(condition, body) => visitor.visit_while(condition, body),
+            Stmt::While(condition, body, span) => visitor.visit_while(condition, body, span),
+            Stmt::DoWhile(body, condition, span) => visitor.visit_do_while(body, condition, span),
+            Stmt::ForIn(name, collection, body) => visitor.visit_for_in(name, collection, body),
+            Stmt::Switch(subject, cases, default) => visitor.visit_switch(subject, cases, default),
             Stmt::FunctionDeclaration(name, arguments, body) => {
                 visitor.visit_function_declaration(name, arguments, body)
             }
+            Stmt::Throw(expr, span) => visitor.visit_throw(expr, span),
+            Stmt::Try(body, name, handler) => visitor.visit_try(body, name, handler),
+            Stmt::Assert(condition, message, span) => {
+                visitor.visit_assert(condition, message, span)
+            }
+            Stmt::Import(path, alias) => visitor.visit_import(path, alias),
         }
     }
 }
@@ -34,19 +64,201 @@ impl Stmt {
 pub trait StmtVisitor<T> {
     fn visit_print(&mut self, expr: &Box<Expr>) -> T;
     fn visit_expr(&mut self, expr: &Box<Expr>) -> T;
-    fn visit_var_declaration(&mut self, name: &String, initializer: &Option<Box<Expr>>) -> T;
+    fn visit_var_declaration(
+        &mut self,
+        name: &Symbol,
+        initializer: &Option<Box<Expr>>,
+        span: &Span,
+    ) -> T;
+    fn visit_multi_var_declaration(
+        &mut self,
+        declarations: &Vec<(Symbol, Option<Box<Expr>>, Span)>,
+    ) -> T;
+    fn visit_const_declaration(&mut self, name: &Symbol, initializer: &Box<Expr>, span: &Span)
+        -> T;
     fn visit_block(&mut self, stmts: &Vec<Stmt>) -> T;
     fn visit_if(
         &mut self,
         condition: &Box<Expr>,
         then_branch: &Box<Stmt>,
         else_branch: &Option<Box<Stmt>>,
+        span: &Span,
+    ) -> T;
+    fn visit_while(&mut self, condition: &Box<Expr>, body: &Box<Stmt>, span: &Span) -> T;
+    fn visit_do_while(&mut self, body: &Box<Stmt>, condition: &Box<Expr>, span: &Span) -> T;
+    fn visit_for_in(&mut self, name: &Symbol, collection: &Box<Expr>, body: &Box<Stmt>) -> T;
+    fn visit_switch(
+        &mut self,
+        subject: &Box<Expr>,
+        cases: &Vec<(Box<Expr>, Vec<Stmt>, Span)>,
+        default: &Option<Vec<Stmt>>,
     ) -> T;
-    fn visit_while(&mut self, condition: &Box<Expr>, body: &Box<Stmt>) -> T;
     fn visit_function_declaration(
         &mut self,
-        name: &String,
-        arguments: &Vec<String>,
+        name: &Symbol,
+        arguments: &Vec<Symbol>,
         body: &Box<Stmt>,
     ) -> T;
+    fn visit_throw(&mut self, expr: &Box<Expr>, span: &Span) -> T;
+    fn visit_try(&mut self, body: &Vec<Stmt>, name: &Symbol, handler: &Vec<Stmt>) -> T;
+    fn visit_assert(
+        &mut self,
+        condition: &Box<Expr>,
+        message: &Option<Box<Expr>>,
+        span: &Span,
+    ) -> T;
+    fn visit_import(&mut self, path: &String, alias: &Option<Symbol>) -> T;
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::BTreeSet;
+    use std::path::PathBuf;
+
+    use crate::lox::{self, Analyzer, AstPrinter, AstPrinterOptions, AstStyle, Formatter};
+
+    use super::Stmt;
+
+    // Program with a statement of every kind
+    const ALL_STATEMENTS: &str = r#"
+import "counter.lox" as c;
+var a = 1;
+var b = 2, d;
+const e = 3;
+print a;
+a + b;
+{ var f = a; }
+if (a < b) print a; else print b;
+while (a < 3) a = a + 1;
+do print a; while (a > 3);
+for (x in [1, 2]) print x;
+switch (a) { case 1: print a; default: print b; }
+try { throw a; } catch (x) { print x; }
+assert a > 0, "positive";
+fun g(x, y) { print x + y; }
+"#;
+
+    const VARIANTS: [&str; 16] = [
+        "print",
+        "expr",
+        "var",
+        "multi_var",
+        "const",
+        "block",
+        "if",
+        "while",
+        "do_while",
+        "for_in",
+        "switch",
+        "throw",
+        "try",
+        "assert",
+        "function",
+        "import",
+    ];
+
+    // The match has no wildcard, so a new variant does not compile until it is named here
+    fn variant(stmt: &Stmt) -> &'static str {
+        match stmt {
+            Stmt::Print(_) => "print",
+            Stmt::Expr(_) => "expr",
+            Stmt::VarDeclaration(_, _, _) => "var",
+            Stmt::MultiVarDeclaration(_) => "multi_var",
+            Stmt::ConstDeclaration(_, _, _) => "const",
+            Stmt::Block(_) => "block",
+            Stmt::If(_, _, _, _) => "if",
+            Stmt::While(_, _, _) => "while",
+            Stmt::DoWhile(_, _, _) => "do_while",
+            Stmt::ForIn(_, _, _) => "for_in",
+            Stmt::Switch(_, _, _) => "switch",
+            Stmt::Throw(_, _) => "throw",
+            Stmt::Try(_, _, _) => "try",
+            Stmt::Assert(_, _, _) => "assert",
+            Stmt::FunctionDeclaration(_, _, _) => "function",
+            Stmt::Import(_, _) => "import",
+        }
+    }
+
+    fn collect_variants(stmts: &[Stmt], variants: &mut BTreeSet<&'static str>) {
+        for stmt in stmts {
+            variants.insert(variant(stmt));
+            match stmt {
+                Stmt::Block(stmts) => collect_variants(stmts, variants),
+                Stmt::If(_, then_branch, else_branch, _) => {
+                    collect_variants(std::slice::from_ref(then_branch), variants);
+                    if let Some(else_branch) = else_branch {
+                        collect_variants(std::slice::from_ref(else_branch), variants);
+                    }
+                }
+                Stmt::While(_, body, _)
+                | Stmt::DoWhile(body, _, _)
+                | Stmt::ForIn(_, _, body)
+                | Stmt::FunctionDeclaration(_, _, body) => {
+                    collect_variants(std::slice::from_ref(body), variants)
+                }
+                Stmt::Switch(_, cases, default) => {
+                    for (_, body, _) in cases {
+                        collect_variants(body, variants);
+                    }
+                    if let Some(default) = default {
+                        collect_variants(default, variants);
+                    }
+                }
+                Stmt::Try(body, _, handler) => {
+                    collect_variants(body, variants);
+                    collect_variants(handler, variants);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_every_statement_visited() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a program using every kind of statement
+        let program = lox::parse(ALL_STATEMENTS)?;
+
+        let mut variants = BTreeSet::new();
+        collect_variants(&program.statements, &mut variants);
+        assert_eq!(variants, BTreeSet::from(VARIANTS));
+
+        ///////////////////////////////////////////////////////////////////////
+        // When running it through every visitor
+        let mut printer = AstPrinter::new();
+        let printed = printer.print(&program.statements);
+
+        let mut tree_printer = AstPrinter::with_options(AstPrinterOptions {
+            style: AstStyle::Tree,
+            spans: false,
+        });
+        let tree = tree_printer.print(&program.statements);
+
+        let formatted = Formatter::format_source(ALL_STATEMENTS)?;
+        let reparsed = lox::parse(&formatted)?;
+
+        let lints = Analyzer::analyze(&program.statements);
+
+        let mut interpreter = lox::Interpreter::new();
+        interpreter.set_output(Box::new(std::io::sink()));
+        interpreter.set_module_search_paths(vec![PathBuf::from("test-data/modules")]);
+        interpreter.execute_program(&program)?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then every statement is handled: printed, formatted back to the same program and
+        // executed
+        assert_eq!(printed.lines().count(), program.statements.len());
+        assert_eq!(
+            tree.lines().filter(|line| !line.starts_with(' ')).count(),
+            program.statements.len()
+        );
+        assert_eq!(printer.print(&reparsed.statements), printed);
+        assert_eq!(lints, vec![]);
+
+        let a = interpreter.execute("a;".to_string())?;
+        assert_eq!(*a.borrow(), lox::Value::Number(3.0));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,198 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    fmt::{Debug, Display},
+    hash::{Hash, Hasher},
+    ops::Deref,
+    rc::Rc,
+};
+
+// Size of the interner above which the names no longer used by any symbol are dropped
+const MIN_PURGE_SIZE: usize = 1024;
+
+thread_local! {
+    // Names interned by the thread. Symbols are not shared between threads, like the values
+    // and environments they are used with
+    static INTERNER: RefCell<Interner> = RefCell::new(Interner::default());
+}
+
+#[derive(Default)]
+struct Interner {
+    names: HashSet<Rc<str>>,
+    // size the set can grow to before it is purged, doubled after each purge so interning
+    // stays constant time on average
+    purge_size: usize,
+}
+
+impl Interner {
+    fn intern(&mut self, name: &str) -> Rc<str> {
+        if let Some(interned) = self.names.get(name) {
+            return interned.clone();
+        }
+
+        if self.names.len() >= self.purge_size {
+            // names only referred to by the set are not used by any symbol, they can be given a
+            // new string when interned again as no symbol is left to compare with
+            self.names.retain(|name| Rc::strong_count(name) > 1);
+            self.purge_size = MIN_PURGE_SIZE.max(self.names.len() * 2);
+        }
+
+        let interned: Rc<str> = Rc::from(name);
+        self.names.insert(interned.clone());
+        interned
+    }
+}
+
+// Interned identifier. Symbols are cheap to clone, and two symbols with the same name share the
+// same string, so comparing and hashing them only looks at its address. Names are freed once the
+// last symbol using them is dropped
+#[derive(Clone)]
+pub struct Symbol(Rc<str>);
+
+impl Symbol {
+    // Symbol for the name, interning it the first time it is seen
+    pub fn new(name: &str) -> Self {
+        Symbol(INTERNER.with(|interner| interner.borrow_mut().intern(name)))
+    }
+
+    // Symbol for the name if it is interned. Used for lookups by names that may have never been
+    // declared, no variable can be bound to a name that is not interned
+    pub fn find(name: &str) -> Option<Self> {
+        INTERNER.with(|interner| interner.borrow().names.get(name).cloned().map(Symbol))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_ptr().hash(state);
+    }
+}
+
+// Ordered by name, so sorted symbols do not depend on the order they were interned in
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::new(name)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Debug for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Symbol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Symbol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Symbol::new(&name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::HashMap;
+
+    use super::Symbol;
+
+    #[test]
+    fn test_symbol_interning() {
+        let a = Symbol::new("counter");
+        let b = Symbol::new(&String::from("counter"));
+        let c = Symbol::new("count");
+
+        assert_eq!(a, b);
+        assert!(std::ptr::eq(a.as_str(), b.as_str()));
+        assert_ne!(a, c);
+        assert_eq!(a, "counter");
+        assert!(c < a);
+        assert_eq!(Symbol::find("counter"), Some(a));
+        assert_eq!(Symbol::find("never interned"), None);
+    }
+
+    #[test]
+    fn test_symbol_map_keys() {
+        let mut map = HashMap::new();
+        map.insert(Symbol::new("x"), 1);
+        map.insert(Symbol::new("y"), 2);
+
+        assert_eq!(map.get(&Symbol::new("x")), Some(&1));
+        assert_eq!(map.get(&Symbol::new("z")), None);
+    }
+
+    #[test]
+    fn test_symbol_release() {
+        ///////////////////////////////////////////////////////////////////////
+        // Given a name only used by a dropped symbol
+        drop(Symbol::new("temporary"));
+
+        ///////////////////////////////////////////////////////////////////////
+        // When interning enough other names to purge the interner
+        let names: Vec<Symbol> = (0..2 * super::MIN_PURGE_SIZE)
+            .map(|i| Symbol::new(&format!("name{i}")))
+            .collect();
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then the dropped name is released while the ones in use are kept
+        assert_eq!(Symbol::find("temporary"), None);
+        assert_eq!(Symbol::find("name0"), Some(names[0].clone()));
+    }
+}
@@ -0,0 +1,282 @@
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::{Interpreter, InterpreterOptions, Stmt};
+
+// Runs the tests written in Lox: the functions named `test_*` without parameters declared at the
+// top level of the `*_test.lox` files. Each test runs in a new interpreter, which executes the
+// whole file before calling the test, so tests do not see each other's changes
+
+const TEST_FILE_SUFFIX: &str = "_test.lox";
+const TEST_FUNCTION_PREFIX: &str = "test_";
+
+// Outcome of a single test
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestResult {
+    pub file: PathBuf,
+    // name of the test function, empty if the file itself could not be parsed
+    pub name: String,
+    // output printed by the test, including the top level of its file
+    pub output: String,
+    // error that made the test fail, e.g. a failed assertion
+    pub error: Option<String>,
+}
+
+impl TestResult {
+    pub fn passed(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+impl Display for TestResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = if self.passed() { "PASS" } else { "FAIL" };
+        write!(f, "{status} {}", self.file.display())?;
+
+        if !self.name.is_empty() {
+            write!(f, "::{}", self.name)?;
+        }
+
+        match &self.error {
+            Some(error) => write!(f, ": {error}"),
+            None => Ok(()),
+        }
+    }
+}
+
+// Results of all the tests run, in the order the files were found
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TestReport {
+    pub results: Vec<TestResult>,
+}
+
+impl TestReport {
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|result| result.passed()).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.results.len() - self.passed()
+    }
+
+    pub fn success(&self) -> bool {
+        self.failed() == 0
+    }
+
+    // One line summary, e.g. "3 passed, 1 failed"
+    pub fn summary(&self) -> String {
+        format!("{} passed, {} failed", self.passed(), self.failed())
+    }
+}
+
+// Test files among the given paths. Directories are searched recursively for `*_test.lox`
+// files, files are taken as given
+pub fn discover_tests(paths: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+
+    for path in paths {
+        if path.is_dir() {
+            discover_in_directory(path, &mut files)?;
+        } else if path.is_file() {
+            files.push(path.clone());
+        } else {
+            return Err(format!("Test path '{}' not found", path.display()));
+        }
+    }
+
+    Ok(files)
+}
+
+fn discover_in_directory(directory: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = std::fs::read_dir(directory)
+        .map_err(|e| format!("Error reading '{}': {e}", directory.display()))?;
+
+    // sorted for the tests to run in the same order everywhere
+    let mut paths = entries
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Error reading '{}': {e}", directory.display()))?;
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() {
+            discover_in_directory(&path, files)?;
+        } else if path
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().ends_with(TEST_FILE_SUFFIX))
+        {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+// Discovers and runs the tests of the given paths with the given interpreter options
+pub fn run_tests(paths: &[PathBuf], options: InterpreterOptions) -> Result<TestReport, String> {
+    let mut report = TestReport::default();
+
+    for file in discover_tests(paths)? {
+        report
+            .results
+            .extend(run_test_file(&file, options.clone())?);
+    }
+
+    Ok(report)
+}
+
+// Runs the tests of a single file, a file that does not parse is a single failed result
+pub fn run_test_file(file: &Path, options: InterpreterOptions) -> Result<Vec<TestResult>, String> {
+    let source = std::fs::read_to_string(file)
+        .map_err(|e| format!("Error reading '{}': {e}", file.display()))?;
+
+    let tests = match test_functions(&source) {
+        Ok(tests) => tests,
+        Err(error) => {
+            return Ok(vec![TestResult {
+                file: file.to_path_buf(),
+                name: String::new(),
+                output: String::new(),
+                error: Some(error),
+            }])
+        }
+    };
+
+    let results = tests
+        .into_iter()
+        .map(|name| run_test(file, &source, name, options.clone()))
+        .collect();
+
+    Ok(results)
+}
+
+// Names of the test functions declared at the top level of the source, in declaration order
+fn test_functions(source: &str) -> Result<Vec<String>, String> {
+    let program = super::parse(source)?;
+
+    let tests = program
+        .statements
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::FunctionDeclaration(name, arguments, _)
+                if arguments.is_empty() && name.as_str().starts_with(TEST_FUNCTION_PREFIX) =>
+            {
+                Some(name.to_string())
+            }
+            _ => None,
+        })
+        .collect();
+
+    Ok(tests)
+}
+
+fn run_test(file: &Path, source: &str, name: String, options: InterpreterOptions) -> TestResult {
+    let output = CapturedOutput::default();
+
+    let mut interpreter = Interpreter::with_options(options);
+    interpreter.set_output(Box::new(output.clone()));
+
+    // imports are looked up next to the test file first, as for scripts run by the CLI
+    let directory = file.parent().map(Path::to_path_buf).unwrap_or_default();
+    interpreter.set_module_search_paths(vec![directory, PathBuf::from(".")]);
+
+    let error = interpreter
+        .execute(source.to_string())
+        .and_then(|_| interpreter.execute(format!("{name}();")))
+        .err()
+        .map(String::from);
+
+    TestResult {
+        file: file.to_path_buf(),
+        name,
+        output: output.contents(),
+        error,
+    }
+}
+
+// Writer keeping what a program prints, readable after handing it to the interpreter
+#[derive(Clone, Default)]
+pub struct CapturedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl Write for CapturedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl CapturedOutput {
+    pub fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.borrow()).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::path::PathBuf;
+
+    use crate::lox::InterpreterOptions;
+
+    use super::{discover_tests, run_tests};
+
+    #[test]
+    fn test_discover_tests() -> Result<(), String> {
+        let files = discover_tests(&[PathBuf::from("test-data/test_runner")])?;
+
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from("test-data/test_runner/math_test.lox"),
+                PathBuf::from("test-data/test_runner/nested/strings_test.lox"),
+                PathBuf::from("test-data/test_runner/syntax_error_test.lox"),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_tests() -> Result<(), String> {
+        ///////////////////////////////////////////////////////////////////////
+        // Given test files with passing and failing tests
+        let paths = [PathBuf::from("test-data/test_runner")];
+
+        ///////////////////////////////////////////////////////////////////////
+        // When running them
+        let report = run_tests(&paths, InterpreterOptions::default())?;
+
+        ///////////////////////////////////////////////////////////////////////
+        // Then each test function has a result with its output and failure
+        let results: Vec<String> = report.results.iter().map(|r| r.to_string()).collect();
+        assert_eq!(
+            results,
+            vec![
+                "PASS test-data/test_runner/math_test.lox::test_add",
+                "FAIL test-data/test_runner/math_test.lox::test_divide: Assertion failed: 1 / 4 is 0.25 at line 17",
+                "PASS test-data/test_runner/math_test.lox::test_isolated",
+                "PASS test-data/test_runner/nested/strings_test.lox::test_concat",
+                "FAIL test-data/test_runner/syntax_error_test.lox: Expected ';' after expression.",
+            ]
+        );
+        assert_eq!(report.results[1].output, "setup\ndividing\n");
+        assert_eq!(report.summary(), "3 passed, 2 failed");
+        assert!(!report.success());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_path() {
+        assert_eq!(
+            discover_tests(&[PathBuf::from("test-data/test_runner/missing")]),
+            Err("Test path 'test-data/test_runner/missing' not found".to_string())
+        );
+    }
+}
@@ -1,6 +1,9 @@
 use std::fmt::Display;
 
+use super::Symbol;
+
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Token {
     ///////////////////////////////////////////////////////////////////////////
     // single-character tokens
@@ -8,8 +11,11 @@ pub enum Token {
     RightParenthesis,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
     Dot,
+    Colon,
     Semicolon,
     Plus,
     Minus,
@@ -27,35 +33,127 @@ pub enum Token {
     GreaterEqual, // >=
     BangEqual,    // !=
 
+    // range operators
+    DotDot,      // ..
+    DotDotEqual, // ..=
+
     ///////////////////////////////////////////////////////////////////////////
     // keywords
     And,
+    Assert,
+    Case,
+    Catch,
     Class,
+    Const,
+    Default,
+    Do,
     Else,
     False,
     Fun,
     For,
     If,
+    Import,
+    In,
     Nil,
     Or,
     Print,
     Return,
     Super,
+    Switch,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
 
     ///////////////////////////////////////////////////////////////////////////
     /// Literals
     StringLiteral(String),
+    // """...""" string, it can contain quotes
+    RawStringLiteral(String),
     NumberLiteral(f64),
-    Identifier(String),
+    Identifier(Symbol),
 
     // end of file
     Eof,
 }
 
+// Keywords of the language and their corresponding tokens. Single source for the scanner,
+// Display and TryFrom, both lookup directions go through this table.
+const KEYWORDS: [(&str, Token); 27] = [
+    ("and", Token::And),
+    ("assert", Token::Assert),
+    ("case", Token::Case),
+    ("catch", Token::Catch),
+    ("class", Token::Class),
+    ("const", Token::Const),
+    ("default", Token::Default),
+    ("do", Token::Do),
+    ("else", Token::Else),
+    ("false", Token::False),
+    ("fun", Token::Fun),
+    ("for", Token::For),
+    ("if", Token::If),
+    ("import", Token::Import),
+    ("in", Token::In),
+    ("nil", Token::Nil),
+    ("or", Token::Or),
+    ("print", Token::Print),
+    ("return", Token::Return),
+    ("super", Token::Super),
+    ("switch", Token::Switch),
+    ("this", Token::This),
+    ("throw", Token::Throw),
+    ("true", Token::True),
+    ("try", Token::Try),
+    ("var", Token::Var),
+    ("while", Token::While),
+];
+
+impl Token {
+    // Keyword token for the given word, None if the word is not a keyword
+    pub fn from_keyword(word: &str) -> Option<Token> {
+        KEYWORDS
+            .iter()
+            .find(|(keyword, _)| *keyword == word)
+            .map(|(_, token)| token.clone())
+    }
+
+    // Source text of a keyword token, None if the token is not a keyword
+    pub fn keyword(&self) -> Option<&'static str> {
+        KEYWORDS
+            .iter()
+            .find(|(_, token)| token == self)
+            .map(|(keyword, _)| *keyword)
+    }
+
+    pub fn keywords() -> impl Iterator<Item = &'static str> {
+        KEYWORDS.iter().map(|(keyword, _)| *keyword)
+    }
+}
+
+// Location of a token in the source code
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub line: u64, // 1-based, 0 when the location is unknown
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}", self.line)
+    }
+}
+
+// Token together with its location in the source
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+}
+
 impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -64,8 +162,11 @@ impl Display for Token {
             Token::RightParenthesis => write!(f, ")"),
             Token::LeftBrace => write!(f, "{{"),
             Token::RightBrace => write!(f, "}}"),
+            Token::LeftBracket => write!(f, "["),
+            Token::RightBracket => write!(f, "]"),
             Token::Comma => write!(f, ","),
             Token::Dot => write!(f, "."),
+            Token::Colon => write!(f, ":"),
             Token::Semicolon => write!(f, ";"),
             Token::Bang => write!(f, "!"),
             Token::Plus => write!(f, "+"),
@@ -80,31 +181,19 @@ impl Display for Token {
             Token::BangEqual => write!(f, "!="),
             Token::LessEqual => write!(f, "<="),
             Token::GreaterEqual => write!(f, ">="),
+            Token::DotDot => write!(f, ".."),
+            Token::DotDotEqual => write!(f, "..="),
 
             // literals
             Token::StringLiteral(s) => write!(f, "\"{}\"", s),
+            Token::RawStringLiteral(s) => write!(f, "\"\"\"{s}\"\"\""),
             Token::NumberLiteral(n) => write!(f, "{}", n),
             Token::Identifier(s) => write!(f, "{}", s),
 
-            // keywords
-            Token::And => write!(f, "and"),
-            Token::Class => write!(f, "class"),
-            Token::Else => write!(f, "else"),
-            Token::False => write!(f, "false"),
-            Token::Fun => write!(f, "fun"),
-            Token::For => write!(f, "for"),
-            Token::If => write!(f, "if"),
-            Token::Nil => write!(f, "nil"),
-            Token::Or => write!(f, "or"),
-            Token::Print => write!(f, "print"),
-            Token::Return => write!(f, "return"),
-            Token::Super => write!(f, "super"),
-            Token::This => write!(f, "this"),
-            Token::True => write!(f, "true"),
-            Token::Var => write!(f, "var"),
-            Token::While => write!(f, "while"),
-
             Token::Eof => write!(f, ""),
+
+            // keywords
+            keyword => write!(f, "{}", keyword.keyword().unwrap_or_default()),
         }
     }
 }
@@ -113,9 +202,15 @@ impl TryFrom<&str> for Token {
     type Error = String;
 
     fn try_from(value: &str) -> Result<Self, Self::Error> {
+        // raw string literal
+        if value.len() >= 6 && value.starts_with("\"\"\"") && value.ends_with("\"\"\"") {
+            return Ok(Token::RawStringLiteral(
+                value[3..value.len() - 3].to_string(),
+            ));
+        }
+
         // string literal
         if value.starts_with("\"") {
-            println!("value: {} : {}", value, value.len());
             if !(value.chars().count() > 1) {
                 return Err(format!(
                     "String literal len must be greater than 1, got {} : {}",
@@ -130,7 +225,6 @@ impl TryFrom<&str> for Token {
 
             let s = value[1..value.len() - 1].to_string();
 
-            println!("PROCESSED LITERAL: {}", s);
             return Ok(Token::StringLiteral(s));
         }
 
@@ -140,6 +234,11 @@ impl TryFrom<&str> for Token {
             return Ok(Token::NumberLiteral(n));
         }
 
+        // keywords, written with the kw: prefix to tell them apart from identifiers
+        if let Some(word) = value.strip_prefix("kw:") {
+            return Token::from_keyword(word).ok_or(format!("Unknown keyword: {}", word));
+        }
+
         match value {
             "+" => Ok(Token::Plus),
             "-" => Ok(Token::Minus),
@@ -157,33 +256,45 @@ impl TryFrom<&str> for Token {
             ")" => Ok(Token::RightParenthesis),
             "{" => Ok(Token::LeftBrace),
             "}" => Ok(Token::RightBrace),
+            "[" => Ok(Token::LeftBracket),
+            "]" => Ok(Token::RightBracket),
             "," => Ok(Token::Comma),
             "." => Ok(Token::Dot),
+            ".." => Ok(Token::DotDot),
+            "..=" => Ok(Token::DotDotEqual),
+            ":" => Ok(Token::Colon),
             ";" => Ok(Token::Semicolon),
-            "kw:and" => Ok(Token::And),
-            "kw:class" => Ok(Token::Class),
-            "kw:else" => Ok(Token::Else),
-            "kw:false" => Ok(Token::False),
-            "kw:fun" => Ok(Token::Fun),
-            "kw:for" => Ok(Token::For),
-            "kw:if" => Ok(Token::If),
-            "kw:nil" => Ok(Token::Nil),
-            "kw:or" => Ok(Token::Or),
-            "kw:print" => Ok(Token::Print),
-            "kw:return" => Ok(Token::Return),
-            "kw:super" => Ok(Token::Super),
-            "kw:this" => Ok(Token::This),
-            "kw:true" => Ok(Token::True),
-            "kw:var" => Ok(Token::Var),
-            "kw:while" => Ok(Token::While),
             identifier
                 if identifier
                     .chars()
                     .all(|c| c.is_ascii_alphanumeric() || c == '_') =>
             {
-                Ok(Token::Identifier(identifier.to_string()))
+                Ok(Token::Identifier(Symbol::new(identifier)))
             }
             _ => Err(format!("Unknown token: len: {} : {}", value.len(), value)),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::Token;
+    use crate::lox::Symbol;
+
+    #[test]
+    fn test_keyword_table_round_trip() -> Result<(), String> {
+        for keyword in Token::keywords() {
+            let token = Token::from_keyword(keyword).ok_or(format!("Missing keyword {keyword}"))?;
+
+            assert_eq!(token.keyword(), Some(keyword));
+            assert_eq!(token.to_string(), keyword);
+            assert_eq!(Token::try_from(format!("kw:{keyword}").as_str())?, token);
+        }
+
+        assert_eq!(Token::from_keyword("lox"), None);
+        assert_eq!(Token::Identifier(Symbol::new("and")).keyword(), None);
+
+        Ok(())
+    }
+}
@@ -1,58 +1,377 @@
-use std::{fmt::Display, rc::Rc, sync::Arc, sync::RwLock};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
+    rc::Rc,
+};
 
-use super::Stmt;
+use super::{Environment, FunctionSignature, ModuleEnvironment, Stmt, Symbol};
 
 // Possible value types allowed in Lox
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Value {
     Number(f64),
     String(String),
     Boolean(bool),
     Callable(Rc<Box<dyn Callable>>),
+    List(Vec<ValueBox>),
+    // read-only string keyed records created by natives, ordered by key
+    Map(BTreeMap<String, ValueBox>),
+    Module(Rc<Module>),
+    Range(Range),
     Nil,
 }
 
+// Numbers from start counting by one while below end, or up to end for inclusive ranges
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Range {
+    pub start: f64,
+    pub end: f64,
+    pub inclusive: bool,
+}
+
+impl Range {
+    // Number of values in the range, saturating for the ranges without an end
+    pub fn len(&self) -> u64 {
+        let distance = self.end - self.start;
+
+        // also empty if a bound is NaN
+        if distance.is_nan() || distance < 0.0 {
+            return 0;
+        }
+
+        if self.inclusive {
+            (distance.floor() as u64).saturating_add(1)
+        } else {
+            distance.ceil() as u64
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Values of the range in order, computed as they are needed
+    pub fn values(&self) -> impl Iterator<Item = f64> {
+        let start = self.start;
+        (0..self.len()).map(move |n| start + n as f64)
+    }
+}
+
+// Imported module bound to a name, giving access to its top-level declarations as properties
+#[derive(Debug)]
+pub struct Module {
+    pub name: String,
+    pub exports: BTreeMap<String, ValueBox>,
+}
+
 impl Value {
+    // Name of the type of the value, as reported by error messages and the typeof native.
+    // These strings are part of the public API and must not change.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "boolean",
+            Value::Callable(_) => "function",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::Module(_) => "module",
+            Value::Range(_) => "range",
+            Value::Nil => "nil",
+        }
+    }
+
+    // Canonical truthiness, interpreters may be configured with other rules, see Truthiness
     pub fn is_truthy(&self) -> bool {
+        super::is_truthy(self)
+    }
+
+    // Explicit conversion to a number, used by the number native. Numbers are kept, booleans
+    // convert to 1 and 0 and strings must hold a decimal number, optionally negative and
+    // surrounded by whitespace. Any other value has no numeric form
+    pub fn to_number(&self) -> Option<f64> {
         match self {
-            Value::Boolean(b) => *b,
-            Value::Number(n) => *n != 0.0,
-            Value::String(s) => !s.is_empty(),
-            Value::Nil => false,
-            Value::Callable(_) => false,
+            Value::Number(n) => Some(*n),
+            Value::Boolean(b) => Some(if *b { 1.0 } else { 0.0 }),
+            Value::String(s) => {
+                let s = s.trim();
+                let digits = s.strip_prefix('-').unwrap_or(s);
+                let mut parts = digits.splitn(2, '.');
+                let is_decimal =
+                    parts.all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()));
+
+                if is_decimal {
+                    s.parse::<f64>().ok()
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // Approximate bytes of heap owned by the value, not counting the boxes of list and map
+    // elements or module declarations, which are values of their own
+    pub fn heap_size(&self) -> usize {
+        match self {
+            Value::String(s) => s.capacity(),
+            Value::Callable(callable) => {
+                std::mem::size_of::<Box<dyn Callable>>() + std::mem::size_of_val(&***callable)
+            }
+            Value::List(elements) => elements.capacity() * std::mem::size_of::<ValueBox>(),
+            Value::Map(entries) => entries_heap_size(entries),
+            Value::Module(module) => {
+                std::mem::size_of::<Module>()
+                    + module.name.capacity()
+                    + entries_heap_size(&module.exports)
+            }
+            Value::Number(_) | Value::Boolean(_) | Value::Range(_) | Value::Nil => 0,
+        }
+    }
+}
+
+fn entries_heap_size(entries: &BTreeMap<String, ValueBox>) -> usize {
+    entries
+        .keys()
+        .map(|key| key.capacity() + std::mem::size_of::<(String, ValueBox)>())
+        .sum()
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Number(left), Value::Number(right)) => left == right,
+            (Value::String(left), Value::String(right)) => left == right,
+            (Value::Boolean(left), Value::Boolean(right)) => left == right,
+            (Value::Callable(left), Value::Callable(right)) => Rc::ptr_eq(left, right),
+            (Value::List(left), Value::List(right)) => {
+                // lists are equal if all their elements are equal
+                left.len() == right.len()
+                    && left
+                        .iter()
+                        .zip(right.iter())
+                        .all(|(l, r)| value_box_eq(l, r))
+            }
+            (Value::Map(left), Value::Map(right)) => {
+                // maps are ordered by key, so equal maps yield the same entries in the same order
+                left.len() == right.len()
+                    && left
+                        .iter()
+                        .zip(right.iter())
+                        .all(|((lk, l), (rk, r))| lk == rk && value_box_eq(l, r))
+            }
+            (Value::Module(left), Value::Module(right)) => Rc::ptr_eq(left, right),
+            (Value::Range(left), Value::Range(right)) => left == right,
+            (Value::Nil, Value::Nil) => true,
+            _ => false,
         }
     }
 }
 
+// Compares the values stored in two boxes, borrowing them one pair at a time
+fn value_box_eq(left: &ValueBox, right: &ValueBox) -> bool {
+    if Rc::ptr_eq(left, right) {
+        return true;
+    }
+
+    match (left.try_borrow(), right.try_borrow()) {
+        (Ok(l_guard), Ok(r_guard)) => *l_guard == *r_guard,
+        _ => false,
+    }
+}
+
+// Text of a number as printed by Lox, also used when concatenating numbers to strings.
+// Integers print without a fractional part and other numbers with the fewest digits that read
+// back as the same number. Magnitudes from 1e21 and below 1e-7 use an exponent instead, as in
+// JavaScript, e.g. 1e21 and 1.5e-8
+pub fn format_number(n: f64) -> String {
+    let magnitude = n.abs();
+    if magnitude.is_finite() && (magnitude >= 1e21 || (magnitude != 0.0 && magnitude < 1e-7)) {
+        format!("{n:e}")
+    } else {
+        format!("{n}")
+    }
+}
+
 impl Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Value::Number(n) => write!(f, "{}", n),
+            Value::Number(n) => write!(f, "{}", format_number(*n)),
             Value::String(s) => write!(f, "{}", s),
             Value::Boolean(b) => write!(f, "{}", b),
             Value::Nil => write!(f, "nil"),
-            Value::Callable(c) => write!(f, "<callable> {}", c.to_string()),
+            Value::Callable(c) => write!(f, "{c}"),
+            Value::List(l) => {
+                write!(f, "[")?;
+                for (i, element) in l.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    match element.try_borrow() {
+                        Ok(guard) => write!(f, "{}", guard)?,
+                        Err(_) => write!(f, "<borrowed>")?,
+                    }
+                }
+                write!(f, "]")
+            }
+            Value::Map(m) => {
+                write!(f, "{{")?;
+                for (i, (key, element)) in m.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    match element.try_borrow() {
+                        Ok(guard) => write!(f, "{key}: {}", guard)?,
+                        Err(_) => write!(f, "{key}: <borrowed>")?,
+                    }
+                }
+                write!(f, "}}")
+            }
+            Value::Module(m) => write!(f, "<module {}>", m.name),
+            Value::Range(r) => {
+                let operator = if r.inclusive { "..=" } else { ".." };
+                write!(
+                    f,
+                    "{}{operator}{}",
+                    format_number(r.start),
+                    format_number(r.end)
+                )
+            }
         }
     }
 }
 
 // Type used to store a Value in a interpreter session.
-pub type ValueBox = Arc<RwLock<Box<Value>>>;
+pub type ValueBox = Rc<RefCell<Value>>;
 
 pub fn new_value_box(value: Value) -> ValueBox {
-    Arc::new(RwLock::new(Box::new(value)))
+    Rc::new(RefCell::new(value))
+}
+
+// Approximate bytes of heap taken by a box, its reference counts included, without the heap
+// owned by its value
+pub const VALUE_BOX_SIZE: usize =
+    std::mem::size_of::<RefCell<Value>>() + 2 * std::mem::size_of::<usize>();
+
+// Approximate bytes of heap taken by a new list of the given length, the boxes of its elements
+// included but not their contents
+pub fn list_heap_size(length: usize) -> usize {
+    length.saturating_mul(std::mem::size_of::<ValueBox>() + VALUE_BOX_SIZE)
+}
+
+// Approximate bytes of heap used by the values reachable from the given boxes, each distinct box
+// counted once
+pub fn reachable_heap_size<'a>(
+    values: impl IntoIterator<Item = &'a ValueBox>,
+) -> Result<usize, String> {
+    let mut visited = HashSet::new();
+    let mut size = 0;
+
+    for value in values {
+        visit_reachable(value, &mut visited, &mut |_, value| {
+            size += VALUE_BOX_SIZE + value.heap_size();
+        })?;
+    }
+
+    Ok(size)
+}
+
+// Calls the visitor once for each distinct box reachable from the value, including the elements
+// of lists and maps and the declarations of modules. Boxes are identified by their address in
+// the visited set, which can be shared between calls
+pub fn visit_reachable(
+    value: &ValueBox,
+    visited: &mut HashSet<usize>,
+    visitor: &mut dyn FnMut(&ValueBox, &Value),
+) -> Result<(), String> {
+    if !visited.insert(Rc::as_ptr(value) as usize) {
+        return Ok(());
+    }
+
+    let value_guard = value.try_borrow().map_err(|e| e.to_string())?;
+    visitor(value, &value_guard);
+
+    let children: Vec<&ValueBox> = match &*value_guard {
+        Value::List(elements) => elements.iter().collect(),
+        Value::Map(entries) => entries.values().collect(),
+        Value::Module(module) => module.exports.values().collect(),
+        _ => Vec::new(),
+    };
+
+    for child in children {
+        visit_reachable(child, visited, visitor)?;
+    }
+
+    Ok(())
+}
+
+// Whether the box is reachable from the value, e.g. as an element of a list nested in it
+pub fn is_reachable_from(target: &ValueBox, value: &Value) -> Result<bool, String> {
+    let mut found = false;
+    visit_reachable(
+        &new_value_box(value.clone()),
+        &mut HashSet::new(),
+        &mut |value_box, _| found |= Rc::ptr_eq(value_box, target),
+    )?;
+
+    Ok(found)
 }
 
 pub trait Callable: std::fmt::Display + std::fmt::Debug {
-    fn get_arg_name(&self, arg_number: usize) -> Result<String, String>;
+    fn get_arg_name(&self, arg_number: usize) -> Result<Symbol, String>;
     fn get_arg_count(&self) -> usize;
-    fn call(&self) -> Result<ValueBox, String>;
-    fn get_body(&self) -> &Box<Stmt>;
+
+    // Calls the function directly with the evaluated arguments. Used for callables without a body
+    fn call(&self, arguments: &[Value]) -> Result<ValueBox, String>;
+
+    // Same as call, giving access to the interpreter executing the call
+    fn call_in(
+        &self,
+        _context: &mut dyn CallContext,
+        arguments: &[Value],
+    ) -> Result<ValueBox, String> {
+        self.call(arguments)
+    }
+
+    // Body to be executed by the interpreter, None for native functions
+    fn get_body(&self) -> Option<&Box<Stmt>>;
+
+    // Environment the body must be executed in, None to use the one of the caller
+    fn get_module_environment(&self) -> Option<ModuleEnvironment> {
+        None
+    }
+
+    // Name and parameters of the function, for printing and tooling
+    fn get_signature(&self) -> FunctionSignature;
+}
+
+// Access to the interpreter given to the natives it calls. Natives can inspect its environment
+// and call back Lox functions received as arguments
+pub trait CallContext {
+    fn get_environment(&self) -> &dyn Environment;
+
+    // Calls a callable value with copies of the arguments, returning a copy of its result
+    fn call(&mut self, callee: &Value, arguments: &[Value]) -> Result<Value, String>;
+
+    // Truthiness of a value following the rules of the interpreter, see Truthiness
+    fn is_truthy(&self, value: &Value) -> bool {
+        value.is_truthy()
+    }
+
+    // Fails if the given bytes do not fit in the memory budget, for the natives to check the
+    // size of a large value before building it. Any size fits by default
+    fn reserve(&mut self, _size: usize) -> Result<(), String> {
+        Ok(())
+    }
 }
 
+// Callables are equal only to themselves, two functions with the same declaration are different
 impl PartialEq for dyn Callable {
     fn eq(&self, other: &Self) -> bool {
-        self == other
+        std::ptr::addr_eq(self, other)
     }
 }
 
@@ -60,19 +379,28 @@ impl PartialEq for dyn Callable {
 mod tests {
 
     use std::borrow::BorrowMut;
+    use std::collections::BTreeMap;
+    use std::path::PathBuf;
+    use std::rc::Rc;
 
-    use super::Value;
+    use rstest::*;
+
+    use super::{
+        format_number, new_value_box, reachable_heap_size, Callable, Range, Value, ValueBox,
+        VALUE_BOX_SIZE,
+    };
+    use crate::lox::{Interpreter, NativeFunction};
 
     #[test]
     fn test_value_truthiness() {
         let value = Value::Number(0.0);
-        assert_eq!(value.is_truthy(), false);
+        assert_eq!(value.is_truthy(), true);
 
         let value = Value::Number(1.0);
         assert_eq!(value.is_truthy(), true);
 
         let value = Value::String("".to_string());
-        assert_eq!(value.is_truthy(), false);
+        assert_eq!(value.is_truthy(), true);
 
         let value = Value::String("Hello".to_string());
         assert_eq!(value.is_truthy(), true);
@@ -85,5 +413,185 @@ mod tests {
 
         let value = Value::Nil;
         assert_eq!(value.is_truthy(), false);
+
+        let value = Value::List(vec![]);
+        assert_eq!(value.is_truthy(), true);
+
+        let value = Value::List(vec![new_value_box(Value::Nil)]);
+        assert_eq!(value.is_truthy(), true);
+    }
+
+    #[rstest]
+    #[case::integer(4.0, "4")]
+    #[case::negative_integer(-7.0, "-7")]
+    #[case::fraction(2.5, "2.5")]
+    #[case::shortest_digits(0.1 + 0.2, "0.30000000000000004")]
+    #[case::largest_positional(999999e15, "999999000000000000000")]
+    #[case::large(1e21, "1e21")]
+    #[case::large_fraction(-1.5e300, "-1.5e300")]
+    #[case::smallest_positional(1e-7, "0.0000001")]
+    #[case::small(1.5e-8, "1.5e-8")]
+    #[case::zero(0.0, "0")]
+    #[case::infinity(f64::INFINITY, "inf")]
+    #[case::nan(f64::NAN, "NaN")]
+    fn test_format_number(#[case] n: f64, #[case] expected: &str) {
+        assert_eq!(format_number(n), expected);
+        assert_eq!(Value::Number(n).to_string(), expected);
+    }
+
+    #[test]
+    fn test_type_name() {
+        let function = NativeFunction::new("f", &[], |_| Ok(Value::Nil));
+
+        assert_eq!(Value::Number(1.0).type_name(), "number");
+        assert_eq!(Value::String("".to_string()).type_name(), "string");
+        assert_eq!(Value::Boolean(true).type_name(), "boolean");
+        assert_eq!(
+            Value::Callable(Rc::new(Box::new(function))).type_name(),
+            "function"
+        );
+        assert_eq!(Value::List(vec![]).type_name(), "list");
+        assert_eq!(Value::Nil.type_name(), "nil");
+    }
+
+    #[test]
+    fn test_list_equality() {
+        let list1 = Value::List(vec![
+            new_value_box(Value::Number(1.0)),
+            new_value_box(Value::String("a".to_string())),
+        ]);
+        let list2 = Value::List(vec![
+            new_value_box(Value::Number(1.0)),
+            new_value_box(Value::String("a".to_string())),
+        ]);
+        let list3 = Value::List(vec![new_value_box(Value::Number(1.0))]);
+
+        assert_eq!(list1, list2);
+        assert_ne!(list1, list3);
+    }
+
+    #[rstest]
+    #[case::exclusive(1.0, 4.0, false, 3, "1..4")]
+    #[case::inclusive(1.0, 4.0, true, 4, "1..=4")]
+    #[case::fractional_end(1.0, 2.5, false, 2, "1..2.5")]
+    #[case::fractional_inclusive(0.5, 2.0, true, 2, "0.5..=2")]
+    #[case::single(2.0, 2.0, true, 1, "2..=2")]
+    #[case::empty(2.0, 2.0, false, 0, "2..2")]
+    #[case::reversed(3.0, 1.0, true, 0, "3..=1")]
+    #[case::nan(f64::NAN, 1.0, false, 0, "NaN..1")]
+    #[case::unbounded(0.0, f64::INFINITY, false, u64::MAX, "0..inf")]
+    fn test_range(
+        #[case] start: f64,
+        #[case] end: f64,
+        #[case] inclusive: bool,
+        #[case] len: u64,
+        #[case] display: &str,
+    ) {
+        let range = Range {
+            start,
+            end,
+            inclusive,
+        };
+
+        assert_eq!(range.len(), len);
+        assert_eq!(range.is_empty(), len == 0);
+        assert_eq!(Value::Range(range).to_string(), display);
+    }
+
+    #[test]
+    fn test_reachable_heap_size() -> Result<(), String> {
+        // the element shared by the list and a variable is counted once
+        let element = new_value_box(Value::from("abc"));
+        let list = new_value_box(Value::List(vec![element.clone(), element.clone()]));
+
+        assert_eq!(
+            reachable_heap_size([&list, &element])?,
+            2 * VALUE_BOX_SIZE + 3 + 2 * std::mem::size_of::<ValueBox>()
+        );
+        assert_eq!(Value::Number(1.0).heap_size(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_display() {
+        let list = Value::List(vec![
+            new_value_box(Value::Number(1.0)),
+            new_value_box(Value::List(vec![new_value_box(Value::Boolean(true))])),
+        ]);
+
+        assert_eq!(list.to_string(), "[1, [true]]");
+    }
+
+    #[test]
+    fn test_map_display_and_equality() {
+        let map = |value: f64| {
+            Value::Map(BTreeMap::from([
+                ("b".to_string(), new_value_box(Value::Number(value))),
+                ("a".to_string(), new_value_box(Value::Nil)),
+            ]))
+        };
+
+        assert_eq!(map(1.0).to_string(), "{a: nil, b: 1}");
+        assert_eq!(map(1.0).type_name(), "map");
+        assert_eq!(map(1.0), map(1.0));
+        assert_ne!(map(1.0), map(2.0));
+    }
+
+    #[rstest]
+    #[case::function("fun showA(arg1, arg2) {}", "showA;", "<fn showA(arg1, arg2)>")]
+    #[case::no_arguments("fun f() {}", "f;", "<fn f()>")]
+    #[case::native("", "random;", "<native fn random()>")]
+    #[case::native_arguments("", "len;", "<native fn len(s)>")]
+    #[case::module_function("import \"counter.lox\" as c;", "c.increment;", "<fn increment()>")]
+    #[case::in_list("fun f(x) {}", "[f, random];", "[<fn f(x)>, <native fn random()>]")]
+    fn test_callable_display(
+        #[case] setup: &str,
+        #[case] source: &str,
+        #[case] expected: &str,
+    ) -> Result<(), String> {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_module_search_paths(vec![PathBuf::from("test-data/modules")]);
+        interpreter.execute(setup.to_string())?;
+
+        let result = interpreter.execute(source.to_string())?;
+        let result_guard = result.try_borrow().map_err(|e| e.to_string())?;
+        assert_eq!(result_guard.to_string(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_callable_equality() {
+        let function: Rc<Box<dyn Callable>> =
+            Rc::new(Box::new(NativeFunction::new("f", &[], |_| Ok(Value::Nil))));
+        let same_declaration: Rc<Box<dyn Callable>> =
+            Rc::new(Box::new(NativeFunction::new("f", &[], |_| Ok(Value::Nil))));
+
+        assert!(**function == **function);
+        assert!(**function != **same_declaration);
+        assert_eq!(
+            Value::Callable(function.clone()),
+            Value::Callable(function.clone())
+        );
+        assert_ne!(
+            Value::Callable(function.clone()),
+            Value::Callable(same_declaration)
+        );
+        assert_eq!(
+            Value::List(vec![new_value_box(Value::Callable(function.clone()))]),
+            Value::List(vec![new_value_box(Value::Callable(function))])
+        );
+    }
+
+    #[test]
+    fn test_callable_signature() {
+        let function = NativeFunction::new("max", &["a", "b"], |_| Ok(Value::Nil));
+
+        let signature = function.get_signature();
+
+        assert_eq!(signature.name, "max");
+        assert_eq!(signature.arguments, vec!["a", "b"]);
+        assert!(signature.native);
     }
 }
@@ -0,0 +1,95 @@
+use std::fmt::Display;
+
+// Prefix of the comment declaring the language version of a script, e.g. `// lox-version: 1`
+const VERSION_HEADER: &str = "// lox-version:";
+
+// Language levels accepted by the parser. Classic Lox excludes the extensions of this
+// implementation, lists and imports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LanguageVersion {
+    Lox1,
+    #[default]
+    LoxRustExtended,
+}
+
+impl LanguageVersion {
+    pub fn from_name(name: &str) -> Option<LanguageVersion> {
+        match name {
+            "1" => Some(LanguageVersion::Lox1),
+            "extended" => Some(LanguageVersion::LoxRustExtended),
+            _ => None,
+        }
+    }
+
+    // Name used in the version header and the command line
+    pub fn name(&self) -> &'static str {
+        match self {
+            LanguageVersion::Lox1 => "1",
+            LanguageVersion::LoxRustExtended => "extended",
+        }
+    }
+
+    // Reads the version declared by the header of a source. The header must be among the
+    // comment lines at the start of the source
+    pub fn from_source(source: &str) -> Result<Option<LanguageVersion>, String> {
+        for line in source.lines().map(|l| l.trim()) {
+            if line.is_empty() {
+                continue;
+            }
+
+            if !line.starts_with("//") {
+                break;
+            }
+
+            if let Some(name) = line.strip_prefix(VERSION_HEADER) {
+                let name = name.trim();
+                return match LanguageVersion::from_name(name) {
+                    Some(version) => Ok(Some(version)),
+                    None => Err(format!("Unknown language version '{name}'")),
+                };
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+impl Display for LanguageVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LanguageVersion::Lox1 => write!(f, "Lox 1"),
+            LanguageVersion::LoxRustExtended => write!(f, "Lox Rust extended"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rstest::rstest;
+
+    use super::LanguageVersion;
+
+    #[rstest]
+    #[case::no_header("print 1;", Ok(None))]
+    #[case::classic("// lox-version: 1\nprint 1;", Ok(Some(LanguageVersion::Lox1)))]
+    #[case::extended(
+        "// lox-version: extended\nprint 1;",
+        Ok(Some(LanguageVersion::LoxRustExtended))
+    )]
+    #[case::after_comments(
+        "\n// a script\n  // lox-version: 1\nprint 1;",
+        Ok(Some(LanguageVersion::Lox1))
+    )]
+    #[case::after_code("print 1;\n// lox-version: 1", Ok(None))]
+    #[case::unknown(
+        "// lox-version: 7\nprint 1;",
+        Err("Unknown language version '7'".to_string())
+    )]
+    fn test_from_source(
+        #[case] source: &str,
+        #[case] expected: Result<Option<LanguageVersion>, String>,
+    ) {
+        assert_eq!(LanguageVersion::from_source(source), expected);
+    }
+}